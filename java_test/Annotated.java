@@ -0,0 +1,35 @@
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+
+@Retention(RetentionPolicy.RUNTIME)
+@interface Test {
+  Class<? extends Throwable> expected();
+  long timeout() default 0;
+}
+
+@interface GuardedBy {
+  String value();
+}
+
+@interface P {
+  String value();
+}
+
+@interface Positive {}
+
+@interface Text {
+  String value() default "y";
+}
+
+@Deprecated
+class Annotated {
+  @GuardedBy("this") private int size;
+
+  @Test(expected = IllegalStateException.class, timeout = 5)
+  public void t(@P("x") int a, int b) {}
+
+  @Text("x\uD800y")
+  void unpaired() {}
+}
+
+record Size(@Positive int size) {}
@@ -0,0 +1,36 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.List;
+import java.util.Map;
+
+@Target(ElementType.TYPE_USE)
+@Retention(RetentionPolicy.RUNTIME)
+@interface NonNull {}
+
+@Target({ElementType.TYPE_USE, ElementType.TYPE_PARAMETER})
+@interface Tainted {}
+
+class TypeAnnotated<@Tainted T extends @NonNull Object>
+    implements @NonNull Comparable<TypeAnnotated<T>> {
+  @NonNull List<@Tainted String @NonNull []> names;
+  Map.@NonNull Entry<? extends @Tainted Number, String> entry;
+
+  @NonNull String describe(@Tainted Object value) throws @NonNull IllegalStateException {
+    @NonNull String text = (@Tainted String) value;
+    if (value instanceof @NonNull Integer) {
+      text = new @Tainted String(text);
+    }
+    try {
+      text.length();
+    } catch (@NonNull RuntimeException e) {
+      return TypeAnnotated.<@Tainted String>first(text);
+    }
+    return text;
+  }
+
+  static <E> E first(E value) { return value; }
+
+  public int compareTo(TypeAnnotated<T> other) { return 0; }
+}
@@ -0,0 +1,9 @@
+package app;
+
+import app.spi.Service;
+
+public class Main implements Service {
+  public void run() {}
+
+  public static void main(String[] args) { new Main().run(); }
+}
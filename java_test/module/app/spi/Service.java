@@ -0,0 +1,5 @@
+package app.spi;
+
+public interface Service {
+  void run();
+}
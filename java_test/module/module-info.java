@@ -0,0 +1,9 @@
+module fixture.app {
+  requires transitive java.logging;
+  requires static java.sql;
+  exports app;
+  exports app.spi to java.logging;
+  opens app to java.logging;
+  uses app.spi.Service;
+  provides app.spi.Service with app.Main;
+}
@@ -0,0 +1,344 @@
+use self::{
+  assemble_error::AssembleError,
+  attribute::table,
+  instruction::constant,
+  lexer::{Line, Token, Tokens},
+  method::MethodAssembler
+};
+use crate::{
+  builder::{build_error::BuildError, ClassBuilder},
+  disassembler::{
+    jasmin::keyword,
+    text::{FlagName, CLASS_FLAGS, FIELD_FLAGS, METHOD_FLAGS}
+  },
+  parser::{
+    attribute_info::{
+      attribute::{constant::Constant, record_component::RecordComponent},
+      Attribute
+    },
+    classfile::{ClassAccessFlags, ClassFile},
+    cp_info_resolved::ResolvedCpInfo,
+    field_info::FieldAccessFlags,
+    method_info::MethodAccessFlags
+  }
+};
+
+pub mod assemble_error;
+mod attribute;
+mod instruction;
+mod lexer;
+mod method;
+
+/// Assembles a class from source in the syntax `disassembler::jasmin` writes, which follows Jasmin:
+///
+/// ```text
+/// .version 52 0
+/// .class public super Hello
+/// .super java/lang/Object
+///
+/// .method public static main([Ljava/lang/String;)V
+///     .limit stack 2
+///     getstatic java/lang/System/out Ljava/io/PrintStream;
+///     ldc "Hello"
+///     invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V
+///     return
+/// .end method
+/// ```
+///
+/// Mnemonics are those of `Instructions`, and branches go to labels such as `L12:`.
+/// A method with `.limit stack` is kept as written, with only the frames its `.stack` directives
+/// give, so it can hold code that doesn't verify. Otherwise `ClassBuilder::build` works out its
+/// limits and frames.
+pub fn assemble(source: &str) -> Result<ClassFile, AssembleError> {
+  let lines = lexer::lines(source)?;
+  let mut header = Vec::new();
+  let mut members: Vec<(&Line, &[Line])> = Vec::new();
+  let mut position = 0;
+  while let Some(line) = lines.get(position) {
+    position += 1;
+    match &line.tokens[0] {
+      Token::Word(word) if word == ".field" => {
+        // Attributes that don't fit on the line go in a block up to `.end field`
+        let start = position;
+        let end = lines[start..]
+          .iter()
+          .position(|line| is_member(line) || is_end(line, "field"))
+          .map(|end| start + end)
+          .filter(|end| is_end(&lines[*end], "field"));
+        match end {
+          Some(end) => {
+            members.push((line, &lines[start..end]));
+            position = end + 1;
+          }
+          None => members.push((line, &[]))
+        }
+      }
+      Token::Word(word) if word == ".method" => {
+        let start = position;
+        while !is_end(
+          lines.get(position).ok_or_else(|| AssembleError::Syntax {
+            line: line.number,
+            message: "Method without .end method".to_string()
+          })?,
+          "method"
+        ) {
+          position += 1;
+        }
+        members.push((line, &lines[start..position]));
+        position += 1;
+      }
+      _ => header.push(line)
+    }
+  }
+
+  let mut class = class(&header)?;
+  for (line, body) in members {
+    let mut tokens = Tokens::new(line.number, &line.tokens);
+    match tokens.word("a directive")? {
+      ".field" => field(&mut class, &mut tokens, body)?,
+      _ => {
+        let (access_flags, name) = flags_and_name(&mut tokens, METHOD_FLAGS)?;
+        let (name, descriptor) = name.split_at(
+          name
+            .find('(')
+            .ok_or_else(|| tokens.error(format!("Expected a descriptor after {}", name)))?
+        );
+        let method = class
+          .method(MethodAccessFlags::from_mask(access_flags), name, descriptor)
+          .map_err(|error| build_error(line.number, error))?;
+        MethodAssembler::assemble(method, body)?;
+      }
+    }
+  }
+  class.build().map_err(|error| AssembleError::BuildError { error })
+}
+
+fn is_member(line: &Line) -> bool {
+  matches!(&line.tokens[0], Token::Word(word) if word == ".field" || word == ".method")
+}
+
+/// Whether the line is `.end <what>`.
+fn is_end(line: &Line, what: &str) -> bool {
+  line.tokens == [Token::Word(".end".to_string()), Token::Word(what.to_string())]
+}
+
+fn is_record(line: &Line) -> bool {
+  matches!(&line.tokens[0], Token::Word(word) if word == ".record")
+}
+
+/// Starts the class from the directives outside fields and methods.
+fn class(header: &[&Line]) -> Result<ClassBuilder, AssembleError> {
+  let mut version = None;
+  let mut this_class = None;
+  let mut super_class = None;
+  let mut interfaces = Vec::new();
+  let mut attributes = Vec::new();
+  let mut position = 0;
+  while let Some(line) = header.get(position) {
+    position += 1;
+    let mut tokens = Tokens::new(line.number, &line.tokens);
+    match tokens.word("a directive")? {
+      ".version" => {
+        version = Some((tokens.number("a major version")?, tokens.number("a minor version")?))
+      }
+      ".class" if this_class.is_some() => {
+        return Err(tokens.error("More than one .class directive".to_string()))
+      }
+      ".class" => this_class = Some(flags_and_name(&mut tokens, CLASS_FLAGS)?),
+      ".super" => super_class = Some(tokens.word("a class name")?),
+      ".implements" => interfaces.push(tokens.word("an interface name")?),
+      ".source" => {
+        attributes.push(Attribute::SourceFile { sourcefile: tokens.text("a file name")? })
+      }
+      ".signature" => {
+        attributes.push(Attribute::Signature { signature: tokens.text("a signature")? })
+      }
+      ".record" => {
+        // Attributes that don't fit on the line go in a block up to `.end component`, as for a field
+        let start = position;
+        let end = header[start..]
+          .iter()
+          .position(|line| is_record(line) || is_end(line, "component"))
+          .map(|end| start + end)
+          .filter(|end| is_end(header[*end], "component"));
+        let body = match end {
+          Some(end) => {
+            position = end + 1;
+            &header[start..end]
+          }
+          None => &[]
+        };
+        record(&mut tokens, body, &mut attributes)?;
+      }
+      directive => {
+        if !attribute::directive(directive, &mut tokens, &mut attributes)? {
+          return Err(tokens.error(format!("Unknown directive {}", directive)));
+        }
+      }
+    }
+    tokens.end()?;
+  }
+
+  let (access_flags, name) = this_class.ok_or(AssembleError::MissingClass)?;
+  let mut class = ClassBuilder::new(ClassAccessFlags::from_mask(access_flags), name, super_class);
+  if let Some((major_version, minor_version)) = version {
+    class.version(major_version, minor_version);
+  }
+  for interface in interfaces {
+    class.interface(interface);
+  }
+  for attribute in attributes {
+    class.attribute(attribute);
+  }
+  Ok(class)
+}
+
+/// `.field <flags> <name> <descriptor>`, then its generic signature and initial value in either
+/// order, as `signature "<signature>"` and `= <constant>`. The directives in `body` give its other
+/// attributes.
+fn field(
+  class: &mut ClassBuilder,
+  tokens: &mut Tokens,
+  body: &[Line]
+) -> Result<(), AssembleError> {
+  // The flags are the words before the name and descriptor, and a field may be named `signature`
+  let mut words = Vec::new();
+  while let [Token::Word(word), rest @ ..] = tokens.rest() {
+    if word == "=" || (word == "signature" && matches!(rest.first(), Some(Token::String(_)))) {
+      break;
+    }
+    words.push(word.as_str());
+    tokens.next_token();
+  }
+  let (descriptor, words) =
+    words.split_last().ok_or_else(|| tokens.error("Expected a name".to_string()))?;
+  let (name, words) =
+    words.split_last().ok_or_else(|| tokens.error("Expected a descriptor".to_string()))?;
+  let access_flags = flags(tokens, words, FIELD_FLAGS)?;
+  let field = class
+    .field(FieldAccessFlags::from_mask(access_flags), name, descriptor)
+    .map_err(|error| build_error(tokens.line(), error))?;
+  while !tokens.is_empty() {
+    if tokens.next_is("signature") {
+      field.attributes.push(Attribute::Signature { signature: tokens.text("a signature")? });
+      continue;
+    }
+    tokens.keyword("=")?;
+    let value = match constant(tokens)? {
+      ResolvedCpInfo::Integer(value) => Constant::Int(value),
+      ResolvedCpInfo::Long(value) => Constant::Long(value),
+      ResolvedCpInfo::Float(value) => Constant::Float(value),
+      ResolvedCpInfo::Double(value) => Constant::Double(value),
+      ResolvedCpInfo::String(value) => Constant::String(value),
+      _ => return Err(tokens.error("A field's value must be a number or a string".to_string()))
+    };
+    field.attributes.push(Attribute::ConstantValue { value });
+  }
+  for line in body {
+    let mut tokens = Tokens::new(line.number, &line.tokens);
+    let directive = tokens.word("a directive")?;
+    if !attribute::directive(directive, &mut tokens, &mut field.attributes)? {
+      return Err(tokens.error(format!("Unknown directive {}", directive)));
+    }
+    tokens.end()?;
+  }
+  Ok(())
+}
+
+/// `.record` alone for a record without components, or `.record component <name> <descriptor>` for
+/// each component, then its generic signature as `signature "<signature>"` if it has one. The
+/// directives in `body` give its other attributes.
+fn record(
+  tokens: &mut Tokens,
+  body: &[&Line],
+  attributes: &mut Vec<Attribute>
+) -> Result<(), AssembleError> {
+  let components =
+    table(attributes, Attribute::Record { components: Vec::new() }, |attribute| match attribute {
+      Attribute::Record { components } => Some(components),
+      _ => None
+    });
+  if tokens.is_empty() && body.is_empty() {
+    return Ok(());
+  }
+  tokens.keyword("component")?;
+  let name = tokens.word("a component name")?.to_string();
+  let descriptor = tokens.word("a descriptor")?.to_string();
+  let mut component = RecordComponent { name, descriptor, attributes: Vec::new() };
+  while tokens.next_is("signature") {
+    component.attributes.push(Attribute::Signature { signature: tokens.text("a signature")? });
+  }
+  for line in body {
+    let mut tokens = Tokens::new(line.number, &line.tokens);
+    let directive = tokens.word("a directive")?;
+    if !attribute::directive(directive, &mut tokens, &mut component.attributes)? {
+      return Err(tokens.error(format!("Unknown directive {}", directive)));
+    }
+    tokens.end()?;
+  }
+  components.push(component);
+  Ok(())
+}
+
+/// Flag keywords such as `public`, or numbers such as `0x0100` for flags without one, then the name
+/// as the last word.
+fn flags_and_name<'a>(
+  tokens: &mut Tokens<'a>,
+  names: &[FlagName]
+) -> Result<(u16, &'a str), AssembleError> {
+  let mut words = Vec::new();
+  while !tokens.is_empty() {
+    words.push(tokens.word("a name")?);
+  }
+  let (name, words) =
+    words.split_last().ok_or_else(|| tokens.error("Expected a name".to_string()))?;
+  Ok((flags(tokens, words, names)?, name))
+}
+
+fn flags(tokens: &Tokens, words: &[&str], names: &[FlagName]) -> Result<u16, AssembleError> {
+  let mut mask = 0;
+  for word in words {
+    mask |= match names.iter().find(|(_, name, _)| keyword(name) == *word) {
+      Some((flag, ..)) => *flag,
+      None => word
+        .strip_prefix("0x")
+        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| tokens.error(format!("Unknown flag {}", word)))?
+    };
+  }
+  Ok(mask)
+}
+
+fn build_error(line: usize, error: BuildError) -> AssembleError {
+  match error {
+    BuildError::ClassFormatError { error } => AssembleError::ClassFormatError { line, error },
+    error => AssembleError::BuildError { error }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::disassembler::jasmin;
+
+  #[test]
+  fn disassembled_classes_assemble_back_equal() {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/java_test");
+    let mut count = 0;
+    for entry in std::fs::read_dir(directory).unwrap() {
+      let path = entry.unwrap().path();
+      if path.extension().is_some_and(|extension| extension == "class") {
+        let (mut class, _) = ClassFile::parse(&std::fs::read(&path).unwrap()).unwrap();
+        let source = jasmin::disassemble(&class).unwrap();
+        let assembled = assemble(&source).unwrap();
+        // The bootstrap methods are put back together when the class is written
+        class
+          .attributes
+          .retain(|attribute| !matches!(attribute, Attribute::BootstrapMethods { .. }));
+        assert_eq!(assembled, class, "{}", path.display());
+        count += 1;
+      }
+    }
+    assert_eq!(count, 17);
+  }
+}
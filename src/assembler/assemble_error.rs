@@ -0,0 +1,59 @@
+use std::{error::Error, fmt};
+
+use crate::{builder::build_error::BuildError, parser::class_format_error::ClassFormatError};
+
+/// Errors in assembly source, with the line they were found on, counting from 1.
+#[derive(Debug, Clone)]
+pub enum AssembleError {
+  /// Something other than what the syntax allows, described by `message`.
+  Syntax {
+    line: usize,
+    message: String
+  },
+  UnknownInstruction {
+    line: usize,
+    mnemonic: String
+  },
+  UndefinedLabel {
+    line: usize,
+    label: String
+  },
+  DuplicateLabel {
+    line: usize,
+    label: String
+  },
+  /// A descriptor that doesn't parse.
+  ClassFormatError {
+    line: usize,
+    error: ClassFormatError
+  },
+  /// The source has no `.class` directive.
+  MissingClass,
+  /// The class couldn't be put together, such as when a method without `.limit stack` doesn't
+  /// verify.
+  BuildError {
+    error: BuildError
+  }
+}
+
+impl fmt::Display for AssembleError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AssembleError::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+      AssembleError::UnknownInstruction { line, mnemonic } => {
+        write!(f, "Line {}: Unknown instruction {}", line, mnemonic)
+      }
+      AssembleError::UndefinedLabel { line, label } => {
+        write!(f, "Line {}: Label {} is never defined", line, label)
+      }
+      AssembleError::DuplicateLabel { line, label } => {
+        write!(f, "Line {}: Label {} is already defined", line, label)
+      }
+      AssembleError::ClassFormatError { line, error } => write!(f, "Line {}: {}", line, error),
+      AssembleError::MissingClass => write!(f, "No .class directive"),
+      AssembleError::BuildError { error } => write!(f, "{}", error)
+    }
+  }
+}
+
+impl Error for AssembleError {}
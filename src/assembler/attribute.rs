@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+
+use super::{
+  assemble_error::AssembleError,
+  flags,
+  instruction::constant,
+  lexer::{Token, Tokens}
+};
+use crate::{
+  disassembler::text::{
+    FlagName,
+    EXPORTS_FLAGS,
+    INNER_CLASS_FLAGS,
+    MODULE_FLAGS,
+    PARAMETER_FLAGS,
+    REQUIRES_FLAGS,
+    TARGET_TYPES
+  },
+  parser::{
+    attribute_info::{
+      attribute::{
+        annotation::Annotation,
+        classes::Classes,
+        element_value::ElementValue,
+        method_parameter::{MethodParameter, MethodParameterFlags},
+        module::{
+          Exports,
+          ExportsFlags,
+          ModuleFlags,
+          Opens,
+          OpensFlags,
+          Provides,
+          Requires,
+          RequiresFlags
+        },
+        parameter_annotation::ParameterAnnotation,
+        type_annotation::{
+          LocalvarTarget,
+          TargetInfo,
+          TypeAnnotation,
+          TypePathEntry,
+          TypePathKind
+        }
+      },
+      Attribute
+    },
+    cp_info_resolved::{Class, JavaString, NameAndType, ResolvedCpInfo}
+  }
+};
+
+/// Adds the attribute for a directive that a class, field or method may have, as
+/// `jasmin::disassemble` writes it. A directive for a table entry, such as `.nestmember`, adds to
+/// the table.
+/// Gives back `false` for any other directive, leaving the tokens as they are.
+pub(super) fn directive(
+  directive: &str,
+  tokens: &mut Tokens,
+  attributes: &mut Vec<Attribute>
+) -> Result<bool, AssembleError> {
+  match directive {
+    ".deprecated" => attributes.push(Attribute::Deprecated),
+    ".synthetic" => attributes.push(Attribute::Synthetic),
+    ".annotation" => annotation_directive(tokens, attributes)?,
+    ".inner" => {
+      tokens.keyword("class")?;
+      // The flags and class, then `outer <class>` and `name "<name>"` if the class has them
+      let mut words = Vec::new();
+      while let [Token::Word(word), rest @ ..] = tokens.rest() {
+        if word == "name" && matches!(rest.first(), Some(Token::String(_))) {
+          break;
+        }
+        words.push(word.as_str());
+        tokens.next_token();
+      }
+      let outer_class = match words.as_slice() {
+        [.., _, "outer", outer_class] => {
+          let outer_class = Class { name: outer_class.to_string() };
+          words.truncate(words.len() - 2);
+          Some(outer_class)
+        }
+        _ => None
+      };
+      let (inner_class, words) =
+        words.split_last().ok_or_else(|| tokens.error("Expected a class name".to_string()))?;
+      let inner_class_access_flags = flags(tokens, words, INNER_CLASS_FLAGS)?;
+      let inner_name = match tokens.next_is("name") {
+        true => Some(tokens.text("a name")?),
+        false => None
+      };
+      let inner = Classes {
+        inner_class: Class { name: inner_class.to_string() },
+        outer_class,
+        inner_name,
+        inner_class_access_flags
+      };
+      table(
+        attributes,
+        Attribute::InnerClasses { classes: Vec::new() },
+        |attribute| match attribute {
+          Attribute::InnerClasses { classes } => Some(classes),
+          _ => None
+        }
+      )
+      .push(inner);
+    }
+    ".enclosing" => {
+      tokens.keyword("method")?;
+      let class = Class { name: tokens.word("a class name")?.to_string() };
+      let method = match tokens.peek() {
+        Some(Token::Word(method)) => {
+          tokens.next_token();
+          let (name, descriptor) = method.split_at(
+            method
+              .find('(')
+              .ok_or_else(|| tokens.error(format!("Expected a descriptor after {}", method)))?
+          );
+          Some(NameAndType { name: name.to_string(), descriptor: descriptor.to_string() })
+        }
+        _ => None
+      };
+      attributes.push(Attribute::EnclosingMethod { class, method });
+    }
+    ".nesthost" => {
+      let host_class = Class { name: tokens.word("a class name")?.to_string() };
+      attributes.push(Attribute::NestHost { host_class });
+    }
+    ".nestmember" => {
+      let class = Class { name: tokens.word("a class name")?.to_string() };
+      table(
+        attributes,
+        Attribute::NestMembers { classes: Vec::new() },
+        |attribute| match attribute {
+          Attribute::NestMembers { classes } => Some(classes),
+          _ => None
+        }
+      )
+      .push(class);
+    }
+    ".permittedsubclass" => {
+      let class = Class { name: tokens.word("a class name")?.to_string() };
+      table(attributes, Attribute::PermittedSubclasses { classes: Vec::new() }, |attribute| {
+        match attribute {
+          Attribute::PermittedSubclasses { classes } => Some(classes),
+          _ => None
+        }
+      })
+      .push(class);
+    }
+    ".parameter" => {
+      // The flags, then the name in quotes unless the parameter has none
+      let mut words = Vec::new();
+      while let Some(Token::Word(word)) = tokens.peek() {
+        words.push(word.as_str());
+        tokens.next_token();
+      }
+      let access_flags = MethodParameterFlags::from_mask(flags(tokens, &words, PARAMETER_FLAGS)?);
+      let name = match tokens.is_empty() {
+        true => None,
+        false => Some(tokens.text("a name")?)
+      };
+      table(attributes, Attribute::MethodParameters { parameters: Vec::new() }, |attribute| {
+        match attribute {
+          Attribute::MethodParameters { parameters } => Some(parameters),
+          _ => None
+        }
+      })
+      .push(MethodParameter { name, access_flags });
+    }
+    ".module" => {
+      let (flags, module_name) = flagged_name(tokens, MODULE_FLAGS, is_version)?;
+      attributes.push(Attribute::Module {
+        module_name: module_name.to_string(),
+        module_flags: ModuleFlags::from_mask(flags),
+        module_version: version(tokens)?,
+        requires: Vec::new(),
+        exports: Vec::new(),
+        opens: Vec::new(),
+        uses: Vec::new(),
+        provides: Vec::new()
+      });
+    }
+    ".requires" => {
+      let (flags, requires) = flagged_name(tokens, REQUIRES_FLAGS, is_version)?;
+      let required = Requires {
+        requires: requires.to_string(),
+        requires_flags: RequiresFlags::from_mask(flags),
+        requires_version: version(tokens)?
+      };
+      module_table(tokens, attributes, |attribute| match attribute {
+        Attribute::Module { requires, .. } => Some(requires),
+        _ => None
+      })?
+      .push(required);
+    }
+    ".exports" => {
+      let (flags, exports) = flagged_name(tokens, EXPORTS_FLAGS, is_to)?;
+      let exported = Exports {
+        exports: exports.to_string(),
+        exports_flags: ExportsFlags::from_mask(flags),
+        exports_to: modules_to(tokens)?
+      };
+      module_table(tokens, attributes, |attribute| match attribute {
+        Attribute::Module { exports, .. } => Some(exports),
+        _ => None
+      })?
+      .push(exported);
+    }
+    ".opens" => {
+      let (flags, opens) = flagged_name(tokens, EXPORTS_FLAGS, is_to)?;
+      let opened = Opens {
+        opens: opens.to_string(),
+        opens_flags: OpensFlags::from_mask(flags),
+        opens_to: modules_to(tokens)?
+      };
+      module_table(tokens, attributes, |attribute| match attribute {
+        Attribute::Module { opens, .. } => Some(opens),
+        _ => None
+      })?
+      .push(opened);
+    }
+    ".uses" => {
+      let service = Class { name: tokens.word("a class name")?.to_string() };
+      module_table(tokens, attributes, |attribute| match attribute {
+        Attribute::Module { uses, .. } => Some(uses),
+        _ => None
+      })?
+      .push(service);
+    }
+    ".provides" => {
+      let provides = Class { name: tokens.word("a class name")?.to_string() };
+      tokens.keyword("with")?;
+      let mut provides_with = Vec::new();
+      while !tokens.is_empty() {
+        provides_with.push(Class { name: tokens.word("a class name")?.to_string() });
+      }
+      module_table(tokens, attributes, |attribute| match attribute {
+        Attribute::Module { provides, .. } => Some(provides),
+        _ => None
+      })?
+      .push(Provides { provides, provides_with });
+    }
+    ".modulepackage" => {
+      let package = tokens.word("a package name")?.to_string();
+      table(attributes, Attribute::ModulePackages { packages: Vec::new() }, |attribute| {
+        match attribute {
+          Attribute::ModulePackages { packages } => Some(packages),
+          _ => None
+        }
+      })
+      .push(package);
+    }
+    ".modulemainclass" => {
+      let main_class = Class { name: tokens.word("a class name")?.to_string() };
+      attributes.push(Attribute::ModuleMainClass { main_class });
+    }
+    _ => return Ok(false)
+  }
+  Ok(true)
+}
+
+/// Flag keywords or numbers, then a name, as the words before the rest of the line that `stop`
+/// matches. The name is the last of them, so one that is also a flag keyword, such as a module
+/// named `transitive`, still reads back.
+fn flagged_name<'a>(
+  tokens: &mut Tokens<'a>,
+  names: &[FlagName],
+  stop: fn(&[Token]) -> bool
+) -> Result<(u16, &'a str), AssembleError> {
+  let mut words = Vec::new();
+  while let [Token::Word(word), ..] = tokens.rest() {
+    if stop(tokens.rest()) {
+      break;
+    }
+    words.push(word.as_str());
+    tokens.next_token();
+  }
+  let (name, words) =
+    words.split_last().ok_or_else(|| tokens.error("Expected a name".to_string()))?;
+  Ok((flags(tokens, words, names)?, name))
+}
+
+fn is_version(rest: &[Token]) -> bool {
+  matches!(rest, [Token::Word(word), Token::String(_), ..] if word == "version")
+}
+
+fn is_to(rest: &[Token]) -> bool { matches!(rest, [Token::Word(word), ..] if word == "to") }
+
+/// A module's version as `version "<version>"`, if it is given.
+fn version(tokens: &mut Tokens) -> Result<Option<String>, AssembleError> {
+  match tokens.next_is("version") {
+    true => Ok(Some(tokens.text("a version")?)),
+    false => Ok(None)
+  }
+}
+
+/// The modules after `to` that a package is exported or opened to, or none for every module.
+fn modules_to(tokens: &mut Tokens) -> Result<Vec<String>, AssembleError> {
+  let mut modules = Vec::new();
+  if tokens.next_is("to") {
+    while !tokens.is_empty() {
+      modules.push(tokens.word("a module name")?.to_string());
+    }
+  }
+  Ok(modules)
+}
+
+/// The table of the `Module` attribute that `find` picks out, which a `.module` directive must
+/// have started.
+fn module_table<'a, T>(
+  tokens: &Tokens,
+  attributes: &'a mut [Attribute],
+  find: fn(&mut Attribute) -> Option<&mut Vec<T>>
+) -> Result<&'a mut Vec<T>, AssembleError> {
+  attributes
+    .iter_mut()
+    .find_map(find)
+    .ok_or_else(|| tokens.error("Expected .module before the module's directives".to_string()))
+}
+
+/// `.annotation` then `visible` or `invisible` and an annotation, `default` and the value of an
+/// annotation interface's element, or annotations on parameters: `parameters`, the visibility and
+/// how many parameters there are, then `parameter`, the visibility, a parameter's position and an
+/// annotation on it for each. `type` and the visibility start an annotation on a type, which
+/// outside code can't apply to an instruction or local variable.
+fn annotation_directive(
+  tokens: &mut Tokens,
+  attributes: &mut Vec<Attribute>
+) -> Result<(), AssembleError> {
+  match tokens.word("visible, invisible, default, parameters, parameter or type")? {
+    "visible" => {
+      let annotation = annotation(tokens)?;
+      table(
+        attributes,
+        Attribute::RuntimeVisibleAnnotations { annotations: Vec::new() },
+        |attribute| match attribute {
+          Attribute::RuntimeVisibleAnnotations { annotations } => Some(annotations),
+          _ => None
+        }
+      )
+      .push(annotation);
+    }
+    "invisible" => {
+      let annotation = annotation(tokens)?;
+      table(
+        attributes,
+        Attribute::RuntimeInvisibleAnnotations { annotations: Vec::new() },
+        |attribute| match attribute {
+          Attribute::RuntimeInvisibleAnnotations { annotations } => Some(annotations),
+          _ => None
+        }
+      )
+      .push(annotation);
+    }
+    "default" => {
+      attributes.push(Attribute::AnnotationDefault { default_value: element_value(tokens)? })
+    }
+    "parameters" => {
+      let visible = visibility(tokens)?;
+      let count: u8 = tokens.number("a number of parameters")?;
+      let parameter_annotations =
+        vec![ParameterAnnotation { annotations: Vec::new() }; count as usize];
+      attributes.push(match visible {
+        true => Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations },
+        false => Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations }
+      });
+    }
+    "parameter" => {
+      let visible = visibility(tokens)?;
+      let position: usize = tokens.number("a parameter position")?;
+      let annotation = annotation(tokens)?;
+      let parameters = attributes.iter_mut().rev().find_map(|attribute| match attribute {
+        Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } if visible => {
+          Some(parameter_annotations)
+        }
+        Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } if !visible => {
+          Some(parameter_annotations)
+        }
+        _ => None
+      });
+      let parameter = parameters.and_then(|parameters| parameters.get_mut(position));
+      let message =
+        format!("Parameter {} is not among those .annotation parameters gives", position);
+      parameter.ok_or_else(|| tokens.error(message))?.annotations.push(annotation);
+    }
+    "type" => {
+      let visible = visibility(tokens)?;
+      let annotation = type_annotation(tokens, None)?;
+      add_type_annotation(attributes, visible, annotation);
+    }
+    kind => {
+      let message =
+        format!("Expected visible, invisible, default, parameters or type, not {}", kind);
+      return Err(tokens.error(message));
+    }
+  }
+  Ok(())
+}
+
+pub(super) fn visibility(tokens: &mut Tokens) -> Result<bool, AssembleError> {
+  match tokens.word("visible or invisible")? {
+    "visible" => Ok(true),
+    "invisible" => Ok(false),
+    word => Err(tokens.error(format!("Expected visible or invisible, not {}", word)))
+  }
+}
+
+/// A type annotation as `jasmin::type_annotations` writes it after `.annotation type` and the
+/// visibility: what it applies to, the steps into the type after `path`, if any, then the
+/// annotation.
+/// Inside code, `positions` gives the instruction index of each label.
+pub(super) fn type_annotation(
+  tokens: &mut Tokens,
+  positions: Option<&HashMap<String, usize>>
+) -> Result<TypeAnnotation, AssembleError> {
+  let target = tokens.word("what the annotation applies to")?;
+  let target_type = TARGET_TYPES
+    .iter()
+    .find(|(_, name)| name.to_lowercase() == target)
+    .map(|(target_type, _)| *target_type)
+    .ok_or_else(|| tokens.error(format!("Unknown type annotation target {}", target)))?;
+  // Code targets, even those without an offset, belong to the Code attribute
+  if positions.is_none() && target_type >= 0x40 {
+    return Err(tokens.error(format!("A {} annotation belongs in a method's code", target)));
+  }
+  let label = |tokens: &mut Tokens| -> Result<u16, AssembleError> {
+    let label = tokens.word("a label")?;
+    let position = positions.and_then(|positions| positions.get(label));
+    position.map(|position| *position as u16).ok_or_else(|| AssembleError::UndefinedLabel {
+      line: tokens.line(),
+      label: label.to_string()
+    })
+  };
+  let target_info = match target_type {
+    0x00 | 0x01 => {
+      TargetInfo::TypeParameter { type_parameter_index: tokens.number("a type parameter index")? }
+    }
+    0x10 => TargetInfo::Supertype { supertype_index: tokens.number("a supertype index")? },
+    0x11 | 0x12 => TargetInfo::TypeParameterBound {
+      type_parameter_index: tokens.number("a type parameter index")?,
+      bound_index: tokens.number("a bound index")?
+    },
+    0x13..=0x15 => TargetInfo::Empty,
+    0x16 => {
+      TargetInfo::FormalParameter { formal_parameter_index: tokens.number("a parameter index")? }
+    }
+    0x17 => TargetInfo::Throws { throws_type_index: tokens.number("a throws index")? },
+    0x40 | 0x41 => {
+      // `{ <local> from <label> to <label>, ... }`
+      tokens.punct('{')?;
+      let mut table = Vec::new();
+      while tokens.peek() != Some(&Token::Punct('}')) {
+        if !table.is_empty() {
+          tokens.punct(',')?;
+        }
+        let index = tokens.number("a local variable")?;
+        tokens.keyword("from")?;
+        let start_pc = label(tokens)?;
+        tokens.keyword("to")?;
+        let length = label(tokens)?.saturating_sub(start_pc);
+        table.push(LocalvarTarget { start_pc, length, index });
+      }
+      tokens.next_token();
+      TargetInfo::Localvar { table }
+    }
+    0x42 => TargetInfo::Catch { exception_table_index: tokens.number("an exception table index")? },
+    0x43..=0x46 => TargetInfo::Offset { offset: label(tokens)? },
+    _ => TargetInfo::TypeArgument {
+      offset: label(tokens)?,
+      type_argument_index: tokens.number("a type argument index")?
+    }
+  };
+  let mut type_path = Vec::new();
+  if tokens.next_is("path") {
+    loop {
+      let type_path_kind = match tokens.peek() {
+        Some(Token::Word(word)) if word == "array" => TypePathKind::Array,
+        Some(Token::Word(word)) if word == "nested" => TypePathKind::Nested,
+        Some(Token::Word(word)) if word == "wildcard" => TypePathKind::WildcardBound,
+        Some(Token::Word(word)) if word == "argument" => TypePathKind::TypeArgument,
+        _ => break
+      };
+      tokens.next_token();
+      // Written for a type argument, and for any other step whose index isn't 0
+      let type_argument_index = match tokens.peek() {
+        Some(Token::Word(word)) if word.parse::<u8>().is_ok() => tokens.number("an index")?,
+        _ => 0
+      };
+      type_path.push(TypePathEntry { type_path_kind, type_argument_index });
+    }
+  }
+  Ok(TypeAnnotation { target_type, target_info, type_path, annotation: annotation(tokens)? })
+}
+
+/// Adds to the visible or invisible type annotations in `attributes`.
+pub(super) fn add_type_annotation(
+  attributes: &mut Vec<Attribute>,
+  visible: bool,
+  annotation: TypeAnnotation
+) {
+  let annotations = match visible {
+    true => table(
+      attributes,
+      Attribute::RuntimeVisibleTypeAnnotations { annotations: Vec::new() },
+      |attribute| match attribute {
+        Attribute::RuntimeVisibleTypeAnnotations { annotations } => Some(annotations),
+        _ => None
+      }
+    ),
+    false => table(
+      attributes,
+      Attribute::RuntimeInvisibleTypeAnnotations { annotations: Vec::new() },
+      |attribute| match attribute {
+        Attribute::RuntimeInvisibleTypeAnnotations { annotations } => Some(annotations),
+        _ => None
+      }
+    )
+  };
+  annotations.push(annotation);
+}
+
+/// An annotation as `jasmin::annotation` writes it: its type, then its elements in braces if it has
+/// any.
+fn annotation(tokens: &mut Tokens) -> Result<Annotation, AssembleError> {
+  let type_name = tokens.word("an annotation type")?.to_string();
+  let mut element_value_pairs = Vec::new();
+  if tokens.peek() == Some(&Token::Punct('{')) {
+    tokens.next_token();
+    loop {
+      let name = tokens.word("an element name")?.to_string();
+      tokens.keyword("=")?;
+      element_value_pairs.push((name, element_value(tokens)?));
+      match tokens.next_token() {
+        Some(Token::Punct(',')) => {}
+        Some(Token::Punct('}')) => break,
+        _ => return Err(tokens.error("Expected , or }".to_string()))
+      }
+    }
+  }
+  Ok(Annotation { type_name, element_value_pairs })
+}
+
+/// An element value as `jasmin::element_value` writes it: its tag, then the value.
+fn element_value(tokens: &mut Tokens) -> Result<ElementValue, AssembleError> {
+  Ok(match tokens.word("an element value tag")? {
+    "B" => ElementValue::Byte(tokens.number("a byte")?),
+    "C" => ElementValue::Char(tokens.number("a char")?),
+    "S" => ElementValue::Short(tokens.number("a short")?),
+    "Z" => ElementValue::Boolean(tokens.number("true or false")?),
+    tag @ ("I" | "J" | "F" | "D") => match (tag, constant(tokens)?) {
+      ("I", ResolvedCpInfo::Integer(value)) => ElementValue::Int(value),
+      ("J", ResolvedCpInfo::Long(value)) => ElementValue::Long(value),
+      ("F", ResolvedCpInfo::Float(value)) => ElementValue::Float(value),
+      ("D", ResolvedCpInfo::Double(value)) => ElementValue::Double(value),
+      _ => return Err(tokens.error(format!("Expected a value of type {}", tag)))
+    },
+    "s" => match tokens.next_token() {
+      Some(Token::String(units)) => ElementValue::String(JavaString { chars: units.clone() }),
+      _ => return Err(tokens.error("Expected a string in quotes".to_string()))
+    },
+    "e" => ElementValue::Enum {
+      type_name: tokens.word("an enum type")?.to_string(),
+      const_name: tokens.word("an enum constant")?.to_string()
+    },
+    "c" => ElementValue::Class(tokens.word("a descriptor")?.to_string()),
+    "@" => ElementValue::Annotation(annotation(tokens)?),
+    "[" => {
+      let mut values = Vec::new();
+      while !tokens.next_is("]") {
+        if !values.is_empty() {
+          tokens.punct(',')?;
+        }
+        values.push(element_value(tokens)?);
+      }
+      ElementValue::Array(values)
+    }
+    tag => return Err(tokens.error(format!("Unknown element value tag {}", tag)))
+  })
+}
+
+/// The table `find` picks out of `attributes`, with `empty` added first if there isn't one yet.
+pub(super) fn table<T>(
+  attributes: &mut Vec<Attribute>,
+  empty: Attribute,
+  find: fn(&mut Attribute) -> Option<&mut Vec<T>>
+) -> &mut Vec<T> {
+  let position = match attributes.iter_mut().position(|attribute| find(attribute).is_some()) {
+    Some(position) => position,
+    None => {
+      attributes.push(empty);
+      attributes.len() - 1
+    }
+  };
+  find(&mut attributes[position]).unwrap()
+}
@@ -0,0 +1,501 @@
+use std::{rc::Rc, str::FromStr};
+
+use super::{
+  assemble_error::AssembleError,
+  lexer::{Line, Token, Tokens},
+  method::MethodAssembler
+};
+use crate::{
+  builder::method_builder::Label,
+  disassembler::{
+    jasmin::{ARRAY_TYPES, WIDE_OPCODES},
+    text::reference_kind
+  },
+  parser::{
+    attribute_info::{
+      attribute::bootstrap_method::BootstrapMethod,
+      code::code_generator::Instructions
+    },
+    cp_info_resolved::{
+      Class,
+      Dynamic,
+      Fieldref,
+      InterfaceMethodref,
+      InvokeDynamic,
+      JavaString,
+      MethodHandle,
+      MethodHandleRef,
+      MethodType,
+      Methodref,
+      NameAndType,
+      ReferenceKind,
+      ResolvedCpInfo
+    },
+    descriptor::MethodDescriptor
+  }
+};
+
+impl MethodAssembler<'_> {
+  /// Adds the instruction on a line, with `cases` holding the lines that list a switch's targets.
+  pub(super) fn instruction(
+    &mut self,
+    tokens: &mut Tokens,
+    cases: &[Line]
+  ) -> Result<(), AssembleError> {
+    let mnemonic = tokens.word("an instruction")?;
+    let short_form =
+      mnemonic.rsplit_once('_').and_then(|(mnemonic, operand)| short_form(mnemonic, operand));
+    if let Some(instruction) = simple(mnemonic).or(short_form) {
+      tokens.end()?;
+      self.method.emit(instruction);
+      return Ok(());
+    }
+    let instruction = match mnemonic {
+      "bipush" => Instructions::bipush { value: tokens.number("a byte")? },
+      "sipush" => Instructions::sipush { value: tokens.number("a short")? },
+      "ldc" => Instructions::ldc { constant: constant(tokens)? },
+      "ldc_w" => Instructions::ldc_w { constant: constant(tokens)? },
+      "ldc2_w" => Instructions::ldc2_w { constant: constant(tokens)? },
+      "iload" | "lload" | "fload" | "dload" | "aload" | "istore" | "lstore" | "fstore"
+      | "dstore" | "astore" | "ret" => {
+        let index: u16 = tokens.number("a local variable")?;
+        match u8::try_from(index) {
+          Ok(index) => local(mnemonic, index),
+          // Too big for the instruction, so extended with `wide`, as Jasmin does
+          Err(_) => Instructions::wide1 { opcode: wide_opcode(mnemonic), index_extension: index }
+        }
+      }
+      "iinc" => {
+        let index: u16 = tokens.number("a local variable")?;
+        let constant: i16 = tokens.number("an increment")?;
+        match (u8::try_from(index), i8::try_from(constant)) {
+          (Ok(index), Ok(constant)) => Instructions::iinc { index, r#const: constant },
+          _ => Instructions::wide2 { opcode: 132, index_extension: index, constbytes: constant }
+        }
+      }
+      "wide" => match tokens.word("an instruction to widen")? {
+        "iinc" => Instructions::wide2 {
+          opcode: 132,
+          index_extension: tokens.number("a local variable")?,
+          constbytes: tokens.number("an increment")?
+        },
+        widened if WIDE_OPCODES.iter().any(|(_, name)| *name == widened) => Instructions::wide1 {
+          opcode: wide_opcode(widened),
+          index_extension: tokens.number("a local variable")?
+        },
+        widened => return Err(tokens.error(format!("{} can't be widened", widened)))
+      },
+      "ifeq" | "ifne" | "iflt" | "ifge" | "ifgt" | "ifle" | "if_icmpeq" | "if_icmpne"
+      | "if_icmplt" | "if_icmpge" | "if_icmpgt" | "if_icmple" | "if_acmpeq" | "if_acmpne"
+      | "ifnull" | "ifnonnull" | "goto" | "jsr" | "goto_w" | "jsr_w" => {
+        let label = self.label(tokens.word("a label")?, tokens.line())?;
+        tokens.end()?;
+        self.method.jump(branch(mnemonic), label);
+        return Ok(());
+      }
+      "tableswitch" => {
+        let low: i32 = tokens.number("the lowest key")?;
+        let high: i32 = tokens.number("the highest key")?;
+        tokens.end()?;
+        let (default, cases) = cases.split_last().expect("a switch ends with its default");
+        if high as i64 - low as i64 + 1 != cases.len() as i64 {
+          let message =
+            format!("Expected {} labels from {} to {}", high as i64 - low as i64 + 1, low, high);
+          return Err(tokens.error(message));
+        }
+        let targets = cases
+          .iter()
+          .map(|case| {
+            let mut tokens = Tokens::new(case.number, &case.tokens);
+            let label = self.label(tokens.word("a label")?, case.number)?;
+            tokens.end()?;
+            Ok(label)
+          })
+          .collect::<Result<Vec<_>, AssembleError>>()?;
+        let default = self.default(default)?;
+        self.method.tableswitch(low, default, &targets);
+        return Ok(());
+      }
+      "lookupswitch" => {
+        tokens.end()?;
+        let (default, cases) = cases.split_last().expect("a switch ends with its default");
+        let pairs = cases
+          .iter()
+          .map(|case| {
+            let mut tokens = Tokens::new(case.number, &case.tokens);
+            let key = tokens.number("a key")?;
+            tokens.punct(':')?;
+            let label = self.label(tokens.word("a label")?, case.number)?;
+            tokens.end()?;
+            Ok((key, label))
+          })
+          .collect::<Result<Vec<_>, AssembleError>>()?;
+        let default = self.default(default)?;
+        self.method.lookupswitch(default, &pairs);
+        return Ok(());
+      }
+      "getstatic" => Instructions::getstatic { fieldref: fieldref(tokens)? },
+      "putstatic" => Instructions::putstatic { fieldref: fieldref(tokens)? },
+      "getfield" => Instructions::getfield { fieldref: fieldref(tokens)? },
+      "putfield" => Instructions::putfield { fieldref: fieldref(tokens)? },
+      "invokevirtual" => Instructions::invokevirtual { methodref: methodref(tokens)? },
+      "invokespecial" => Instructions::invokespecial { methodref: methodref(tokens)? },
+      "invokestatic" => Instructions::invokestatic { methodref: methodref(tokens)? },
+      "invokeinterface" => {
+        let Methodref { class, name_and_type } = methodref(tokens)?;
+        // The count can be left out, as it follows from the descriptor
+        let count = match tokens.is_empty() {
+          true => {
+            let descriptor = MethodDescriptor::parse(&name_and_type.descriptor)
+              .map_err(|error| AssembleError::ClassFormatError { line: tokens.line(), error })?;
+            descriptor.param_slots() as u8 + 1
+          }
+          false => tokens.number("an argument count")?
+        };
+        let interfacemethodref = InterfaceMethodref { class, name_and_type };
+        Instructions::invokeinterface { interfacemethodref, count }
+      }
+      "invokedynamic" => {
+        let name_and_type = name_and_type(tokens)?;
+        let bootstrap_method_attr = bootstrap_method(tokens)?;
+        Instructions::invokedynamic {
+          invokedynamic: InvokeDynamic {
+            bootstrap_method_attr_index: 0,
+            bootstrap_method_attr,
+            name_and_type
+          }
+        }
+      }
+      "new" => Instructions::new { class: class(tokens)? },
+      "anewarray" => Instructions::anewarray { class: class(tokens)? },
+      "checkcast" => Instructions::checkcast { class: class(tokens)? },
+      "instanceof" => Instructions::instanceof { class: class(tokens)? },
+      "multianewarray" => Instructions::multianewarray {
+        class: class(tokens)?,
+        dimensions: tokens.number("a number of dimensions")?
+      },
+      "newarray" => {
+        let element = tokens.word("an element type")?;
+        let atype = match ARRAY_TYPES.iter().find(|(_, name)| *name == element) {
+          Some((atype, _)) => *atype,
+          None => element.parse().map_err(|_| tokens.error(format!("Unknown type {}", element)))?
+        };
+        Instructions::newarray { atype }
+      }
+      mnemonic => {
+        return Err(AssembleError::UnknownInstruction {
+          line: tokens.line(),
+          mnemonic: mnemonic.to_string()
+        })
+      }
+    };
+    tokens.end()?;
+    self.method.emit(instruction);
+    Ok(())
+  }
+
+  /// The label of a switch's `default : L1` line.
+  fn default(&mut self, line: &Line) -> Result<Label, AssembleError> {
+    let mut tokens = Tokens::new(line.number, &line.tokens);
+    tokens.keyword("default")?;
+    tokens.punct(':')?;
+    let label = self.label(tokens.word("a label")?, line.number)?;
+    tokens.end()?;
+    Ok(label)
+  }
+}
+
+/// A loadable constant as `jasmin::constant` writes it.
+pub(super) fn constant(tokens: &mut Tokens) -> Result<ResolvedCpInfo, AssembleError> {
+  let word = match tokens.next_token() {
+    Some(Token::String(units)) => {
+      return Ok(ResolvedCpInfo::String(JavaString { chars: units.clone() }))
+    }
+    Some(Token::Word(word)) => word.as_str(),
+    _ => return Err(tokens.error("Expected a constant".to_string()))
+  };
+  Ok(match word {
+    "Class" => ResolvedCpInfo::Class(class(tokens)?),
+    "MethodType" => ResolvedCpInfo::MethodType(MethodType {
+      descriptor: tokens.word("a descriptor")?.to_string()
+    }),
+    "MethodHandle" => ResolvedCpInfo::MethodHandle(method_handle(tokens)?),
+    "Dynamic" => {
+      let name_and_type = name_and_type(tokens)?;
+      ResolvedCpInfo::Dynamic(Dynamic {
+        bootstrap_method_attr_index: 0,
+        bootstrap_method_attr: bootstrap_method(tokens)?,
+        name_and_type
+      })
+    }
+    number => {
+      let bad = || tokens.error(format!("Expected a constant, not {}", number));
+      if let Some(value) = number.strip_suffix('L') {
+        ResolvedCpInfo::Long(value.parse().map_err(|_| bad())?)
+      } else if let Some(value) = number.strip_suffix('f') {
+        let from_bits = |bits| Some(f32::from_bits(u32::try_from(bits).ok()?));
+        ResolvedCpInfo::Float(floating(value, from_bits).ok_or_else(bad)?)
+      } else if let Ok(value) = number.parse() {
+        ResolvedCpInfo::Integer(value)
+      } else {
+        ResolvedCpInfo::Double(floating(number, |bits| Some(f64::from_bits(bits))).ok_or_else(bad)?)
+      }
+    }
+  })
+}
+
+/// A float or double, which may be `Infinity`, `-Infinity`, `NaN` or `NaN<0x..>` with its bits.
+fn floating<T: FromStr>(text: &str, from_bits: fn(u64) -> Option<T>) -> Option<T> {
+  if let Some(hex) = text.strip_prefix("NaN<0x").and_then(|text| text.strip_suffix('>')) {
+    return from_bits(u64::from_str_radix(hex, 16).ok()?);
+  }
+  match text {
+    "Infinity" => "inf",
+    "-Infinity" => "-inf",
+    text => text
+  }
+  .parse()
+  .ok()
+}
+
+fn class(tokens: &mut Tokens) -> Result<Class, AssembleError> {
+  Ok(Class { name: tokens.word("a class name")?.to_string() })
+}
+
+fn name_and_type(tokens: &mut Tokens) -> Result<NameAndType, AssembleError> {
+  let name = tokens.word("a name")?.to_string();
+  Ok(NameAndType { name, descriptor: tokens.word("a descriptor")?.to_string() })
+}
+
+/// A field as `java/lang/System/out Ljava/io/PrintStream;`.
+fn fieldref(tokens: &mut Tokens) -> Result<Fieldref, AssembleError> {
+  let reference = tokens.word("a field")?;
+  let (class, name) = reference
+    .rsplit_once('/')
+    .ok_or_else(|| tokens.error(format!("Expected a class before field {}", reference)))?;
+  let descriptor = tokens.word("a descriptor")?.to_string();
+  Ok(Fieldref {
+    class: Class { name: class.to_string() },
+    name_and_type: NameAndType { name: name.to_string(), descriptor }
+  })
+}
+
+/// A method as `java/lang/Object/<init>()V`.
+fn methodref(tokens: &mut Tokens) -> Result<Methodref, AssembleError> {
+  let reference = tokens.word("a method")?;
+  let bad = || tokens.error(format!("Expected a class, name and descriptor, not {}", reference));
+  let (owner, descriptor) = reference.split_at(reference.find('(').ok_or_else(bad)?);
+  let (class, name) = owner.rsplit_once('/').ok_or_else(bad)?;
+  Ok(Methodref {
+    class: Class { name: class.to_string() },
+    name_and_type: NameAndType { name: name.to_string(), descriptor: descriptor.to_string() }
+  })
+}
+
+/// A method handle as `REF_invokeStatic Method java/lang/Integer/valueOf(I)Ljava/lang/Integer;`.
+fn method_handle(tokens: &mut Tokens) -> Result<MethodHandle, AssembleError> {
+  let kind = tokens.word("a reference kind")?;
+  let reference_kind = (1..=9)
+    .filter_map(ReferenceKind::from)
+    .find(|reference_kind| self::reference_kind(*reference_kind) == kind)
+    .ok_or_else(|| tokens.error(format!("Unknown reference kind {}", kind)))?;
+  let reference = match tokens.word("Field, Method or InterfaceMethod")? {
+    "Field" => MethodHandleRef::Field(fieldref(tokens)?),
+    "Method" => MethodHandleRef::Method(methodref(tokens)?),
+    "InterfaceMethod" => {
+      let Methodref { class, name_and_type } = methodref(tokens)?;
+      MethodHandleRef::InterfaceMethod(InterfaceMethodref { class, name_and_type })
+    }
+    other => {
+      return Err(tokens.error(format!("Expected Field, Method or InterfaceMethod, not {}", other)))
+    }
+  };
+  Ok(MethodHandle { reference_kind, reference })
+}
+
+/// A bootstrap method as its method handle, then its arguments in braces.
+fn bootstrap_method(tokens: &mut Tokens) -> Result<Rc<BootstrapMethod>, AssembleError> {
+  let bootstrap_method = method_handle(tokens)?;
+  tokens.punct('{')?;
+  let mut bootstrap_arguments = Vec::new();
+  if tokens.peek() == Some(&Token::Punct('}')) {
+    tokens.next_token();
+    return Ok(Rc::new(BootstrapMethod { bootstrap_method, bootstrap_arguments }));
+  }
+  loop {
+    bootstrap_arguments.push(constant(tokens)?);
+    match tokens.next_token() {
+      Some(Token::Punct(',')) => {}
+      Some(Token::Punct('}')) => {
+        return Ok(Rc::new(BootstrapMethod { bootstrap_method, bootstrap_arguments }))
+      }
+      _ => return Err(tokens.error("Expected , or }".to_string()))
+    }
+  }
+}
+
+fn local(mnemonic: &str, index: u8) -> Instructions {
+  match mnemonic {
+    "iload" => Instructions::iload { index },
+    "lload" => Instructions::lload { index },
+    "fload" => Instructions::fload { index },
+    "dload" => Instructions::dload { index },
+    "aload" => Instructions::aload { index },
+    "istore" => Instructions::istore { index },
+    "lstore" => Instructions::lstore { index },
+    "fstore" => Instructions::fstore { index },
+    "dstore" => Instructions::dstore { index },
+    "astore" => Instructions::astore { index },
+    _ => Instructions::ret { index }
+  }
+}
+
+fn wide_opcode(mnemonic: &str) -> u8 {
+  WIDE_OPCODES.iter().find(|(_, name)| *name == mnemonic).map_or(169, |(opcode, _)| *opcode)
+}
+
+/// Instructions such as `iload_1` and `iconst_m1`, which have their operand in their name.
+fn short_form(mnemonic: &str, operand: &str) -> Option<Instructions> {
+  let value: i8 = match operand {
+    "m1" => -1,
+    operand => operand.parse().ok()?
+  };
+  Some(match (mnemonic, value) {
+    ("iconst", -1..=5) => Instructions::iconst { value: value as i32 },
+    ("lconst", 0..=1) => Instructions::lconst { value: value as i64 },
+    ("fconst", 0..=2) => Instructions::fconst { value: value as f32 },
+    ("dconst", 0..=1) => Instructions::dconst { value: value as f64 },
+    (
+      "iload" | "lload" | "fload" | "dload" | "aload" | "istore" | "lstore" | "fstore" | "dstore"
+      | "astore",
+      0..=3
+    ) => local(mnemonic, value as u8),
+    _ => return None
+  })
+}
+
+/// A branch waiting for `MethodBuilder::jump` to point it at its label.
+fn branch(mnemonic: &str) -> Instructions {
+  match mnemonic {
+    "ifeq" => Instructions::ifeq { offset: 0 },
+    "ifne" => Instructions::ifne { offset: 0 },
+    "iflt" => Instructions::iflt { offset: 0 },
+    "ifge" => Instructions::ifge { offset: 0 },
+    "ifgt" => Instructions::ifgt { offset: 0 },
+    "ifle" => Instructions::ifle { offset: 0 },
+    "if_icmpeq" => Instructions::if_icmpeq { offset: 0 },
+    "if_icmpne" => Instructions::if_icmpne { offset: 0 },
+    "if_icmplt" => Instructions::if_icmplt { offset: 0 },
+    "if_icmpge" => Instructions::if_icmpge { offset: 0 },
+    "if_icmpgt" => Instructions::if_icmpgt { offset: 0 },
+    "if_icmple" => Instructions::if_icmple { offset: 0 },
+    "if_acmpeq" => Instructions::if_acmpeq { offset: 0 },
+    "if_acmpne" => Instructions::if_acmpne { offset: 0 },
+    "ifnull" => Instructions::ifnull { offset: 0 },
+    "ifnonnull" => Instructions::ifnonnull { offset: 0 },
+    "goto" => Instructions::goto { offset: 0 },
+    "jsr" => Instructions::jsr { offset: 0 },
+    "goto_w" => Instructions::goto_w { offset: 0 },
+    _ => Instructions::jsr_w { offset: 0 }
+  }
+}
+
+/// The instructions without operands.
+fn simple(mnemonic: &str) -> Option<Instructions> {
+  Some(match mnemonic {
+    "nop" => Instructions::nop,
+    "aconst_null" => Instructions::aconst_null,
+    "iaload" => Instructions::iaload,
+    "laload" => Instructions::laload,
+    "faload" => Instructions::faload,
+    "daload" => Instructions::daload,
+    "aaload" => Instructions::aaload,
+    "baload" => Instructions::baload,
+    "caload" => Instructions::caload,
+    "saload" => Instructions::saload,
+    "iastore" => Instructions::iastore,
+    "lastore" => Instructions::lastore,
+    "fastore" => Instructions::fastore,
+    "dastore" => Instructions::dastore,
+    "aastore" => Instructions::aastore,
+    "bastore" => Instructions::bastore,
+    "castore" => Instructions::castore,
+    "sastore" => Instructions::sastore,
+    "pop" => Instructions::pop,
+    "pop2" => Instructions::pop2,
+    "dup" => Instructions::dup,
+    "dup_x1" => Instructions::dup_x1,
+    "dup_x2" => Instructions::dup_x2,
+    "dup2" => Instructions::dup2,
+    "dup2_x1" => Instructions::dup2_x1,
+    "dup2_x2" => Instructions::dup2_x2,
+    "swap" => Instructions::swap,
+    "iadd" => Instructions::iadd,
+    "ladd" => Instructions::ladd,
+    "fadd" => Instructions::fadd,
+    "dadd" => Instructions::dadd,
+    "isub" => Instructions::isub,
+    "lsub" => Instructions::lsub,
+    "fsub" => Instructions::fsub,
+    "dsub" => Instructions::dsub,
+    "imul" => Instructions::imul,
+    "lmul" => Instructions::lmul,
+    "fmul" => Instructions::fmul,
+    "dmul" => Instructions::dmul,
+    "idiv" => Instructions::idiv,
+    "ldiv" => Instructions::ldiv,
+    "fdiv" => Instructions::fdiv,
+    "ddiv" => Instructions::ddiv,
+    "irem" => Instructions::irem,
+    "lrem" => Instructions::lrem,
+    "frem" => Instructions::frem,
+    "drem" => Instructions::drem,
+    "ineg" => Instructions::ineg,
+    "lneg" => Instructions::lneg,
+    "fneg" => Instructions::fneg,
+    "dneg" => Instructions::dneg,
+    "ishl" => Instructions::ishl,
+    "lshl" => Instructions::lshl,
+    "ishr" => Instructions::ishr,
+    "lshr" => Instructions::lshr,
+    "iushr" => Instructions::iushr,
+    "lushr" => Instructions::lushr,
+    "iand" => Instructions::iand,
+    "land" => Instructions::land,
+    "ior" => Instructions::ior,
+    "lor" => Instructions::lor,
+    "ixor" => Instructions::ixor,
+    "lxor" => Instructions::lxor,
+    "i2l" => Instructions::i2l,
+    "i2f" => Instructions::i2f,
+    "i2d" => Instructions::i2d,
+    "l2i" => Instructions::l2i,
+    "l2f" => Instructions::l2f,
+    "l2d" => Instructions::l2d,
+    "f2i" => Instructions::f2i,
+    "f2l" => Instructions::f2l,
+    "f2d" => Instructions::f2d,
+    "d2i" => Instructions::d2i,
+    "d2l" => Instructions::d2l,
+    "d2f" => Instructions::d2f,
+    "i2b" => Instructions::i2b,
+    "i2c" => Instructions::i2c,
+    "i2s" => Instructions::i2s,
+    "lcmp" => Instructions::lcmp,
+    "fcmpl" => Instructions::fcmpl,
+    "fcmpg" => Instructions::fcmpg,
+    "dcmpl" => Instructions::dcmpl,
+    "dcmpg" => Instructions::dcmpg,
+    "ireturn" => Instructions::ireturn,
+    "lreturn" => Instructions::lreturn,
+    "freturn" => Instructions::freturn,
+    "dreturn" => Instructions::dreturn,
+    "areturn" => Instructions::areturn,
+    "return" => Instructions::r#return,
+    "arraylength" => Instructions::arraylength,
+    "athrow" => Instructions::athrow,
+    "monitorenter" => Instructions::monitorenter,
+    "monitorexit" => Instructions::monitorexit,
+    _ => return None
+  })
+}
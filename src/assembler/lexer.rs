@@ -0,0 +1,180 @@
+use std::{iter::Peekable, str::Chars, str::FromStr};
+
+use super::assemble_error::AssembleError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+  Word(String),
+  /// A quoted string, as UTF-16 code units so that it can hold any `java.lang.String`.
+  String(Vec<u16>),
+  /// `,`, `{` or `}`, or a `:` ending a word.
+  Punct(char)
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+  pub number: usize,
+  pub tokens: Vec<Token>
+}
+
+/// Splits the source into lines of tokens, leaving out blank lines and comments.
+/// A comment starts with a `;` at the start of a word, as descriptors have `;` inside them.
+pub fn lines(source: &str) -> Result<Vec<Line>, AssembleError> {
+  let mut lines = Vec::new();
+  for (number, text) in (1..).zip(source.lines()) {
+    let tokens = tokens(text, number)?;
+    if !tokens.is_empty() {
+      lines.push(Line { number, tokens });
+    }
+  }
+  Ok(lines)
+}
+
+fn tokens(text: &str, line: usize) -> Result<Vec<Token>, AssembleError> {
+  let mut tokens = Vec::new();
+  let mut word = String::new();
+  let mut chars = text.chars().peekable();
+  let end_word = |word: &mut String, tokens: &mut Vec<Token>| {
+    if !word.is_empty() {
+      tokens.push(Token::Word(std::mem::take(word)));
+    }
+  };
+  while let Some(char) = chars.next() {
+    match char {
+      ';' if word.is_empty() => break,
+      '"' => {
+        end_word(&mut word, &mut tokens);
+        tokens.push(Token::String(string(&mut chars, line)?));
+      }
+      ',' | '{' | '}' => {
+        end_word(&mut word, &mut tokens);
+        tokens.push(Token::Punct(char));
+      }
+      ':' if chars.peek().is_none_or(|next| next.is_whitespace()) => {
+        end_word(&mut word, &mut tokens);
+        tokens.push(Token::Punct(':'));
+      }
+      char if char.is_whitespace() => end_word(&mut word, &mut tokens),
+      char => word.push(char)
+    }
+  }
+  end_word(&mut word, &mut tokens);
+  Ok(tokens)
+}
+
+/// Reads a quoted string up to its closing quote, undoing the escapes `jasmin::quote` writes.
+fn string(chars: &mut Peekable<Chars>, line: usize) -> Result<Vec<u16>, AssembleError> {
+  let error = |message: &str| AssembleError::Syntax { line, message: message.to_string() };
+  let mut units = Vec::new();
+  loop {
+    match chars.next().ok_or_else(|| error("Unterminated string"))? {
+      '"' => return Ok(units),
+      '\\' => match chars.next().ok_or_else(|| error("Unterminated string"))? {
+        't' => units.push('\t' as u16),
+        'n' => units.push('\n' as u16),
+        'r' => units.push('\r' as u16),
+        'u' => {
+          let digits: String = chars.by_ref().take(4).collect();
+          let unit = u16::from_str_radix(&digits, 16)
+            .map_err(|_| error(&format!("Bad escape \\u{}", digits)))?;
+          units.push(unit);
+        }
+        char => units.extend(char.encode_utf16(&mut [0; 2]).iter())
+      },
+      char => units.extend(char.encode_utf16(&mut [0; 2]).iter())
+    }
+  }
+}
+
+/// Reads the tokens of a line in order.
+pub struct Tokens<'a> {
+  line: usize,
+  tokens: &'a [Token]
+}
+
+impl<'a> Tokens<'a> {
+  pub fn new(line: usize, tokens: &'a [Token]) -> Self { Tokens { line, tokens } }
+
+  pub fn line(&self) -> usize { self.line }
+
+  pub fn error(&self, message: String) -> AssembleError {
+    AssembleError::Syntax { line: self.line, message }
+  }
+
+  pub fn peek(&self) -> Option<&'a Token> { self.tokens.first() }
+
+  pub fn is_empty(&self) -> bool { self.tokens.is_empty() }
+
+  /// The tokens not yet read.
+  pub fn rest(&self) -> &'a [Token] { self.tokens }
+
+  pub fn next_token(&mut self) -> Option<&'a Token> {
+    let (first, rest) = self.tokens.split_first()?;
+    self.tokens = rest;
+    Some(first)
+  }
+
+  /// The next token, which must be a word; `what` says what it should be for the error otherwise.
+  pub fn word(&mut self, what: &str) -> Result<&'a str, AssembleError> {
+    match self.next_token() {
+      Some(Token::Word(word)) => Ok(word),
+      _ => Err(self.error(format!("Expected {}", what)))
+    }
+  }
+
+  /// Takes the next token if it is the word `word`.
+  pub fn next_is(&mut self, word: &str) -> bool {
+    match self.peek() {
+      Some(Token::Word(next)) if next == word => {
+        self.next_token();
+        true
+      }
+      _ => false
+    }
+  }
+
+  /// The next token, which must be the word `word`.
+  pub fn keyword(&mut self, word: &str) -> Result<(), AssembleError> {
+    match self.next_is(word) {
+      true => Ok(()),
+      false => Err(self.error(format!("Expected {}", word)))
+    }
+  }
+
+  pub fn punct(&mut self, punct: char) -> Result<(), AssembleError> {
+    match self.next_token() {
+      Some(Token::Punct(next)) if *next == punct => Ok(()),
+      _ => Err(self.error(format!("Expected {}", punct)))
+    }
+  }
+
+  pub fn number<T: FromStr>(&mut self, what: &str) -> Result<T, AssembleError> {
+    let word = self.word(what)?;
+    word.parse().map_err(|_| self.error(format!("Expected {}, not {}", what, word)))
+  }
+
+  /// Text that may be given as a word or in quotes.
+  pub fn text(&mut self, what: &str) -> Result<String, AssembleError> {
+    match self.next_token() {
+      Some(Token::Word(word)) => Ok(word.clone()),
+      Some(Token::String(units)) => Ok(String::from_utf16_lossy(units)),
+      _ => Err(self.error(format!("Expected {}", what)))
+    }
+  }
+
+  /// Checks that nothing is left over.
+  pub fn end(&self) -> Result<(), AssembleError> {
+    match self.tokens.first() {
+      None => Ok(()),
+      Some(token) => Err(self.error(format!("Unexpected {}", describe(token))))
+    }
+  }
+}
+
+fn describe(token: &Token) -> String {
+  match token {
+    Token::Word(word) => word.clone(),
+    Token::String(units) => format!("\"{}\"", String::from_utf16_lossy(units)),
+    Token::Punct(punct) => punct.to_string()
+  }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use super::{
+  assemble_error::AssembleError,
+  attribute::{self, table},
+  lexer::{Line, Token, Tokens}
+};
+use crate::{
+  builder::method_builder::{Label, MethodBuilder},
+  parser::{
+    attribute_info::{
+      attribute::stack_map_frame::{verification_type_info::VerificationTypeInfo, StackMapFrame},
+      Attribute
+    },
+    cp_info_resolved::Class
+  }
+};
+
+/// Puts the body of a `.method` into its `MethodBuilder`.
+pub(super) struct MethodAssembler<'a> {
+  pub(super) method: &'a mut MethodBuilder,
+  /// The instruction index each label is defined at, found before the body is assembled so that
+  /// frames can refer to `new` instructions by label.
+  positions: HashMap<String, usize>,
+  labels: HashMap<String, Label>,
+  attributes: Vec<Attribute>,
+  /// Attributes of the code, rather than the method, such as type annotations on instructions.
+  code_attributes: Vec<Attribute>
+}
+
+/// A line of a method body with the labels it starts with taken off, and for a switch, the lines
+/// listing its targets, up to and including its `default`.
+struct Statement<'a> {
+  line: usize,
+  labels: Vec<&'a str>,
+  tokens: &'a [Token],
+  cases: &'a [Line]
+}
+
+impl<'a> MethodAssembler<'a> {
+  /// Assembles the lines between `.method` and `.end method`.
+  pub(super) fn assemble(
+    method: &'a mut MethodBuilder,
+    body: &[Line]
+  ) -> Result<(), AssembleError> {
+    let statements = statements(body)?;
+    let mut positions = HashMap::new();
+    let mut index = 0;
+    for statement in &statements {
+      for label in &statement.labels {
+        if positions.insert(label.to_string(), index).is_some() {
+          let label = label.to_string();
+          return Err(AssembleError::DuplicateLabel { line: statement.line, label });
+        }
+      }
+      if matches!(statement.tokens.first(), Some(Token::Word(word)) if !word.starts_with('.')) {
+        index += 1;
+      }
+    }
+
+    let mut assembler = MethodAssembler {
+      method,
+      positions,
+      labels: HashMap::new(),
+      attributes: Vec::new(),
+      code_attributes: Vec::new()
+    };
+    for statement in &statements {
+      for label in &statement.labels {
+        let label = assembler.label(label, statement.line)?;
+        assembler.method.bind(label);
+      }
+      let mut tokens = Tokens::new(statement.line, statement.tokens);
+      match tokens.peek() {
+        None => {}
+        Some(Token::Word(word)) if word.starts_with('.') => assembler.directive(&mut tokens)?,
+        Some(_) => assembler.instruction(&mut tokens, statement.cases)?
+      }
+    }
+    for attribute in assembler.attributes {
+      assembler.method.attribute(attribute);
+    }
+    for attribute in assembler.code_attributes {
+      assembler.method.code_attribute(attribute);
+    }
+    Ok(())
+  }
+
+  /// The label named `name`, which must be defined somewhere in the method.
+  pub(super) fn label(&mut self, name: &str, line: usize) -> Result<Label, AssembleError> {
+    if !self.positions.contains_key(name) {
+      return Err(AssembleError::UndefinedLabel { line, label: name.to_string() });
+    }
+    if let Some(label) = self.labels.get(name) {
+      return Ok(*label);
+    }
+    let label = self.method.new_label();
+    self.labels.insert(name.to_string(), label);
+    Ok(label)
+  }
+
+  fn directive(&mut self, tokens: &mut Tokens) -> Result<(), AssembleError> {
+    match tokens.word("a directive")? {
+      ".limit" => match tokens.word("stack or locals")? {
+        "stack" => {
+          self.method.max_stack(tokens.number("a stack size")?);
+        }
+        "locals" => {
+          self.method.max_locals(tokens.number("a number of locals")?);
+        }
+        limit => return Err(tokens.error(format!("Expected stack or locals, not {}", limit)))
+      },
+      ".throws" => {
+        let class = Class { name: tokens.word("a class name")?.to_string() };
+        // All the `.throws` go in one attribute
+        let empty = Attribute::Exceptions { exceptions: Vec::new() };
+        table(&mut self.attributes, empty, |attribute| match attribute {
+          Attribute::Exceptions { exceptions } => Some(exceptions),
+          _ => None
+        })
+        .push(class);
+      }
+      ".signature" => {
+        let signature = tokens.text("a signature")?;
+        self.attributes.push(Attribute::Signature { signature });
+      }
+      ".catch" => {
+        let catch_type = match tokens.word("a class name or all")? {
+          "all" => None,
+          class => Some(class)
+        };
+        tokens.keyword("from")?;
+        let start = self.label(tokens.word("a label")?, tokens.line())?;
+        tokens.keyword("to")?;
+        let end = self.label(tokens.word("a label")?, tokens.line())?;
+        tokens.keyword("using")?;
+        let handler = self.label(tokens.word("a label")?, tokens.line())?;
+        self.method.try_catch(start, end, handler, catch_type);
+      }
+      ".var" => {
+        let index = tokens.number("a local variable")?;
+        tokens.keyword("is")?;
+        let name = tokens.word("a name")?;
+        // A variable only in the LocalVariableTypeTable has a signature but no descriptor
+        let descriptor = match tokens.rest() {
+          [Token::Word(word), Token::String(_), ..] if word == "signature" => None,
+          _ => Some(tokens.word("a descriptor")?)
+        };
+        let signature = match tokens.next_is("signature") {
+          true => Some(tokens.text("a signature")?),
+          false => None
+        };
+        tokens.keyword("from")?;
+        let start = self.label(tokens.word("a label")?, tokens.line())?;
+        tokens.keyword("to")?;
+        let end = self.label(tokens.word("a label")?, tokens.line())?;
+        match (descriptor, signature) {
+          (Some(descriptor), signature) => {
+            self.method.local_variable(start, end, index, name, descriptor, signature.as_deref())
+          }
+          (None, Some(signature)) => {
+            self.method.local_variable_type(start, end, index, name, &signature)
+          }
+          (None, None) => unreachable!("a variable without a descriptor has a signature")
+        };
+      }
+      ".line" => {
+        self.method.line_number(tokens.number("a line number")?);
+      }
+      ".stack" => {
+        let frame = self.stack_map_frame(tokens)?;
+        self.method.stack_map_frame(frame);
+      }
+      ".annotation" if tokens.next_is("type") => {
+        let visible = attribute::visibility(tokens)?;
+        let annotation = attribute::type_annotation(tokens, Some(&self.positions))?;
+        // Annotations on instructions and local variables go with the code
+        let attributes = match annotation.target_type >= 0x40 {
+          true => &mut self.code_attributes,
+          false => &mut self.attributes
+        };
+        attribute::add_type_annotation(attributes, visible, annotation);
+      }
+      directive => {
+        if !attribute::directive(directive, tokens, &mut self.attributes)? {
+          return Err(tokens.error(format!("Unknown directive {}", directive)));
+        }
+      }
+    }
+    tokens.end()
+  }
+
+  /// A frame as `jasmin::stack_map_frame` writes it. The offsets are left for the `MethodBuilder`
+  /// to work out.
+  fn stack_map_frame(&mut self, tokens: &mut Tokens) -> Result<StackMapFrame, AssembleError> {
+    Ok(match tokens.word("a kind of frame")? {
+      "same" => StackMapFrame::SameFrame { frame_type: 0 },
+      "same_extended" => StackMapFrame::SameFrameExtended { frame_type: 251, offset_delta: 0 },
+      "stack_1" => StackMapFrame::SameLocals1StackItemFrame {
+        frame_type: 64,
+        stack: [self.verification_type(tokens)?]
+      },
+      "stack_1_extended" => StackMapFrame::SameLocals1StackItemFrameExtended {
+        frame_type: 247,
+        offset_delta: 0,
+        stack: [self.verification_type(tokens)?]
+      },
+      "chop" => {
+        let chopped: u8 = tokens.number("a number of locals")?;
+        if !(1..=3).contains(&chopped) {
+          return Err(tokens.error("A chop frame removes 1 to 3 locals".to_string()));
+        }
+        StackMapFrame::ChopFrame { frame_type: 251 - chopped, offset_delta: 0 }
+      }
+      "append" => {
+        let locals = self.verification_types(tokens)?;
+        if !(1..=3).contains(&locals.len()) {
+          return Err(tokens.error("An append frame adds 1 to 3 locals".to_string()));
+        }
+        StackMapFrame::AppendFrame { frame_type: 251 + locals.len() as u8, offset_delta: 0, locals }
+      }
+      "full" => {
+        tokens.keyword("locals")?;
+        let locals = self.verification_types(tokens)?;
+        tokens.keyword("stack")?;
+        let stack = self.verification_types(tokens)?;
+        StackMapFrame::FullFrame {
+          frame_type: 255,
+          offset_delta: 0,
+          number_of_locals: locals.len() as u16,
+          locals,
+          number_of_stack_items: stack.len() as u16,
+          stack
+        }
+      }
+      kind => return Err(tokens.error(format!("Unknown kind of frame {}", kind)))
+    })
+  }
+
+  /// Types up to the end of the line or a `stack` keyword.
+  fn verification_types(
+    &mut self,
+    tokens: &mut Tokens
+  ) -> Result<Vec<VerificationTypeInfo>, AssembleError> {
+    let mut types = Vec::new();
+    while !tokens.is_empty() && tokens.peek() != Some(&Token::Word("stack".to_string())) {
+      types.push(self.verification_type(tokens)?);
+    }
+    Ok(types)
+  }
+
+  fn verification_type(
+    &mut self,
+    tokens: &mut Tokens
+  ) -> Result<VerificationTypeInfo, AssembleError> {
+    Ok(match tokens.word("a type")? {
+      "Top" => VerificationTypeInfo::TopVariable,
+      "Integer" => VerificationTypeInfo::IntegerVariable,
+      "Float" => VerificationTypeInfo::FloatVariable,
+      "Long" => VerificationTypeInfo::LongVariable,
+      "Double" => VerificationTypeInfo::DoubleVariable,
+      "Null" => VerificationTypeInfo::NullVariable,
+      "UninitializedThis" => VerificationTypeInfo::UninitializedThisVariable,
+      "Object" => VerificationTypeInfo::ObjectVariable {
+        class: Class { name: tokens.word("a class name")?.to_string() }
+      },
+      "Uninitialized" => {
+        let label = tokens.word("the label of a new instruction")?;
+        let offset = self.positions.get(label).ok_or_else(|| AssembleError::UndefinedLabel {
+          line: tokens.line(),
+          label: label.to_string()
+        })?;
+        VerificationTypeInfo::UninitializedVariable { offset: *offset as u16 }
+      }
+      other => return Err(tokens.error(format!("Unknown type {}", other)))
+    })
+  }
+}
+
+fn statements(body: &[Line]) -> Result<Vec<Statement<'_>>, AssembleError> {
+  let mut statements = Vec::new();
+  let mut position = 0;
+  while let Some(line) = body.get(position) {
+    position += 1;
+    let mut tokens = &line.tokens[..];
+    let mut labels = Vec::new();
+    while let [Token::Word(label), Token::Punct(':'), rest @ ..] = tokens {
+      labels.push(label.as_str());
+      tokens = rest;
+    }
+    let is_switch = matches!(
+      tokens.first(),
+      Some(Token::Word(word)) if word == "tableswitch" || word == "lookupswitch"
+    );
+    let start = position;
+    if is_switch {
+      loop {
+        let case = body.get(position).ok_or_else(|| AssembleError::Syntax {
+          line: line.number,
+          message: "Switch without a default".to_string()
+        })?;
+        position += 1;
+        if case.tokens.first() == Some(&Token::Word("default".to_string())) {
+          break;
+        }
+      }
+    }
+    statements.push(Statement { line: line.number, labels, tokens, cases: &body[start..position] });
+  }
+  Ok(statements)
+}
@@ -0,0 +1,247 @@
+use self::{build_error::BuildError, method_builder::MethodBuilder};
+use crate::{
+  jvm::verifier::compute_frames,
+  parser::{
+    attribute_info::{
+      attribute::stack_map_frame::frame::{self, initial_locals},
+      code::code_generator::Instructions,
+      Attribute
+    },
+    classfile::{ClassAccessFlags, ClassFile},
+    cp_info_resolved::{Class, NameAndType},
+    descriptor::{FieldType, MethodDescriptor},
+    field_info::{FieldAccessFlags, FieldInfo},
+    method_info::{self, MethodAccessFlags}
+  }
+};
+
+pub mod build_error;
+pub mod method_builder;
+
+/// Puts a class together in code, ready for `ClassFile::write` to encode.
+/// `build` works out what a compiler would: each method's `max_stack` and `max_locals`, and from
+/// version 50 on its StackMapTable.
+#[derive(Debug, Clone)]
+pub struct ClassBuilder {
+  class: ClassFile,
+  methods: Vec<MethodBuilder>
+}
+
+impl ClassBuilder {
+  /// A class file for Java 8 (version 52.0), which can be changed with `version`.
+  pub fn new(access_flags: ClassAccessFlags, name: &str, super_class: Option<&str>) -> Self {
+    ClassBuilder {
+      class: ClassFile {
+        minor_version: 0,
+        major_version: 52,
+        access_flags,
+        this_class: Class { name: name.to_string() },
+        super_class: super_class.map(|name| Class { name: name.to_string() }),
+        interfaces: Vec::new(),
+        fields: Vec::new(),
+        methods: Vec::new(),
+        attributes: Vec::new()
+      },
+      methods: Vec::new()
+    }
+  }
+
+  pub fn version(&mut self, major_version: u16, minor_version: u16) -> &mut Self {
+    self.class.major_version = major_version;
+    self.class.minor_version = minor_version;
+    self
+  }
+
+  pub fn interface(&mut self, name: &str) -> &mut Self {
+    self.class.interfaces.push(Class { name: name.to_string() });
+    self
+  }
+
+  /// Adds a field, which attributes such as `ConstantValue` can be added to.
+  pub fn field(
+    &mut self,
+    access_flags: FieldAccessFlags,
+    name: &str,
+    descriptor: &str
+  ) -> Result<&mut FieldInfo, BuildError> {
+    let field = FieldInfo {
+      access_flags,
+      descriptor: FieldType::parse(descriptor)
+        .map_err(|error| BuildError::ClassFormatError { error })?,
+      attributes: Vec::new()
+    };
+    let key = NameAndType { name: name.to_string(), descriptor: descriptor.to_string() };
+    self.class.fields.push((key.to_string(), field));
+    Ok(&mut self.class.fields.last_mut().unwrap().1)
+  }
+
+  /// Starts a method, whose code goes into the `MethodBuilder` given back.
+  pub fn method(
+    &mut self,
+    access_flags: MethodAccessFlags,
+    name: &str,
+    descriptor: &str
+  ) -> Result<&mut MethodBuilder, BuildError> {
+    let parsed = MethodDescriptor::parse(descriptor)
+      .map_err(|error| BuildError::ClassFormatError { error })?;
+    let key = NameAndType { name: name.to_string(), descriptor: descriptor.to_string() };
+    self.methods.push(MethodBuilder::new(key.to_string(), access_flags, parsed));
+    Ok(self.methods.last_mut().unwrap())
+  }
+
+  /// Adds an attribute to the class, such as `SourceFile`.
+  pub fn attribute(&mut self, attribute: Attribute) -> &mut Self {
+    self.class.attributes.push(attribute);
+    self
+  }
+
+  /// Resolves every method's labels and fills in its limits and frames.
+  /// The frames are inferred the way the verifier infers them for old class files, so the code
+  /// must verify, and any instruction needing a frame must be reachable.
+  /// Methods given a `max_stack` are left as they are written, frames and all.
+  /// From version 50 on, code using subroutines (`jsr` and `ret`) is refused, as it can't be given
+  /// frames the verifier accepts.
+  pub fn build(self) -> Result<ClassFile, BuildError> {
+    let mut class = self.class;
+    let as_written: Vec<bool> = self.methods.iter().map(MethodBuilder::has_max_stack).collect();
+    class.methods =
+      self.methods.into_iter().map(MethodBuilder::finish).collect::<Result<_, _>>()?;
+    if class.major_version >= 50 {
+      check_no_subroutines(&class)?;
+    }
+    let computed = class
+      .methods
+      .iter()
+      .zip(&as_written)
+      .map(|((key, method), as_written)| match as_written {
+        true => Ok(None),
+        false => compute_frames(&class, key, method).map(Some)
+      })
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|error| BuildError::VerifyError { error })?;
+
+    let this_class = class.this_class.clone();
+    let with_frames = class.major_version >= 50;
+    for ((key, method), computed) in class.methods.iter_mut().zip(computed) {
+      let Some((computed_stack, frames)) = computed else {
+        continue;
+      };
+      let name = key.split(':').next().unwrap_or_default();
+      let is_static = method.access_flags.contains(method_info::Flags::AccStatic);
+      let locals = initial_locals(&this_class, name, is_static, &method.descriptor);
+      let Some(Attribute::Code { max_stack, attributes, .. }) =
+        method.attributes.iter_mut().find(|attribute| matches!(attribute, Attribute::Code { .. }))
+      else {
+        continue;
+      };
+      *max_stack = computed_stack;
+      attributes.retain(|attribute| !matches!(attribute, Attribute::StackMapTable { .. }));
+      if with_frames && !frames.is_empty() {
+        attributes.push(Attribute::StackMapTable { entries: frame::compress(&locals, &frames) });
+      }
+    }
+    Ok(class)
+  }
+}
+
+fn check_no_subroutines(class: &ClassFile) -> Result<(), BuildError> {
+  for (key, method) in &class.methods {
+    for attribute in &method.attributes {
+      let Attribute::Code { code, .. } = attribute else {
+        continue;
+      };
+      let subroutine = code.iter().position(|instruction| {
+        matches!(
+          instruction,
+          Instructions::jsr { .. }
+            | Instructions::jsr_w { .. }
+            | Instructions::ret { .. }
+            | Instructions::wide1 { opcode: 169, .. }
+        )
+      });
+      if let Some(index) = subroutine {
+        return Err(BuildError::Subroutine { method: key.clone(), index });
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    jvm::verifier::verify,
+    parser::attribute_info::attribute::stack_map_frame::{
+      verification_type_info::VerificationTypeInfo,
+      StackMapFrame
+    }
+  };
+
+  /// `static int abs(int a) { int r = a; if (r < 0) r = -r; return r; }`, with a branch around
+  /// the negation.
+  fn abs() -> ClassFile {
+    let mut class =
+      ClassBuilder::new(ClassAccessFlags::from_mask(0x0021), "Abs", Some("java/lang/Object"));
+    let method = class.method(MethodAccessFlags::from_mask(0x0009), "abs", "(I)I").unwrap();
+    let positive = method.new_label();
+    method
+      .emit(Instructions::iload { index: 0 })
+      .emit(Instructions::istore { index: 1 })
+      .emit(Instructions::iload { index: 1 })
+      .jump(Instructions::ifge { offset: 0 }, positive)
+      .emit(Instructions::iload { index: 1 })
+      .emit(Instructions::ineg)
+      .emit(Instructions::istore { index: 1 })
+      .bind(positive)
+      .emit(Instructions::iload { index: 1 })
+      .emit(Instructions::ireturn);
+    class.build().unwrap()
+  }
+
+  #[test]
+  fn computes_limits_and_frames_that_verify() {
+    let class = abs();
+    verify(&class).unwrap();
+    let Some(Attribute::Code { max_stack, max_locals, code, attributes, .. }) =
+      class.methods[0].1.attributes.first()
+    else {
+      panic!("abs has no code");
+    };
+    assert_eq!((*max_stack, *max_locals), (1, 2));
+    // The branch counts instructions, from `ifge` to the bound label
+    assert_eq!(code[3], Instructions::ifge { offset: 4 });
+    // `r` is only an int from the branch target on, so the frame there appends it
+    let frame = StackMapFrame::AppendFrame {
+      frame_type: 252,
+      offset_delta: 7,
+      locals: vec![VerificationTypeInfo::IntegerVariable]
+    };
+    assert_eq!(attributes, &[Attribute::StackMapTable { entries: vec![frame] }]);
+    assert_eq!(ClassFile::parse(&class.write().unwrap()).unwrap().0, class);
+  }
+
+  /// `static void sub() { jsr s; return; s: astore_0; ret 0 }`, built for `major_version`.
+  fn subroutine(major_version: u16) -> Result<ClassFile, BuildError> {
+    let mut class =
+      ClassBuilder::new(ClassAccessFlags::from_mask(0x0021), "Sub", Some("java/lang/Object"));
+    class.version(major_version, 0);
+    let method = class.method(MethodAccessFlags::from_mask(0x0009), "sub", "()V").unwrap();
+    let body = method.new_label();
+    method
+      .jump(Instructions::jsr { offset: 0 }, body)
+      .emit(Instructions::r#return)
+      .bind(body)
+      .emit(Instructions::astore { index: 0 })
+      .emit(Instructions::ret { index: 0 });
+    class.build()
+  }
+
+  #[test]
+  fn subroutines_need_an_old_version() {
+    let class = subroutine(49).unwrap();
+    verify(&class).unwrap();
+    let error = subroutine(50).unwrap_err();
+    assert!(matches!(error, BuildError::Subroutine { index: 0, .. }), "{}", error);
+  }
+}
@@ -0,0 +1,60 @@
+use std::{error::Error, fmt};
+
+use crate::{jvm::linkage_error::LinkageError, parser::class_format_error::ClassFormatError};
+
+/// Errors found while putting a class together with `ClassBuilder`.
+/// `method` is keyed as in `ClassFile::methods`.
+#[derive(Debug, Clone)]
+pub enum BuildError {
+  UnboundLabel {
+    method: String
+  },
+  /// A branch whose instruction can't count as far as its label, at instruction `index`.
+  BranchTooFar {
+    method: String,
+    index: usize
+  },
+  /// A frame given for instruction `index` when one was already given for it, or for a later one.
+  DuplicateFrame {
+    method: String,
+    index: usize
+  },
+  /// A `jsr` or `ret` at instruction `index`, in a class of version 50 or later where the verifier
+  /// no longer allows subroutines.
+  Subroutine {
+    method: String,
+    index: usize
+  },
+  ClassFormatError {
+    error: ClassFormatError
+  },
+  /// The code doesn't verify, so its frames can't be worked out.
+  VerifyError {
+    error: LinkageError
+  }
+}
+
+impl fmt::Display for BuildError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BuildError::UnboundLabel { method } => {
+        write!(f, "Method {} jumps to a label that was never bound", method)
+      }
+      BuildError::BranchTooFar { method, index } => {
+        write!(f, "Branch at instruction {} of method {} is too far from its label", index, method)
+      }
+      BuildError::DuplicateFrame { method, index } => {
+        write!(f, "Method {} has more than one frame for instruction {}", method, index)
+      }
+      BuildError::Subroutine { method, index } => write!(
+        f,
+        "Method {} uses a subroutine at instruction {}, which needs a class version before 50",
+        method, index
+      ),
+      BuildError::ClassFormatError { error } => write!(f, "{}", error),
+      BuildError::VerifyError { error } => write!(f, "{}", error)
+    }
+  }
+}
+
+impl Error for BuildError {}
@@ -0,0 +1,456 @@
+use super::build_error::BuildError;
+use crate::parser::{
+  attribute_info::{
+    attribute::{
+      exception::Exception,
+      line_number::LineNumber,
+      local_variable::LocalVariable,
+      local_variable_type::LocalVariableType,
+      stack_map_frame::StackMapFrame
+    },
+    code::{code_generator::Instructions, code_writer::layout},
+    Attribute
+  },
+  cp_info_resolved::Class,
+  descriptor::MethodDescriptor,
+  method_info::{Flags, MethodAccessFlags, MethodInfo}
+};
+
+/// A position in a method's code, which branches, switches and exception handlers can refer to
+/// before it is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// Collects the code of one method, made by `ClassBuilder::method`.
+/// Branches and switches go to labels rather than relative instruction counts, and `max_stack`,
+/// `max_locals` and the StackMapTable are left for `ClassBuilder::build` to work out unless they
+/// are given.
+#[derive(Debug, Clone)]
+pub struct MethodBuilder {
+  key: String,
+  access_flags: MethodAccessFlags,
+  descriptor: MethodDescriptor,
+  code: Vec<Instructions>,
+  /// The instruction index each label is bound to.
+  labels: Vec<Option<usize>>,
+  /// The branches and switches, with the labels they go to. A switch's default comes first.
+  jumps: Vec<(usize, Vec<Label>)>,
+  handlers: Vec<(Label, Label, Label, Option<Class>)>,
+  line_numbers: Vec<LineNumber>,
+  /// Each variable's scope, and its generic signature if it has one.
+  local_variables: Vec<(Label, Label, LocalVariable, Option<String>)>,
+  /// Variables only in the LocalVariableTypeTable, with their scopes.
+  local_variable_types: Vec<(Label, Label, LocalVariableType)>,
+  /// The instruction index of each frame given by hand.
+  frames: Vec<(usize, StackMapFrame)>,
+  max_stack: Option<u16>,
+  max_locals: Option<u16>,
+  attributes: Vec<Attribute>,
+  code_attributes: Vec<Attribute>
+}
+
+impl MethodBuilder {
+  pub fn new(key: String, access_flags: MethodAccessFlags, descriptor: MethodDescriptor) -> Self {
+    MethodBuilder {
+      key,
+      access_flags,
+      descriptor,
+      code: Vec::new(),
+      labels: Vec::new(),
+      jumps: Vec::new(),
+      handlers: Vec::new(),
+      line_numbers: Vec::new(),
+      local_variables: Vec::new(),
+      local_variable_types: Vec::new(),
+      frames: Vec::new(),
+      max_stack: None,
+      max_locals: None,
+      attributes: Vec::new(),
+      code_attributes: Vec::new()
+    }
+  }
+
+  pub fn new_label(&mut self) -> Label {
+    self.labels.push(None);
+    Label(self.labels.len() - 1)
+  }
+
+  /// Places `label` at the next instruction.
+  /// Panics if the label is already bound or belongs to another method.
+  pub fn bind(&mut self, label: Label) -> &mut Self {
+    let slot = &mut self.labels[label.0];
+    assert!(slot.is_none(), "Label bound twice in method {}", self.key);
+    *slot = Some(self.code.len());
+    self
+  }
+
+  /// Appends an instruction as it is. Any branch offset counts instructions, so `jump` is the way
+  /// to branch to a label.
+  pub fn emit(&mut self, instruction: Instructions) -> &mut Self {
+    self.code.push(instruction);
+    self
+  }
+
+  /// Appends a branch to `target`, such as `Instructions::goto { offset: 0 }`, whose offset is
+  /// filled in once every label is bound.
+  /// Panics if `instruction` isn't a branch.
+  pub fn jump(&mut self, instruction: Instructions, target: Label) -> &mut Self {
+    assert!(
+      retarget(&mut instruction.clone(), &[0]).is_some(),
+      "{:?} is not a branch",
+      instruction
+    );
+    self.jumps.push((self.code.len(), vec![target]));
+    self.emit(instruction)
+  }
+
+  /// Appends a `tableswitch` from `low` with one label per key, in order.
+  pub fn tableswitch(&mut self, low: i32, default: Label, targets: &[Label]) -> &mut Self {
+    self.jumps.push((self.code.len(), [&[default], targets].concat()));
+    self.emit(Instructions::tableswitch {
+      default: 0,
+      low,
+      high: low + targets.len() as i32 - 1,
+      offsets: vec![0; targets.len()]
+    })
+  }
+
+  /// Appends a `lookupswitch`, sorting `pairs` by key as the instruction requires.
+  pub fn lookupswitch(&mut self, default: Label, pairs: &[(i32, Label)]) -> &mut Self {
+    let mut pairs = pairs.to_vec();
+    pairs.sort_by_key(|(key, _)| *key);
+    let targets: Vec<Label> = pairs.iter().map(|(_, label)| *label).collect();
+    self.jumps.push((self.code.len(), [&[default], &targets[..]].concat()));
+    self.emit(Instructions::lookupswith {
+      default: 0,
+      npairs: pairs.len() as i32,
+      pairs: pairs.iter().map(|(key, _)| (*key, 0)).collect()
+    })
+  }
+
+  /// Sends exceptions of `catch_type` thrown from `start` up to `end` to `handler`, or every
+  /// exception for `None`.
+  /// Handlers are tried in the order they are added.
+  pub fn try_catch(
+    &mut self,
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: Option<&str>
+  ) -> &mut Self {
+    let catch_type = catch_type.map(|name| Class { name: name.to_string() });
+    self.handlers.push((start, end, handler, catch_type));
+    self
+  }
+
+  /// Marks the next instruction as the start of source line `line`.
+  pub fn line_number(&mut self, line: u16) -> &mut Self {
+    self.line_numbers.push(LineNumber { start_pc: self.code.len() as u16, line_number: line });
+    self
+  }
+
+  /// Describes local variable `index` from `start` up to `end` for debuggers, in the
+  /// LocalVariableTable and, given a `signature`, the LocalVariableTypeTable.
+  pub fn local_variable(
+    &mut self,
+    start: Label,
+    end: Label,
+    index: u16,
+    name: &str,
+    descriptor: &str,
+    signature: Option<&str>
+  ) -> &mut Self {
+    let variable = LocalVariable {
+      start_pc: 0,
+      length: 0,
+      name: name.to_string(),
+      descriptor: descriptor.to_string(),
+      index
+    };
+    self.local_variables.push((start, end, variable, signature.map(str::to_string)));
+    self
+  }
+
+  /// Describes local variable `index` in the LocalVariableTypeTable alone, for a variable that has
+  /// no LocalVariableTable entry to go with it.
+  pub fn local_variable_type(
+    &mut self,
+    start: Label,
+    end: Label,
+    index: u16,
+    name: &str,
+    signature: &str
+  ) -> &mut Self {
+    let variable = LocalVariableType {
+      start_pc: 0,
+      length: 0,
+      name: name.to_string(),
+      signature: signature.to_string(),
+      index
+    };
+    self.local_variable_types.push((start, end, variable));
+    self
+  }
+
+  /// Sets `max_stack`, which leaves the method as it is written: `ClassBuilder::build` neither
+  /// checks the code nor works out its frames, so only frames added with `stack_map_frame` go in
+  /// the StackMapTable.
+  pub fn max_stack(&mut self, max_stack: u16) -> &mut Self {
+    self.max_stack = Some(max_stack);
+    self
+  }
+
+  /// Sets `max_locals` instead of counting the locals the instructions use.
+  pub fn max_locals(&mut self, max_locals: u16) -> &mut Self {
+    self.max_locals = Some(max_locals);
+    self
+  }
+
+  /// Whether `max_stack` was given, so `ClassBuilder::build` is to leave the method alone.
+  pub fn has_max_stack(&self) -> bool { self.max_stack.is_some() }
+
+  /// Adds a StackMapTable entry for the next instruction. Its `offset_delta` is worked out once the
+  /// code is finished, and a `same` or `same_locals_1_stack_item` frame takes its extended form
+  /// when that is too far from the previous frame.
+  pub fn stack_map_frame(&mut self, frame: StackMapFrame) -> &mut Self {
+    self.frames.push((self.code.len(), frame));
+    self
+  }
+
+  /// Adds an attribute to the method, such as `Exceptions` or `Signature`.
+  pub fn attribute(&mut self, attribute: Attribute) -> &mut Self {
+    self.attributes.push(attribute);
+    self
+  }
+
+  /// Adds an attribute to the Code attribute after those worked out from the code, such as
+  /// `RuntimeVisibleTypeAnnotations`. Any offsets in it count instructions.
+  pub fn code_attribute(&mut self, attribute: Attribute) -> &mut Self {
+    self.code_attributes.push(attribute);
+    self
+  }
+
+  /// Resolves the labels, giving the method as `ClassBuilder::build` first puts it in the class:
+  /// unless they were given, `max_locals` is counted from the instructions, while `max_stack` is
+  /// left at its limit and the StackMapTable is still to be filled in.
+  /// Abstract and native methods have no Code attribute.
+  pub fn finish(self) -> Result<(String, MethodInfo), BuildError> {
+    let MethodBuilder {
+      key,
+      access_flags,
+      descriptor,
+      mut code,
+      labels,
+      jumps,
+      handlers,
+      line_numbers,
+      local_variables,
+      local_variable_types,
+      frames,
+      max_stack,
+      max_locals,
+      attributes,
+      code_attributes: extra_attributes
+    } = self;
+    if access_flags.intersects(Flags::AccAbstract | Flags::AccNative) {
+      return Ok((key, MethodInfo { access_flags, descriptor, attributes }));
+    }
+
+    let position = |label: &Label| {
+      labels[label.0].ok_or_else(|| BuildError::UnboundLabel { method: key.clone() })
+    };
+    for (index, targets) in &jumps {
+      let targets: Vec<isize> = targets
+        .iter()
+        .map(|label| Ok(position(label)? as isize - *index as isize))
+        .collect::<Result<_, BuildError>>()?;
+      retarget(&mut code[*index], &targets)
+        .ok_or_else(|| BuildError::BranchTooFar { method: key.clone(), index: *index })?;
+    }
+    let exception_table = handlers
+      .iter()
+      .map(|(start, end, handler, catch_type)| {
+        Ok(Exception {
+          start_pc: position(start)? as u16,
+          end_pc: position(end)? as u16,
+          handler_pc: position(handler)? as u16,
+          catch_type: catch_type.clone()
+        })
+      })
+      .collect::<Result<_, BuildError>>()?;
+
+    let mut local_variable_table = Vec::new();
+    let mut local_variable_type_table = Vec::new();
+    for (start, end, mut variable, signature) in local_variables {
+      variable.start_pc = position(&start)? as u16;
+      variable.length = position(&end)?.saturating_sub(variable.start_pc as usize) as u16;
+      if let Some(signature) = signature {
+        local_variable_type_table.push(LocalVariableType {
+          start_pc: variable.start_pc,
+          length: variable.length,
+          name: variable.name.clone(),
+          signature,
+          index: variable.index
+        });
+      }
+      local_variable_table.push(variable);
+    }
+    for (start, end, mut variable) in local_variable_types {
+      variable.start_pc = position(&start)? as u16;
+      variable.length = position(&end)?.saturating_sub(variable.start_pc as usize) as u16;
+      local_variable_type_table.push(variable);
+    }
+    let mut entries = Vec::with_capacity(frames.len());
+    let mut previous: Option<usize> = None;
+    for (index, frame) in frames {
+      let offset_delta = match previous {
+        Some(previous) if index <= previous => {
+          return Err(BuildError::DuplicateFrame { method: key.clone(), index })
+        }
+        Some(previous) => index - previous - 1,
+        None => index
+      };
+      previous = Some(index);
+      entries.push(place(frame, offset_delta as u16));
+    }
+
+    let this = !access_flags.contains(Flags::AccStatic) as usize;
+    let max_locals = max_locals.unwrap_or_else(|| {
+      code
+        .iter()
+        .filter_map(locals_used)
+        .chain([descriptor.param_slots() + this])
+        .max()
+        .unwrap_or_default() as u16
+    });
+    let offsets = layout(&code).map_err(|error| BuildError::ClassFormatError { error })?;
+    // In the order javac writes them
+    let mut code_attributes = Vec::new();
+    if !line_numbers.is_empty() {
+      code_attributes.push(Attribute::LineNumberTable { line_number_table: line_numbers });
+    }
+    if !local_variable_table.is_empty() {
+      code_attributes.push(Attribute::LocalVariableTable { local_variable_table });
+    }
+    if !local_variable_type_table.is_empty() {
+      code_attributes.push(Attribute::LocalVariableTypeTable { local_variable_type_table });
+    }
+    if !entries.is_empty() {
+      code_attributes.push(Attribute::StackMapTable { entries });
+    }
+    code_attributes.extend(extra_attributes);
+    let code = Attribute::Code {
+      max_stack: max_stack.unwrap_or(u16::MAX),
+      max_locals,
+      code,
+      exception_table,
+      attributes: code_attributes,
+      offsets
+    };
+    let attributes = [vec![code], attributes].concat();
+    Ok((key, MethodInfo { access_flags, descriptor, attributes }))
+  }
+}
+
+/// Points a branch or switch at `targets`, counted in instructions from it, with a switch's default
+/// first.
+/// Gives `None` if the instruction isn't a branch or can't reach that far.
+fn retarget(instruction: &mut Instructions, targets: &[isize]) -> Option<()> {
+  match instruction {
+    Instructions::ifeq { offset }
+    | Instructions::ifne { offset }
+    | Instructions::iflt { offset }
+    | Instructions::ifge { offset }
+    | Instructions::ifgt { offset }
+    | Instructions::ifle { offset }
+    | Instructions::if_icmpeq { offset }
+    | Instructions::if_icmpne { offset }
+    | Instructions::if_icmplt { offset }
+    | Instructions::if_icmpge { offset }
+    | Instructions::if_icmpgt { offset }
+    | Instructions::if_icmple { offset }
+    | Instructions::if_acmpeq { offset }
+    | Instructions::if_acmpne { offset }
+    | Instructions::ifnull { offset }
+    | Instructions::ifnonnull { offset }
+    | Instructions::goto { offset }
+    | Instructions::jsr { offset } => *offset = i16::try_from(targets[0]).ok()?,
+    Instructions::goto_w { offset } | Instructions::jsr_w { offset } => {
+      *offset = i32::try_from(targets[0]).ok()?
+    }
+    Instructions::tableswitch { default, offsets, .. } => {
+      *default = targets[0] as i32;
+      offsets.iter_mut().zip(&targets[1..]).for_each(|(offset, target)| *offset = *target as i32);
+    }
+    Instructions::lookupswith { default, pairs, .. } => {
+      *default = targets[0] as i32;
+      pairs
+        .iter_mut()
+        .zip(&targets[1..])
+        .for_each(|((_, offset), target)| *offset = *target as i32);
+    }
+    _ => return None
+  }
+  Some(())
+}
+
+/// Gives a frame its `offset_delta`, switching between the compact and extended forms of frames that
+/// have both.
+fn place(frame: StackMapFrame, offset_delta: u16) -> StackMapFrame {
+  match frame {
+    StackMapFrame::SameFrame { .. } if offset_delta <= 63 => {
+      StackMapFrame::SameFrame { frame_type: offset_delta as u8 }
+    }
+    StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => {
+      StackMapFrame::SameFrameExtended { frame_type: 251, offset_delta }
+    }
+    StackMapFrame::SameLocals1StackItemFrame { stack, .. } if offset_delta <= 63 => {
+      StackMapFrame::SameLocals1StackItemFrame { frame_type: 64 + offset_delta as u8, stack }
+    }
+    StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+    | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+      StackMapFrame::SameLocals1StackItemFrameExtended { frame_type: 247, offset_delta, stack }
+    }
+    StackMapFrame::ChopFrame { frame_type, .. } => {
+      StackMapFrame::ChopFrame { frame_type, offset_delta }
+    }
+    StackMapFrame::AppendFrame { frame_type, locals, .. } => {
+      StackMapFrame::AppendFrame { frame_type, offset_delta, locals }
+    }
+    StackMapFrame::FullFrame { number_of_locals, locals, number_of_stack_items, stack, .. } => {
+      StackMapFrame::FullFrame {
+        frame_type: 255,
+        offset_delta,
+        number_of_locals,
+        locals,
+        number_of_stack_items,
+        stack
+      }
+    }
+  }
+}
+
+/// The number of local variables an instruction needs: one past the last slot it touches.
+fn locals_used(instruction: &Instructions) -> Option<usize> {
+  match instruction {
+    Instructions::iload { index }
+    | Instructions::fload { index }
+    | Instructions::aload { index }
+    | Instructions::istore { index }
+    | Instructions::fstore { index }
+    | Instructions::astore { index }
+    | Instructions::iinc { index, .. }
+    | Instructions::ret { index } => Some(*index as usize + 1),
+    Instructions::lload { index }
+    | Instructions::dload { index }
+    | Instructions::lstore { index }
+    | Instructions::dstore { index } => Some(*index as usize + 2),
+    Instructions::wide1 { opcode: 22 | 24 | 55 | 57, index_extension } => {
+      Some(*index_extension as usize + 2)
+    }
+    Instructions::wide1 { index_extension, .. } | Instructions::wide2 { index_extension, .. } => {
+      Some(*index_extension as usize + 1)
+    }
+    _ => None
+  }
+}
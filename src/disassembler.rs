@@ -0,0 +1,590 @@
+use std::collections::HashMap;
+
+use self::{
+  jasmin::keyword,
+  text::{
+    describe,
+    flags,
+    java_name,
+    java_type,
+    loadable,
+    method_handle,
+    modifiers,
+    type_annotation_target,
+    CLASS_FLAGS,
+    FIELD_FLAGS,
+    INNER_CLASS_FLAGS,
+    METHOD_FLAGS,
+    PARAMETER_FLAGS
+  }
+};
+use crate::parser::{
+  attribute_info::{attribute::constant::Constant, Attribute},
+  class_format_error::ClassFormatError,
+  classfile::{self, ClassFile},
+  constant_pool::ConstantPool,
+  cp_info::CpInfo,
+  cp_info_resolved::{Class, JavaString, ResolvedCpInfo},
+  descriptor::FieldType,
+  field_info::FieldInfo,
+  format_check::member_name,
+  method_info::{self, MethodInfo},
+  signature::type_parameters_to_java
+};
+
+mod annotations;
+mod code;
+pub mod jasmin;
+pub mod text;
+
+/// Prints a class file the way `javap -c -v -p` does: the version and flags, the constant pool
+/// with its indices, every field and method with its code, and the attributes, with constant pool
+/// references resolved in comments.
+pub fn disassemble(bytes: &[u8]) -> Result<String, ClassFormatError> {
+  let (class, _) = ClassFile::parse(bytes)?;
+  let (constant_pool, resolved_constant_pool) = ClassFile::constant_pool(bytes)?;
+  let mut indices = HashMap::new();
+  for (index, constant) in resolved_constant_pool.iter() {
+    indices.entry((constant.kind(), describe(constant))).or_insert(index);
+  }
+
+  let mut disassembler = Disassembler { class: &class, indices, out: String::new() };
+  disassembler.header(&constant_pool, &resolved_constant_pool);
+  disassembler.line(0, "{");
+  for (position, (key, field)) in class.fields.iter().enumerate() {
+    if position > 0 {
+      disassembler.line(0, "");
+    }
+    disassembler.field(key, field)?;
+  }
+  for (position, (key, method)) in class.methods.iter().enumerate() {
+    if position > 0 || !class.fields.is_empty() {
+      disassembler.line(0, "");
+    }
+    disassembler.method(key, method)?;
+  }
+  disassembler.line(0, "}");
+  for attribute in &class.attributes {
+    disassembler.attribute(attribute, 0)?;
+  }
+  Ok(disassembler.out)
+}
+
+struct Disassembler<'a> {
+  class: &'a ClassFile,
+  /// The constant pool index of each constant, by its kind and `describe` text.
+  /// Instructions and attributes hold resolved constants, so this finds where they came from.
+  indices: HashMap<(&'static str, String), u16>,
+  out: String
+}
+
+impl Disassembler<'_> {
+  fn line(&mut self, indent: usize, text: &str) {
+    self.out.push_str(&" ".repeat(indent));
+    self.out.push_str(text);
+    self.out.push('\n');
+  }
+
+  /// A line ending in a comment lined up with the others, as javap prints them.
+  fn commented_line(&mut self, indent: usize, text: &str, comment: &str) {
+    self.line(indent, &format!("{:<39} // {}", text, comment));
+  }
+
+  /// The index of `constant` in the constant pool, or 0 if it isn't there. A constant the pool
+  /// holds more than once gets its first index.
+  fn index(&self, constant: &ResolvedCpInfo) -> u16 {
+    self.indices.get(&(constant.kind(), describe(constant))).copied().unwrap_or_default()
+  }
+
+  fn class_index(&self, class: &Class) -> u16 { self.index(&ResolvedCpInfo::Class(class.clone())) }
+
+  fn utf8_index(&self, text: &str) -> u16 {
+    self.index(&ResolvedCpInfo::Utf8(JavaString { chars: text.encode_utf16().collect() }))
+  }
+
+  fn header(&mut self, constant_pool: &ConstantPool<CpInfo>, resolved: &ConstantPool) {
+    let class = self.class;
+    let source_file = class.attributes.iter().find_map(|attribute| match attribute {
+      Attribute::SourceFile { sourcefile } => Some(sourcefile),
+      _ => None
+    });
+    if let Some(source_file) = source_file {
+      self.line(2, &format!("Compiled from \"{}\"", source_file));
+    }
+    self.line(0, &self.declaration());
+    self.line(2, &format!("minor version: {}", class.minor_version));
+    self.line(2, &format!("major version: {}", class.major_version));
+    self.line(2, &format!("flags: {}", flags(*class.access_flags, CLASS_FLAGS)));
+    let this_class = format!("this_class: #{}", self.class_index(&class.this_class));
+    self.commented_line(2, &this_class, &class.this_class.name);
+    match &class.super_class {
+      Some(super_class) => {
+        let text = format!("super_class: #{}", self.class_index(super_class));
+        self.commented_line(2, &text, &super_class.name)
+      }
+      None => self.line(2, "super_class: #0")
+    }
+    self.line(
+      2,
+      &format!(
+        "interfaces: {}, fields: {}, methods: {}, attributes: {}",
+        class.interfaces.len(),
+        class.fields.len(),
+        class.methods.len(),
+        class.attributes.len()
+      )
+    );
+
+    self.line(0, "Constant pool:");
+    let width = format!("#{}", constant_pool.count()).len() + 2;
+    for (index, constant) in constant_pool.iter() {
+      let Ok(resolved) = resolved.get(index) else { continue };
+      let operands = match constant {
+        CpInfo::Class { name_index }
+        | CpInfo::Module { name_index }
+        | CpInfo::Package { name_index } => format!("#{}", name_index),
+        CpInfo::Fieldref { class_index, name_and_type_index }
+        | CpInfo::Methodref { class_index, name_and_type_index }
+        | CpInfo::InterfaceMethodref { class_index, name_and_type_index } => {
+          format!("#{}.#{}", class_index, name_and_type_index)
+        }
+        CpInfo::String { string_index } => format!("#{}", string_index),
+        CpInfo::NameAndType { name_index, descriptor_index } => {
+          format!("#{}:#{}", name_index, descriptor_index)
+        }
+        CpInfo::MethodHandle { reference_kind, reference_index } => {
+          format!("{}:#{}", reference_kind, reference_index)
+        }
+        CpInfo::MethodType { descriptor_index } => format!("#{}", descriptor_index),
+        CpInfo::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+          format!("#{}:#{}", bootstrap_method_attr_index, name_and_type_index)
+        }
+        CpInfo::InvokeDynamic { bootstrap_method_attr_index, reference_index } => {
+          format!("#{}:#{}", bootstrap_method_attr_index, reference_index)
+        }
+        // Values are shown in place rather than in a comment
+        CpInfo::Integer { .. }
+        | CpInfo::Float { .. }
+        | CpInfo::Long { .. }
+        | CpInfo::Double { .. }
+        | CpInfo::Utf8 { .. } => {
+          let entry = format!(
+            "{:>width$} = {:<18} {}",
+            format!("#{}", index),
+            constant.kind(),
+            describe(resolved)
+          );
+          self.line(0, &entry);
+          continue;
+        }
+      };
+      // javap sets method types off by an extra space
+      let separator = if let CpInfo::MethodType { .. } = constant { "  " } else { " " };
+      let entry = format!(
+        "{:>width$} = {:<18} {:<14} //{}{}",
+        format!("#{}", index),
+        constant.kind(),
+        operands,
+        separator,
+        describe(resolved)
+      );
+      self.line(0, &entry);
+    }
+  }
+
+  /// The class as Java would declare it, such as `public final class Foo extends Bar`.
+  fn declaration(&self) -> String {
+    let class = self.class;
+    if class.access_flags.contains(classfile::Flags::AccModule) {
+      let name = class.attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Module { module_name, .. } => Some(module_name.as_str()),
+        _ => None
+      });
+      return format!("module {}", name.unwrap_or(&class.this_class.name));
+    }
+    // A generic class declares its type parameters and supertypes in its signature
+    let (type_parameters, super_class, interfaces): (String, _, Vec<String>) =
+      match class.signature() {
+        Ok(Some(signature)) => (
+          type_parameters_to_java(&signature.type_parameters, true),
+          Some(signature.superclass.to_java(true)),
+          signature.superinterfaces.iter().map(|interface| interface.to_java(true)).collect()
+        ),
+        _ => (
+          String::new(),
+          class.super_class.as_ref().map(|super_class| java_name(&super_class.name)),
+          class.interfaces.iter().map(|interface| java_name(&interface.name)).collect()
+        )
+      };
+    let name = java_name(&class.this_class.name) + &type_parameters;
+    if class.access_flags.contains(classfile::Flags::AccInterface) {
+      // Interfaces are abstract without saying so
+      let mask = *class.access_flags & !*classfile::Flags::AccAbstract;
+      let mut declaration = format!("{}interface {}", modifiers(mask, CLASS_FLAGS), name);
+      if !interfaces.is_empty() {
+        declaration += &format!(" extends {}", interfaces.join(", "));
+      }
+      return declaration;
+    }
+    let mut declaration = format!("{}class {}", modifiers(*class.access_flags, CLASS_FLAGS), name);
+    if let Some(super_class) = super_class.filter(|super_class| super_class != "java.lang.Object") {
+      declaration += &format!(" extends {}", super_class);
+    }
+    if !interfaces.is_empty() {
+      declaration += &format!(" implements {}", interfaces.join(", "));
+    }
+    declaration
+  }
+
+  fn field(&mut self, key: &str, field: &FieldInfo) -> Result<(), ClassFormatError> {
+    let descriptor = field.descriptor.to_string();
+    let field_type = match field.signature() {
+      Ok(Some(signature)) => signature.to_java(true),
+      _ => java_type(&field.descriptor)
+    };
+    let declaration = format!(
+      "{}{} {};",
+      modifiers(*field.access_flags, FIELD_FLAGS),
+      field_type,
+      member_name(key, &descriptor)
+    );
+    self.line(2, &declaration);
+    self.line(4, &format!("descriptor: {}", descriptor));
+    self.line(4, &format!("flags: {}", flags(*field.access_flags, FIELD_FLAGS)));
+    for attribute in &field.attributes {
+      self.attribute(attribute, 4)?;
+    }
+    Ok(())
+  }
+
+  fn method(&mut self, key: &str, method: &MethodInfo) -> Result<(), ClassFormatError> {
+    let descriptor = method.descriptor.to_string();
+    self.line(2, &format!("{};", self.method_declaration(member_name(key, &descriptor), method)));
+    self.line(4, &format!("descriptor: {}", descriptor));
+    self.line(4, &format!("flags: {}", flags(*method.access_flags, METHOD_FLAGS)));
+    // javap counts the arguments rather than the slots they take
+    let this = !method.access_flags.contains(method_info::Flags::AccStatic) as usize;
+    let args_size = method.descriptor.params.len() + this;
+    for attribute in &method.attributes {
+      match attribute {
+        Attribute::Code { max_stack, max_locals, code, exception_table, attributes, offsets } => {
+          self.line(4, "Code:");
+          self.line(
+            6,
+            &format!("stack={}, locals={}, args_size={}", max_stack, max_locals, args_size)
+          );
+          self.code(code, offsets)?;
+          self.exception_table(exception_table, offsets)?;
+          for attribute in attributes {
+            self.code_attribute(attribute, offsets)?;
+          }
+        }
+        _ => self.attribute(attribute, 4)?
+      }
+    }
+    Ok(())
+  }
+
+  /// The method as Java would declare it, such as `public static void main(java.lang.String[])`.
+  fn method_declaration(&self, name: &str, method: &MethodInfo) -> String {
+    use method_info::Flags::*;
+
+    let flags = method.access_flags;
+    if name == "<clinit>" {
+      return "static {}".to_string();
+    }
+    let (type_parameters, ret, mut params, mut throws): (String, _, Vec<String>, Vec<String>) =
+      match method.signature() {
+        Ok(Some(signature)) => (
+          type_parameters_to_java(&signature.type_parameters, true),
+          signature.ret.as_ref().map(|ret| ret.to_java(true)),
+          signature.params.iter().map(|param| param.to_java(true)).collect(),
+          signature.throws.iter().map(|throws| throws.to_java(true)).collect()
+        ),
+        _ => (
+          String::new(),
+          method.descriptor.ret.as_ref().map(java_type),
+          method.descriptor.params.iter().map(java_type).collect(),
+          Vec::new()
+        )
+      };
+    if flags.contains(AccVarArgs) {
+      if let Some(last) = params.last_mut() {
+        if let Some(element) = last.strip_suffix("[]") {
+          *last = format!("{}...", element);
+        }
+      }
+    }
+    let mut modifiers = modifiers(*flags, METHOD_FLAGS);
+    let is_interface = self.class.access_flags.contains(classfile::Flags::AccInterface);
+    if is_interface && !flags.intersects(AccAbstract | AccStatic | AccPrivate) {
+      modifiers += "default ";
+    }
+    if !type_parameters.is_empty() {
+      modifiers += &format!("{} ", type_parameters);
+    }
+    let name = match name {
+      "<init>" => java_name(&self.class.this_class.name),
+      _ => format!("{} {}", ret.unwrap_or_else(|| "void".to_string()), name)
+    };
+    let mut declaration = format!("{}{}({})", modifiers, name, params.join(", "));
+    if throws.is_empty() {
+      let exceptions = method.attributes.iter().find_map(|attribute| match attribute {
+        Attribute::Exceptions { exceptions } => Some(exceptions),
+        _ => None
+      });
+      throws =
+        exceptions.into_iter().flatten().map(|exception| java_name(&exception.name)).collect();
+    }
+    if !throws.is_empty() {
+      declaration += &format!(" throws {}", throws.join(", "));
+    }
+    declaration
+  }
+
+  /// Prints an attribute of the class, a field or a method, or of a record component.
+  fn attribute(&mut self, attribute: &Attribute, indent: usize) -> Result<(), ClassFormatError> {
+    match attribute {
+      Attribute::ConstantValue { value } => {
+        let constant = match value {
+          Constant::Int(value) => ResolvedCpInfo::Integer(*value),
+          Constant::Long(value) => ResolvedCpInfo::Long(*value),
+          Constant::Float(value) => ResolvedCpInfo::Float(*value),
+          Constant::Double(value) => ResolvedCpInfo::Double(*value),
+          Constant::String(value) => ResolvedCpInfo::String(value.clone())
+        };
+        self.line(indent, &format!("ConstantValue: {}", loadable(&constant)));
+      }
+      Attribute::Exceptions { exceptions } => {
+        let exceptions: Vec<String> =
+          exceptions.iter().map(|exception| java_name(&exception.name)).collect();
+        self.line(indent, "Exceptions:");
+        self.line(indent + 2, &format!("throws {}", exceptions.join(", ")));
+      }
+      Attribute::InnerClasses { classes } => {
+        self.line(indent, "InnerClasses:");
+        for inner in classes {
+          let mut mask = inner.inner_class_access_flags;
+          // Interfaces are abstract without saying so
+          if mask & *classfile::Flags::AccInterface != 0 {
+            mask &= !*classfile::Flags::AccAbstract;
+          }
+          let modifiers = modifiers(mask, INNER_CLASS_FLAGS);
+          let inner_class = format!("#{}", self.class_index(&inner.inner_class));
+          let (text, comment) = match &inner.inner_name {
+            Some(name) => {
+              (format!("#{}= {}", self.utf8_index(name), inner_class), format!("{}=", name))
+            }
+            None => (inner_class, String::new())
+          };
+          let (text, comment) = match &inner.outer_class {
+            Some(outer) => (
+              format!("{} of #{}", text, self.class_index(outer)),
+              format!("{}class {} of class {}", comment, inner.inner_class.name, outer.name)
+            ),
+            None => (text, format!("{}class {}", comment, inner.inner_class.name))
+          };
+          self.commented_line(indent + 2, &format!("{}{};", modifiers, text), &comment);
+        }
+      }
+      Attribute::EnclosingMethod { class, method } => {
+        let class_index = self.class_index(class);
+        let (method_index, comment) = match method {
+          Some(method) => (
+            self.index(&ResolvedCpInfo::NameAndType(method.clone())),
+            format!("{}.{}", class.name, method.name)
+          ),
+          None => (0, class.name.clone())
+        };
+        let text = format!("EnclosingMethod: #{}.#{}", class_index, method_index);
+        self.commented_line(indent, &text, &comment);
+      }
+      Attribute::Synthetic => self.line(indent, "Synthetic: true"),
+      Attribute::Deprecated => self.line(indent, "Deprecated: true"),
+      Attribute::Signature { signature } => {
+        let text = format!("Signature: #{}", self.utf8_index(signature));
+        self.commented_line(indent, &text, signature);
+      }
+      Attribute::SourceFile { sourcefile } => {
+        self.line(indent, &format!("SourceFile: \"{}\"", sourcefile))
+      }
+      Attribute::SourceDebugExtension { debug_extension } => {
+        self.line(indent, "SourceDebugExtension:");
+        for line in String::from_utf8_lossy(debug_extension).lines() {
+          self.line(indent + 2, line);
+        }
+      }
+      Attribute::RuntimeVisibleAnnotations { annotations }
+      | Attribute::RuntimeInvisibleAnnotations { annotations } => {
+        self.line(indent, &format!("{}:", attribute.name()));
+        for (position, value) in annotations.iter().enumerate() {
+          self.annotation(position, value, indent + 2);
+        }
+      }
+      Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations }
+      | Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+        self.line(indent, &format!("{}:", attribute.name()));
+        for (parameter, annotations) in parameter_annotations.iter().enumerate() {
+          self.line(indent + 2, &format!("parameter {}:", parameter));
+          for (position, value) in annotations.annotations.iter().enumerate() {
+            self.annotation(position, value, indent + 4);
+          }
+        }
+      }
+      Attribute::RuntimeVisibleTypeAnnotations { annotations }
+      | Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+        self.line(indent, &format!("{}:", attribute.name()));
+        for (position, value) in annotations.iter().enumerate() {
+          let text = format!(
+            "{}: {}: {}",
+            position,
+            self.annotation_indices(&value.annotation),
+            type_annotation_target(value)
+          );
+          self.line(indent + 2, &text);
+          self.annotation_java("", &value.annotation, indent + 4);
+        }
+      }
+      Attribute::AnnotationDefault { default_value } => {
+        self.line(indent, "AnnotationDefault:");
+        self.annotation_default(default_value, indent + 2);
+      }
+      Attribute::BootstrapMethods { bootstrap_methods } => {
+        self.line(indent, "BootstrapMethods:");
+        for (index, bootstrap_method) in bootstrap_methods.iter().enumerate() {
+          let handle = &bootstrap_method.bootstrap_method;
+          let handle_index = self.index(&ResolvedCpInfo::MethodHandle(handle.clone()));
+          self.line(indent + 2, &format!("{}: #{} {}", index, handle_index, method_handle(handle)));
+          self.line(indent + 4, "Method arguments:");
+          for argument in &bootstrap_method.bootstrap_arguments {
+            self.line(indent + 6, &format!("#{} {}", self.index(argument), describe(argument)));
+          }
+        }
+      }
+      Attribute::MethodParameters { parameters } => {
+        self.line(indent, "MethodParameters:");
+        self.line(indent + 2, &format!("{:<30} Flags", "Name"));
+        for parameter in parameters {
+          let flags: Vec<String> = PARAMETER_FLAGS
+            .iter()
+            .filter(|(flag, ..)| *parameter.access_flags & flag != 0)
+            .map(|(_, name, _)| keyword(name))
+            .collect();
+          let name = parameter.name.as_deref().unwrap_or("<no name>");
+          self.line(indent + 2, format!("{:<30} {}", name, flags.join(" ")).trim_end());
+        }
+      }
+      Attribute::Module {
+        module_name,
+        module_flags,
+        module_version,
+        requires,
+        exports,
+        opens,
+        uses,
+        provides
+      } => {
+        self.line(indent, "Module:");
+        let version =
+          module_version.as_deref().map_or(String::new(), |version| format!("@{}", version));
+        self.line(indent + 2, &format!("{}{} (0x{:04x})", module_name, version, **module_flags));
+        for required in requires {
+          let version = required
+            .requires_version
+            .as_deref()
+            .map_or(String::new(), |version| format!("@{}", version));
+          self.line(
+            indent + 2,
+            &format!(
+              "requires {}{} (0x{:04x})",
+              required.requires, version, *required.requires_flags
+            )
+          );
+        }
+        for exported in exports {
+          let to = match exported.exports_to.is_empty() {
+            true => String::new(),
+            false => format!(" to {}", exported.exports_to.join(", "))
+          };
+          self.line(
+            indent + 2,
+            &format!("exports {}{} (0x{:04x})", exported.exports, to, *exported.exports_flags)
+          );
+        }
+        for opened in opens {
+          let to = match opened.opens_to.is_empty() {
+            true => String::new(),
+            false => format!(" to {}", opened.opens_to.join(", "))
+          };
+          self.line(
+            indent + 2,
+            &format!("opens {}{} (0x{:04x})", opened.opens, to, *opened.opens_flags)
+          );
+        }
+        for service in uses {
+          self.line(indent + 2, &format!("uses {}", service.name));
+        }
+        for provided in provides {
+          let with: Vec<&str> =
+            provided.provides_with.iter().map(|class| class.name.as_str()).collect();
+          self.line(
+            indent + 2,
+            &format!("provides {} with {}", provided.provides.name, with.join(", "))
+          );
+        }
+      }
+      Attribute::ModulePackages { packages } => {
+        self.line(indent, "ModulePackages:");
+        for package in packages {
+          self.line(indent + 2, package);
+        }
+      }
+      Attribute::ModuleMainClass { main_class } => {
+        let text = format!("ModuleMainClass: #{}", self.class_index(main_class));
+        self.commented_line(indent, &text, &main_class.name);
+      }
+      Attribute::NestHost { host_class } => {
+        self.line(indent, &format!("NestHost: class {}", host_class.name))
+      }
+      Attribute::NestMembers { classes } | Attribute::PermittedSubclasses { classes } => {
+        self.line(indent, &format!("{}:", attribute.name()));
+        for class in classes {
+          self.line(indent + 2, &class.name);
+        }
+      }
+      Attribute::Record { components } => {
+        self.line(indent, "Record:");
+        for component in components {
+          let component_type = match component.signature() {
+            Ok(Some(signature)) => signature.to_java(true),
+            _ => java_type(&FieldType::parse(&component.descriptor)?)
+          };
+          self.line(indent + 2, &format!("{} {};", component_type, component.name));
+          self.line(indent + 4, &format!("descriptor: {}", component.descriptor));
+          for attribute in &component.attributes {
+            self.attribute(attribute, indent + 4)?;
+          }
+          self.line(0, "");
+        }
+      }
+      Attribute::Custom { bytes, .. } | Attribute::Unknown { bytes, .. } => {
+        let text =
+          format!("{}: length = 0x{:x} (unknown attribute)", attribute.name(), bytes.len());
+        self.line(indent + 2, &text);
+        if !bytes.is_empty() {
+          let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+          self.line(indent + 3, &bytes.join(" "));
+        }
+      }
+      // These belong in a Code attribute, so only turn up elsewhere in a malformed class
+      Attribute::Code { .. }
+      | Attribute::StackMapTable { .. }
+      | Attribute::LineNumberTable { .. }
+      | Attribute::LocalVariableTable { .. }
+      | Attribute::LocalVariableTypeTable { .. } => {
+        self.line(indent, &format!("{}: {:?}", attribute.name(), attribute))
+      }
+    }
+    Ok(())
+  }
+}
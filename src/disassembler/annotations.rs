@@ -0,0 +1,84 @@
+use super::{
+  text::{element_value, java_type},
+  Disassembler
+};
+use crate::parser::{
+  attribute_info::attribute::{annotation::Annotation, element_value::ElementValue},
+  cp_info_resolved::ResolvedCpInfo,
+  descriptor::FieldType
+};
+
+impl Disassembler<'_> {
+  /// Prints an annotation the way javap does: first with the constant pool indices it is made of,
+  /// then in something like Java syntax.
+  pub(super) fn annotation(&mut self, position: usize, annotation: &Annotation, indent: usize) {
+    self.line(indent, &format!("{}: {}", position, self.annotation_indices(annotation)));
+    self.annotation_java("", annotation, indent + 2);
+  }
+
+  /// Prints the default value of an annotation interface's element.
+  pub(super) fn annotation_default(&mut self, value: &ElementValue, indent: usize) {
+    self.line(indent, &format!("default_value: {}", self.element_value_indices(value)));
+    self.element_value_java("", value, indent + 2);
+  }
+
+  pub(super) fn annotation_java(&mut self, prefix: &str, annotation: &Annotation, indent: usize) {
+    let type_name = FieldType::parse(&annotation.type_name)
+      .map(|field_type| java_type(&field_type))
+      .unwrap_or_else(|_| annotation.type_name.clone());
+    if annotation.element_value_pairs.is_empty() {
+      return self.line(indent, &format!("{}{}", prefix, type_name));
+    }
+    self.line(indent, &format!("{}{}(", prefix, type_name));
+    for (name, value) in &annotation.element_value_pairs {
+      self.element_value_java(&format!("{}=", name), value, indent + 2);
+    }
+    self.line(indent, ")");
+  }
+
+  fn element_value_java(&mut self, prefix: &str, value: &ElementValue, indent: usize) {
+    match value {
+      ElementValue::Annotation(nested) => {
+        self.annotation_java(&format!("{}@", prefix), nested, indent)
+      }
+      value => self.line(indent, &format!("{}{}", prefix, element_value(value)))
+    }
+  }
+
+  /// An annotation as javap first shows it, such as `#14(#15=c#16,#17=J#18)`.
+  pub(super) fn annotation_indices(&self, annotation: &Annotation) -> String {
+    let pairs: Vec<String> = annotation
+      .element_value_pairs
+      .iter()
+      .map(|(name, value)| {
+        format!("#{}={}", self.utf8_index(name), self.element_value_indices(value))
+      })
+      .collect();
+    format!("#{}({})", self.utf8_index(&annotation.type_name), pairs.join(","))
+  }
+
+  fn element_value_indices(&self, value: &ElementValue) -> String {
+    let constant = match value {
+      ElementValue::Byte(value) => ResolvedCpInfo::Integer(*value as i32),
+      ElementValue::Char(value) => ResolvedCpInfo::Integer(*value as i32),
+      ElementValue::Double(value) => ResolvedCpInfo::Double(*value),
+      ElementValue::Float(value) => ResolvedCpInfo::Float(*value),
+      ElementValue::Int(value) => ResolvedCpInfo::Integer(*value),
+      ElementValue::Long(value) => ResolvedCpInfo::Long(*value),
+      ElementValue::Short(value) => ResolvedCpInfo::Integer(*value as i32),
+      ElementValue::Boolean(value) => ResolvedCpInfo::Integer(*value as i32),
+      ElementValue::String(value) => ResolvedCpInfo::Utf8(value.clone()),
+      ElementValue::Class(value) => return format!("c#{}", self.utf8_index(value)),
+      ElementValue::Enum { type_name, const_name } => {
+        return format!("e#{}.#{}", self.utf8_index(type_name), self.utf8_index(const_name));
+      }
+      ElementValue::Annotation(nested) => return format!("@{}", self.annotation_indices(nested)),
+      ElementValue::Array(values) => {
+        let values: Vec<String> =
+          values.iter().map(|value| self.element_value_indices(value)).collect();
+        return format!("[{}]", values.join(","));
+      }
+    };
+    format!("{}#{}", value.tag() as char, self.index(&constant))
+  }
+}
@@ -0,0 +1,378 @@
+use super::{
+  text::{describe, loadable},
+  Disassembler
+};
+use crate::parser::{
+  attribute_info::{
+    attribute::{
+      exception::Exception,
+      stack_map_frame::{verification_type_info::VerificationTypeInfo, StackMapFrame}
+    },
+    code::{code_generator::Instructions, offset_map::OffsetMap, untranslate},
+    Attribute
+  },
+  class_format_error::ClassFormatError,
+  cp_info_resolved::{Class, ResolvedCpInfo}
+};
+
+impl Disassembler<'_> {
+  /// Lists the instructions with their byte offsets, as javap does.
+  pub(super) fn code(
+    &mut self,
+    code: &[Instructions],
+    offsets: &OffsetMap
+  ) -> Result<(), ClassFormatError> {
+    for (index, instruction) in code.iter().enumerate() {
+      let offset = offset_of(offsets, index)?;
+      let size = offset_of(offsets, index + 1)? - offset;
+      let target = |delta: isize| {
+        usize::try_from(index as isize + delta)
+          .ok()
+          .filter(|target| *target < code.len())
+          .and_then(|target| offsets.offset_of(target))
+          .ok_or(ClassFormatError::BadCodeIndex { index: index as isize + delta })
+      };
+      let (mnemonic, operands, comment) = match instruction {
+        Instructions::tableswitch { default, low, high, offsets: targets } => {
+          self.line(0, &format!("{:>10}: {:<13} {{ // {} to {}", offset, "tableswitch", low, high));
+          for (key, delta) in (*low..).zip(targets) {
+            self.line(0, &format!("{:>24}: {}", key, target(*delta as isize)?));
+          }
+          self.line(0, &format!("{:>24}: {}", "default", target(*default as isize)?));
+          self.line(12, "}");
+          continue;
+        }
+        Instructions::lookupswith { default, npairs, pairs } => {
+          self.line(0, &format!("{:>10}: {:<13} {{ // {}", offset, "lookupswitch", npairs));
+          for (key, delta) in pairs {
+            self.line(0, &format!("{:>24}: {}", key, target(*delta as isize)?));
+          }
+          self.line(0, &format!("{:>24}: {}", "default", target(*default as isize)?));
+          self.line(12, "}");
+          continue;
+        }
+        instruction => self.instruction(instruction, size, target)?
+      };
+      let text = match operands.is_empty() {
+        true => mnemonic,
+        false => format!("{:<13} {}", mnemonic, operands)
+      };
+      match comment {
+        Some(comment) => self.line(0, &format!("{:>10}: {:<33} // {}", offset, text, comment)),
+        None => self.line(0, &format!("{:>10}: {}", offset, text))
+      }
+    }
+    Ok(())
+  }
+
+  /// The mnemonic, operands and comment of an instruction other than a switch.
+  /// `size` is the length of its encoding, which tells `iload_1` from `iload 1`.
+  fn instruction(
+    &self,
+    instruction: &Instructions,
+    size: usize,
+    target: impl Fn(isize) -> Result<usize, ClassFormatError>
+  ) -> Result<(String, String, Option<String>), ClassFormatError> {
+    let plain = |mnemonic: &str, operands: String| (mnemonic.to_string(), operands, None);
+    let local = |mnemonic: &str, index: u8| match size {
+      1 => plain(&format!("{}_{}", mnemonic, index), String::new()),
+      _ => plain(mnemonic, index.to_string())
+    };
+    let constant = |mnemonic: &str, operands: String, constant: ResolvedCpInfo, comment: String| {
+      let operands = format!("#{}{}", self.index(&constant), operands);
+      (mnemonic.to_string(), operands, Some(comment))
+    };
+    let class = |mnemonic: &str, class: &Class, operands: String| {
+      let reference = ResolvedCpInfo::Class(class.clone());
+      let comment = format!("class {}", describe(&reference));
+      constant(mnemonic, operands, reference, comment)
+    };
+    let member = |mnemonic: &str, kind: &str, reference: ResolvedCpInfo, class: &Class| {
+      // Like javap, leave out the class for members of this one
+      let text = describe(&reference);
+      let prefix = format!("{}.", class.name);
+      let text = match class == &self.class.this_class {
+        true => text.strip_prefix(&prefix).unwrap_or(&text).to_string(),
+        false => text
+      };
+      constant(mnemonic, String::new(), reference, format!("{} {}", kind, text))
+    };
+    let branch = |mnemonic: &str, delta: isize| Ok(plain(mnemonic, target(delta)?.to_string()));
+
+    Ok(match instruction {
+      Instructions::iconst { value: -1 } => plain("iconst_m1", String::new()),
+      Instructions::iconst { value } => plain(&format!("iconst_{}", value), String::new()),
+      Instructions::lconst { value } => plain(&format!("lconst_{}", value), String::new()),
+      Instructions::fconst { value } => plain(&format!("fconst_{}", value), String::new()),
+      Instructions::dconst { value } => plain(&format!("dconst_{}", value), String::new()),
+      Instructions::bipush { value } => plain("bipush", value.to_string()),
+      Instructions::sipush { value } => plain("sipush", value.to_string()),
+      Instructions::ldc { constant: value } => {
+        constant("ldc", String::new(), value.clone(), loadable(value))
+      }
+      Instructions::ldc_w { constant: value } => {
+        constant("ldc_w", String::new(), value.clone(), loadable(value))
+      }
+      Instructions::ldc2_w { constant: value } => {
+        constant("ldc2_w", String::new(), value.clone(), loadable(value))
+      }
+      Instructions::iload { index } => local("iload", *index),
+      Instructions::lload { index } => local("lload", *index),
+      Instructions::fload { index } => local("fload", *index),
+      Instructions::dload { index } => local("dload", *index),
+      Instructions::aload { index } => local("aload", *index),
+      Instructions::istore { index } => local("istore", *index),
+      Instructions::lstore { index } => local("lstore", *index),
+      Instructions::fstore { index } => local("fstore", *index),
+      Instructions::dstore { index } => local("dstore", *index),
+      Instructions::astore { index } => local("astore", *index),
+      Instructions::ret { index } => plain("ret", index.to_string()),
+      Instructions::iinc { index, r#const } => plain("iinc", format!("{}, {}", index, r#const)),
+      Instructions::wide1 { opcode, index_extension } => {
+        let mnemonic = match opcode {
+          21 => "iload_w",
+          22 => "lload_w",
+          23 => "fload_w",
+          24 => "dload_w",
+          25 => "aload_w",
+          54 => "istore_w",
+          55 => "lstore_w",
+          56 => "fstore_w",
+          57 => "dstore_w",
+          58 => "astore_w",
+          _ => "ret_w"
+        };
+        plain(mnemonic, index_extension.to_string())
+      }
+      Instructions::wide2 { index_extension, constbytes, .. } => {
+        plain("iinc_w", format!("{}, {}", index_extension, constbytes))
+      }
+      Instructions::ifeq { offset } => branch("ifeq", *offset as isize)?,
+      Instructions::ifne { offset } => branch("ifne", *offset as isize)?,
+      Instructions::iflt { offset } => branch("iflt", *offset as isize)?,
+      Instructions::ifge { offset } => branch("ifge", *offset as isize)?,
+      Instructions::ifgt { offset } => branch("ifgt", *offset as isize)?,
+      Instructions::ifle { offset } => branch("ifle", *offset as isize)?,
+      Instructions::if_icmpeq { offset } => branch("if_icmpeq", *offset as isize)?,
+      Instructions::if_icmpne { offset } => branch("if_icmpne", *offset as isize)?,
+      Instructions::if_icmplt { offset } => branch("if_icmplt", *offset as isize)?,
+      Instructions::if_icmpge { offset } => branch("if_icmpge", *offset as isize)?,
+      Instructions::if_icmpgt { offset } => branch("if_icmpgt", *offset as isize)?,
+      Instructions::if_icmple { offset } => branch("if_icmple", *offset as isize)?,
+      Instructions::if_acmpeq { offset } => branch("if_acmpeq", *offset as isize)?,
+      Instructions::if_acmpne { offset } => branch("if_acmpne", *offset as isize)?,
+      Instructions::ifnull { offset } => branch("ifnull", *offset as isize)?,
+      Instructions::ifnonnull { offset } => branch("ifnonnull", *offset as isize)?,
+      Instructions::goto { offset } => branch("goto", *offset as isize)?,
+      Instructions::jsr { offset } => branch("jsr", *offset as isize)?,
+      Instructions::goto_w { offset } => branch("goto_w", *offset as isize)?,
+      Instructions::jsr_w { offset } => branch("jsr_w", *offset as isize)?,
+      Instructions::getstatic { fieldref } => {
+        member("getstatic", "Field", ResolvedCpInfo::Fieldref(fieldref.clone()), &fieldref.class)
+      }
+      Instructions::putstatic { fieldref } => {
+        member("putstatic", "Field", ResolvedCpInfo::Fieldref(fieldref.clone()), &fieldref.class)
+      }
+      Instructions::getfield { fieldref } => {
+        member("getfield", "Field", ResolvedCpInfo::Fieldref(fieldref.clone()), &fieldref.class)
+      }
+      Instructions::putfield { fieldref } => {
+        member("putfield", "Field", ResolvedCpInfo::Fieldref(fieldref.clone()), &fieldref.class)
+      }
+      Instructions::invokevirtual { methodref } => member(
+        "invokevirtual",
+        "Method",
+        ResolvedCpInfo::Methodref(methodref.clone()),
+        &methodref.class
+      ),
+      Instructions::invokespecial { methodref } => member(
+        "invokespecial",
+        "Method",
+        ResolvedCpInfo::Methodref(methodref.clone()),
+        &methodref.class
+      ),
+      Instructions::invokestatic { methodref } => member(
+        "invokestatic",
+        "Method",
+        ResolvedCpInfo::Methodref(methodref.clone()),
+        &methodref.class
+      ),
+      Instructions::invokeinterface { interfacemethodref, count } => {
+        let (mnemonic, operands, comment) = member(
+          "invokeinterface",
+          "InterfaceMethod",
+          ResolvedCpInfo::InterfaceMethodref(interfacemethodref.clone()),
+          &interfacemethodref.class
+        );
+        (mnemonic, format!("{},  {}", operands, count), comment)
+      }
+      Instructions::invokedynamic { invokedynamic } => {
+        let reference = ResolvedCpInfo::InvokeDynamic(invokedynamic.clone());
+        let comment = format!("InvokeDynamic {}", describe(&reference));
+        constant("invokedynamic", ",  0".to_string(), reference, comment)
+      }
+      Instructions::new { class: type_name } => class("new", type_name, String::new()),
+      Instructions::anewarray { class: type_name } => class("anewarray", type_name, String::new()),
+      Instructions::checkcast { class: type_name } => class("checkcast", type_name, String::new()),
+      Instructions::instanceof { class: type_name } => {
+        class("instanceof", type_name, String::new())
+      }
+      Instructions::multianewarray { class: type_name, dimensions } => {
+        class("multianewarray", type_name, format!(",  {}", dimensions))
+      }
+      Instructions::newarray { atype } => {
+        let element = match atype {
+          4 => "boolean".to_string(),
+          5 => "char".to_string(),
+          6 => "float".to_string(),
+          7 => "double".to_string(),
+          8 => "byte".to_string(),
+          9 => "short".to_string(),
+          10 => "int".to_string(),
+          11 => "long".to_string(),
+          atype => atype.to_string()
+        };
+        plain("newarray", element)
+      }
+      Instructions::r#return => plain("return", String::new()),
+      // Every other instruction has no operands, and its variant is named after it
+      instruction => plain(&format!("{:?}", instruction), String::new())
+    })
+  }
+
+  pub(super) fn exception_table(
+    &mut self,
+    exception_table: &[Exception],
+    offsets: &OffsetMap
+  ) -> Result<(), ClassFormatError> {
+    if exception_table.is_empty() {
+      return Ok(());
+    }
+    self.line(6, "Exception table:");
+    self.line(9, "from    to  target type");
+    for exception in exception_table {
+      let mut exception = exception.clone();
+      exception.untranslate(offsets)?;
+      let catch_type = match &exception.catch_type {
+        Some(class) => format!("Class {}", class.name),
+        None => "any".to_string()
+      };
+      let text = format!(
+        "{:>5} {:>5} {:>5}   {}",
+        exception.start_pc, exception.end_pc, exception.handler_pc, catch_type
+      );
+      self.line(9, &text);
+    }
+    Ok(())
+  }
+
+  /// Prints an attribute of a Code attribute, with positions as byte offsets.
+  pub(super) fn code_attribute(
+    &mut self,
+    attribute: &Attribute,
+    offsets: &OffsetMap
+  ) -> Result<(), ClassFormatError> {
+    let mut attribute = attribute.clone();
+    untranslate(&mut attribute, offsets)?;
+    match &attribute {
+      Attribute::LineNumberTable { line_number_table } => {
+        self.line(6, "LineNumberTable:");
+        for line_number in line_number_table {
+          let text = format!("line {}: {}", line_number.line_number, line_number.start_pc);
+          self.line(8, &text);
+        }
+      }
+      Attribute::LocalVariableTable { local_variable_table } => {
+        self.line(6, "LocalVariableTable:");
+        self.line(8, "Start  Length  Slot  Name   Signature");
+        for variable in local_variable_table {
+          let text = format!(
+            "{:>5} {:>7} {:>5} {:>5}   {}",
+            variable.start_pc, variable.length, variable.index, variable.name, variable.descriptor
+          );
+          self.line(8, &text);
+        }
+      }
+      Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+        self.line(6, "LocalVariableTypeTable:");
+        self.line(8, "Start  Length  Slot  Name   Signature");
+        for variable in local_variable_type_table {
+          let text = format!(
+            "{:>5} {:>7} {:>5} {:>5}   {}",
+            variable.start_pc, variable.length, variable.index, variable.name, variable.signature
+          );
+          self.line(8, &text);
+        }
+      }
+      Attribute::StackMapTable { entries } => {
+        self.line(6, &format!("StackMapTable: number_of_entries = {}", entries.len()));
+        for entry in entries {
+          self.stack_map_frame(entry);
+        }
+      }
+      attribute => self.attribute(attribute, 6)?
+    }
+    Ok(())
+  }
+
+  fn stack_map_frame(&mut self, frame: &StackMapFrame) {
+    let (frame_type, name) = match frame {
+      StackMapFrame::SameFrame { frame_type } => (*frame_type, "same"),
+      StackMapFrame::SameLocals1StackItemFrame { frame_type, .. } => {
+        (*frame_type, "same_locals_1_stack_item")
+      }
+      StackMapFrame::SameLocals1StackItemFrameExtended { .. } => {
+        (247, "same_locals_1_stack_item_frame_extended")
+      }
+      StackMapFrame::ChopFrame { frame_type, .. } => (*frame_type, "chop"),
+      StackMapFrame::SameFrameExtended { .. } => (251, "same_frame_extended"),
+      StackMapFrame::AppendFrame { locals, .. } => (251 + locals.len() as u8, "append"),
+      StackMapFrame::FullFrame { .. } => (255, "full_frame")
+    };
+    self.line(8, &format!("frame_type = {} /* {} */", frame_type, name));
+    let (locals, stack): (Option<&[VerificationTypeInfo]>, Option<&[VerificationTypeInfo]>) =
+      match frame {
+        StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+        | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => (None, Some(stack)),
+        StackMapFrame::AppendFrame { locals, .. } => (Some(locals), None),
+        StackMapFrame::FullFrame { locals, stack, .. } => (Some(locals), Some(stack)),
+        _ => (None, None)
+      };
+    if !matches!(
+      frame,
+      StackMapFrame::SameFrame { .. } | StackMapFrame::SameLocals1StackItemFrame { .. }
+    ) {
+      self.line(10, &format!("offset_delta = {}", frame.offset_delta()));
+    }
+    if let Some(locals) = locals {
+      self.line(10, &format!("locals = [ {} ]", verification_types(locals)));
+    }
+    if let Some(stack) = stack {
+      self.line(10, &format!("stack = [ {} ]", verification_types(stack)));
+    }
+  }
+}
+
+fn offset_of(offsets: &OffsetMap, index: usize) -> Result<usize, ClassFormatError> {
+  offsets.offset_of(index).ok_or(ClassFormatError::BadCodeIndex { index: index as isize })
+}
+
+fn verification_types(types: &[VerificationTypeInfo]) -> String {
+  let types: Vec<String> = types
+    .iter()
+    .map(|verification_type| match verification_type {
+      VerificationTypeInfo::TopVariable => "top".to_string(),
+      VerificationTypeInfo::IntegerVariable => "int".to_string(),
+      VerificationTypeInfo::FloatVariable => "float".to_string(),
+      VerificationTypeInfo::LongVariable => "long".to_string(),
+      VerificationTypeInfo::DoubleVariable => "double".to_string(),
+      VerificationTypeInfo::NullVariable => "null".to_string(),
+      VerificationTypeInfo::UninitializedThisVariable => "this".to_string(),
+      VerificationTypeInfo::ObjectVariable { class } => {
+        format!("class {}", describe(&ResolvedCpInfo::Class(class.clone())))
+      }
+      VerificationTypeInfo::UninitializedVariable { offset } => format!("uninitialized {}", offset)
+    })
+    .collect();
+  types.join(", ")
+}
@@ -0,0 +1,995 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::text::{
+  reference_kind,
+  FlagName,
+  CLASS_FLAGS,
+  EXPORTS_FLAGS,
+  FIELD_FLAGS,
+  INNER_CLASS_FLAGS,
+  METHOD_FLAGS,
+  MODULE_FLAGS,
+  PARAMETER_FLAGS,
+  REQUIRES_FLAGS,
+  TARGET_TYPES
+};
+use crate::parser::{
+  attribute_info::{
+    attribute::{
+      annotation::Annotation,
+      bootstrap_method::BootstrapMethod,
+      constant::Constant,
+      element_value::ElementValue,
+      parameter_annotation::ParameterAnnotation,
+      record_component::RecordComponent,
+      stack_map_frame::{verification_type_info::VerificationTypeInfo, StackMapFrame},
+      type_annotation::{TargetInfo, TypeAnnotation, TypePathKind}
+    },
+    code::{code_generator::Instructions, offset_map::OffsetMap},
+    Attribute
+  },
+  class_format_error::ClassFormatError,
+  classfile::ClassFile,
+  cp_info_resolved::{MethodHandle, MethodHandleRef, NameAndType, ResolvedCpInfo},
+  field_info::FieldInfo,
+  method_info::MethodInfo
+};
+
+/// The element types of `newarray`, by their `atype`.
+pub const ARRAY_TYPES: &[(u8, &str)] = &[
+  (4, "boolean"),
+  (5, "char"),
+  (6, "float"),
+  (7, "double"),
+  (8, "byte"),
+  (9, "short"),
+  (10, "int"),
+  (11, "long")
+];
+
+/// The loads and stores `wide` can extend, by their opcode.
+pub const WIDE_OPCODES: &[(u8, &str)] = &[
+  (21, "iload"),
+  (22, "lload"),
+  (23, "fload"),
+  (24, "dload"),
+  (25, "aload"),
+  (54, "istore"),
+  (55, "lstore"),
+  (56, "fstore"),
+  (57, "dstore"),
+  (58, "astore"),
+  (169, "ret")
+];
+
+/// How an access flag is written in assembly: `ACC_PUBLIC` is `public`.
+pub fn keyword(name: &str) -> String { name.trim_start_matches("ACC_").to_lowercase() }
+
+/// Prints a class in the syntax `assembler::assemble` reads, so that assembling the text gives the
+/// class back, stack map frames and all.
+/// Fails on attributes the syntax has no directive for, such as those a registered decoder read,
+/// rather than leave them out.
+pub fn disassemble(class: &ClassFile) -> Result<String, ClassFormatError> {
+  let mut jasmin = Jasmin { out: String::new() };
+  jasmin.line(0, &format!(".version {} {}", class.major_version, class.minor_version));
+  let flags = flag_words(*class.access_flags, CLASS_FLAGS);
+  jasmin.line(0, &format!(".class {}{}", flags, class.this_class.name));
+  if let Some(super_class) = &class.super_class {
+    jasmin.line(0, &format!(".super {}", super_class.name));
+  }
+  for interface in &class.interfaces {
+    jasmin.line(0, &format!(".implements {}", interface.name));
+  }
+  for attribute in &class.attributes {
+    match attribute {
+      Attribute::SourceFile { sourcefile } => {
+        jasmin.line(0, &format!(".source {}", quote_str(sourcefile)))
+      }
+      Attribute::Signature { signature } => {
+        jasmin.line(0, &format!(".signature {}", quote_str(signature)))
+      }
+      // Put back together from the dynamic constants that use it
+      Attribute::BootstrapMethods { .. } => {}
+      Attribute::Record { components } => jasmin.record(components)?,
+      attribute => jasmin.attribute(attribute, 0)?
+    }
+  }
+  for (key, field) in &class.fields {
+    jasmin.line(0, "");
+    jasmin.field(key, field)?;
+  }
+  for (key, method) in &class.methods {
+    jasmin.line(0, "");
+    jasmin.method(key, method)?;
+  }
+  Ok(jasmin.out)
+}
+
+struct Jasmin {
+  out: String
+}
+
+impl Jasmin {
+  fn line(&mut self, indent: usize, text: &str) {
+    self.out.push_str(&format!("{:indent$}{}\n", "", text, indent = indent));
+  }
+
+  /// Prints the directive for an attribute that a class, field or method may have.
+  fn attribute(&mut self, attribute: &Attribute, indent: usize) -> Result<(), ClassFormatError> {
+    let lines = match attribute {
+      Attribute::Deprecated => vec![".deprecated".to_string()],
+      Attribute::Synthetic => vec![".synthetic".to_string()],
+      Attribute::RuntimeVisibleAnnotations { annotations } => annotations
+        .iter()
+        .map(|annotation| format!(".annotation visible {}", self::annotation(annotation)))
+        .collect(),
+      Attribute::RuntimeInvisibleAnnotations { annotations } => annotations
+        .iter()
+        .map(|annotation| format!(".annotation invisible {}", self::annotation(annotation)))
+        .collect(),
+      Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations } => {
+        self::parameter_annotations("visible", parameter_annotations)
+      }
+      Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+        self::parameter_annotations("invisible", parameter_annotations)
+      }
+      Attribute::AnnotationDefault { default_value } => {
+        vec![format!(".annotation default {}", element_value(default_value))]
+      }
+      Attribute::InnerClasses { classes } => classes
+        .iter()
+        .map(|inner| {
+          let flags = flag_words(inner.inner_class_access_flags, INNER_CLASS_FLAGS);
+          let mut text = format!(".inner class {}{}", flags, inner.inner_class.name);
+          if let Some(outer_class) = &inner.outer_class {
+            text.push_str(&format!(" outer {}", outer_class.name));
+          }
+          if let Some(inner_name) = &inner.inner_name {
+            text.push_str(&format!(" name {}", quote_str(inner_name)));
+          }
+          text
+        })
+        .collect(),
+      Attribute::EnclosingMethod { class, method } => match method {
+        Some(method) => {
+          vec![format!(".enclosing method {} {}{}", class.name, method.name, method.descriptor)]
+        }
+        None => vec![format!(".enclosing method {}", class.name)]
+      },
+      Attribute::NestHost { host_class } => vec![format!(".nesthost {}", host_class.name)],
+      Attribute::NestMembers { classes } => {
+        classes.iter().map(|class| format!(".nestmember {}", class.name)).collect()
+      }
+      Attribute::PermittedSubclasses { classes } => {
+        classes.iter().map(|class| format!(".permittedsubclass {}", class.name)).collect()
+      }
+      Attribute::MethodParameters { parameters } => parameters
+        .iter()
+        .map(|parameter| {
+          let flags = flag_words(*parameter.access_flags, PARAMETER_FLAGS);
+          let name = parameter.name.as_deref().map_or(String::new(), quote_str);
+          format!(".parameter {}{}", flags, name).trim_end().to_string()
+        })
+        .collect(),
+      Attribute::RuntimeVisibleTypeAnnotations { .. }
+      | Attribute::RuntimeInvisibleTypeAnnotations { .. } => type_annotations(attribute, None)?,
+      Attribute::Module {
+        module_name,
+        module_flags,
+        module_version,
+        requires,
+        exports,
+        opens,
+        uses,
+        provides
+      } => {
+        let version = |version: &Option<String>| {
+          version
+            .as_deref()
+            .map_or(String::new(), |version| format!(" version {}", quote_str(version)))
+        };
+        let to = |modules: &[String]| match modules.is_empty() {
+          true => String::new(),
+          false => format!(" to {}", modules.join(" "))
+        };
+        let flags = flag_words(**module_flags, MODULE_FLAGS);
+        let mut lines =
+          vec![format!(".module {}{}{}", flags, module_name, version(module_version))];
+        lines.extend(requires.iter().map(|required| {
+          let flags = flag_words(*required.requires_flags, REQUIRES_FLAGS);
+          format!(".requires {}{}{}", flags, required.requires, version(&required.requires_version))
+        }));
+        lines.extend(exports.iter().map(|exported| {
+          let flags = flag_words(*exported.exports_flags, EXPORTS_FLAGS);
+          format!(".exports {}{}{}", flags, exported.exports, to(&exported.exports_to))
+        }));
+        lines.extend(opens.iter().map(|opened| {
+          let flags = flag_words(*opened.opens_flags, EXPORTS_FLAGS);
+          format!(".opens {}{}{}", flags, opened.opens, to(&opened.opens_to))
+        }));
+        lines.extend(uses.iter().map(|service| format!(".uses {}", service.name)));
+        lines.extend(provides.iter().map(|provided| {
+          let with: Vec<&str> =
+            provided.provides_with.iter().map(|class| class.name.as_str()).collect();
+          format!(".provides {} with {}", provided.provides.name, with.join(" "))
+        }));
+        lines
+      }
+      Attribute::ModulePackages { packages } => {
+        packages.iter().map(|package| format!(".modulepackage {}", package)).collect()
+      }
+      Attribute::ModuleMainClass { main_class } => {
+        vec![format!(".modulemainclass {}", main_class.name)]
+      }
+      _ => Vec::new()
+    };
+    // Neither do empty tables have a directive to stand for them
+    if lines.is_empty() {
+      return Err(ClassFormatError::InexpressibleAttribute { name: attribute.name().to_string() });
+    }
+    for line in lines {
+      self.line(indent, &line);
+    }
+    Ok(())
+  }
+
+  /// A field is one line, or when it has attributes that don't fit on it, a block up to
+  /// `.end field`.
+  fn field(&mut self, key: &str, field: &FieldInfo) -> Result<(), ClassFormatError> {
+    let (name, descriptor) = key.split_once(':').unwrap_or((key, ""));
+    let mut text =
+      format!(".field {}{} {}", flag_words(*field.access_flags, FIELD_FLAGS), name, descriptor);
+    let mut others = Vec::new();
+    for attribute in &field.attributes {
+      match attribute {
+        Attribute::Signature { signature } => {
+          text.push_str(&format!(" signature {}", quote_str(signature)))
+        }
+        Attribute::ConstantValue { value } => {
+          let value = match value {
+            Constant::Int(value) => ResolvedCpInfo::Integer(*value),
+            Constant::Long(value) => ResolvedCpInfo::Long(*value),
+            Constant::Float(value) => ResolvedCpInfo::Float(*value),
+            Constant::Double(value) => ResolvedCpInfo::Double(*value),
+            Constant::String(value) => ResolvedCpInfo::String(value.clone())
+          };
+          text.push_str(&format!(" = {}", constant(&value)));
+        }
+        attribute => others.push(attribute)
+      }
+    }
+    self.block(&text, &others, "field")
+  }
+
+  /// Each component is one line like a field, or a block up to `.end component`. A record without
+  /// components is `.record` alone.
+  fn record(&mut self, components: &[RecordComponent]) -> Result<(), ClassFormatError> {
+    if components.is_empty() {
+      self.line(0, ".record");
+    }
+    for component in components {
+      let mut text = format!(".record component {} {}", component.name, component.descriptor);
+      let mut others = Vec::new();
+      for attribute in &component.attributes {
+        match attribute {
+          Attribute::Signature { signature } => {
+            text.push_str(&format!(" signature {}", quote_str(signature)))
+          }
+          attribute => others.push(attribute)
+        }
+      }
+      self.block(&text, &others, "component")?;
+    }
+    Ok(())
+  }
+
+  /// The line declaring a field or record component, then its other attributes up to `.end <what>`
+  /// if it has any.
+  fn block(
+    &mut self,
+    text: &str,
+    others: &[&Attribute],
+    what: &str
+  ) -> Result<(), ClassFormatError> {
+    self.line(0, text);
+    if !others.is_empty() {
+      for attribute in others {
+        self.attribute(attribute, 4)?;
+      }
+      self.line(0, &format!(".end {}", what));
+    }
+    Ok(())
+  }
+
+  fn method(&mut self, key: &str, method: &MethodInfo) -> Result<(), ClassFormatError> {
+    let (name, descriptor) = key.split_once(':').unwrap_or((key, ""));
+    let flags = flag_words(*method.access_flags, METHOD_FLAGS);
+    self.line(0, &format!(".method {}{}{}", flags, name, descriptor));
+    for attribute in &method.attributes {
+      match attribute {
+        Attribute::Exceptions { exceptions } => {
+          for exception in exceptions {
+            self.line(4, &format!(".throws {}", exception.name));
+          }
+        }
+        Attribute::Signature { signature } => {
+          self.line(4, &format!(".signature {}", quote_str(signature)))
+        }
+        Attribute::Code { .. } => {}
+        attribute => self.attribute(attribute, 4)?
+      }
+    }
+    for attribute in &method.attributes {
+      if let Attribute::Code { max_stack, max_locals, code, exception_table, attributes, offsets } =
+        attribute
+      {
+        self.line(4, &format!(".limit stack {}", max_stack));
+        self.line(4, &format!(".limit locals {}", max_locals));
+        let labels = Labels { offsets };
+        for exception in exception_table {
+          let catch_type = exception.catch_type.as_ref().map_or("all", |class| &class.name);
+          let text = format!(
+            ".catch {} from {} to {} using {}",
+            catch_type,
+            labels.name(exception.start_pc as usize)?,
+            labels.name(exception.end_pc as usize)?,
+            labels.name(exception.handler_pc as usize)?
+          );
+          self.line(4, &text);
+        }
+        self.code(
+          code,
+          attributes,
+          &labels,
+          exception_table.iter().flat_map(|exception| {
+            [exception.start_pc, exception.end_pc, exception.handler_pc].map(|pc| pc as usize)
+          })
+        )?;
+      }
+    }
+    self.line(0, ".end method");
+    Ok(())
+  }
+
+  /// Prints the local variables, then the instructions with their labels, line numbers and frames.
+  /// `targets` are the positions the exception table refers to.
+  fn code(
+    &mut self,
+    code: &[Instructions],
+    attributes: &[Attribute],
+    labels: &Labels,
+    targets: impl Iterator<Item = usize>
+  ) -> Result<(), ClassFormatError> {
+    let mut targets: BTreeSet<usize> = targets.collect();
+    let mut lines: BTreeMap<usize, Vec<u16>> = BTreeMap::new();
+    let mut frames: BTreeMap<usize, &StackMapFrame> = BTreeMap::new();
+    let mut type_annotation_lines = Vec::new();
+    let mut type_signatures = Vec::new();
+    for attribute in attributes {
+      if let Attribute::LocalVariableTypeTable { local_variable_type_table } = attribute {
+        type_signatures.extend(local_variable_type_table);
+      }
+    }
+    for attribute in attributes {
+      match attribute {
+        Attribute::LineNumberTable { line_number_table } => {
+          for line_number in line_number_table {
+            let lines = lines.entry(line_number.start_pc as usize).or_default();
+            lines.push(line_number.line_number);
+          }
+        }
+        Attribute::LocalVariableTable { local_variable_table } => {
+          for variable in local_variable_table {
+            let start = variable.start_pc as usize;
+            let end = start + variable.length as usize;
+            targets.extend([start, end]);
+            let signature = type_signatures
+              .iter()
+              .position(|typed| {
+                (typed.start_pc, typed.length, typed.index, &typed.name)
+                  == (variable.start_pc, variable.length, variable.index, &variable.name)
+              })
+              .map(|position| type_signatures.remove(position))
+              .map_or(String::new(), |typed| format!(" signature {}", quote_str(&typed.signature)));
+            let text = format!(
+              ".var {} is {} {}{} from {} to {}",
+              variable.index,
+              variable.name,
+              variable.descriptor,
+              signature,
+              labels.name(start)?,
+              labels.name(end)?
+            );
+            self.line(4, &text);
+          }
+        }
+        // The entries are printed with their variables, and any left over after them
+        Attribute::LocalVariableTypeTable { .. } => {}
+        Attribute::StackMapTable { entries } => {
+          let mut previous: Option<usize> = None;
+          for entry in entries {
+            let index = match previous {
+              Some(previous) => previous + entry.offset_delta() as usize + 1,
+              None => entry.offset_delta() as usize
+            };
+            previous = Some(index);
+            frames.insert(index, entry);
+            let (locals, stack) = frame_types(entry);
+            for verification_type in locals.iter().chain(stack) {
+              if let VerificationTypeInfo::UninitializedVariable { offset } = verification_type {
+                targets.insert(*offset as usize);
+              }
+            }
+          }
+        }
+        Attribute::RuntimeVisibleTypeAnnotations { annotations }
+        | Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+          for annotation in annotations {
+            match &annotation.target_info {
+              TargetInfo::Localvar { table } => {
+                for variable in table {
+                  let start = variable.start_pc as usize;
+                  targets.extend([start, start + variable.length as usize]);
+                }
+              }
+              TargetInfo::Offset { offset } | TargetInfo::TypeArgument { offset, .. } => {
+                targets.insert(*offset as usize);
+              }
+              _ => {}
+            }
+          }
+          type_annotation_lines.extend(type_annotations(attribute, Some(labels))?);
+        }
+        attribute => {
+          let name = attribute.name().to_string();
+          return Err(ClassFormatError::InexpressibleAttribute { name });
+        }
+      }
+    }
+    for typed in type_signatures {
+      let start = typed.start_pc as usize;
+      let end = start + typed.length as usize;
+      targets.extend([start, end]);
+      let text = format!(
+        ".var {} is {} signature {} from {} to {}",
+        typed.index,
+        typed.name,
+        quote_str(&typed.signature),
+        labels.name(start)?,
+        labels.name(end)?
+      );
+      self.line(4, &text);
+    }
+    for line in type_annotation_lines {
+      self.line(4, &line);
+    }
+    for (index, instruction) in code.iter().enumerate() {
+      targets.extend(branch_targets(instruction, index)?);
+    }
+
+    for (index, instruction) in code.iter().enumerate() {
+      if targets.contains(&index) {
+        self.line(0, &format!("{}:", labels.name(index)?));
+      }
+      for line in lines.get(&index).into_iter().flatten() {
+        self.line(4, &format!(".line {}", line));
+      }
+      if let Some(frame) = frames.get(&index) {
+        self.line(4, &format!(".stack {}", stack_map_frame(frame, labels)?));
+      }
+      self.instruction(instruction, index, labels)?;
+    }
+    if targets.contains(&code.len()) {
+      self.line(0, &format!("{}:", labels.name(code.len())?));
+    }
+    Ok(())
+  }
+
+  fn instruction(
+    &mut self,
+    instruction: &Instructions,
+    index: usize,
+    labels: &Labels
+  ) -> Result<(), ClassFormatError> {
+    let target = |delta: isize| labels.name(target(index, delta)?);
+    let local = |mnemonic: &str, local: u8| match local {
+      0..=3 => format!("{}_{}", mnemonic, local),
+      _ => format!("{} {}", mnemonic, local)
+    };
+    let text = match instruction {
+      Instructions::iconst { value: -1 } => "iconst_m1".to_string(),
+      Instructions::iconst { value } => format!("iconst_{}", value),
+      Instructions::lconst { value } => format!("lconst_{}", value),
+      Instructions::fconst { value } => format!("fconst_{}", value),
+      Instructions::dconst { value } => format!("dconst_{}", value),
+      Instructions::bipush { value } => format!("bipush {}", value),
+      Instructions::sipush { value } => format!("sipush {}", value),
+      Instructions::ldc { constant: value } => format!("ldc {}", constant(value)),
+      Instructions::ldc_w { constant: value } => format!("ldc_w {}", constant(value)),
+      Instructions::ldc2_w { constant: value } => format!("ldc2_w {}", constant(value)),
+      Instructions::iload { index } => local("iload", *index),
+      Instructions::lload { index } => local("lload", *index),
+      Instructions::fload { index } => local("fload", *index),
+      Instructions::dload { index } => local("dload", *index),
+      Instructions::aload { index } => local("aload", *index),
+      Instructions::istore { index } => local("istore", *index),
+      Instructions::lstore { index } => local("lstore", *index),
+      Instructions::fstore { index } => local("fstore", *index),
+      Instructions::dstore { index } => local("dstore", *index),
+      Instructions::astore { index } => local("astore", *index),
+      Instructions::ret { index } => format!("ret {}", index),
+      Instructions::iinc { index, r#const } => format!("iinc {} {}", index, r#const),
+      Instructions::wide1 { opcode, index_extension } => {
+        let mnemonic = WIDE_OPCODES.iter().find(|(wide, _)| wide == opcode).map_or("ret", |w| w.1);
+        format!("wide {} {}", mnemonic, index_extension)
+      }
+      Instructions::wide2 { index_extension, constbytes, .. } => {
+        format!("wide iinc {} {}", index_extension, constbytes)
+      }
+      Instructions::ifeq { offset } => format!("ifeq {}", target(*offset as isize)?),
+      Instructions::ifne { offset } => format!("ifne {}", target(*offset as isize)?),
+      Instructions::iflt { offset } => format!("iflt {}", target(*offset as isize)?),
+      Instructions::ifge { offset } => format!("ifge {}", target(*offset as isize)?),
+      Instructions::ifgt { offset } => format!("ifgt {}", target(*offset as isize)?),
+      Instructions::ifle { offset } => format!("ifle {}", target(*offset as isize)?),
+      Instructions::if_icmpeq { offset } => format!("if_icmpeq {}", target(*offset as isize)?),
+      Instructions::if_icmpne { offset } => format!("if_icmpne {}", target(*offset as isize)?),
+      Instructions::if_icmplt { offset } => format!("if_icmplt {}", target(*offset as isize)?),
+      Instructions::if_icmpge { offset } => format!("if_icmpge {}", target(*offset as isize)?),
+      Instructions::if_icmpgt { offset } => format!("if_icmpgt {}", target(*offset as isize)?),
+      Instructions::if_icmple { offset } => format!("if_icmple {}", target(*offset as isize)?),
+      Instructions::if_acmpeq { offset } => format!("if_acmpeq {}", target(*offset as isize)?),
+      Instructions::if_acmpne { offset } => format!("if_acmpne {}", target(*offset as isize)?),
+      Instructions::ifnull { offset } => format!("ifnull {}", target(*offset as isize)?),
+      Instructions::ifnonnull { offset } => format!("ifnonnull {}", target(*offset as isize)?),
+      Instructions::goto { offset } => format!("goto {}", target(*offset as isize)?),
+      Instructions::jsr { offset } => format!("jsr {}", target(*offset as isize)?),
+      Instructions::goto_w { offset } => format!("goto_w {}", target(*offset as isize)?),
+      Instructions::jsr_w { offset } => format!("jsr_w {}", target(*offset as isize)?),
+      Instructions::tableswitch { default, low, high, offsets } => {
+        self.line(4, &format!("tableswitch {} {}", low, high));
+        for offset in offsets {
+          self.line(8, &target(*offset as isize)?);
+        }
+        self.line(8, &format!("default : {}", target(*default as isize)?));
+        return Ok(());
+      }
+      Instructions::lookupswith { default, pairs, .. } => {
+        self.line(4, "lookupswitch");
+        for (key, offset) in pairs {
+          self.line(8, &format!("{} : {}", key, target(*offset as isize)?));
+        }
+        self.line(8, &format!("default : {}", target(*default as isize)?));
+        return Ok(());
+      }
+      Instructions::getstatic { fieldref } => {
+        format!("getstatic {}", field(&fieldref.class.name, &fieldref.name_and_type))
+      }
+      Instructions::putstatic { fieldref } => {
+        format!("putstatic {}", field(&fieldref.class.name, &fieldref.name_and_type))
+      }
+      Instructions::getfield { fieldref } => {
+        format!("getfield {}", field(&fieldref.class.name, &fieldref.name_and_type))
+      }
+      Instructions::putfield { fieldref } => {
+        format!("putfield {}", field(&fieldref.class.name, &fieldref.name_and_type))
+      }
+      Instructions::invokevirtual { methodref } => {
+        format!("invokevirtual {}", method(&methodref.class.name, &methodref.name_and_type))
+      }
+      Instructions::invokespecial { methodref } => {
+        format!("invokespecial {}", method(&methodref.class.name, &methodref.name_and_type))
+      }
+      Instructions::invokestatic { methodref } => {
+        format!("invokestatic {}", method(&methodref.class.name, &methodref.name_and_type))
+      }
+      Instructions::invokeinterface { interfacemethodref, count } => {
+        let reference = method(&interfacemethodref.class.name, &interfacemethodref.name_and_type);
+        format!("invokeinterface {} {}", reference, count)
+      }
+      Instructions::invokedynamic { invokedynamic } => format!(
+        "invokedynamic {} {}",
+        name_and_type(&invokedynamic.name_and_type),
+        bootstrap_method(&invokedynamic.bootstrap_method_attr)
+      ),
+      Instructions::new { class } => format!("new {}", class.name),
+      Instructions::anewarray { class } => format!("anewarray {}", class.name),
+      Instructions::checkcast { class } => format!("checkcast {}", class.name),
+      Instructions::instanceof { class } => format!("instanceof {}", class.name),
+      Instructions::multianewarray { class, dimensions } => {
+        format!("multianewarray {} {}", class.name, dimensions)
+      }
+      Instructions::newarray { atype } => match ARRAY_TYPES.iter().find(|(t, _)| t == atype) {
+        Some((_, name)) => format!("newarray {}", name),
+        None => format!("newarray {}", atype)
+      },
+      Instructions::r#return => "return".to_string(),
+      // Every other instruction has no operands, and its variant is named after it
+      instruction => format!("{:?}", instruction)
+    };
+    self.line(4, &text);
+    Ok(())
+  }
+}
+
+/// Names positions in the code after their byte offsets, such as `L12`.
+struct Labels<'a> {
+  offsets: &'a OffsetMap
+}
+
+impl Labels<'_> {
+  fn name(&self, index: usize) -> Result<String, ClassFormatError> {
+    let offset = self
+      .offsets
+      .offset_of(index)
+      .ok_or(ClassFormatError::BadCodeIndex { index: index as isize })?;
+    Ok(format!("L{}", offset))
+  }
+}
+
+fn target(index: usize, delta: isize) -> Result<usize, ClassFormatError> {
+  usize::try_from(index as isize + delta)
+    .map_err(|_| ClassFormatError::BadCodeIndex { index: index as isize + delta })
+}
+
+/// The positions a branch or switch at `index` can go to.
+fn branch_targets(
+  instruction: &Instructions,
+  index: usize
+) -> Result<Vec<usize>, ClassFormatError> {
+  let deltas: Vec<isize> = match instruction {
+    Instructions::ifeq { offset }
+    | Instructions::ifne { offset }
+    | Instructions::iflt { offset }
+    | Instructions::ifge { offset }
+    | Instructions::ifgt { offset }
+    | Instructions::ifle { offset }
+    | Instructions::if_icmpeq { offset }
+    | Instructions::if_icmpne { offset }
+    | Instructions::if_icmplt { offset }
+    | Instructions::if_icmpge { offset }
+    | Instructions::if_icmpgt { offset }
+    | Instructions::if_icmple { offset }
+    | Instructions::if_acmpeq { offset }
+    | Instructions::if_acmpne { offset }
+    | Instructions::ifnull { offset }
+    | Instructions::ifnonnull { offset }
+    | Instructions::goto { offset }
+    | Instructions::jsr { offset } => vec![*offset as isize],
+    Instructions::goto_w { offset } | Instructions::jsr_w { offset } => vec![*offset as isize],
+    Instructions::tableswitch { default, offsets, .. } => {
+      [*default].iter().chain(offsets).map(|offset| *offset as isize).collect()
+    }
+    Instructions::lookupswith { default, pairs, .. } => [*default]
+      .into_iter()
+      .chain(pairs.iter().map(|(_, offset)| *offset))
+      .map(|o| o as isize)
+      .collect(),
+    _ => Vec::new()
+  };
+  deltas.into_iter().map(|delta| target(index, delta)).collect()
+}
+
+/// The keywords for the flags in `mask`, each followed by a space. Flags without a name are given
+/// as a number, such as `0x0100`.
+fn flag_words(mask: u16, names: &[FlagName]) -> String {
+  let mut words: String = names
+    .iter()
+    .filter(|(flag, ..)| mask & flag != 0)
+    .map(|(_, name, _)| format!("{} ", keyword(name)))
+    .collect();
+  let unnamed = names.iter().fold(mask, |mask, (flag, ..)| mask & !flag);
+  if unnamed != 0 {
+    words.push_str(&format!("0x{:04x} ", unnamed));
+  }
+  words
+}
+
+/// A string in double quotes, with the characters that wouldn't print on one line escaped.
+/// Code units that aren't valid UTF-16 are escaped too, so any `java.lang.String` can be written.
+pub fn quote(units: &[u16]) -> String {
+  let mut text = String::from("\"");
+  for char in char::decode_utf16(units.iter().copied()) {
+    match char {
+      Ok('\t') => text.push_str("\\t"),
+      Ok('\n') => text.push_str("\\n"),
+      Ok('\r') => text.push_str("\\r"),
+      Ok('\\') => text.push_str("\\\\"),
+      Ok('"') => text.push_str("\\\""),
+      Ok(char) if char.is_control() => text.push_str(&format!("\\u{:04x}", char as u32)),
+      Ok(char) => text.push(char),
+      Err(error) => text.push_str(&format!("\\u{:04x}", error.unpaired_surrogate()))
+    }
+  }
+  text.push('"');
+  text
+}
+
+fn quote_str(text: &str) -> String { quote(&text.encode_utf16().collect::<Vec<_>>()) }
+
+fn field(class: &str, name_and_type: &NameAndType) -> String {
+  format!("{}/{} {}", class, name_and_type.name, name_and_type.descriptor)
+}
+
+fn method(class: &str, name_and_type: &NameAndType) -> String {
+  format!("{}/{}{}", class, name_and_type.name, name_and_type.descriptor)
+}
+
+fn name_and_type(name_and_type: &NameAndType) -> String {
+  format!("{} {}", name_and_type.name, name_and_type.descriptor)
+}
+
+/// A loadable constant: a number such as `5`, `5L`, `1.5f` or `1.5`, a quoted string, or a
+/// `Class`, `MethodType`, `MethodHandle` or `Dynamic` followed by what it refers to.
+pub fn constant(constant: &ResolvedCpInfo) -> String {
+  match constant {
+    ResolvedCpInfo::Integer(value) => value.to_string(),
+    ResolvedCpInfo::Long(value) => format!("{}L", value),
+    ResolvedCpInfo::Float(value) => format!(
+      "{}f",
+      floating(
+        format!("{:?}", value),
+        *value as f64,
+        value.to_bits() as u64,
+        f32::NAN.to_bits() as u64
+      )
+    ),
+    ResolvedCpInfo::Double(value) => {
+      floating(format!("{:?}", value), *value, value.to_bits(), f64::NAN.to_bits())
+    }
+    ResolvedCpInfo::String(value) => quote(&value.chars),
+    ResolvedCpInfo::Class(class) => format!("Class {}", class.name),
+    ResolvedCpInfo::MethodType(method_type) => format!("MethodType {}", method_type.descriptor),
+    ResolvedCpInfo::MethodHandle(handle) => format!("MethodHandle {}", method_handle(handle)),
+    ResolvedCpInfo::Dynamic(dynamic) => format!(
+      "Dynamic {} {}",
+      name_and_type(&dynamic.name_and_type),
+      bootstrap_method(&dynamic.bootstrap_method_attr)
+    ),
+    // Not loadable, so never an operand
+    constant => format!("{:?}", constant)
+  }
+}
+
+/// `Infinity`, `-Infinity` and `NaN` the way Java writes them, with the bits of a NaN written out as
+/// `NaN<0x7ff8000000000001>` when they aren't the ones `NaN` stands for.
+fn floating(text: String, value: f64, bits: u64, nan_bits: u64) -> String {
+  match value {
+    value if value.is_nan() && bits != nan_bits => format!("NaN<0x{:x}>", bits),
+    value if value.is_nan() => "NaN".to_string(),
+    f64::INFINITY => "Infinity".to_string(),
+    f64::NEG_INFINITY => "-Infinity".to_string(),
+    _ => text
+  }
+}
+
+/// A method handle as `REF_invokeStatic Method java/lang/Integer/valueOf(I)Ljava/lang/Integer;`.
+fn method_handle(handle: &MethodHandle) -> String {
+  let reference = match &handle.reference {
+    MethodHandleRef::Field(fieldref) => {
+      format!("Field {}", field(&fieldref.class.name, &fieldref.name_and_type))
+    }
+    MethodHandleRef::Method(methodref) => {
+      format!("Method {}", method(&methodref.class.name, &methodref.name_and_type))
+    }
+    MethodHandleRef::InterfaceMethod(interfacemethodref) => format!(
+      "InterfaceMethod {}",
+      method(&interfacemethodref.class.name, &interfacemethodref.name_and_type)
+    )
+  };
+  format!("{} {}", reference_kind(handle.reference_kind), reference)
+}
+
+/// An annotation as its type, then its elements in braces if it has any, such as
+/// `Ljava/lang/Deprecated; { since = s "9", forRemoval = Z true }`.
+fn annotation(annotation: &Annotation) -> String {
+  if annotation.element_value_pairs.is_empty() {
+    return annotation.type_name.clone();
+  }
+  let pairs: Vec<String> = annotation
+    .element_value_pairs
+    .iter()
+    .map(|(name, value)| format!("{} = {}", name, element_value(value)))
+    .collect();
+  format!("{} {{ {} }}", annotation.type_name, pairs.join(", "))
+}
+
+/// An element value as its tag, then the value: `I 5`, `s "text"`, `e Ljava/time/DayOfWeek; MONDAY`,
+/// `c Ljava/lang/String;`, `@` and an annotation, or `[` with the values of an array up to `]`.
+fn element_value(value: &ElementValue) -> String {
+  let tag = value.tag() as char;
+  match value {
+    ElementValue::Byte(value) => format!("{} {}", tag, value),
+    ElementValue::Char(value) => format!("{} {}", tag, value),
+    ElementValue::Short(value) => format!("{} {}", tag, value),
+    ElementValue::Boolean(value) => format!("{} {}", tag, value),
+    ElementValue::Int(value) => format!("{} {}", tag, constant(&ResolvedCpInfo::Integer(*value))),
+    ElementValue::Long(value) => format!("{} {}", tag, constant(&ResolvedCpInfo::Long(*value))),
+    ElementValue::Float(value) => format!("{} {}", tag, constant(&ResolvedCpInfo::Float(*value))),
+    ElementValue::Double(value) => {
+      format!("{} {}", tag, constant(&ResolvedCpInfo::Double(*value)))
+    }
+    ElementValue::String(value) => format!("{} {}", tag, quote(&value.chars)),
+    ElementValue::Enum { type_name, const_name } => format!("{} {} {}", tag, type_name, const_name),
+    ElementValue::Class(descriptor) => format!("{} {}", tag, descriptor),
+    ElementValue::Annotation(nested) => format!("{} {}", tag, annotation(nested)),
+    ElementValue::Array(values) => {
+      let values: Vec<String> = values.iter().map(element_value).collect();
+      match values.is_empty() {
+        true => "[ ]".to_string(),
+        false => format!("[ {} ]", values.join(", "))
+      }
+    }
+  }
+}
+
+/// A line for each annotation of a type annotations attribute: `.annotation type`, the visibility
+/// and what the annotation applies to, such as `method_formal_parameter 0` or `new L12`, then
+/// `path` and the steps into the type if it is on part of the type, such as `path array argument 0`,
+/// and the annotation.
+/// Only in code, given its `labels`, can an annotation apply to an instruction or local variable.
+fn type_annotations(
+  attribute: &Attribute,
+  labels: Option<&Labels>
+) -> Result<Vec<String>, ClassFormatError> {
+  let (visibility, annotations) = match attribute {
+    Attribute::RuntimeVisibleTypeAnnotations { annotations } => ("visible", annotations),
+    Attribute::RuntimeInvisibleTypeAnnotations { annotations } => ("invisible", annotations),
+    _ => return Ok(Vec::new())
+  };
+  let inexpressible =
+    || ClassFormatError::InexpressibleAttribute { name: attribute.name().to_string() };
+  let label = |index: usize| labels.ok_or_else(inexpressible)?.name(index);
+  annotations
+    .iter()
+    .map(|type_annotation| {
+      let TypeAnnotation { target_type, target_info, type_path, annotation } = type_annotation;
+      // Code targets, even those without an offset, belong to the Code attribute
+      if labels.is_none() && *target_type >= 0x40 {
+        return Err(inexpressible());
+      }
+      let target = TARGET_TYPES
+        .iter()
+        .find(|(known, _)| known == target_type)
+        .map(|(_, name)| name.to_lowercase())
+        .ok_or(ClassFormatError::BadTargetType { target_type: *target_type })?;
+      let info = match target_info {
+        TargetInfo::TypeParameter { type_parameter_index } => format!(" {}", type_parameter_index),
+        TargetInfo::Supertype { supertype_index } => format!(" {}", supertype_index),
+        TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+          format!(" {} {}", type_parameter_index, bound_index)
+        }
+        TargetInfo::Empty => String::new(),
+        TargetInfo::FormalParameter { formal_parameter_index } => {
+          format!(" {}", formal_parameter_index)
+        }
+        TargetInfo::Throws { throws_type_index } => format!(" {}", throws_type_index),
+        TargetInfo::Localvar { table } => {
+          let variables = table
+            .iter()
+            .map(|variable| {
+              let start = variable.start_pc as usize;
+              let end = start + variable.length as usize;
+              Ok(format!("{} from {} to {}", variable.index, label(start)?, label(end)?))
+            })
+            .collect::<Result<Vec<_>, ClassFormatError>>()?;
+          match variables.is_empty() {
+            true => " { }".to_string(),
+            false => format!(" {{ {} }}", variables.join(", "))
+          }
+        }
+        TargetInfo::Catch { exception_table_index } => format!(" {}", exception_table_index),
+        TargetInfo::Offset { offset } => format!(" {}", label(*offset as usize)?),
+        TargetInfo::TypeArgument { offset, type_argument_index } => {
+          format!(" {} {}", label(*offset as usize)?, type_argument_index)
+        }
+      };
+      let path: String = type_path
+        .iter()
+        .map(|entry| {
+          let kind = match entry.type_path_kind {
+            TypePathKind::Array => "array",
+            TypePathKind::Nested => "nested",
+            TypePathKind::WildcardBound => "wildcard",
+            TypePathKind::TypeArgument => "argument"
+          };
+          // Only a type argument has an index, which the others keep at 0
+          match (entry.type_path_kind, entry.type_argument_index) {
+            (TypePathKind::TypeArgument, index) | (_, index @ 1..) => {
+              format!(" {} {}", kind, index)
+            }
+            _ => format!(" {}", kind)
+          }
+        })
+        .collect();
+      let path = match path.is_empty() {
+        true => path,
+        false => format!(" path{}", path)
+      };
+      Ok(format!(
+        ".annotation type {} {}{}{} {}",
+        visibility,
+        target,
+        info,
+        path,
+        self::annotation(annotation)
+      ))
+    })
+    .collect()
+}
+
+/// The number of parameters the annotations are given for, then each parameter's annotations
+/// with its position.
+fn parameter_annotations(visibility: &str, parameters: &[ParameterAnnotation]) -> Vec<String> {
+  let mut lines = vec![format!(".annotation parameters {} {}", visibility, parameters.len())];
+  for (position, parameter) in parameters.iter().enumerate() {
+    for annotation in &parameter.annotations {
+      let annotation = self::annotation(annotation);
+      lines.push(format!(".annotation parameter {} {} {}", visibility, position, annotation));
+    }
+  }
+  lines
+}
+
+/// A bootstrap method as its method handle, then its arguments in braces.
+fn bootstrap_method(bootstrap_method: &BootstrapMethod) -> String {
+  let arguments: Vec<String> = bootstrap_method.bootstrap_arguments.iter().map(constant).collect();
+  let arguments = match arguments.is_empty() {
+    true => "{ }".to_string(),
+    false => format!("{{ {} }}", arguments.join(", "))
+  };
+  format!("{} {}", method_handle(&bootstrap_method.bootstrap_method), arguments)
+}
+
+fn frame_types(frame: &StackMapFrame) -> (&[VerificationTypeInfo], &[VerificationTypeInfo]) {
+  match frame {
+    StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+    | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => (&[], stack),
+    StackMapFrame::AppendFrame { locals, .. } => (locals, &[]),
+    StackMapFrame::FullFrame { locals, stack, .. } => (locals, stack),
+    _ => (&[], &[])
+  }
+}
+
+/// A frame as the `.stack` directive gives it, such as `append Integer Object java/lang/String`.
+fn stack_map_frame(frame: &StackMapFrame, labels: &Labels) -> Result<String, ClassFormatError> {
+  let types = |types: &[VerificationTypeInfo]| {
+    types
+      .iter()
+      .map(|verification_type| verification_type_name(verification_type, labels))
+      .collect::<Result<Vec<_>, _>>()
+      .map(|types| types.iter().map(|name| format!(" {}", name)).collect::<String>())
+  };
+  Ok(match frame {
+    StackMapFrame::SameFrame { .. } => "same".to_string(),
+    StackMapFrame::SameFrameExtended { .. } => "same_extended".to_string(),
+    StackMapFrame::SameLocals1StackItemFrame { stack, .. } => format!("stack_1{}", types(stack)?),
+    StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+      format!("stack_1_extended{}", types(stack)?)
+    }
+    StackMapFrame::ChopFrame { frame_type, .. } => format!("chop {}", 251 - frame_type),
+    StackMapFrame::AppendFrame { locals, .. } => format!("append{}", types(locals)?),
+    StackMapFrame::FullFrame { locals, stack, .. } => {
+      format!("full locals{} stack{}", types(locals)?, types(stack)?)
+    }
+  })
+}
+
+fn verification_type_name(
+  verification_type: &VerificationTypeInfo,
+  labels: &Labels
+) -> Result<String, ClassFormatError> {
+  Ok(match verification_type {
+    VerificationTypeInfo::TopVariable => "Top".to_string(),
+    VerificationTypeInfo::IntegerVariable => "Integer".to_string(),
+    VerificationTypeInfo::FloatVariable => "Float".to_string(),
+    VerificationTypeInfo::LongVariable => "Long".to_string(),
+    VerificationTypeInfo::DoubleVariable => "Double".to_string(),
+    VerificationTypeInfo::NullVariable => "Null".to_string(),
+    VerificationTypeInfo::UninitializedThisVariable => "UninitializedThis".to_string(),
+    VerificationTypeInfo::ObjectVariable { class } => format!("Object {}", class.name),
+    VerificationTypeInfo::UninitializedVariable { offset } => {
+      format!("Uninitialized {}", labels.name(*offset as usize)?)
+    }
+  })
+}
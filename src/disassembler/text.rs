@@ -0,0 +1,379 @@
+use crate::parser::{
+  attribute_info::attribute::{
+    annotation::Annotation,
+    element_value::ElementValue,
+    type_annotation::{TargetInfo, TypeAnnotation, TypePathKind}
+  },
+  cp_info_resolved::{MethodHandle, MethodHandleRef, ReferenceKind, ResolvedCpInfo},
+  descriptor::FieldType
+};
+
+/// An access flag's mask, its name in the class file format and the modifier it is written as in
+/// Java source, if any.
+pub type FlagName = (u16, &'static str, &'static str);
+
+pub const CLASS_FLAGS: &[FlagName] = &[
+  (0x0001, "ACC_PUBLIC", "public"),
+  (0x0010, "ACC_FINAL", "final"),
+  (0x0020, "ACC_SUPER", ""),
+  (0x0200, "ACC_INTERFACE", ""),
+  (0x0400, "ACC_ABSTRACT", "abstract"),
+  (0x1000, "ACC_SYNTHETIC", ""),
+  (0x2000, "ACC_ANNOTATION", ""),
+  (0x4000, "ACC_ENUM", ""),
+  (0x8000, "ACC_MODULE", "")
+];
+
+pub const INNER_CLASS_FLAGS: &[FlagName] = &[
+  (0x0001, "ACC_PUBLIC", "public"),
+  (0x0002, "ACC_PRIVATE", "private"),
+  (0x0004, "ACC_PROTECTED", "protected"),
+  (0x0008, "ACC_STATIC", "static"),
+  (0x0010, "ACC_FINAL", "final"),
+  (0x0200, "ACC_INTERFACE", ""),
+  (0x0400, "ACC_ABSTRACT", "abstract"),
+  (0x1000, "ACC_SYNTHETIC", ""),
+  (0x2000, "ACC_ANNOTATION", ""),
+  (0x4000, "ACC_ENUM", "")
+];
+
+pub const FIELD_FLAGS: &[FlagName] = &[
+  (0x0001, "ACC_PUBLIC", "public"),
+  (0x0002, "ACC_PRIVATE", "private"),
+  (0x0004, "ACC_PROTECTED", "protected"),
+  (0x0008, "ACC_STATIC", "static"),
+  (0x0010, "ACC_FINAL", "final"),
+  (0x0040, "ACC_VOLATILE", "volatile"),
+  (0x0080, "ACC_TRANSIENT", "transient"),
+  (0x1000, "ACC_SYNTHETIC", ""),
+  (0x4000, "ACC_ENUM", "")
+];
+
+pub const PARAMETER_FLAGS: &[FlagName] =
+  &[(0x0010, "ACC_FINAL", "final"), (0x1000, "ACC_SYNTHETIC", ""), (0x8000, "ACC_MANDATED", "")];
+
+pub const MODULE_FLAGS: &[FlagName] =
+  &[(0x0020, "ACC_OPEN", "open"), (0x1000, "ACC_SYNTHETIC", ""), (0x8000, "ACC_MANDATED", "")];
+
+pub const REQUIRES_FLAGS: &[FlagName] = &[
+  (0x0020, "ACC_TRANSITIVE", "transitive"),
+  (0x0040, "ACC_STATIC_PHASE", "static"),
+  (0x1000, "ACC_SYNTHETIC", ""),
+  (0x8000, "ACC_MANDATED", "")
+];
+
+/// The flags of both `exports` and `opens`.
+pub const EXPORTS_FLAGS: &[FlagName] =
+  &[(0x1000, "ACC_SYNTHETIC", ""), (0x8000, "ACC_MANDATED", "")];
+
+/// What a type annotation can apply to, by its `target_type`, named as javap names it.
+pub const TARGET_TYPES: &[(u8, &str)] = &[
+  (0x00, "CLASS_TYPE_PARAMETER"),
+  (0x01, "METHOD_TYPE_PARAMETER"),
+  (0x10, "CLASS_EXTENDS"),
+  (0x11, "CLASS_TYPE_PARAMETER_BOUND"),
+  (0x12, "METHOD_TYPE_PARAMETER_BOUND"),
+  (0x13, "FIELD"),
+  (0x14, "METHOD_RETURN"),
+  (0x15, "METHOD_RECEIVER"),
+  (0x16, "METHOD_FORMAL_PARAMETER"),
+  (0x17, "THROWS"),
+  (0x40, "LOCAL_VARIABLE"),
+  (0x41, "RESOURCE_VARIABLE"),
+  (0x42, "EXCEPTION_PARAMETER"),
+  (0x43, "INSTANCEOF"),
+  (0x44, "NEW"),
+  (0x45, "CONSTRUCTOR_REFERENCE"),
+  (0x46, "METHOD_REFERENCE"),
+  (0x47, "CAST"),
+  (0x48, "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT"),
+  (0x49, "METHOD_INVOCATION_TYPE_ARGUMENT"),
+  (0x4a, "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT"),
+  (0x4b, "METHOD_REFERENCE_TYPE_ARGUMENT")
+];
+
+pub const METHOD_FLAGS: &[FlagName] = &[
+  (0x0001, "ACC_PUBLIC", "public"),
+  (0x0002, "ACC_PRIVATE", "private"),
+  (0x0004, "ACC_PROTECTED", "protected"),
+  (0x0008, "ACC_STATIC", "static"),
+  (0x0010, "ACC_FINAL", "final"),
+  (0x0020, "ACC_SYNCHRONIZED", "synchronized"),
+  (0x0040, "ACC_BRIDGE", ""),
+  (0x0080, "ACC_VARARGS", ""),
+  (0x0100, "ACC_NATIVE", "native"),
+  (0x0400, "ACC_ABSTRACT", "abstract"),
+  (0x0800, "ACC_STRICT", "strictfp"),
+  (0x1000, "ACC_SYNTHETIC", "")
+];
+
+/// The flags line of a class or member, such as `(0x0021) ACC_PUBLIC, ACC_SUPER`.
+pub fn flags(mask: u16, names: &[FlagName]) -> String {
+  let names: Vec<&str> =
+    names.iter().filter(|(flag, ..)| mask & flag != 0).map(|(_, name, _)| *name).collect();
+  format!("(0x{:04x}) {}", mask, names.join(", ")).trim_end().to_string()
+}
+
+/// The Java modifiers for `mask`, each followed by a space.
+pub fn modifiers(mask: u16, names: &[FlagName]) -> String {
+  names
+    .iter()
+    .filter(|(flag, _, modifier)| mask & flag != 0 && !modifier.is_empty())
+    .map(|(.., modifier)| format!("{} ", modifier))
+    .collect()
+}
+
+/// A binary name as Java source spells it, such as `java.lang.String`.
+pub fn java_name(name: &str) -> String { name.replace('/', ".") }
+
+/// A field type as Java source spells it, such as `java.lang.String[]`.
+pub fn java_type(field_type: &FieldType) -> String {
+  match field_type {
+    FieldType::Byte => "byte".to_string(),
+    FieldType::Char => "char".to_string(),
+    FieldType::Double => "double".to_string(),
+    FieldType::Float => "float".to_string(),
+    FieldType::Int => "int".to_string(),
+    FieldType::Long => "long".to_string(),
+    FieldType::Short => "short".to_string(),
+    FieldType::Boolean => "boolean".to_string(),
+    FieldType::Object(name) => java_name(name),
+    FieldType::Array { dimensions, element } => {
+      format!("{}{}", java_type(element), "[]".repeat(*dimensions as usize))
+    }
+  }
+}
+
+/// Escapes the characters of a string constant that wouldn't print on one line.
+pub fn escape(text: &str) -> String {
+  text
+    .chars()
+    .map(|char| match char {
+      '\t' => "\\t".to_string(),
+      '\n' => "\\n".to_string(),
+      '\r' => "\\r".to_string(),
+      '\\' => "\\\\".to_string(),
+      '"' => "\\\"".to_string(),
+      char if char.is_control() => format!("\\u{:04x}", char as u32),
+      char => char.to_string()
+    })
+    .collect()
+}
+
+/// Quotes class names that are array descriptors, as javap does.
+fn class_name(name: &str) -> String {
+  match name.starts_with('[') {
+    true => format!("\"{}\"", name),
+    false => name.to_string()
+  }
+}
+
+/// Quotes the names of `<init>` and `<clinit>`, as javap does.
+fn member_name(name: &str) -> String {
+  match name.starts_with('<') {
+    true => format!("\"{}\"", name),
+    false => name.to_string()
+  }
+}
+
+/// A float the way Java prints it: `NaN`, `Infinity`, or with at least one digit after the point.
+pub fn java_float(value: f64) -> String {
+  if value.is_nan() {
+    return "NaN".to_string();
+  }
+  if value.is_infinite() {
+    return (if value > 0.0 { "Infinity" } else { "-Infinity" }).to_string();
+  }
+  let text = format!("{:?}", value);
+  match text.split_once('e') {
+    Some((mantissa, exponent)) if mantissa.contains('.') => format!("{}E{}", mantissa, exponent),
+    Some((mantissa, exponent)) => format!("{}.0E{}", mantissa, exponent),
+    None => text
+  }
+}
+
+pub fn reference_kind(kind: ReferenceKind) -> &'static str {
+  match kind {
+    ReferenceKind::GetField => "REF_getField",
+    ReferenceKind::GetStatic => "REF_getStatic",
+    ReferenceKind::PutField => "REF_putField",
+    ReferenceKind::PutStatic => "REF_putStatic",
+    ReferenceKind::InvokeVirtual => "REF_invokeVirtual",
+    ReferenceKind::InvokeStatic => "REF_invokeStatic",
+    ReferenceKind::InvokeSpecial => "REF_invokeSpecial",
+    ReferenceKind::NewInvokeSpecial => "REF_newInvokeSpecial",
+    ReferenceKind::InvokeInterface => "REF_invokeInterface"
+  }
+}
+
+pub fn method_handle(handle: &MethodHandle) -> String {
+  let reference = match &handle.reference {
+    MethodHandleRef::Field(fieldref) => ResolvedCpInfo::Fieldref(fieldref.clone()),
+    MethodHandleRef::Method(methodref) => ResolvedCpInfo::Methodref(methodref.clone()),
+    MethodHandleRef::InterfaceMethod(interfacemethodref) => {
+      ResolvedCpInfo::InterfaceMethodref(interfacemethodref.clone())
+    }
+  };
+  format!("{} {}", reference_kind(handle.reference_kind), describe(&reference))
+}
+
+/// What a constant holds, the way javap comments it: `java/lang/Object."<init>":()V` for a
+/// method, or the text of a string.
+pub fn describe(constant: &ResolvedCpInfo) -> String {
+  match constant {
+    ResolvedCpInfo::Class(class) => class_name(&class.name),
+    ResolvedCpInfo::Fieldref(fieldref) => format!(
+      "{}.{}:{}",
+      class_name(&fieldref.class.name),
+      member_name(&fieldref.name_and_type.name),
+      fieldref.name_and_type.descriptor
+    ),
+    ResolvedCpInfo::Methodref(methodref) => format!(
+      "{}.{}:{}",
+      class_name(&methodref.class.name),
+      member_name(&methodref.name_and_type.name),
+      methodref.name_and_type.descriptor
+    ),
+    ResolvedCpInfo::InterfaceMethodref(interfacemethodref) => format!(
+      "{}.{}:{}",
+      class_name(&interfacemethodref.class.name),
+      member_name(&interfacemethodref.name_and_type.name),
+      interfacemethodref.name_and_type.descriptor
+    ),
+    ResolvedCpInfo::String(string) => escape(&string.to_string()),
+    ResolvedCpInfo::Integer(value) => value.to_string(),
+    ResolvedCpInfo::Float(value) => format!("{}f", java_float(*value as f64)),
+    ResolvedCpInfo::Long(value) => format!("{}l", value),
+    ResolvedCpInfo::Double(value) => format!("{}d", java_float(*value)),
+    ResolvedCpInfo::NameAndType(name_and_type) => {
+      format!("{}:{}", member_name(&name_and_type.name), name_and_type.descriptor)
+    }
+    ResolvedCpInfo::MethodHandle(handle) => method_handle(handle),
+    ResolvedCpInfo::MethodType(method_type) => method_type.descriptor.clone(),
+    ResolvedCpInfo::Dynamic(dynamic) => format!(
+      "#{}:{}:{}",
+      dynamic.bootstrap_method_attr_index,
+      member_name(&dynamic.name_and_type.name),
+      dynamic.name_and_type.descriptor
+    ),
+    ResolvedCpInfo::InvokeDynamic(invokedynamic) => format!(
+      "#{}:{}:{}",
+      invokedynamic.bootstrap_method_attr_index,
+      member_name(&invokedynamic.name_and_type.name),
+      invokedynamic.name_and_type.descriptor
+    ),
+    ResolvedCpInfo::Module(module) => module.name.clone(),
+    ResolvedCpInfo::Package(package) => package.name.clone(),
+    ResolvedCpInfo::Utf8(text) => escape(&text.to_string())
+  }
+}
+
+/// A loadable constant with its type, as `ldc` and the bootstrap arguments show it: `String a`,
+/// `int 5` or `class java/lang/Object`.
+pub fn loadable(constant: &ResolvedCpInfo) -> String {
+  let kind = match constant {
+    ResolvedCpInfo::Integer(_) => "int",
+    ResolvedCpInfo::Float(_) => "float",
+    ResolvedCpInfo::Long(_) => "long",
+    ResolvedCpInfo::Double(_) => "double",
+    ResolvedCpInfo::Class(_) => "class",
+    other => other.kind()
+  };
+  format!("{} {}", kind, describe(constant))
+}
+
+/// An annotation in something like Java syntax, such as `java.lang.Deprecated(since="9")`.
+pub fn annotation(annotation: &Annotation) -> String {
+  let type_name = FieldType::parse(&annotation.type_name)
+    .map(|field_type| java_type(&field_type))
+    .unwrap_or_else(|_| annotation.type_name.clone());
+  let elements: Vec<String> = annotation
+    .element_value_pairs
+    .iter()
+    .map(|(name, value)| format!("{}={}", name, element_value(value)))
+    .collect();
+  format!("{}({})", type_name, elements.join(","))
+}
+
+pub fn element_value(value: &ElementValue) -> String {
+  match value {
+    ElementValue::Byte(value) => format!("(byte) {}", value),
+    ElementValue::Char(value) => {
+      format!("'{}'", escape(&String::from_utf16_lossy(&[*value])))
+    }
+    ElementValue::Double(value) => format!("{}d", java_float(*value)),
+    ElementValue::Float(value) => format!("{}f", java_float(*value as f64)),
+    ElementValue::Int(value) => value.to_string(),
+    ElementValue::Long(value) => format!("{}l", value),
+    ElementValue::Short(value) => format!("(short) {}", value),
+    ElementValue::Boolean(value) => value.to_string(),
+    ElementValue::String(value) => format!("\"{}\"", escape(&value.to_string())),
+    ElementValue::Enum { type_name, const_name } => format!("{}.{}", type_name, const_name),
+    ElementValue::Class(descriptor) => format!("class {}", descriptor),
+    ElementValue::Annotation(nested) => format!("@{}", annotation(nested)),
+    ElementValue::Array(values) => {
+      let values: Vec<String> = values.iter().map(element_value).collect();
+      format!("[{}]", values.join(","))
+    }
+  }
+}
+
+/// What a type annotation applies to, as javap describes it: `METHOD_FORMAL_PARAMETER,
+/// param_index=0`.
+pub fn type_annotation_target(type_annotation: &TypeAnnotation) -> String {
+  // `TargetInfo::read` only accepts the target types in the table
+  let kind = TARGET_TYPES
+    .iter()
+    .find(|(target_type, _)| *target_type == type_annotation.target_type)
+    .map_or("METHOD_REFERENCE_TYPE_ARGUMENT", |(_, name)| name);
+  let mut parts = vec![kind.to_string()];
+  match &type_annotation.target_info {
+    TargetInfo::TypeParameter { type_parameter_index } => {
+      parts.push(format!("param_index={}", type_parameter_index))
+    }
+    TargetInfo::Supertype { supertype_index } => {
+      parts.push(format!("type_index={}", supertype_index))
+    }
+    TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+      parts.push(format!("param_index={}", type_parameter_index));
+      parts.push(format!("bound_index={}", bound_index));
+    }
+    TargetInfo::Empty => {}
+    TargetInfo::FormalParameter { formal_parameter_index } => {
+      parts.push(format!("param_index={}", formal_parameter_index))
+    }
+    TargetInfo::Throws { throws_type_index } => {
+      parts.push(format!("type_index={}", throws_type_index))
+    }
+    TargetInfo::Localvar { table } => {
+      for variable in table {
+        parts.push(format!(
+          "{{start_pc={}, length={}, index={}}}",
+          variable.start_pc, variable.length, variable.index
+        ));
+      }
+    }
+    TargetInfo::Catch { exception_table_index } => {
+      parts.push(format!("exception_index={}", exception_table_index))
+    }
+    TargetInfo::Offset { offset } => parts.push(format!("offset={}", offset)),
+    TargetInfo::TypeArgument { offset, type_argument_index } => {
+      parts.push(format!("offset={}", offset));
+      parts.push(format!("type_index={}", type_argument_index));
+    }
+  }
+  if !type_annotation.type_path.is_empty() {
+    let location: Vec<String> = type_annotation
+      .type_path
+      .iter()
+      .map(|entry| match entry.type_path_kind {
+        TypePathKind::Array => "ARRAY".to_string(),
+        TypePathKind::Nested => "INNER_TYPE".to_string(),
+        TypePathKind::WildcardBound => "WILDCARD".to_string(),
+        TypePathKind::TypeArgument => format!("TYPE_ARGUMENT({})", entry.type_argument_index)
+      })
+      .collect();
+    parts.push(format!("location=[{}]", location.join(", ")));
+  }
+  parts.join(", ")
+}
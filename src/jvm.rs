@@ -1,14 +1,20 @@
-use std::{collections::HashMap, env, path::Path};
+use std::{
+  collections::{HashMap, HashSet},
+  env,
+  path::Path
+};
 
-use self::{static_class::ClassStatics, eval::Eval};
+use self::{eval::Eval, linkage_error::LinkageError, static_class::ClassStatics};
 use crate::parser::classfile::ClassFile;
 
 mod dynamic_class;
 mod eval;
 mod helpers;
+pub mod linkage_error;
 mod resolver;
 mod static_class;
 mod types;
+pub mod verifier;
 
 #[derive(Debug)]
 pub struct JVM {
@@ -20,17 +26,87 @@ pub struct JVM {
 //set current directory to the target's directory
 //resolve other necessary classfiles
 impl JVM {
-  pub fn from_path(path: String) -> Self {
-    env::set_current_dir(Path::new(&path).parent().unwrap()).unwrap();
-    let newpath = String::from(Path::new(&path).file_name().unwrap().to_str().unwrap());
-    let (name, cf, depends) = ClassFile::read(newpath);
+  pub fn from_path(path: String) -> Result<Self, LinkageError> {
+    let not_found = || LinkageError::NoClassDefFoundError { class: path.clone() };
+    // A path such as `..` names no file
+    let file_name =
+      Path::new(&path).file_name().and_then(|name| name.to_str()).ok_or_else(not_found)?;
+    let newpath = String::from(file_name);
+    let directory = Path::new(&path).parent().filter(|directory| !directory.as_os_str().is_empty());
+    if let Some(directory) = directory {
+      env::set_current_dir(directory).map_err(|_| not_found())?;
+    }
+    let (cf, depends) = resolver::load(newpath)?;
+    Self::link(cf, depends)
+  }
+
+  /// Runs a class that was put together in memory, such as by `ClassBuilder`, after the same
+  /// checks a class file gets. The classes it uses are looked for in the current directory.
+  pub fn from_class(cf: ClassFile) -> Result<Self, LinkageError> {
+    let depends = resolver::dependencies(&cf);
+    Self::link(resolver::check(cf)?, depends)
+  }
+
+  fn link(cf: ClassFile, depends: HashSet<String>) -> Result<Self, LinkageError> {
+    let name = cf.this_class.name.clone();
     let mut resolver = resolver::Resolver::new();
-    resolver.resolved.insert(name.clone(), ClassStatics::from(cf));
-    resolver.resolve(depends);
-    Self { entrypoint: name, classes: resolver.resolved }
+    let statics = ClassStatics::from(cf, &mut resolver.strings);
+    resolver.resolved.insert(name.clone(), statics);
+    resolver.resolve(depends)?;
+    Ok(Self { entrypoint: name, classes: resolver.resolved })
   }
 
   pub fn start(&self) {
-    self.classes.get(&self.entrypoint).unwrap().evaluate(&self, String::from("main:([Ljava/lang/String;)V"));
+    self
+      .classes
+      .get(&self.entrypoint)
+      .unwrap()
+      .evaluate(&self, String::from("main:([Ljava/lang/String;)V"));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{linkage_error::MAX_MAJOR_VERSION, types::Type, *};
+  use crate::{
+    builder::ClassBuilder,
+    parser::{
+      attribute_info::code::code_generator::Instructions,
+      classfile::ClassAccessFlags,
+      method_info::MethodAccessFlags
+    }
+  };
+
+  /// `static int pick() { if (2 < 3) return 42; return 0; }`, built with `version` and branching
+  /// to a label so the builder has to compute a frame.
+  fn pick(major_version: u16) -> ClassFile {
+    let mut class =
+      ClassBuilder::new(ClassAccessFlags::from_mask(0x0021), "Pick", Some("java/lang/Object"));
+    class.version(major_version, 0);
+    let method = class.method(MethodAccessFlags::from_mask(0x0009), "pick", "()I").unwrap();
+    let otherwise = method.new_label();
+    method
+      .emit(Instructions::iconst { value: 2 })
+      .emit(Instructions::iconst { value: 3 })
+      .jump(Instructions::if_icmpge { offset: 0 }, otherwise)
+      .emit(Instructions::bipush { value: 42 })
+      .emit(Instructions::ireturn)
+      .bind(otherwise)
+      .emit(Instructions::iconst { value: 0 })
+      .emit(Instructions::ireturn);
+    class.build().unwrap()
+  }
+
+  #[test]
+  fn evaluates_built_class() {
+    let jvm = JVM::from_class(pick(52)).unwrap();
+    let result = jvm.classes["Pick"].evaluate(&jvm, String::from("pick:()I"));
+    assert!(matches!(result, Some(Type::Int(42))), "{:?}", result);
+  }
+
+  #[test]
+  fn built_class_gets_load_checks() {
+    let error = JVM::from_class(pick(MAX_MAJOR_VERSION + 1)).unwrap_err();
+    assert!(matches!(error, LinkageError::UnsupportedClassVersionError { .. }), "{}", error);
   }
 }
@@ -30,7 +30,7 @@ pub trait Eval {
 
   fn evaluate(&self, jvm: &JVM, method: String) -> Option<Type> {
     let attr = &self.get_methods().get(&method).unwrap().attributes[0];
-    let Attribute::Code { max_stack, max_locals, code, exception_table: _, attributes: _ } = attr else {panic!()};
+    let Attribute::Code { max_stack, max_locals, code, exception_table: _, attributes: _, offsets: _ } = attr else {panic!()};
     let mut stack = Vec::with_capacity(*max_stack as usize);
     let mut locals = Vec::with_capacity(*max_locals as usize);
     let mut pc = 0;
@@ -45,9 +45,9 @@ pub trait Eval {
         Instructions::dconst { value } => stack.push(Type::Double(*value)),
         Instructions::bipush { value } => stack.push(Type::Int(*value as i32)),
         Instructions::sipush { value } => stack.push(Type::Int(*value as i32)),
-        Instructions::ldc { index: _ } => todo!(),
-        Instructions::ldc_w { index: _ } => todo!(),
-        Instructions::ldc2_w { index: _ } => todo!(),
+        Instructions::ldc { constant: _ } => todo!(),
+        Instructions::ldc_w { constant: _ } => todo!(),
+        Instructions::ldc2_w { constant: _ } => todo!(),
         Instructions::iload { index } => {
           let val = get_type!(Int, &locals[*index as usize]);
           stack.push(Type::Int(*val))
@@ -646,16 +646,13 @@ pub trait Eval {
         Instructions::r#return => return None,
         Instructions::getstatic { fieldref } => {
           let class = jvm.classes.get(&fieldref.class.to_string()).unwrap();
-          let field = class.fields.get(&fieldref.name_and_type.to_string()).unwrap();
-          todo!();
-          // stack.push(field.value);
+          let value = class.static_values.borrow().get(&fieldref.name_and_type.to_string()).unwrap().clone();
+          stack.push(value);
         }
         Instructions::putstatic { fieldref } => {
           let value = stack.pop().unwrap();
           let class = jvm.classes.get(&fieldref.class.to_string()).unwrap();
-          let field = class.fields.get(&fieldref.name_and_type.to_string()).unwrap();
-          todo!();
-          // field.value = value;
+          class.static_values.borrow_mut().insert(fieldref.name_and_type.to_string(), value);
         }
         Instructions::getfield { fieldref } => {
           let objectref = get_type!(Reference, stack.pop().unwrap()).expect("NullPointerException");
@@ -685,16 +682,16 @@ pub trait Eval {
           stack.push(Type::Reference(Some(HeapType::Class(new))));
         }
         Instructions::newarray { atype: _ } => {}
-        Instructions::anewarray { index: _ } => todo!(),
+        Instructions::anewarray { class: _ } => todo!(),
         Instructions::arraylength => todo!(),
         Instructions::athrow => todo!(),
-        Instructions::checkcast { index: _ } => todo!(),
-        Instructions::instanceof { index: _ } => todo!(),
+        Instructions::checkcast { class: _ } => todo!(),
+        Instructions::instanceof { class: _ } => todo!(),
         Instructions::monitorenter => todo!(),
         Instructions::monitorexit => todo!(),
         Instructions::wide1 { opcode: _, index_extension: _ } => todo!(),
         Instructions::wide2 { opcode: _, index_extension: _, constbytes: _ } => todo!(),
-        Instructions::multianewarray { index: _, dimensions: _ } => todo!(),
+        Instructions::multianewarray { class: _, dimensions: _ } => todo!(),
         Instructions::ifnull { offset: _ } => todo!(),
         Instructions::ifnonnull { offset: _ } => todo!(),
         Instructions::goto_w { offset: _ } => todo!(),
@@ -10,6 +10,7 @@ pub fn refeq(value1: Option<HeapType>, value2: Option<HeapType>) -> bool {
     (Some(value1), Some(value2)) => {
       match (value1, value2) {
         (HeapType::Class(ref1), HeapType::Class(ref2)) => Rc::ptr_eq(&ref1, &ref2),
+        (HeapType::String(ref1), HeapType::String(ref2)) => Rc::ptr_eq(&ref1, &ref2),
         //(HeapType::Interface(ref1), HeapType::Interface(ref2)) => Rc::ptr_eq(&ref1, &ref2),
         (HeapType::ArrayI(ref1), HeapType::ArrayI(ref2)) => Rc::ptr_eq(&ref1, &ref2),
         (HeapType::ArrayL(ref1), HeapType::ArrayL(ref2)) => Rc::ptr_eq(&ref1, &ref2),
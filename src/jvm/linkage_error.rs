@@ -0,0 +1,61 @@
+use std::fmt;
+
+use crate::parser::class_format_error::ClassFormatError;
+
+/// The oldest class file version the interpreter loads, from JDK 1.0.2.
+pub const MIN_MAJOR_VERSION: u16 = 45;
+/// The newest class file version the interpreter loads, from Java SE 18.
+pub const MAX_MAJOR_VERSION: u16 = 62;
+
+/// Errors raised while loading and linking classes, named after the `java.lang.LinkageError`
+/// subclass the JVM would throw for them.
+#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+pub enum LinkageError {
+  /// No class file was found at the path or for the class name.
+  NoClassDefFoundError {
+    class: String
+  },
+  ClassFormatError {
+    class: String,
+    error: ClassFormatError
+  },
+  UnsupportedClassVersionError {
+    class: String,
+    major_version: u16,
+    minor_version: u16
+  },
+  /// `method` is keyed as in `ClassFile::methods`, and `index` is an instruction index.
+  VerifyError {
+    class: String,
+    method: String,
+    index: usize,
+    message: String
+  }
+}
+
+impl fmt::Display for LinkageError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      LinkageError::NoClassDefFoundError { class } => {
+        write!(f, "java.lang.NoClassDefFoundError: {}", class)
+      }
+      LinkageError::ClassFormatError { class, error } => {
+        write!(f, "java.lang.ClassFormatError: {} in class file {}", error, class)
+      }
+      LinkageError::UnsupportedClassVersionError { class, major_version, minor_version } => {
+        write!(
+          f,
+          "java.lang.UnsupportedClassVersionError: {} has unsupported class file version {}.{}, \
+           this interpreter only recognizes class file versions {}.0 to {}.0",
+          class, major_version, minor_version, MIN_MAJOR_VERSION, MAX_MAJOR_VERSION
+        )
+      }
+      LinkageError::VerifyError { class, method, index, message } => write!(
+        f,
+        "java.lang.VerifyError: (class: {}, method: {}, instruction: {}) {}",
+        class, method, index, message
+      )
+    }
+  }
+}
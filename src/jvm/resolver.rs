@@ -1,23 +1,99 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+  collections::{HashMap, HashSet},
+  fs
+};
 
-use super::static_class::ClassStatics;
-use crate::parser::classfile::ClassFile;
+use super::{
+  linkage_error::{LinkageError, MAX_MAJOR_VERSION, MIN_MAJOR_VERSION},
+  static_class::ClassStatics,
+  types::StringPool,
+  verifier
+};
+use crate::parser::{
+  attribute_info::{code::code_generator::Instructions, Attribute},
+  classfile::ClassFile,
+  format_check::check_format
+};
 
 pub struct Resolver {
-  pub resolved: HashMap<String, ClassStatics>
+  pub resolved: HashMap<String, ClassStatics>,
+  /// Shared by every class loaded, so that their string literals are interned together.
+  pub strings: StringPool
 }
 
 impl Resolver {
-  pub fn new() -> Self { Self { resolved: HashMap::new() } }
+  pub fn new() -> Self { Self { resolved: HashMap::new(), strings: StringPool::default() } }
 
-  pub fn resolve(&mut self, depends: HashSet<String>) {
+  pub fn resolve(&mut self, depends: HashSet<String>) -> Result<(), LinkageError> {
     for module in depends {
       if module == "java/lang/Object" || self.resolved.contains_key(&module) {
         continue;
       };
-      let (name, cf, depends) = ClassFile::read(module.to_string());
-      self.resolved.insert(name, ClassStatics::from(cf));
-      self.resolve(depends)
+      let (cf, depends) = load(module)?;
+      let name = cf.this_class.name.clone();
+      self.resolved.insert(name, ClassStatics::from(cf, &mut self.strings));
+      self.resolve(depends)?
     }
+    Ok(())
   }
 }
+
+/// Reads the class file at `path`, or `path` with `.class` added, rejecting any with a version the
+/// interpreter doesn't support, that breaks the format rules or whose code fails verification.
+pub fn load(path: String) -> Result<(ClassFile, HashSet<String>), LinkageError> {
+  let bytes = fs::read(&path)
+    .or_else(|_| fs::read(format!("{}.class", path)))
+    .map_err(|_| LinkageError::NoClassDefFoundError { class: path.clone() })?;
+  let (cf, depends) = ClassFile::parse(&bytes)
+    .map_err(|error| LinkageError::ClassFormatError { class: path, error })?;
+  Ok((check(cf)?, depends))
+}
+
+/// Applies the checks `load` makes to a class that didn't come from a file.
+pub fn check(cf: ClassFile) -> Result<ClassFile, LinkageError> {
+  // Since Java SE 12 a non-zero minor version marks a class using preview features
+  let preview = cf.major_version >= 56 && cf.minor_version != 0;
+  if !(MIN_MAJOR_VERSION..=MAX_MAJOR_VERSION).contains(&cf.major_version) || preview {
+    return Err(LinkageError::UnsupportedClassVersionError {
+      class: cf.this_class.name,
+      major_version: cf.major_version,
+      minor_version: cf.minor_version
+    });
+  }
+  if let Err(mut errors) = check_format(&cf) {
+    // Like the JVM, report the first problem
+    return Err(LinkageError::ClassFormatError {
+      class: cf.this_class.name,
+      error: errors.remove(0)
+    });
+  }
+  verifier::verify(&cf)?;
+  Ok(cf)
+}
+
+/// The classes whose members the code of `cf` refers to, as `ClassFile::parse` finds them in the
+/// constant pool.
+pub fn dependencies(cf: &ClassFile) -> HashSet<String> {
+  cf.methods
+    .iter()
+    .flat_map(|(_, method)| &method.attributes)
+    .filter_map(|attribute| match attribute {
+      Attribute::Code { code, .. } => Some(code),
+      _ => None
+    })
+    .flatten()
+    .filter_map(|instruction| match instruction {
+      Instructions::getstatic { fieldref }
+      | Instructions::putstatic { fieldref }
+      | Instructions::getfield { fieldref }
+      | Instructions::putfield { fieldref } => Some(fieldref.class.name.clone()),
+      Instructions::invokevirtual { methodref }
+      | Instructions::invokespecial { methodref }
+      | Instructions::invokestatic { methodref } => Some(methodref.class.name.clone()),
+      Instructions::invokeinterface { interfacemethodref, .. } => {
+        Some(interfacemethodref.class.name.clone())
+      }
+      _ => None
+    })
+    .collect()
+}
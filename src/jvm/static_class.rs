@@ -1,6 +1,10 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use super::{dynamic_class::ClassDynamics, eval::Eval};
+use super::{
+  dynamic_class::ClassDynamics,
+  eval::Eval,
+  types::{StringPool, Type}
+};
 use crate::parser::{
   attribute_info::Attribute,
   classfile::{ClassAccessFlags, ClassFile},
@@ -12,16 +16,18 @@ use crate::parser::{
 #[derive(Debug, Clone)]
 pub struct ClassStatics {
   pub access_flags: ClassAccessFlags,
-  pub super_class: Class,
-  pub interfaces: Vec<u16>,
+  pub super_class: Option<Class>,
+  pub interfaces: Vec<Class>,
   pub fields: HashMap<String, FieldInfo>,
+  /// The current value of each static field, keyed like `fields`.
+  pub static_values: RefCell<HashMap<String, Type>>,
   pub methods: HashMap<String, MethodInfo>,
   pub attributes: Vec<Attribute>,
   pub dynamic: ClassDynamics
 }
 
 impl ClassStatics {
-  pub fn from(cf: ClassFile) -> Self {
+  pub fn from(cf: ClassFile, strings: &mut StringPool) -> Self {
     let access_flags = cf.access_flags;
     let super_class = cf.super_class;
     let interfaces = cf.interfaces;
@@ -35,6 +41,18 @@ impl ClassStatics {
       }
     }
 
+    // Static fields are prepared before <clinit> runs, taking their ConstantValue if they have one
+    let static_values = static_fields
+      .iter()
+      .map(|(name, field)| {
+        let value = match field.constant_value() {
+          Some(constant) => Type::from_constant(constant, strings),
+          None => Type::default_for(&field.descriptor)
+        };
+        (name.clone(), value)
+      })
+      .collect();
+
     let (mut static_methods, mut dynamic_methods) = (HashMap::new(), HashMap::new());
     for (name, method) in cf.methods {
       if method.access_flags.contains(method_info::Flags::AccStatic) {
@@ -51,6 +69,7 @@ impl ClassStatics {
       super_class,
       interfaces,
       fields: static_fields,
+      static_values: RefCell::new(static_values),
       methods: static_methods,
       attributes,
       dynamic: ClassDynamics { fields: dynamic_fields, methods: dynamic_methods }
@@ -63,7 +82,5 @@ impl ClassStatics {
 }
 
 impl Eval for ClassStatics {
-    fn get_methods(&self) -> &HashMap<String, MethodInfo> {
-        &self.methods
-    }
+  fn get_methods(&self) -> &HashMap<String, MethodInfo> { &self.methods }
 }
@@ -1,11 +1,17 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::dynamic_class::ClassDynamics;
+use crate::parser::{
+  attribute_info::attribute::constant::Constant,
+  cp_info_resolved::JavaString,
+  descriptor::FieldType
+};
 
 #[derive(Debug, Clone)]
 pub enum HeapType {
   Class(Rc<RefCell<ClassDynamics>>),
   Interface(),
+  String(Rc<JavaString>),
 
   ArrayI(Rc<RefCell<Vec<i32>>>),
   ArrayL(Rc<RefCell<Vec<i64>>>),
@@ -27,3 +33,66 @@ pub enum Type {
   ReturnAddress(usize),
   Reference(Option<HeapType>)
 }
+
+impl Type {
+  /// The value a field with this descriptor holds before anything is assigned to it.
+  pub fn default_for(descriptor: &FieldType) -> Self {
+    match descriptor {
+      FieldType::Long => Type::Long(0),
+      FieldType::Float => Type::Float(0.0),
+      FieldType::Double => Type::Double(0.0),
+      FieldType::Object(_) | FieldType::Array { .. } => Type::Reference(None),
+      _ => Type::Int(0)
+    }
+  }
+
+  pub fn from_constant(constant: &Constant, strings: &mut StringPool) -> Self {
+    match constant {
+      Constant::Int(value) => Type::Int(*value),
+      Constant::Long(value) => Type::Long(*value),
+      Constant::Float(value) => Type::Float(*value),
+      Constant::Double(value) => Type::Double(*value),
+      Constant::String(value) => Type::Reference(Some(HeapType::String(strings.intern(value))))
+    }
+  }
+}
+
+/// The strings of every literal loaded so far. String literals are interned (JVMS §5.1), so the
+/// same literal is the same reference wherever it appears.
+#[derive(Debug, Default)]
+pub struct StringPool {
+  strings: HashMap<Vec<u16>, Rc<JavaString>>
+}
+
+impl StringPool {
+  pub fn intern(&mut self, string: &JavaString) -> Rc<JavaString> {
+    let interned =
+      self.strings.entry(string.chars.clone()).or_insert_with(|| Rc::new(string.clone()));
+    interned.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::jvm::helpers::refeq;
+
+  fn reference(value: Type) -> Option<HeapType> {
+    match value {
+      Type::Reference(reference) => reference,
+      _ => panic!("expected a reference")
+    }
+  }
+
+  #[test]
+  fn equal_string_literals_are_the_same_reference() {
+    let mut strings = StringPool::default();
+    let literal =
+      |text: &str| Constant::String(JavaString { chars: text.encode_utf16().collect() });
+    let a = reference(Type::from_constant(&literal("hi"), &mut strings));
+    let b = reference(Type::from_constant(&literal("hi"), &mut strings));
+    let c = reference(Type::from_constant(&literal("ho"), &mut strings));
+    assert!(refeq(a.clone(), b));
+    assert!(!refeq(a, c));
+  }
+}
@@ -0,0 +1,90 @@
+use self::class_hierarchy::ClassHierarchy;
+use super::linkage_error::LinkageError;
+use crate::parser::{
+  attribute_info::{attribute::stack_map_frame::frame::Frame, Attribute},
+  classfile::ClassFile,
+  method_info::MethodInfo
+};
+
+mod class_hierarchy;
+mod frame;
+mod instruction;
+mod type_checker;
+mod type_inference;
+mod verification_type;
+
+/// Checks the code of every method in `class` before any of it runs (JVMS §4.10).
+/// Class files from version 50 on are checked against their StackMapTable frames, and older ones
+/// have their frames inferred.
+pub fn verify(class: &ClassFile) -> Result<(), LinkageError> {
+  let hierarchy = ClassHierarchy::new(class);
+  for (key, method) in &class.methods {
+    let name = key.split(':').next().unwrap_or_default();
+    let result = match class.major_version {
+      50.. => type_checker::check(class, name, method, &hierarchy),
+      _ => type_inference::check(class, name, method, &hierarchy)
+    };
+    result.map_err(|(index, message)| LinkageError::VerifyError {
+      class: class.this_class.name.clone(),
+      method: key.clone(),
+      index,
+      message
+    })?;
+  }
+  Ok(())
+}
+
+/// The deepest the operand stack gets in the method keyed `key`, and the frames its StackMapTable
+/// needs: those of branch targets, exception handlers and instructions control can't fall into.
+/// The frames are inferred as for a class file without a StackMapTable, so the method's Code
+/// attribute only needs its instructions, exception table and `max_locals`.
+/// An unreachable instruction that needs a frame has none to give, and is reported as an error.
+pub fn compute_frames(
+  class: &ClassFile,
+  key: &str,
+  method: &MethodInfo
+) -> Result<(u16, Vec<(usize, Frame)>), LinkageError> {
+  let verify_error = |index, message| LinkageError::VerifyError {
+    class: class.this_class.name.clone(),
+    method: key.to_string(),
+    index,
+    message
+  };
+  let name = key.split(':').next().unwrap_or_default();
+  let hierarchy = ClassHierarchy::new(class);
+  let inferred = type_inference::infer(class, name, method, &hierarchy)
+    .map_err(|(index, message)| verify_error(index, message))?;
+  let Some(Attribute::Code { code, exception_table, .. }) =
+    method.attributes.iter().find(|attribute| matches!(attribute, Attribute::Code { .. }))
+  else {
+    return Ok((0, Vec::new()));
+  };
+
+  let mut needed = vec![false; code.len()];
+  for index in 0..code.len() {
+    let successors =
+      instruction::successors(code, index).map_err(|message| verify_error(index, message))?;
+    for target in successors.targets {
+      needed[target] = true;
+    }
+    if !successors.falls_through && index + 1 < code.len() {
+      needed[index + 1] = true;
+    }
+  }
+  for handler in exception_table {
+    needed[handler.handler_pc as usize] = true;
+  }
+
+  let max_stack = inferred.iter().flatten().map(|frame| frame.stack.len()).max().unwrap_or(0);
+  let mut frames = Vec::new();
+  for (index, frame) in inferred.iter().enumerate().filter(|(index, _)| needed[*index]) {
+    let Some(frame) = frame else {
+      return Err(verify_error(index, "Unreachable code needs a frame".to_string()));
+    };
+    frames.push((index, Frame {
+      locals: frame.locals.iter().map(|local| local.to_info()).collect(),
+      stack: frame.stack.iter().map(|item| item.to_info()).collect()
+    }));
+  }
+  Ok((max_stack as u16, frames))
+}
@@ -0,0 +1,173 @@
+use std::{
+  cell::RefCell,
+  collections::{HashMap, HashSet},
+  fs
+};
+
+use super::verification_type::VerificationType;
+use crate::parser::{
+  classfile::{ClassFile, Flags},
+  descriptor::FieldType
+};
+
+#[derive(Debug, Clone)]
+struct Summary {
+  super_class: Option<String>,
+  is_interface: bool
+}
+
+/// What the verifier knows of the classes named by the one it's checking, read from their class
+/// files the same way the resolver finds them.
+/// A class that can't be read gets the benefit of the doubt: any real mismatch surfaces when it's
+/// loaded.
+pub struct ClassHierarchy<'a> {
+  class: &'a ClassFile,
+  loaded: RefCell<HashMap<String, Option<Summary>>>
+}
+
+impl<'a> ClassHierarchy<'a> {
+  pub fn new(class: &'a ClassFile) -> Self { ClassHierarchy { class, loaded: RefCell::default() } }
+
+  fn summary(&self, name: &str) -> Option<Summary> {
+    if name == self.class.this_class.name {
+      return Some(Summary::of(self.class));
+    }
+    if let Some(summary) = self.loaded.borrow().get(name) {
+      return summary.clone();
+    }
+    let summary = fs::read(format!("{}.class", name))
+      .ok()
+      .and_then(|bytes| ClassFile::parse(&bytes).ok())
+      .map(|(class, _)| Summary::of(&class));
+    self.loaded.borrow_mut().insert(name.to_string(), summary.clone());
+    summary
+  }
+
+  /// Whether a value of type `from` can be used where `to` is expected.
+  pub fn is_assignable(&self, from: &VerificationType, to: &VerificationType) -> bool {
+    match (from, to) {
+      _ if from == to => true,
+      (_, VerificationType::Top) => true,
+      (VerificationType::Null, VerificationType::Object(_)) => true,
+      (VerificationType::Object(from), VerificationType::Object(to)) => {
+        self.is_java_assignable(from, to)
+      }
+      _ => false
+    }
+  }
+
+  /// The most specific type both `a` and `b` can be used as, where control flow joins, or `None`
+  /// if they have nothing in common.
+  pub fn merge(&self, a: &VerificationType, b: &VerificationType) -> Option<VerificationType> {
+    match (a, b) {
+      _ if a == b => Some(a.clone()),
+      (VerificationType::Null, VerificationType::Object(_)) => Some(b.clone()),
+      (VerificationType::Object(_), VerificationType::Null) => Some(a.clone()),
+      (VerificationType::Object(a), VerificationType::Object(b)) => {
+        Some(VerificationType::Object(self.common_superclass(a, b)))
+      }
+      _ => None
+    }
+  }
+
+  fn common_superclass(&self, a: &str, b: &str) -> String {
+    if self.is_java_assignable(a, b) {
+      return b.to_string();
+    }
+    if self.is_java_assignable(b, a) {
+      return a.to_string();
+    }
+    if let (Some(a), Some(b)) = (a.strip_prefix('['), b.strip_prefix('[')) {
+      if let (Ok(a), Ok(b)) = (FieldType::parse(a), FieldType::parse(b)) {
+        if let (true, true) = (is_reference(&a), is_reference(&b)) {
+          let component = self.common_superclass(&reference_name(&a), &reference_name(&b));
+          return match component.starts_with('[') {
+            true => format!("[{}", component),
+            false => format!("[L{};", component)
+          };
+        }
+      }
+      return "java/lang/Object".to_string();
+    }
+    let mut superclasses = HashSet::new();
+    let mut current = Some(a.to_string());
+    while let Some(class) = current.filter(|class| superclasses.insert(class.clone())) {
+      current = self.summary(&class).and_then(|summary| summary.super_class);
+    }
+    let mut current = Some(b.to_string());
+    let mut visited = HashSet::new();
+    while let Some(class) = current.filter(|class| visited.insert(class.clone())) {
+      if superclasses.contains(&class) {
+        return class;
+      }
+      current = self.summary(&class).and_then(|summary| summary.super_class);
+    }
+    "java/lang/Object".to_string()
+  }
+
+  /// Whether class `from` can be used where class `to` is expected. Interfaces are treated like
+  /// `java/lang/Object`, leaving the check to `invokeinterface` at run time (JVMS §4.10.1.2).
+  pub fn is_java_assignable(&self, from: &str, to: &str) -> bool {
+    if from == to || to == "java/lang/Object" {
+      return true;
+    }
+    match (from.strip_prefix('['), to.strip_prefix('[')) {
+      (Some(from), Some(to)) => match (FieldType::parse(from), FieldType::parse(to)) {
+        (Ok(FieldType::Object(from)), Ok(FieldType::Object(to))) => {
+          self.is_java_assignable(&from, &to)
+        }
+        (Ok(from @ FieldType::Array { .. }), Ok(to))
+        | (Ok(from), Ok(to @ FieldType::Array { .. })) => {
+          self.is_java_assignable(&reference_name(&from), &reference_name(&to))
+        }
+        // Arrays of primitives are only assignable to arrays of the same primitive
+        _ => false
+      },
+      (Some(_), None) => to == "java/lang/Cloneable" || to == "java/io/Serializable",
+      (None, Some(_)) => false,
+      (None, None) => match self.summary(to) {
+        Some(Summary { is_interface: false, .. }) => self.is_subclass(from, to),
+        _ => true
+      }
+    }
+  }
+
+  /// Whether `to` is `from` or one of its superclasses.
+  pub fn is_subclass(&self, from: &str, to: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut current = from.to_string();
+    while current != to {
+      if !visited.insert(current.clone()) {
+        // A circular hierarchy is rejected when the classes are loaded
+        return false;
+      }
+      current = match self.summary(&current) {
+        Some(Summary { super_class: Some(super_class), .. }) => super_class,
+        Some(Summary { super_class: None, .. }) => return false,
+        None => return true
+      };
+    }
+    true
+  }
+}
+
+impl Summary {
+  fn of(class: &ClassFile) -> Self {
+    Summary {
+      super_class: class.super_class.as_ref().map(|super_class| super_class.name.clone()),
+      is_interface: class.access_flags.contains(Flags::AccInterface)
+    }
+  }
+}
+
+fn is_reference(field_type: &FieldType) -> bool {
+  matches!(field_type, FieldType::Object(_) | FieldType::Array { .. })
+}
+
+/// The name of a reference type as the constant pool gives it: arrays by their descriptor.
+fn reference_name(field_type: &FieldType) -> String {
+  match field_type {
+    FieldType::Object(name) => name.clone(),
+    other => other.to_string()
+  }
+}
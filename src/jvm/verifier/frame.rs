@@ -0,0 +1,172 @@
+use super::{class_hierarchy::ClassHierarchy, verification_type::VerificationType};
+use crate::parser::attribute_info::attribute::stack_map_frame::frame::Frame as StackMapFrame;
+
+/// The types of the local variables and operand stack, one per slot.
+/// A long or double is followed by a `Top` standing in for its second slot, on the stack too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+  pub locals: Vec<VerificationType>,
+  pub stack: Vec<VerificationType>
+}
+
+impl Frame {
+  pub fn from(frame: &StackMapFrame) -> Self {
+    Frame {
+      locals: frame.locals.iter().map(VerificationType::from_info).collect(),
+      stack: frame.stack.iter().map(VerificationType::from_info).collect()
+    }
+  }
+
+  /// Whether this frame can flow into `to`, slot by slot.
+  pub fn is_assignable(&self, to: &Frame, hierarchy: &ClassHierarchy) -> bool {
+    let slots_assignable = |from: &[VerificationType], to: &[VerificationType]| {
+      from.len() == to.len()
+        && from.iter().zip(to).all(|(from, to)| hierarchy.is_assignable(from, to))
+    };
+    slots_assignable(&self.locals, &to.locals) && slots_assignable(&self.stack, &to.stack)
+  }
+
+  /// The frame where control from this frame and `other` joins: locals that disagree become
+  /// unusable, while the stacks must agree slot by slot.
+  pub fn merge(&self, other: &Frame, hierarchy: &ClassHierarchy) -> Result<Frame, String> {
+    if self.stack.len() != other.stack.len() {
+      return Err(format!(
+        "Inconsistent stack height {} != {}",
+        self.stack.len(),
+        other.stack.len()
+      ));
+    }
+    let stack = self
+      .stack
+      .iter()
+      .zip(&other.stack)
+      .map(|(a, b)| {
+        hierarchy
+          .merge(a, b)
+          .ok_or_else(|| format!("Mismatched stack types: {} and {}", a.name(), b.name()))
+      })
+      .collect::<Result<_, _>>()?;
+    let locals = self
+      .locals
+      .iter()
+      .zip(&other.locals)
+      .map(|(a, b)| hierarchy.merge(a, b).unwrap_or(VerificationType::Top))
+      .collect();
+    Ok(Frame { locals, stack })
+  }
+
+  pub fn push(&mut self, value: VerificationType) {
+    if value.size() == 2 {
+      self.stack.extend([value, VerificationType::Top]);
+    } else {
+      self.stack.push(value);
+    }
+  }
+
+  /// Pops a value that can be used as `expected`, returning its actual type.
+  pub fn pop(
+    &mut self,
+    expected: &VerificationType,
+    hierarchy: &ClassHierarchy
+  ) -> Result<VerificationType, String> {
+    let value = match expected.size() {
+      2 => self.pop_slots(2)?.remove(0),
+      _ => self.pop_slots(1)?.remove(0)
+    };
+    match hierarchy.is_assignable(&value, expected) {
+      true => Ok(value),
+      false => Err(format!(
+        "Bad type on operand stack: expected {} but found {}",
+        expected.name(),
+        value.name()
+      ))
+    }
+  }
+
+  /// Pops a reference, which may still be uninitialized.
+  pub fn pop_reference(&mut self) -> Result<VerificationType, String> {
+    let value = self.pop_slots(1)?.remove(0);
+    match value.is_reference() {
+      true => Ok(value),
+      false => {
+        Err(format!("Bad type on operand stack: expected a reference but found {}", value.name()))
+      }
+    }
+  }
+
+  /// Pops `count` slots as they are, for the instructions that shuffle values of any type.
+  /// The slots must hold whole values: half a long or double can't be split off.
+  pub fn pop_slots(&mut self, count: usize) -> Result<Vec<VerificationType>, String> {
+    if self.stack.len() < count {
+      return Err("Operand stack underflow".to_string());
+    }
+    let slots = self.stack.split_off(self.stack.len() - count);
+    match slots.first() {
+      Some(VerificationType::Top) => {
+        Err("Bad type on operand stack: split a long or double".to_string())
+      }
+      _ => Ok(slots)
+    }
+  }
+
+  /// The value in local variable `index`, which must be usable as `expected`.
+  pub fn load(
+    &self,
+    index: usize,
+    expected: &VerificationType,
+    hierarchy: &ClassHierarchy
+  ) -> Result<VerificationType, String> {
+    let value = self.local(index, expected.size())?;
+    match hierarchy.is_assignable(value, expected) {
+      true => Ok(value.clone()),
+      false => Err(format!(
+        "Bad local variable type: expected {} in local {} but found {}",
+        expected.name(),
+        index,
+        value.name()
+      ))
+    }
+  }
+
+  /// The value in local variable `index`, which must be a reference.
+  pub fn load_reference(&self, index: usize) -> Result<VerificationType, String> {
+    let value = self.local(index, 1)?;
+    match value.is_reference() {
+      true => Ok(value.clone()),
+      false => Err(format!(
+        "Bad local variable type: expected a reference in local {} but found {}",
+        index,
+        value.name()
+      ))
+    }
+  }
+
+  fn local(&self, index: usize, size: usize) -> Result<&VerificationType, String> {
+    match index + size <= self.locals.len() {
+      true => Ok(&self.locals[index]),
+      false => Err(format!("Illegal local variable number {}", index))
+    }
+  }
+
+  /// Stores `value` in local variable `index`, invalidating any long or double it overwrites part of.
+  pub fn store(&mut self, index: usize, value: VerificationType) -> Result<(), String> {
+    self.local(index, value.size())?;
+    if index > 0 && self.locals[index - 1].size() == 2 {
+      self.locals[index - 1] = VerificationType::Top;
+    }
+    if value.size() == 2 {
+      self.locals[index + 1] = VerificationType::Top;
+    }
+    self.locals[index] = value;
+    Ok(())
+  }
+
+  /// Replaces every occurrence of `from`, such as an object whose constructor just ran.
+  pub fn replace(&mut self, from: &VerificationType, to: &VerificationType) {
+    for slot in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+      if slot == from {
+        *slot = to.clone();
+      }
+    }
+  }
+}
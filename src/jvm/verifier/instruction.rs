@@ -0,0 +1,651 @@
+use super::{class_hierarchy::ClassHierarchy, frame::Frame, verification_type::VerificationType};
+use crate::parser::{
+  attribute_info::code::code_generator::Instructions,
+  cp_info_resolved::{Class, NameAndType, ResolvedCpInfo},
+  descriptor::{FieldType, MethodDescriptor}
+};
+
+/// The method being verified, as far as its instructions are concerned.
+pub struct Context<'a> {
+  pub this_class: &'a Class,
+  pub super_class: Option<&'a Class>,
+  pub method_name: &'a str,
+  pub descriptor: &'a MethodDescriptor,
+  pub code: &'a [Instructions],
+  pub max_stack: usize,
+  pub hierarchy: &'a ClassHierarchy<'a>
+}
+
+/// Where control can go once an instruction has run.
+pub struct Successors {
+  pub falls_through: bool,
+  /// Instruction indices, each entered with the frame the instruction leaves behind.
+  pub targets: Vec<usize>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Invoke {
+  Virtual,
+  Special,
+  Static,
+  Interface,
+  Dynamic
+}
+
+/// Where control can go from the instruction at `index`, judging by the code alone.
+/// A `jsr` only targets its subroutine: the instruction after it is reached through `ret`, whose
+/// successors depend on the frame.
+pub fn successors(code: &[Instructions], index: usize) -> Result<Successors, String> {
+  let offsets: Vec<isize> = match &code[index] {
+    Instructions::ifeq { offset }
+    | Instructions::ifne { offset }
+    | Instructions::iflt { offset }
+    | Instructions::ifge { offset }
+    | Instructions::ifgt { offset }
+    | Instructions::ifle { offset }
+    | Instructions::if_icmpeq { offset }
+    | Instructions::if_icmpne { offset }
+    | Instructions::if_icmplt { offset }
+    | Instructions::if_icmpge { offset }
+    | Instructions::if_icmpgt { offset }
+    | Instructions::if_icmple { offset }
+    | Instructions::if_acmpeq { offset }
+    | Instructions::if_acmpne { offset }
+    | Instructions::ifnull { offset }
+    | Instructions::ifnonnull { offset }
+    | Instructions::goto { offset }
+    | Instructions::jsr { offset } => vec![*offset as isize],
+    Instructions::goto_w { offset } | Instructions::jsr_w { offset } => vec![*offset as isize],
+    Instructions::tableswitch { default, offsets, .. } => {
+      std::iter::once(default).chain(offsets).map(|offset| *offset as isize).collect()
+    }
+    Instructions::lookupswith { default, pairs, .. } => std::iter::once(*default)
+      .chain(pairs.iter().map(|(_, offset)| *offset))
+      .map(|offset| offset as isize)
+      .collect(),
+    _ => Vec::new()
+  };
+  let targets = offsets
+    .into_iter()
+    .map(|offset| {
+      let target = index as isize + offset;
+      match (0..code.len() as isize).contains(&target) {
+        true => Ok(target as usize),
+        false => Err(format!("Branch to instruction {} is outside the method", target))
+      }
+    })
+    .collect::<Result<_, _>>()?;
+  let falls_through = !matches!(
+    code[index],
+    Instructions::goto { .. }
+      | Instructions::goto_w { .. }
+      | Instructions::jsr { .. }
+      | Instructions::jsr_w { .. }
+      | Instructions::ret { .. }
+      | Instructions::wide1 { opcode: 169, .. }
+      | Instructions::tableswitch { .. }
+      | Instructions::lookupswith { .. }
+      | Instructions::ireturn
+      | Instructions::lreturn
+      | Instructions::freturn
+      | Instructions::dreturn
+      | Instructions::areturn
+      | Instructions::r#return
+      | Instructions::athrow
+  );
+  Ok(Successors { falls_through, targets })
+}
+
+/// Runs the instruction at `index` over the types in `frame`, checking its operands.
+/// Subroutines are left to the caller, since only the type inference verifier supports them.
+pub fn execute(ctx: &Context, index: usize, frame: &mut Frame) -> Result<Successors, String> {
+  use VerificationType::{Double, Float, Integer, Long, Null};
+
+  let hierarchy = ctx.hierarchy;
+  let object = VerificationType::object("java/lang/Object");
+  let successors = successors(ctx.code, index)?;
+
+  match &ctx.code[index] {
+    Instructions::nop => {}
+    Instructions::aconst_null => frame.push(Null),
+    Instructions::iconst { .. } | Instructions::bipush { .. } | Instructions::sipush { .. } => {
+      frame.push(Integer)
+    }
+    Instructions::lconst { .. } => frame.push(Long),
+    Instructions::fconst { .. } => frame.push(Float),
+    Instructions::dconst { .. } => frame.push(Double),
+    Instructions::ldc { constant } | Instructions::ldc_w { constant } => {
+      frame.push(loadable(constant, 1)?)
+    }
+    Instructions::ldc2_w { constant } => frame.push(loadable(constant, 2)?),
+    Instructions::iload { index } => load(frame, *index as usize, Integer, hierarchy)?,
+    Instructions::lload { index } => load(frame, *index as usize, Long, hierarchy)?,
+    Instructions::fload { index } => load(frame, *index as usize, Float, hierarchy)?,
+    Instructions::dload { index } => load(frame, *index as usize, Double, hierarchy)?,
+    Instructions::aload { index } => {
+      let value = frame.load_reference(*index as usize)?;
+      frame.push(value)
+    }
+    Instructions::iaload => array_load(frame, &["[I"], Integer, hierarchy)?,
+    Instructions::laload => array_load(frame, &["[J"], Long, hierarchy)?,
+    Instructions::faload => array_load(frame, &["[F"], Float, hierarchy)?,
+    Instructions::daload => array_load(frame, &["[D"], Double, hierarchy)?,
+    Instructions::baload => array_load(frame, &["[B", "[Z"], Integer, hierarchy)?,
+    Instructions::caload => array_load(frame, &["[C"], Integer, hierarchy)?,
+    Instructions::saload => array_load(frame, &["[S"], Integer, hierarchy)?,
+    Instructions::aaload => {
+      frame.pop(&Integer, hierarchy)?;
+      let array = frame.pop_reference()?;
+      match array.component() {
+        Some(component) if component.is_reference() => frame.push(component),
+        _ => {
+          return Err(format!(
+            "Bad type on operand stack: {} is not an array of references",
+            array.name()
+          ))
+        }
+      }
+    }
+    Instructions::istore { index } => store(frame, *index as usize, Integer, hierarchy)?,
+    Instructions::lstore { index } => store(frame, *index as usize, Long, hierarchy)?,
+    Instructions::fstore { index } => store(frame, *index as usize, Float, hierarchy)?,
+    Instructions::dstore { index } => store(frame, *index as usize, Double, hierarchy)?,
+    Instructions::astore { index } => astore(frame, *index as usize)?,
+    Instructions::iastore => array_store(frame, &["[I"], Integer, hierarchy)?,
+    Instructions::lastore => array_store(frame, &["[J"], Long, hierarchy)?,
+    Instructions::fastore => array_store(frame, &["[F"], Float, hierarchy)?,
+    Instructions::dastore => array_store(frame, &["[D"], Double, hierarchy)?,
+    Instructions::bastore => array_store(frame, &["[B", "[Z"], Integer, hierarchy)?,
+    Instructions::castore => array_store(frame, &["[C"], Integer, hierarchy)?,
+    Instructions::sastore => array_store(frame, &["[S"], Integer, hierarchy)?,
+    Instructions::aastore => {
+      // Whether the value fits the array's element type is only known at run time
+      frame.pop(&object, hierarchy)?;
+      frame.pop(&Integer, hierarchy)?;
+      let array = frame.pop_reference()?;
+      if !array.component().is_some_and(|component| component.is_reference()) {
+        return Err(format!(
+          "Bad type on operand stack: {} is not an array of references",
+          array.name()
+        ));
+      }
+    }
+    Instructions::pop => {
+      frame.pop_slots(1)?;
+    }
+    Instructions::pop2 => {
+      frame.pop_slots(2)?;
+    }
+    Instructions::dup => shuffle(frame, &[1], &[0, 0])?,
+    Instructions::dup_x1 => shuffle(frame, &[1, 1], &[0, 1, 0])?,
+    Instructions::dup_x2 => shuffle(frame, &[1, 2], &[0, 1, 0])?,
+    Instructions::dup2 => shuffle(frame, &[2], &[0, 0])?,
+    Instructions::dup2_x1 => shuffle(frame, &[2, 1], &[0, 1, 0])?,
+    Instructions::dup2_x2 => shuffle(frame, &[2, 2], &[0, 1, 0])?,
+    Instructions::swap => shuffle(frame, &[1, 1], &[0, 1])?,
+    Instructions::iadd
+    | Instructions::isub
+    | Instructions::imul
+    | Instructions::idiv
+    | Instructions::irem
+    | Instructions::ishl
+    | Instructions::ishr
+    | Instructions::iushr
+    | Instructions::iand
+    | Instructions::ior
+    | Instructions::ixor => operation(frame, &[Integer, Integer], Some(Integer), hierarchy)?,
+    Instructions::ladd
+    | Instructions::lsub
+    | Instructions::lmul
+    | Instructions::ldiv
+    | Instructions::lrem
+    | Instructions::land
+    | Instructions::lor
+    | Instructions::lxor => operation(frame, &[Long, Long], Some(Long), hierarchy)?,
+    Instructions::lshl | Instructions::lshr | Instructions::lushr => {
+      operation(frame, &[Integer, Long], Some(Long), hierarchy)?
+    }
+    Instructions::fadd
+    | Instructions::fsub
+    | Instructions::fmul
+    | Instructions::fdiv
+    | Instructions::frem => operation(frame, &[Float, Float], Some(Float), hierarchy)?,
+    Instructions::dadd
+    | Instructions::dsub
+    | Instructions::dmul
+    | Instructions::ddiv
+    | Instructions::drem => operation(frame, &[Double, Double], Some(Double), hierarchy)?,
+    Instructions::ineg | Instructions::i2b | Instructions::i2c | Instructions::i2s => {
+      operation(frame, &[Integer], Some(Integer), hierarchy)?
+    }
+    Instructions::lneg => operation(frame, &[Long], Some(Long), hierarchy)?,
+    Instructions::fneg => operation(frame, &[Float], Some(Float), hierarchy)?,
+    Instructions::dneg => operation(frame, &[Double], Some(Double), hierarchy)?,
+    Instructions::iinc { index, .. } => {
+      frame.load(*index as usize, &Integer, hierarchy)?;
+    }
+    Instructions::i2l => operation(frame, &[Integer], Some(Long), hierarchy)?,
+    Instructions::i2f => operation(frame, &[Integer], Some(Float), hierarchy)?,
+    Instructions::i2d => operation(frame, &[Integer], Some(Double), hierarchy)?,
+    Instructions::l2i => operation(frame, &[Long], Some(Integer), hierarchy)?,
+    Instructions::l2f => operation(frame, &[Long], Some(Float), hierarchy)?,
+    Instructions::l2d => operation(frame, &[Long], Some(Double), hierarchy)?,
+    Instructions::f2i => operation(frame, &[Float], Some(Integer), hierarchy)?,
+    Instructions::f2l => operation(frame, &[Float], Some(Long), hierarchy)?,
+    Instructions::f2d => operation(frame, &[Float], Some(Double), hierarchy)?,
+    Instructions::d2i => operation(frame, &[Double], Some(Integer), hierarchy)?,
+    Instructions::d2l => operation(frame, &[Double], Some(Long), hierarchy)?,
+    Instructions::d2f => operation(frame, &[Double], Some(Float), hierarchy)?,
+    Instructions::lcmp => operation(frame, &[Long, Long], Some(Integer), hierarchy)?,
+    Instructions::fcmpl | Instructions::fcmpg => {
+      operation(frame, &[Float, Float], Some(Integer), hierarchy)?
+    }
+    Instructions::dcmpl | Instructions::dcmpg => {
+      operation(frame, &[Double, Double], Some(Integer), hierarchy)?
+    }
+    Instructions::ifeq { .. }
+    | Instructions::ifne { .. }
+    | Instructions::iflt { .. }
+    | Instructions::ifge { .. }
+    | Instructions::ifgt { .. }
+    | Instructions::ifle { .. } => {
+      frame.pop(&Integer, hierarchy)?;
+    }
+    Instructions::if_icmpeq { .. }
+    | Instructions::if_icmpne { .. }
+    | Instructions::if_icmplt { .. }
+    | Instructions::if_icmpge { .. }
+    | Instructions::if_icmpgt { .. }
+    | Instructions::if_icmple { .. } => {
+      operation(frame, &[Integer, Integer], None, hierarchy)?;
+    }
+    Instructions::if_acmpeq { .. } | Instructions::if_acmpne { .. } => {
+      frame.pop_reference()?;
+      frame.pop_reference()?;
+    }
+    Instructions::ifnull { .. } | Instructions::ifnonnull { .. } => {
+      frame.pop_reference()?;
+    }
+    Instructions::goto { .. } | Instructions::goto_w { .. } => {}
+    Instructions::jsr { .. }
+    | Instructions::jsr_w { .. }
+    | Instructions::ret { .. }
+    | Instructions::wide1 { opcode: 169, .. } => {
+      return Err(
+        "Subroutines (jsr and ret) are only allowed before class file version 50".to_string()
+      );
+    }
+    Instructions::tableswitch { low, high, .. } => {
+      if low > high {
+        return Err(format!("tableswitch low {} is greater than high {}", low, high));
+      }
+      frame.pop(&Integer, hierarchy)?;
+    }
+    Instructions::lookupswith { pairs, .. } => {
+      if pairs.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err("lookupswitch keys are not sorted".to_string());
+      }
+      frame.pop(&Integer, hierarchy)?;
+    }
+    Instructions::ireturn => return_value(ctx, frame, &Integer)?,
+    Instructions::lreturn => return_value(ctx, frame, &Long)?,
+    Instructions::freturn => return_value(ctx, frame, &Float)?,
+    Instructions::dreturn => return_value(ctx, frame, &Double)?,
+    Instructions::areturn => {
+      let expected = ctx.descriptor.ret.as_ref().map(VerificationType::from);
+      match expected {
+        Some(expected) if expected.is_reference() => {
+          frame.pop(&expected, hierarchy)?;
+        }
+        _ => {
+          return Err("Bad return type: areturn in a method not returning a reference".to_string())
+        }
+      }
+    }
+    Instructions::r#return => {
+      if ctx.descriptor.ret.is_some() {
+        return Err("Bad return type: return in a method returning a value".to_string());
+      }
+      if ctx.method_name == "<init>" && frame.locals.contains(&VerificationType::UninitializedThis)
+      {
+        return Err("Constructor must call super() or this() before return".to_string());
+      }
+    }
+    Instructions::getstatic { fieldref } => {
+      frame.push(field_type(&fieldref.name_and_type)?);
+    }
+    Instructions::putstatic { fieldref } => {
+      frame.pop(&field_type(&fieldref.name_and_type)?, hierarchy)?;
+    }
+    Instructions::getfield { fieldref } => {
+      frame.pop(&VerificationType::object(&fieldref.class.name), hierarchy)?;
+      frame.push(field_type(&fieldref.name_and_type)?);
+    }
+    Instructions::putfield { fieldref } => {
+      frame.pop(&field_type(&fieldref.name_and_type)?, hierarchy)?;
+      // A constructor may set its own class's fields before calling super()
+      let own_field = fieldref.class == *ctx.this_class && ctx.method_name == "<init>";
+      let receiver = frame.pop_reference()?;
+      let class = VerificationType::object(&fieldref.class.name);
+      let uninitialized_this = own_field && receiver == VerificationType::UninitializedThis;
+      if !uninitialized_this && !hierarchy.is_assignable(&receiver, &class) {
+        return Err(format!(
+          "Bad type on operand stack: expected {} but found {}",
+          class.name(),
+          receiver.name()
+        ));
+      }
+    }
+    Instructions::invokevirtual { methodref } => {
+      invoke(ctx, frame, Invoke::Virtual, &methodref.class, &methodref.name_and_type)?
+    }
+    Instructions::invokespecial { methodref } => {
+      invoke(ctx, frame, Invoke::Special, &methodref.class, &methodref.name_and_type)?
+    }
+    Instructions::invokestatic { methodref } => {
+      invoke(ctx, frame, Invoke::Static, &methodref.class, &methodref.name_and_type)?
+    }
+    Instructions::invokeinterface { interfacemethodref, count } => {
+      let descriptor = method_descriptor(&interfacemethodref.name_and_type)?;
+      if *count as usize != descriptor.param_slots() + 1 {
+        return Err(format!("Inconsistent args count operand {} in invokeinterface", count));
+      }
+      let (class, name_and_type) = (&interfacemethodref.class, &interfacemethodref.name_and_type);
+      invoke(ctx, frame, Invoke::Interface, class, name_and_type)?
+    }
+    Instructions::invokedynamic { invokedynamic } => {
+      let class = Class { name: "java/lang/Object".to_string() };
+      invoke(ctx, frame, Invoke::Dynamic, &class, &invokedynamic.name_and_type)?
+    }
+    Instructions::new { class } => {
+      if class.name.starts_with('[') {
+        return Err(format!("Illegal use of new with array class {}", class.name));
+      }
+      frame.push(VerificationType::Uninitialized(index))
+    }
+    Instructions::newarray { atype } => {
+      let element = match atype {
+        4 => "Z",
+        5 => "C",
+        6 => "F",
+        7 => "D",
+        8 => "B",
+        9 => "S",
+        10 => "I",
+        11 => "J",
+        _ => return Err(format!("Illegal newarray type {}", atype))
+      };
+      frame.pop(&Integer, hierarchy)?;
+      frame.push(VerificationType::Object(format!("[{}", element)))
+    }
+    Instructions::anewarray { class } => {
+      let array = match class.name.starts_with('[') {
+        true => format!("[{}", class.name),
+        false => format!("[L{};", class.name)
+      };
+      if FieldType::parse(&array).is_err() {
+        return Err(format!("Illegal anewarray class {}", class.name));
+      }
+      frame.pop(&Integer, hierarchy)?;
+      frame.push(VerificationType::Object(array))
+    }
+    Instructions::arraylength => {
+      let array = frame.pop_reference()?;
+      if array.component().is_none() {
+        return Err(format!("Bad type on operand stack: {} is not an array", array.name()));
+      }
+      frame.push(Integer)
+    }
+    Instructions::athrow => {
+      frame.pop(&VerificationType::object("java/lang/Throwable"), hierarchy)?;
+    }
+    Instructions::checkcast { class } => {
+      frame.pop(&object, hierarchy)?;
+      frame.push(VerificationType::object(&class.name))
+    }
+    Instructions::instanceof { .. } => operation(frame, &[object], Some(Integer), hierarchy)?,
+    Instructions::monitorenter | Instructions::monitorexit => {
+      frame.pop(&object, hierarchy)?;
+    }
+    Instructions::wide1 { opcode, index_extension } => {
+      let index = *index_extension as usize;
+      match opcode {
+        21 => load(frame, index, Integer, hierarchy)?,
+        22 => load(frame, index, Long, hierarchy)?,
+        23 => load(frame, index, Float, hierarchy)?,
+        24 => load(frame, index, Double, hierarchy)?,
+        25 => {
+          let value = frame.load_reference(index)?;
+          frame.push(value)
+        }
+        54 => store(frame, index, Integer, hierarchy)?,
+        55 => store(frame, index, Long, hierarchy)?,
+        56 => store(frame, index, Float, hierarchy)?,
+        57 => store(frame, index, Double, hierarchy)?,
+        _ => astore(frame, index)?
+      }
+    }
+    Instructions::wide2 { index_extension, .. } => {
+      frame.load(*index_extension as usize, &Integer, hierarchy)?;
+    }
+    Instructions::multianewarray { class, dimensions } => {
+      let array_dimensions = class.name.bytes().take_while(|byte| *byte == b'[').count();
+      if *dimensions == 0 || array_dimensions < *dimensions as usize {
+        return Err(format!(
+          "Illegal dimension {} in multianewarray of {}",
+          dimensions, class.name
+        ));
+      }
+      for _ in 0..*dimensions {
+        frame.pop(&Integer, hierarchy)?;
+      }
+      frame.push(VerificationType::object(&class.name))
+    }
+  }
+
+  if frame.stack.len() > ctx.max_stack {
+    return Err("Operand stack overflow".to_string());
+  }
+  Ok(successors)
+}
+
+/// The type `ldc` pushes for `constant`, which must take `size` slots.
+fn loadable(constant: &ResolvedCpInfo, size: usize) -> Result<VerificationType, String> {
+  let value = match constant {
+    ResolvedCpInfo::Integer(_) => VerificationType::Integer,
+    ResolvedCpInfo::Float(_) => VerificationType::Float,
+    ResolvedCpInfo::Long(_) => VerificationType::Long,
+    ResolvedCpInfo::Double(_) => VerificationType::Double,
+    ResolvedCpInfo::String(_) => VerificationType::object("java/lang/String"),
+    ResolvedCpInfo::Class(_) => VerificationType::object("java/lang/Class"),
+    ResolvedCpInfo::MethodType(_) => VerificationType::object("java/lang/invoke/MethodType"),
+    ResolvedCpInfo::MethodHandle(_) => VerificationType::object("java/lang/invoke/MethodHandle"),
+    ResolvedCpInfo::Dynamic(dynamic) => field_type(&dynamic.name_and_type)?,
+    _ => return Err("Illegal type of constant for ldc".to_string())
+  };
+  match value.size() == size {
+    true => Ok(value),
+    false => Err(format!("Illegal {} constant for ldc{}", value.name(), ["", "2_w"][size - 1]))
+  }
+}
+
+fn field_type(name_and_type: &NameAndType) -> Result<VerificationType, String> {
+  match FieldType::parse(&name_and_type.descriptor) {
+    Ok(field_type) => Ok(VerificationType::from(&field_type)),
+    Err(error) => Err(error.to_string())
+  }
+}
+
+fn method_descriptor(name_and_type: &NameAndType) -> Result<MethodDescriptor, String> {
+  MethodDescriptor::parse(&name_and_type.descriptor).map_err(|error| error.to_string())
+}
+
+fn load(
+  frame: &mut Frame,
+  index: usize,
+  expected: VerificationType,
+  hierarchy: &ClassHierarchy
+) -> Result<(), String> {
+  let value = frame.load(index, &expected, hierarchy)?;
+  frame.push(value);
+  Ok(())
+}
+
+fn store(
+  frame: &mut Frame,
+  index: usize,
+  expected: VerificationType,
+  hierarchy: &ClassHierarchy
+) -> Result<(), String> {
+  let value = frame.pop(&expected, hierarchy)?;
+  frame.store(index, value)
+}
+
+/// Stores a reference, or the return address a `jsr` pushed.
+fn astore(frame: &mut Frame, index: usize) -> Result<(), String> {
+  let value = match frame.stack.last() {
+    Some(VerificationType::ReturnAddress(_)) => frame.pop_slots(1)?.remove(0),
+    _ => frame.pop_reference()?
+  };
+  frame.store(index, value)
+}
+
+/// Pops `operands`, starting from the top of the stack, and pushes `result`.
+fn operation(
+  frame: &mut Frame,
+  operands: &[VerificationType],
+  result: Option<VerificationType>,
+  hierarchy: &ClassHierarchy
+) -> Result<(), String> {
+  for operand in operands {
+    frame.pop(operand, hierarchy)?;
+  }
+  if let Some(result) = result {
+    frame.push(result);
+  }
+  Ok(())
+}
+
+/// Pops an index and an array, which must be one of `arrays` or `null`, and pushes an element.
+fn array_load(
+  frame: &mut Frame,
+  arrays: &[&str],
+  element: VerificationType,
+  hierarchy: &ClassHierarchy
+) -> Result<(), String> {
+  frame.pop(&VerificationType::Integer, hierarchy)?;
+  pop_array(frame, arrays)?;
+  frame.push(element);
+  Ok(())
+}
+
+fn array_store(
+  frame: &mut Frame,
+  arrays: &[&str],
+  element: VerificationType,
+  hierarchy: &ClassHierarchy
+) -> Result<(), String> {
+  frame.pop(&element, hierarchy)?;
+  frame.pop(&VerificationType::Integer, hierarchy)?;
+  pop_array(frame, arrays)
+}
+
+fn pop_array(frame: &mut Frame, arrays: &[&str]) -> Result<(), String> {
+  match frame.pop_reference()? {
+    VerificationType::Null => Ok(()),
+    VerificationType::Object(name) if arrays.contains(&name.as_str()) => Ok(()),
+    other => {
+      Err(format!("Bad type on operand stack: expected '{}' but found {}", arrays[0], other.name()))
+    }
+  }
+}
+
+/// Pops groups of slots, the first group from the top, then pushes them back in the order given
+/// by `order`, listed from the bottom. `dup_x1` is groups `[1, 1]`, pushed back as `[0, 1, 0]`.
+fn shuffle(frame: &mut Frame, groups: &[usize], order: &[usize]) -> Result<(), String> {
+  let popped: Vec<Vec<VerificationType>> =
+    groups.iter().map(|size| frame.pop_slots(*size)).collect::<Result<_, _>>()?;
+  for group in order.iter().rev() {
+    frame.stack.extend(popped[*group].iter().cloned());
+  }
+  Ok(())
+}
+
+fn return_value(
+  ctx: &Context,
+  frame: &mut Frame,
+  expected: &VerificationType
+) -> Result<(), String> {
+  match ctx.descriptor.ret.as_ref().map(VerificationType::from) {
+    Some(ret) if ret == *expected => {
+      frame.pop(expected, ctx.hierarchy)?;
+    }
+    _ => {
+      return Err(format!(
+        "Bad return type: returning {} from a method that doesn't return it",
+        expected.name()
+      ))
+    }
+  }
+  Ok(())
+}
+
+fn invoke(
+  ctx: &Context,
+  frame: &mut Frame,
+  kind: Invoke,
+  class: &Class,
+  name_and_type: &NameAndType
+) -> Result<(), String> {
+  let hierarchy = ctx.hierarchy;
+  let name = name_and_type.name.as_str();
+  let descriptor = method_descriptor(name_and_type)?;
+  if name == "<clinit>"
+    || (name == "<init>" && (kind != Invoke::Special || descriptor.ret.is_some()))
+  {
+    return Err(format!("Illegal call to {}", name));
+  }
+  for param in descriptor.params.iter().rev() {
+    frame.pop(&VerificationType::from(param), hierarchy)?;
+  }
+  let class_type = VerificationType::object(&class.name);
+  match kind {
+    Invoke::Static | Invoke::Dynamic => {}
+    Invoke::Special if name == "<init>" => {
+      let receiver = frame.pop_reference()?;
+      let initialized = match &receiver {
+        VerificationType::UninitializedThis
+          if class == ctx.this_class || Some(class) == ctx.super_class =>
+        {
+          VerificationType::object(&ctx.this_class.name)
+        }
+        VerificationType::Uninitialized(new) => match ctx.code.get(*new) {
+          Some(Instructions::new { class: created }) if created == class => class_type,
+          _ => {
+            return Err(format!(
+              "Bad <init> call: instruction {} doesn't create a {}",
+              new, class.name
+            ))
+          }
+        },
+        other => return Err(format!("Bad <init> call on {}", other.name()))
+      };
+      frame.replace(&receiver, &initialized);
+    }
+    // invokespecial can only reach methods of the current class and its supertypes, on itself
+    Invoke::Special => {
+      frame.pop(&VerificationType::object(&ctx.this_class.name), hierarchy)?;
+    }
+    Invoke::Virtual => {
+      frame.pop(&class_type, hierarchy)?;
+    }
+    // Interfaces are treated like java/lang/Object, so any initialized receiver passes
+    Invoke::Interface => {
+      frame.pop(&VerificationType::object("java/lang/Object"), hierarchy)?;
+    }
+  }
+  if let Some(ret) = &descriptor.ret {
+    frame.push(VerificationType::from(ret));
+  }
+  Ok(())
+}
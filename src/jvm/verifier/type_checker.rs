@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use super::{
+  class_hierarchy::ClassHierarchy,
+  frame::Frame,
+  instruction::{self, Context},
+  verification_type::VerificationType
+};
+use crate::parser::{
+  attribute_info::{attribute::exception::Exception, Attribute},
+  classfile::ClassFile,
+  method_info::MethodInfo
+};
+
+/// Checks a method against the frames its StackMapTable declares (JVMS §4.10.1).
+/// Each instruction must leave a frame that fits the declared frame of every instruction it can
+/// pass control to, so one pass over the code suffices.
+/// On failure, gives the index of the offending instruction and what is wrong with it.
+pub fn check(
+  class: &ClassFile,
+  name: &str,
+  method: &MethodInfo,
+  hierarchy: &ClassHierarchy
+) -> Result<(), (usize, String)> {
+  // Abstract and native methods have no code to check
+  let Some(initial) = method.initial_frame(&class.this_class, name) else {
+    return Ok(());
+  };
+  let Some(Attribute::Code { max_stack, max_locals, code, exception_table, .. }) =
+    method.attributes.iter().find(|attribute| matches!(attribute, Attribute::Code { .. }))
+  else {
+    return Ok(());
+  };
+  let ctx = Context {
+    this_class: &class.this_class,
+    super_class: class.super_class.as_ref(),
+    method_name: name,
+    descriptor: &method.descriptor,
+    code,
+    max_stack: *max_stack as usize,
+    hierarchy
+  };
+  let initial = Frame::from(&initial);
+  if initial.locals.len() > *max_locals as usize {
+    return Err((0, "Arguments can't fit into locals".to_string()));
+  }
+  let frames: BTreeMap<usize, Frame> = method
+    .frames(&class.this_class, name)
+    .map_err(|error| (0, error.to_string()))?
+    .iter()
+    .map(|(index, frame)| (*index, Frame::from(frame)))
+    .collect();
+  for (index, frame) in &frames {
+    if frame.locals.len() > *max_locals as usize || frame.stack.len() > *max_stack as usize {
+      return Err((
+        *index,
+        "StackMapTable frame is larger than max_locals or max_stack".to_string()
+      ));
+    }
+  }
+  check_handlers(exception_table, code.len(), hierarchy).map_err(|message| (0, message))?;
+
+  let mut current = Some(initial);
+  for index in 0..code.len() {
+    let frame = match (frames.get(&index), current) {
+      (Some(declared), Some(current)) => {
+        if !current.is_assignable(declared, hierarchy) {
+          return Err((index, "Frame doesn't match the StackMapTable frame here".to_string()));
+        }
+        declared.clone()
+      }
+      (Some(declared), None) => declared.clone(),
+      (None, Some(current)) => current,
+      (None, None) => return Err((index, "Expecting a StackMapTable frame here".to_string()))
+    };
+
+    for handler in exception_table {
+      if !(handler.start_pc as usize..handler.end_pc as usize).contains(&index) {
+        continue;
+      }
+      let catch_type =
+        handler.catch_type.as_ref().map_or("java/lang/Throwable", |class| &class.name);
+      let thrown =
+        Frame { locals: frame.locals.clone(), stack: vec![VerificationType::object(catch_type)] };
+      match frames.get(&(handler.handler_pc as usize)) {
+        Some(declared) if thrown.is_assignable(declared, hierarchy) => {}
+        Some(_) => {
+          return Err((index, "Frame doesn't match the exception handler's frame".to_string()))
+        }
+        None => {
+          return Err((
+            index,
+            "Expecting a StackMapTable frame at the exception handler".to_string()
+          ))
+        }
+      }
+    }
+
+    let mut next = frame;
+    let successors =
+      instruction::execute(&ctx, index, &mut next).map_err(|message| (index, message))?;
+    for target in successors.targets {
+      match frames.get(&target) {
+        Some(declared) if next.is_assignable(declared, hierarchy) => {}
+        Some(_) => {
+          return Err((index, format!("Frame doesn't match the frame at branch target {}", target)))
+        }
+        None => {
+          return Err((
+            index,
+            format!("Expecting a StackMapTable frame at branch target {}", target)
+          ))
+        }
+      }
+    }
+    current = successors.falls_through.then_some(next);
+  }
+  match current {
+    Some(_) => Err((code.len().saturating_sub(1), "Falling off the end of the code".to_string())),
+    None => Ok(())
+  }
+}
+
+pub fn check_handlers(
+  exception_table: &[Exception],
+  code_length: usize,
+  hierarchy: &ClassHierarchy
+) -> Result<(), String> {
+  let throwable = VerificationType::object("java/lang/Throwable");
+  for handler in exception_table {
+    if handler.start_pc >= handler.end_pc || handler.handler_pc as usize >= code_length {
+      return Err("Illegal exception table range".to_string());
+    }
+    if let Some(class) = &handler.catch_type {
+      if !hierarchy.is_assignable(&VerificationType::object(&class.name), &throwable) {
+        return Err(format!("Catch type {} is not a subclass of Throwable", class.name));
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    assembler::assemble,
+    jvm::{linkage_error::LinkageError, verifier::verify}
+  };
+
+  /// Verifies a version 52 class whose one method, `static f<descriptor>`, has the code `body`,
+  /// giving back the instruction and message of any error.
+  fn check(descriptor: &str, body: &str) -> Result<(), (usize, String)> {
+    let source = format!(
+      ".version 52 0\n.class super Check\n.super java/lang/Object\n.method static f{}\n{}\n.end method",
+      descriptor, body
+    );
+    match verify(&assemble(&source).unwrap()) {
+      Ok(()) => Ok(()),
+      Err(LinkageError::VerifyError { index, message, .. }) => Err((index, message)),
+      Err(error) => panic!("{}", error)
+    }
+  }
+
+  #[test]
+  fn rejects_bad_return_type() {
+    let body = |value: &str, returns: &str| {
+      format!(".limit stack 1\n.limit locals 0\n{}\n{}", value, returns)
+    };
+    assert_eq!(check("()I", &body("iconst_0", "ireturn")), Ok(()));
+    let error = check("()I", &body("aconst_null", "areturn")).unwrap_err();
+    assert_eq!(error, (1, "Bad return type: areturn in a method not returning a reference".into()));
+  }
+
+  #[test]
+  fn rejects_stack_overflow() {
+    let body = |max_stack: u16| {
+      format!(".limit stack {}\n.limit locals 0\niconst_1\niconst_1\niadd\nireturn", max_stack)
+    };
+    assert_eq!(check("()I", &body(2)), Ok(()));
+    assert_eq!(check("()I", &body(1)), Err((1, "Operand stack overflow".into())));
+  }
+
+  #[test]
+  fn rejects_missing_frame_at_branch_target() {
+    let body = |frame: &str| {
+      format!(
+        ".limit stack 1\n.limit locals 1\niload_0\nifeq Zero\niconst_1\nireturn\nZero:\n{}\niconst_0\nireturn",
+        frame
+      )
+    };
+    assert_eq!(check("(I)I", &body(".stack same")), Ok(()));
+    let error = check("(I)I", &body("")).unwrap_err();
+    assert_eq!(error, (1, "Expecting a StackMapTable frame at branch target 4".into()));
+  }
+
+  #[test]
+  fn rejects_popping_half_a_long() {
+    let body = |pops: &str| format!(".limit stack 2\n.limit locals 0\nlconst_0\n{}\nreturn", pops);
+    assert_eq!(check("()V", &body("pop2")), Ok(()));
+    let error = check("()V", &body("pop\npop")).unwrap_err();
+    assert_eq!(error, (1, "Bad type on operand stack: split a long or double".into()));
+  }
+}
@@ -0,0 +1,356 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::{
+  class_hierarchy::ClassHierarchy,
+  frame::Frame,
+  instruction::{self, Context},
+  type_checker::check_handlers,
+  verification_type::VerificationType
+};
+use crate::parser::{
+  attribute_info::{
+    attribute::exception::Exception,
+    code::code_generator::Instructions,
+    Attribute
+  },
+  classfile::ClassFile,
+  method_info::MethodInfo
+};
+
+/// A subroutine, entered by `jsr` and left by `ret`.
+struct Subroutine {
+  /// The indices of the `jsr` instructions calling it.
+  callers: Vec<usize>,
+  /// The local variables it or the subroutines it calls can store to. After `ret` these take the
+  /// subroutine's types, and the rest keep the caller's.
+  written: HashSet<usize>
+}
+
+/// Infers the types of a method's frames by data-flow analysis, for class files that predate the
+/// StackMapTable (JVMS §4.10.2).
+/// Frames are merged where control flow joins until nothing changes. A subroutine is verified once
+/// for all its callers, and each caller carries on with its own types for the locals the
+/// subroutine leaves alone.
+/// On failure, gives the index of the offending instruction and what is wrong with it.
+pub fn check(
+  class: &ClassFile,
+  name: &str,
+  method: &MethodInfo,
+  hierarchy: &ClassHierarchy
+) -> Result<(), (usize, String)> {
+  infer(class, name, method, hierarchy).map(|_| ())
+}
+
+/// The frame each instruction is entered with, as `check` infers them, or `None` for unreachable
+/// instructions.
+pub fn infer(
+  class: &ClassFile,
+  name: &str,
+  method: &MethodInfo,
+  hierarchy: &ClassHierarchy
+) -> Result<Vec<Option<Frame>>, (usize, String)> {
+  // Abstract and native methods have no code to check
+  let Some(initial) = method.initial_frame(&class.this_class, name) else {
+    return Ok(Vec::new());
+  };
+  let Some(Attribute::Code { max_stack, max_locals, code, exception_table, .. }) =
+    method.attributes.iter().find(|attribute| matches!(attribute, Attribute::Code { .. }))
+  else {
+    return Ok(Vec::new());
+  };
+  let ctx = Context {
+    this_class: &class.this_class,
+    super_class: class.super_class.as_ref(),
+    method_name: name,
+    descriptor: &method.descriptor,
+    code,
+    max_stack: *max_stack as usize,
+    hierarchy
+  };
+  let initial = Frame::from(&initial);
+  if initial.locals.len() > *max_locals as usize {
+    return Err((0, "Arguments can't fit into locals".to_string()));
+  }
+  if code.is_empty() {
+    return Err((0, "Code is empty".to_string()));
+  }
+  check_handlers(exception_table, code.len(), hierarchy).map_err(|message| (0, message))?;
+  let subroutines = subroutines(code, exception_table)?;
+
+  let mut inference = Inference {
+    hierarchy,
+    code_length: code.len(),
+    frames: vec![None; code.len()],
+    returns: HashMap::new(),
+    changed: BTreeSet::new()
+  };
+  inference.merge(0, 0, &initial)?;
+  while let Some(index) = inference.changed.pop_first() {
+    let Some(frame) = inference.frames[index].clone() else { continue };
+    let mut next = frame.clone();
+    match &code[index] {
+      Instructions::jsr { .. } | Instructions::jsr_w { .. } => {
+        let entry =
+          instruction::successors(code, index).map_err(|message| (index, message))?.targets[0];
+        next.push(VerificationType::ReturnAddress(entry));
+        if next.stack.len() > ctx.max_stack {
+          return Err((index, "Operand stack overflow".to_string()));
+        }
+        inference.merge(index, entry, &next)?;
+        if let Some(returned) = inference.returns.get(&entry).cloned() {
+          inference.resume(index, &frame, &returned, &subroutines[&entry])?;
+        }
+      }
+      Instructions::ret { .. } | Instructions::wide1 { opcode: 169, .. } => {
+        let local = match code[index] {
+          Instructions::ret { index } => index as usize,
+          Instructions::wide1 { index_extension, .. } => index_extension as usize,
+          _ => unreachable!()
+        };
+        let entry = match frame.locals.get(local) {
+          Some(VerificationType::ReturnAddress(entry)) => *entry,
+          _ => return Err((index, format!("Bad return address in local {}", local)))
+        };
+        let returned = match inference.returns.get(&entry) {
+          Some(returned) => frame.merge(returned, hierarchy).map_err(|message| (index, message))?,
+          None => frame.clone()
+        };
+        if inference.returns.get(&entry) != Some(&returned) {
+          inference.returns.insert(entry, returned.clone());
+          let subroutine = &subroutines[&entry];
+          for caller in &subroutine.callers {
+            if let Some(calling) = inference.frames[*caller].clone() {
+              inference.resume(*caller, &calling, &returned, subroutine)?;
+            }
+          }
+        }
+      }
+      _ => {
+        let successors =
+          instruction::execute(&ctx, index, &mut next).map_err(|message| (index, message))?;
+        for target in successors.targets {
+          inference.merge(index, target, &next)?;
+        }
+        if successors.falls_through {
+          inference.merge(index, index + 1, &next)?;
+        }
+      }
+    }
+
+    // A handler can be entered before or after the instruction has changed the locals
+    for handler in exception_table {
+      if !(handler.start_pc as usize..handler.end_pc as usize).contains(&index) {
+        continue;
+      }
+      let catch_type =
+        handler.catch_type.as_ref().map_or("java/lang/Throwable", |class| &class.name);
+      for locals in [&frame.locals, &next.locals] {
+        let thrown =
+          Frame { locals: locals.clone(), stack: vec![VerificationType::object(catch_type)] };
+        inference.merge(index, handler.handler_pc as usize, &thrown)?;
+      }
+    }
+  }
+  Ok(inference.frames)
+}
+
+/// The frames inferred so far, and the instructions whose frames changed since they were last
+/// looked at.
+struct Inference<'a> {
+  hierarchy: &'a ClassHierarchy<'a>,
+  code_length: usize,
+  frames: Vec<Option<Frame>>,
+  /// The frames `ret` leaves behind, by the index their subroutine starts at.
+  returns: HashMap<usize, Frame>,
+  changed: BTreeSet<usize>
+}
+
+impl Inference<'_> {
+  /// Merges `frame`, coming from the instruction at `from`, into the frame of instruction `to`.
+  fn merge(&mut self, from: usize, to: usize, frame: &Frame) -> Result<(), (usize, String)> {
+    if to >= self.code_length {
+      return Err((from, "Falling off the end of the code".to_string()));
+    }
+    let merged = match &self.frames[to] {
+      Some(existing) => existing.merge(frame, self.hierarchy).map_err(|message| (from, message))?,
+      None => frame.clone()
+    };
+    if self.frames[to].as_ref() != Some(&merged) {
+      self.frames[to] = Some(merged);
+      self.changed.insert(to);
+    }
+    Ok(())
+  }
+
+  /// Carries on after the `jsr` at `caller`, which was entered with `calling`, now that its
+  /// subroutine has returned with `returned`.
+  fn resume(
+    &mut self,
+    caller: usize,
+    calling: &Frame,
+    returned: &Frame,
+    subroutine: &Subroutine
+  ) -> Result<(), (usize, String)> {
+    let locals = calling
+      .locals
+      .iter()
+      .zip(&returned.locals)
+      .enumerate()
+      .map(|(index, (calling, returned))| match subroutine.written.contains(&index) {
+        true => returned.clone(),
+        false => calling.clone()
+      })
+      .collect();
+    let frame = Frame { locals, stack: returned.stack.clone() };
+    self.merge(caller, caller + 1, &frame)
+  }
+}
+
+/// Finds the subroutines the `jsr` instructions call, with the locals each one writes.
+/// Subroutines may call others, but not themselves, directly or not.
+fn subroutines(
+  code: &[Instructions],
+  exception_table: &[Exception]
+) -> Result<HashMap<usize, Subroutine>, (usize, String)> {
+  let mut subroutines: HashMap<usize, Subroutine> = HashMap::new();
+  for index in 0..code.len() {
+    if let Instructions::jsr { .. } | Instructions::jsr_w { .. } = code[index] {
+      let entry =
+        instruction::successors(code, index).map_err(|message| (index, message))?.targets[0];
+      let subroutine = subroutines
+        .entry(entry)
+        .or_insert_with(|| Subroutine { callers: Vec::new(), written: HashSet::new() });
+      subroutine.callers.push(index);
+    }
+  }
+
+  // The instructions reachable from each entry without returning, and the subroutines they call
+  let mut calls: HashMap<usize, Vec<usize>> = HashMap::new();
+  for (entry, subroutine) in subroutines.iter_mut() {
+    let mut reached = HashSet::from([*entry]);
+    let mut pending = vec![*entry];
+    while let Some(index) = pending.pop() {
+      let successors = instruction::successors(code, index).map_err(|message| (index, message))?;
+      let mut next = Vec::new();
+      match code[index] {
+        Instructions::jsr { .. } | Instructions::jsr_w { .. } => {
+          calls.entry(*entry).or_default().push(successors.targets[0]);
+          next.push(index + 1);
+        }
+        _ => {
+          next.extend(successors.targets);
+          if successors.falls_through {
+            next.push(index + 1);
+          }
+        }
+      }
+      next.extend(
+        exception_table
+          .iter()
+          .filter(|handler| (handler.start_pc as usize..handler.end_pc as usize).contains(&index))
+          .map(|handler| handler.handler_pc as usize)
+      );
+      if let Some((local, size)) = written(&code[index]) {
+        // Overwriting the second half of a long or double invalidates the first
+        subroutine.written.extend(local.saturating_sub(1)..local + size);
+      }
+      for next in next {
+        if next < code.len() && reached.insert(next) {
+          pending.push(next);
+        }
+      }
+    }
+  }
+
+  // A subroutine also writes whatever the subroutines it calls write
+  let entries: Vec<usize> = subroutines.keys().copied().collect();
+  for entry in &entries {
+    let mut visited = HashSet::new();
+    let mut pending = calls.get(entry).cloned().unwrap_or_default();
+    while let Some(called) = pending.pop() {
+      if called == *entry {
+        let caller = subroutines[entry].callers[0];
+        return Err((caller, format!("Recursive call to the subroutine at instruction {}", entry)));
+      }
+      if visited.insert(called) {
+        pending.extend(calls.get(&called).into_iter().flatten());
+      }
+    }
+    let written: HashSet<usize> =
+      visited.iter().flat_map(|called| subroutines[called].written.clone()).collect();
+    subroutines.get_mut(entry).unwrap().written.extend(written);
+  }
+  Ok(subroutines)
+}
+
+/// The local variable an instruction stores to, and how many slots the value takes.
+fn written(instruction: &Instructions) -> Option<(usize, usize)> {
+  match instruction {
+    Instructions::istore { index }
+    | Instructions::fstore { index }
+    | Instructions::astore { index } => Some((*index as usize, 1)),
+    Instructions::lstore { index } | Instructions::dstore { index } => Some((*index as usize, 2)),
+    Instructions::wide1 { opcode: 54 | 56 | 58, index_extension } => {
+      Some((*index_extension as usize, 1))
+    }
+    Instructions::wide1 { opcode: 55 | 57, index_extension } => {
+      Some((*index_extension as usize, 2))
+    }
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    assembler::assemble,
+    jvm::{linkage_error::LinkageError, verifier::verify}
+  };
+
+  /// Verifies a version 49 class whose one method, `static f<descriptor>`, has the code `body`,
+  /// giving back the instruction and message of any error.
+  fn check(descriptor: &str, body: &str) -> Result<(), (usize, String)> {
+    let source = format!(
+      ".version 49 0\n.class super Check\n.super java/lang/Object\n.method static f{}\n{}\n.end method",
+      descriptor, body
+    );
+    match verify(&assemble(&source).unwrap()) {
+      Ok(()) => Ok(()),
+      Err(LinkageError::VerifyError { index, message, .. }) => Err((index, message)),
+      Err(error) => panic!("{}", error)
+    }
+  }
+
+  /// Calls the subroutine `S` with an int and then an object in local 2, and returns the
+  /// object. The subroutine runs `uses` before it returns.
+  fn merged(uses: &str) -> String {
+    format!(
+      ".limit stack 1\n.limit locals 3\niconst_0\nistore_2\njsr S\naload_0\nastore_2\njsr S\naload_2\nareturn\n\
+       S:\nastore_1\n{}\nret 1",
+      uses
+    )
+  }
+
+  #[test]
+  fn subroutine_keeps_caller_locals() {
+    let descriptor = "(Ljava/lang/Object;)Ljava/lang/Object;";
+    assert_eq!(check(descriptor, &merged("")), Ok(()));
+  }
+
+  #[test]
+  fn rejects_merged_local_in_subroutine() {
+    let descriptor = "(Ljava/lang/Object;)Ljava/lang/Object;";
+    let error = check(descriptor, &merged("iload_2\npop")).unwrap_err();
+    assert_eq!(
+      error,
+      (9, "Bad local variable type: expected integer in local 2 but found top".into())
+    );
+  }
+
+  #[test]
+  fn rejects_recursive_subroutine() {
+    let body = ".limit stack 1\n.limit locals 1\njsr S\nreturn\nS:\nastore_0\njsr S\nret 0";
+    let error = check("()V", body).unwrap_err();
+    assert_eq!(error, (0, "Recursive call to the subroutine at instruction 2".into()));
+  }
+}
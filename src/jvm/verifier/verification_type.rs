@@ -0,0 +1,131 @@
+use crate::parser::{
+  attribute_info::attribute::stack_map_frame::verification_type_info::VerificationTypeInfo,
+  cp_info_resolved::Class,
+  descriptor::FieldType
+};
+
+/// A type in the verifier's frames (JVMS §4.10.1.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationType {
+  Top,
+  Integer,
+  Float,
+  Long,
+  Double,
+  Null,
+  UninitializedThis,
+  /// An object made by the `new` instruction at this index, whose constructor hasn't run yet.
+  Uninitialized(usize),
+  /// A class, interface or array type, named as in the constant pool.
+  Object(String),
+  /// Where to go back to from the subroutine starting at this index, as pushed by `jsr`.
+  /// Only the type inference verifier meets these.
+  ReturnAddress(usize)
+}
+
+impl VerificationType {
+  pub fn object(name: &str) -> Self { VerificationType::Object(name.to_string()) }
+
+  /// The type a value of `field_type` has once loaded, with the small integral types widened to
+  /// `Integer`.
+  pub fn from(field_type: &FieldType) -> Self {
+    match field_type {
+      FieldType::Byte
+      | FieldType::Char
+      | FieldType::Int
+      | FieldType::Short
+      | FieldType::Boolean => VerificationType::Integer,
+      FieldType::Float => VerificationType::Float,
+      FieldType::Long => VerificationType::Long,
+      FieldType::Double => VerificationType::Double,
+      FieldType::Object(name) => VerificationType::object(name),
+      array => VerificationType::Object(array.to_string())
+    }
+  }
+
+  pub fn from_info(info: &VerificationTypeInfo) -> Self {
+    match info {
+      VerificationTypeInfo::TopVariable => VerificationType::Top,
+      VerificationTypeInfo::IntegerVariable => VerificationType::Integer,
+      VerificationTypeInfo::FloatVariable => VerificationType::Float,
+      VerificationTypeInfo::LongVariable => VerificationType::Long,
+      VerificationTypeInfo::DoubleVariable => VerificationType::Double,
+      VerificationTypeInfo::NullVariable => VerificationType::Null,
+      VerificationTypeInfo::UninitializedThisVariable => VerificationType::UninitializedThis,
+      VerificationTypeInfo::ObjectVariable { class } => VerificationType::object(&class.name),
+      VerificationTypeInfo::UninitializedVariable { offset } => {
+        VerificationType::Uninitialized(*offset as usize)
+      }
+    }
+  }
+
+  /// The type as a StackMapTable lists it. A return address has no such form, and is left as
+  /// `TopVariable`, which a frame can't use.
+  pub fn to_info(&self) -> VerificationTypeInfo {
+    match self {
+      VerificationType::Top | VerificationType::ReturnAddress(_) => {
+        VerificationTypeInfo::TopVariable
+      }
+      VerificationType::Integer => VerificationTypeInfo::IntegerVariable,
+      VerificationType::Float => VerificationTypeInfo::FloatVariable,
+      VerificationType::Long => VerificationTypeInfo::LongVariable,
+      VerificationType::Double => VerificationTypeInfo::DoubleVariable,
+      VerificationType::Null => VerificationTypeInfo::NullVariable,
+      VerificationType::UninitializedThis => VerificationTypeInfo::UninitializedThisVariable,
+      VerificationType::Uninitialized(index) => {
+        VerificationTypeInfo::UninitializedVariable { offset: *index as u16 }
+      }
+      VerificationType::Object(name) => {
+        VerificationTypeInfo::ObjectVariable { class: Class { name: name.clone() } }
+      }
+    }
+  }
+
+  /// The number of stack or local variable slots the type takes.
+  pub fn size(&self) -> usize {
+    match self {
+      VerificationType::Long | VerificationType::Double => 2,
+      _ => 1
+    }
+  }
+
+  /// Whether the type is an object reference, initialized or not.
+  pub fn is_reference(&self) -> bool {
+    matches!(
+      self,
+      VerificationType::Null
+        | VerificationType::UninitializedThis
+        | VerificationType::Uninitialized(_)
+        | VerificationType::Object(_)
+    )
+  }
+
+  /// The type of the elements of an array type, or `None` if this isn't an array.
+  /// The elements of `null` are `null`, since loading from it throws before anything is pushed.
+  pub fn component(&self) -> Option<VerificationType> {
+    match self {
+      VerificationType::Null => Some(VerificationType::Null),
+      VerificationType::Object(name) => {
+        let component = FieldType::parse(name.strip_prefix('[')?).ok()?;
+        Some(VerificationType::from(&component))
+      }
+      _ => None
+    }
+  }
+
+  /// The name the type goes by in verification errors.
+  pub fn name(&self) -> String {
+    match self {
+      VerificationType::Top => "top".to_string(),
+      VerificationType::Integer => "integer".to_string(),
+      VerificationType::Float => "float".to_string(),
+      VerificationType::Long => "long".to_string(),
+      VerificationType::Double => "double".to_string(),
+      VerificationType::Null => "null".to_string(),
+      VerificationType::UninitializedThis => "uninitializedThis".to_string(),
+      VerificationType::Uninitialized(index) => format!("uninitialized({})", index),
+      VerificationType::Object(name) => format!("'{}'", name),
+      VerificationType::ReturnAddress(_) => "returnAddress".to_string()
+    }
+  }
+}
@@ -0,0 +1,5 @@
+pub mod assembler;
+pub mod builder;
+pub mod disassembler;
+pub mod jvm;
+pub mod parser;
@@ -1,20 +1,75 @@
-#![feature(let_else)]
+use std::{env, fs, path::Path, process};
 
-use std::env;
-
-use crate::jvm::JVM;
-
-mod jvm;
-mod parser;
+use java_rs::{
+  assembler::assemble,
+  disassembler::{disassemble, jasmin},
+  jvm::JVM,
+  parser::classfile::ClassFile
+};
 
 fn main() {
   let path = env::args().skip(1).next().expect("Expected File Name");
+  if path == "disasm" {
+    return disasm(env::args().skip(2));
+  }
+  if path == "asm" {
+    return asm(env::args().skip(2));
+  }
 
-  // let (name, cf, depends) = ClassFile::read(path.clone());
+  // let (cf, depends) = ClassFile::parse(&fs::read(&path).unwrap()).unwrap();
   // println!("{:#?}\ndepends:{:?}\n", cf, depends);
 
-  let jvm = JVM::from_path(path);
+  let jvm = match JVM::from_path(path) {
+    Ok(jvm) => jvm,
+    Err(error) => {
+      eprintln!("Exception in thread \"main\" {}", error);
+      process::exit(1)
+    }
+  };
   println!("{:#?}", jvm);
 
   jvm.start()
 }
+
+/// Prints each class file the way `javap -c -v -p` would, or after `--jasmin` as assembly that the
+/// `asm` command reads.
+fn disasm(paths: impl Iterator<Item = String>) {
+  let mut paths = paths.peekable();
+  let as_jasmin = paths.next_if(|path| path == "--jasmin").is_some();
+  for path in paths {
+    let text = fs::read(&path).map_err(|error| error.to_string()).and_then(|bytes| {
+      match as_jasmin {
+        true => ClassFile::parse(&bytes).and_then(|(class, _)| jasmin::disassemble(&class)),
+        false => disassemble(&bytes)
+      }
+      .map_err(|error| error.to_string())
+    });
+    match text {
+      Ok(text) => print!("{}", text),
+      Err(error) => {
+        eprintln!("{}: {}", path, error);
+        process::exit(1)
+      }
+    }
+  }
+}
+
+/// Assembles each file, writing the class to where its name puts it under the current directory,
+/// such as `com/example/Main.class`.
+fn asm(paths: impl Iterator<Item = String>) {
+  for path in paths {
+    let written = fs::read_to_string(&path).map_err(|error| error.to_string()).and_then(|source| {
+      let class = assemble(&source).map_err(|error| error.to_string())?;
+      let bytes = class.write().map_err(|error| error.to_string())?;
+      let output = format!("{}.class", class.this_class.name);
+      if let Some(directory) = Path::new(&output).parent() {
+        fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+      }
+      fs::write(&output, bytes).map_err(|error| error.to_string())
+    });
+    if let Err(error) = written {
+      eprintln!("{}: {}", path, error);
+      process::exit(1)
+    }
+  }
+}
@@ -1,7 +1,14 @@
 pub mod attribute_info;
+pub mod class_format_error;
 pub mod classfile;
+pub mod constant_pool;
 pub mod cp_info;
 pub mod cp_info_resolved;
+pub mod descriptor;
 pub mod field_info;
+pub mod format_check;
 pub mod method_info;
+pub mod modified_utf8;
+pub mod signature;
 mod stream_reader;
+mod stream_writer;
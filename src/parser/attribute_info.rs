@@ -1,22 +1,37 @@
+use std::{any::Any, rc::Rc};
+
 use self::{
   attribute::{
     annotation::Annotation,
     bootstrap_method::BootstrapMethod,
     classes::Classes,
+    constant::Constant,
     element_value::ElementValue,
     exception::Exception,
     line_number::LineNumber,
     local_variable::LocalVariable,
     local_variable_type::LocalVariableType,
+    method_parameter::MethodParameter,
+    module::{Exports, ModuleFlags, Opens, Provides, Requires},
     parameter_annotation::ParameterAnnotation,
-    stack_map_frame::StackMapFrame
+    record_component::RecordComponent,
+    stack_map_frame::StackMapFrame,
+    type_annotation::TypeAnnotation
   },
-  code::code_generator::Instructions
+  attribute_registry::{AttributeEncoder, AttributeRegistry, CustomAttribute},
+  code::{code_generator::Instructions, offset_map::OffsetMap}
+};
+use super::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::{Class, NameAndType},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
 };
-use super::{cp_info_resolved::ResolvedCpInfo, stream_reader::StreamReader};
 
 pub mod annotation_default;
 pub mod attribute;
+pub mod attribute_registry;
 pub mod bootstrap_methods;
 pub mod code;
 pub mod constant_value;
@@ -26,38 +41,50 @@ pub mod inner_classes;
 pub mod line_number_table;
 pub mod local_variable_table;
 pub mod local_variable_type_table;
+pub mod method_parameters;
+pub mod module;
+pub mod module_main_class;
+pub mod module_packages;
+pub mod nest_host;
+pub mod nest_members;
+pub mod permitted_subclasses;
+pub mod record;
 pub mod runtime_annotations;
 pub mod runtime_parameter_annotations;
+pub mod runtime_type_annotations;
 pub mod signature;
 pub mod source_debug_extensions;
 pub mod source_file;
 pub mod stack_map_table;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Attribute {
   ConstantValue {
-    // TODO!
-    constantvalue_index: u16
+    value: Constant
   },
   Code {
     max_stack: u16,
     max_locals: u16,
     code: Vec<Instructions>,
     exception_table: Vec<Exception>,
-    attributes: Vec<Attribute>
+    attributes: Vec<Attribute>,
+    /// Positions in the tables above, and in the nested attributes, are instruction indices.
+    /// This maps them back to byte offsets.
+    offsets: OffsetMap
   },
   StackMapTable {
     entries: Vec<StackMapFrame>
   },
   Exceptions {
-    exception_index_table: Vec<u16>
+    exceptions: Vec<Class>
   },
   InnerClasses {
     classes: Vec<Classes>
   },
   EnclosingMethod {
-    class_index: u16,
-    method_index: u16
+    class: Class,
+    /// `None` unless the class is enclosed by a method or constructor.
+    method: Option<NameAndType>
   },
   Synthetic,
   Signature {
@@ -91,45 +118,284 @@ pub enum Attribute {
   RuntimeInvisibleParameterAnnotations {
     parameter_annotations: Vec<ParameterAnnotation>
   },
+  RuntimeVisibleTypeAnnotations {
+    annotations: Vec<TypeAnnotation>
+  },
+  RuntimeInvisibleTypeAnnotations {
+    annotations: Vec<TypeAnnotation>
+  },
   AnnotationDefault {
-    attribute_name: String,
-    attribute_length: u32,
     default_value: ElementValue
   },
   BootstrapMethods {
     bootstrap_methods: Vec<BootstrapMethod>
+  },
+  MethodParameters {
+    parameters: Vec<MethodParameter>
+  },
+  Module {
+    module_name: String,
+    module_flags: ModuleFlags,
+    module_version: Option<String>,
+    requires: Vec<Requires>,
+    exports: Vec<Exports>,
+    opens: Vec<Opens>,
+    uses: Vec<Class>,
+    provides: Vec<Provides>
+  },
+  ModulePackages {
+    packages: Vec<String>
+  },
+  ModuleMainClass {
+    main_class: Class
+  },
+  NestHost {
+    host_class: Class
+  },
+  NestMembers {
+    classes: Vec<Class>
+  },
+  Record {
+    components: Vec<RecordComponent>
+  },
+  PermittedSubclasses {
+    classes: Vec<Class>
+  },
+  /// An attribute decoded by a decoder from the `AttributeRegistry`, with the `bytes` it was read
+  /// from and the encoder registered with the decoder.
+  Custom {
+    name: String,
+    value: Rc<dyn CustomAttribute>,
+    bytes: Vec<u8>,
+    encoder: Option<AttributeEncoder>
+  },
+  /// An attribute nobody knows how to decode, which the JVM is required to ignore.
+  Unknown {
+    name: String,
+    bytes: Vec<u8>
   }
 }
 
 impl Attribute {
-  pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> Attribute {
-    let attribute_name_index = sr.get_u16();
-    let attribute_length = sr.get_u32();
-    match &constant_pool[attribute_name_index as usize - 1] {
-      ResolvedCpInfo::Utf8(string) => match string.as_str() {
-        "ConstantValue" => constant_value::read(sr),
-        "Code" => code::read(sr, constant_pool),
-        "StackMapTable" => stack_map_table::read(sr),
-        "Exceptions" => exceptions::read(sr),
-        "InnerClasses" => inner_classes::read(sr),
-        "EnclosingMethod" => enclosing_method::read(sr),
-        "Synthetic" => Attribute::Synthetic,
-        "Signature" => signature::read(sr, constant_pool),
-        "SourceFile" => source_file::read(sr, constant_pool),
-        "SourceDebugExtension" => source_debug_extensions::read(sr, attribute_length),
-        "LineNumberTable" => line_number_table::read(sr),
-        "LocalVariableTable" => local_variable_table::read(sr),
-        "LocalVariableTypeTable" => local_variable_type_table::read(sr),
-        "Deprecated" => Attribute::Deprecated,
-        "RuntimeVisibleAnnotations" => runtime_annotations::read::<true>(sr),
-        "RuntimeInvisibleAnnotations" => runtime_annotations::read::<false>(sr),
-        "RuntimeVisibleParameterAnnotations" => runtime_parameter_annotations::read::<true>(sr),
-        "RuntimeInvisibleParameterAnnotations" => runtime_parameter_annotations::read::<false>(sr),
-        "AnnotationDefault" => annotation_default::read(sr, constant_pool),
-        "BootstrapMethods" => bootstrap_methods::read(sr),
-        _ => todo!("No support for custom attributes yet")
-      },
-      _ => panic!("Constant at index {} was not a valid Utf8 identifier", attribute_name_index)
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool,
+    registry: &AttributeRegistry
+  ) -> Result<Attribute, ClassFormatError> {
+    let attribute_name_index = sr.get_u16()?;
+    let attribute_length = sr.get_u32()?;
+    let name = constant_pool.get_utf8(attribute_name_index)?;
+    let sr = &mut sr.sub_reader(attribute_length as usize)?;
+    let attribute = match name.as_str() {
+      "ConstantValue" => constant_value::read(sr, constant_pool),
+      "Code" => code::read(sr, constant_pool, registry),
+      "StackMapTable" => stack_map_table::read(sr, constant_pool),
+      "Exceptions" => exceptions::read(sr, constant_pool),
+      "InnerClasses" => inner_classes::read(sr, constant_pool),
+      "EnclosingMethod" => enclosing_method::read(sr, constant_pool),
+      "Synthetic" => Ok(Attribute::Synthetic),
+      "Signature" => signature::read(sr, constant_pool),
+      "SourceFile" => source_file::read(sr, constant_pool),
+      "SourceDebugExtension" => source_debug_extensions::read(sr, attribute_length),
+      "LineNumberTable" => line_number_table::read(sr),
+      "LocalVariableTable" => local_variable_table::read(sr, constant_pool),
+      "LocalVariableTypeTable" => local_variable_type_table::read(sr, constant_pool),
+      "Deprecated" => Ok(Attribute::Deprecated),
+      "RuntimeVisibleAnnotations" => runtime_annotations::read::<true>(sr, constant_pool),
+      "RuntimeInvisibleAnnotations" => runtime_annotations::read::<false>(sr, constant_pool),
+      "RuntimeVisibleParameterAnnotations" => {
+        runtime_parameter_annotations::read::<true>(sr, constant_pool)
+      }
+      "RuntimeInvisibleParameterAnnotations" => {
+        runtime_parameter_annotations::read::<false>(sr, constant_pool)
+      }
+      "RuntimeVisibleTypeAnnotations" => runtime_type_annotations::read::<true>(sr, constant_pool),
+      "RuntimeInvisibleTypeAnnotations" => {
+        runtime_type_annotations::read::<false>(sr, constant_pool)
+      }
+      "AnnotationDefault" => annotation_default::read(sr, constant_pool),
+      "BootstrapMethods" => bootstrap_methods::read(sr, constant_pool),
+      "MethodParameters" => method_parameters::read(sr, constant_pool),
+      "Module" => module::read(sr, constant_pool),
+      "ModulePackages" => module_packages::read(sr, constant_pool),
+      "ModuleMainClass" => module_main_class::read(sr, constant_pool),
+      "NestHost" => nest_host::read(sr, constant_pool),
+      "NestMembers" => nest_members::read(sr, constant_pool),
+      "Record" => record::read(sr, constant_pool, registry),
+      "PermittedSubclasses" => permitted_subclasses::read(sr, constant_pool),
+      _ => {
+        let bytes = sr.take_n(attribute_length as usize)?;
+        match registry.get(&name) {
+          Some((decoder, encoder)) => Ok(Attribute::Custom {
+            name: name.clone(),
+            value: decoder(bytes, constant_pool)?,
+            bytes: bytes.to_vec(),
+            encoder
+          }),
+          None => Ok(Attribute::Unknown { name: name.clone(), bytes: bytes.to_vec() })
+        }
+      }
+    }?;
+    if !sr.done() {
+      return Err(ClassFormatError::BadAttributeLength { name: name.clone(), offset: sr.offset() });
+    }
+    Ok(attribute)
+  }
+
+  /// Writes the attribute with its name and length. Positions in the attributes nested in `Code`
+  /// must have been turned back into byte offsets, which `Code` takes care of for its own.
+  /// `Unknown` attributes, and `Custom` ones without an encoder, can't be written: their bytes may
+  /// hold constant pool indices and code offsets that the rebuilt class would no longer match.
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.utf8(self.name())?);
+    let info = &mut StreamWriter::new();
+    match self {
+      Attribute::ConstantValue { value } => constant_value::write(value, constant_pool, info)?,
+      Attribute::Code { max_stack, max_locals, code, exception_table, attributes, .. } => {
+        code::write(
+          *max_stack,
+          *max_locals,
+          code,
+          exception_table,
+          attributes,
+          constant_pool,
+          info
+        )?
+      }
+      Attribute::StackMapTable { entries } => stack_map_table::write(entries, constant_pool, info)?,
+      Attribute::Exceptions { exceptions } => exceptions::write(exceptions, constant_pool, info)?,
+      Attribute::InnerClasses { classes } => inner_classes::write(classes, constant_pool, info)?,
+      Attribute::EnclosingMethod { class, method } => {
+        enclosing_method::write(class, method.as_ref(), constant_pool, info)?
+      }
+      Attribute::Synthetic | Attribute::Deprecated => {}
+      Attribute::Signature { signature } => signature::write(signature, constant_pool, info)?,
+      Attribute::SourceFile { sourcefile } => source_file::write(sourcefile, constant_pool, info)?,
+      Attribute::SourceDebugExtension { debug_extension } => {
+        source_debug_extensions::write(debug_extension, info)
+      }
+      Attribute::LineNumberTable { line_number_table } => {
+        line_number_table::write(line_number_table, info)?
+      }
+      Attribute::LocalVariableTable { local_variable_table } => {
+        local_variable_table::write(local_variable_table, constant_pool, info)?
+      }
+      Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+        local_variable_type_table::write(local_variable_type_table, constant_pool, info)?
+      }
+      Attribute::RuntimeVisibleAnnotations { annotations }
+      | Attribute::RuntimeInvisibleAnnotations { annotations } => {
+        runtime_annotations::write(annotations, constant_pool, info)?
+      }
+      Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations }
+      | Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+        runtime_parameter_annotations::write(parameter_annotations, constant_pool, info)?
+      }
+      Attribute::RuntimeVisibleTypeAnnotations { annotations }
+      | Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+        runtime_type_annotations::write(annotations, constant_pool, info)?
+      }
+      Attribute::AnnotationDefault { default_value } => {
+        annotation_default::write(default_value, constant_pool, info)?
+      }
+      Attribute::BootstrapMethods { .. } => bootstrap_methods::write(constant_pool, info)?,
+      Attribute::MethodParameters { parameters } => {
+        method_parameters::write(parameters, constant_pool, info)?
+      }
+      Attribute::Module {
+        module_name,
+        module_flags,
+        module_version,
+        requires,
+        exports,
+        opens,
+        uses,
+        provides
+      } => module::write(
+        module_name,
+        *module_flags,
+        module_version.as_deref(),
+        requires,
+        exports,
+        opens,
+        uses,
+        provides,
+        constant_pool,
+        info
+      )?,
+      Attribute::ModulePackages { packages } => {
+        module_packages::write(packages, constant_pool, info)?
+      }
+      Attribute::ModuleMainClass { main_class } => {
+        module_main_class::write(main_class, constant_pool, info)?
+      }
+      Attribute::NestHost { host_class } => nest_host::write(host_class, constant_pool, info)?,
+      Attribute::NestMembers { classes } => nest_members::write(classes, constant_pool, info)?,
+      Attribute::Record { components } => record::write(components, constant_pool, info)?,
+      Attribute::PermittedSubclasses { classes } => {
+        permitted_subclasses::write(classes, constant_pool, info)?
+      }
+      Attribute::Custom { value, encoder: Some(AttributeEncoder(encode)), .. } => {
+        info.put_bytes(&encode(&**value, constant_pool)?)
+      }
+      Attribute::Custom { name, encoder: None, .. } | Attribute::Unknown { name, .. } => {
+        return Err(ClassFormatError::UnwritableAttribute { name: name.clone() })
+      }
+    }
+    sw.put_u32(info.len() as u32);
+    sw.put_bytes(&info.stream);
+    Ok(())
+  }
+
+  /// The name the attribute goes by in the class file.
+  pub fn name(&self) -> &str {
+    match self {
+      Attribute::ConstantValue { .. } => "ConstantValue",
+      Attribute::Code { .. } => "Code",
+      Attribute::StackMapTable { .. } => "StackMapTable",
+      Attribute::Exceptions { .. } => "Exceptions",
+      Attribute::InnerClasses { .. } => "InnerClasses",
+      Attribute::EnclosingMethod { .. } => "EnclosingMethod",
+      Attribute::Synthetic => "Synthetic",
+      Attribute::Signature { .. } => "Signature",
+      Attribute::SourceFile { .. } => "SourceFile",
+      Attribute::SourceDebugExtension { .. } => "SourceDebugExtension",
+      Attribute::LineNumberTable { .. } => "LineNumberTable",
+      Attribute::LocalVariableTable { .. } => "LocalVariableTable",
+      Attribute::LocalVariableTypeTable { .. } => "LocalVariableTypeTable",
+      Attribute::Deprecated => "Deprecated",
+      Attribute::RuntimeVisibleAnnotations { .. } => "RuntimeVisibleAnnotations",
+      Attribute::RuntimeInvisibleAnnotations { .. } => "RuntimeInvisibleAnnotations",
+      Attribute::RuntimeVisibleParameterAnnotations { .. } => "RuntimeVisibleParameterAnnotations",
+      Attribute::RuntimeInvisibleParameterAnnotations { .. } => {
+        "RuntimeInvisibleParameterAnnotations"
+      }
+      Attribute::RuntimeVisibleTypeAnnotations { .. } => "RuntimeVisibleTypeAnnotations",
+      Attribute::RuntimeInvisibleTypeAnnotations { .. } => "RuntimeInvisibleTypeAnnotations",
+      Attribute::AnnotationDefault { .. } => "AnnotationDefault",
+      Attribute::BootstrapMethods { .. } => "BootstrapMethods",
+      Attribute::MethodParameters { .. } => "MethodParameters",
+      Attribute::Module { .. } => "Module",
+      Attribute::ModulePackages { .. } => "ModulePackages",
+      Attribute::ModuleMainClass { .. } => "ModuleMainClass",
+      Attribute::NestHost { .. } => "NestHost",
+      Attribute::NestMembers { .. } => "NestMembers",
+      Attribute::Record { .. } => "Record",
+      Attribute::PermittedSubclasses { .. } => "PermittedSubclasses",
+      Attribute::Custom { name, .. } | Attribute::Unknown { name, .. } => name
+    }
+  }
+
+  /// The value of a `Custom` attribute, if this is one and its decoder produced a `T`.
+  pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+    match self {
+      // Deref past the `Rc`, which is itself `Debug + Any` and so a `CustomAttribute` too
+      Attribute::Custom { value, .. } => (**value).as_any().downcast_ref(),
+      _ => None
     }
   }
 }
@@ -1,15 +1,24 @@
 use super::{attribute::element_value::ElementValue, Attribute};
-use crate::parser::{cp_info_resolved::ResolvedCpInfo, stream_reader::StreamReader};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> Attribute {
-  let attribute_name_index = sr.get_u16();
-  let ResolvedCpInfo::Utf8 (attribute_name) = &constant_pool[attribute_name_index as usize -1] else {panic!()};
-  let attribute_length = sr.get_u32();
-  let default_value = ElementValue::read(sr);
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let default_value = ElementValue::read(sr, constant_pool)?;
 
-  Attribute::AnnotationDefault {
-    attribute_name: attribute_name.to_string(),
-    attribute_length,
-    default_value
-  }
+  Ok(Attribute::AnnotationDefault { default_value })
+}
+
+pub fn write(
+  default_value: &ElementValue,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  default_value.write(constant_pool, sw)
 }
@@ -1,10 +1,15 @@
 pub mod annotation;
 pub mod bootstrap_method;
 pub mod classes;
+pub mod constant;
 pub mod element_value;
 pub mod exception;
 pub mod line_number;
 pub mod local_variable;
 pub mod local_variable_type;
+pub mod method_parameter;
+pub mod module;
 pub mod parameter_annotation;
+pub mod record_component;
 pub mod stack_map_frame;
+pub mod type_annotation;
@@ -1,19 +1,122 @@
 use super::element_value::ElementValue;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  attribute_info::Attribute,
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Annotation {
-  pub type_index: u16,
-  pub element_value_pairs: Vec<(u16, ElementValue)>
+  /// The annotation interface as a field descriptor, such as `Lorg/junit/Test;`.
+  pub type_name: String,
+  pub element_value_pairs: Vec<(String, ElementValue)>
 }
 
 impl Annotation {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let type_index = sr.get_u16();
-    let num_element_value_pairs = sr.get_u16();
-    let element_value_pairs: Vec<(u16, ElementValue)> =
-      (0..num_element_value_pairs).map(|_| (sr.get_u16(), ElementValue::read(sr))).collect();
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Self::read_nested(sr, constant_pool, 0)
+  }
+
+  /// Reads an annotation nested `depth` deep in an element value.
+  pub(super) fn read_nested(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool,
+    depth: usize
+  ) -> Result<Self, ClassFormatError> {
+    let type_name = constant_pool.get_utf8(sr.get_u16()?)?;
+    let num_element_value_pairs = sr.get_u16()?;
+    let element_value_pairs: Vec<(String, ElementValue)> = (0..num_element_value_pairs)
+      .map(|_| {
+        let element_name = constant_pool.get_utf8(sr.get_u16()?)?;
+        Ok((element_name, ElementValue::read_nested(sr, constant_pool, depth)?))
+      })
+      .collect::<Result<_, ClassFormatError>>()?;
+
+    Ok(Annotation { type_name, element_value_pairs })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.utf8(&self.type_name)?);
+    sw.put_count(self.element_value_pairs.len())?;
+    for (element_name, value) in &self.element_value_pairs {
+      sw.put_u16(constant_pool.utf8(element_name)?);
+      value.write(constant_pool, sw)?;
+    }
+    Ok(())
+  }
+
+  /// The value given for an element, which is absent when the element was left at its default.
+  pub fn get(&self, element_name: &str) -> Option<&ElementValue> {
+    self.element_value_pairs.iter().find(|(name, _)| name == element_name).map(|(_, value)| value)
+  }
+}
+
+/// Every annotation, visible or not, among the attributes of a class, field, method or record
+/// component.
+pub fn annotations(attributes: &[Attribute]) -> impl Iterator<Item = &Annotation> {
+  attributes.iter().flat_map(|attribute| match attribute {
+    Attribute::RuntimeVisibleAnnotations { annotations }
+    | Attribute::RuntimeInvisibleAnnotations { annotations } => annotations.as_slice(),
+    _ => &[]
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::classfile::ClassFile;
+
+  /// A class compiled from `java_test/Annotated.java`.
+  fn parse(class: &str) -> ClassFile {
+    let path = format!("{}/java_test/{}.class", env!("CARGO_MANIFEST_DIR"), class);
+    ClassFile::parse(&std::fs::read(path).unwrap()).unwrap().0
+  }
+
+  fn is_string(value: Option<&ElementValue>, expected: &str) -> bool {
+    matches!(value, Some(ElementValue::String(value)) if value.to_string() == expected)
+  }
+
+  #[test]
+  fn reads_element_values_through_lookups() {
+    let class = parse("Annotated");
+    let method = |key: &str| &class.methods.iter().find(|(name, _)| name == key).unwrap().1;
+
+    let t = method("t:(II)V");
+    let test = t.annotation("LTest;").unwrap();
+    assert!(matches!(
+      test.get("expected"),
+      Some(ElementValue::Class(class)) if class == "Ljava/lang/IllegalStateException;"
+    ));
+    assert!(matches!(test.get("timeout"), Some(ElementValue::Long(5))));
+    assert!(test.get("message").is_none());
+    assert!(t.annotation("LP;").is_none());
+
+    let named = t.parameter_annotation(0, "LP;").unwrap();
+    assert!(is_string(named.get("value"), "x"));
+    assert!(t.parameter_annotation(1, "LP;").is_none());
+
+    assert!(class.annotation("Ljava/lang/Deprecated;").is_some());
+    let guarded_by = class.fields[0].1.annotation("LGuardedBy;").unwrap();
+    assert!(is_string(guarded_by.get("value"), "this"));
+
+    let text = parse("Text");
+    let value = &text.methods.iter().find(|(name, _)| name == "value:()Ljava/lang/String;");
+    assert!(is_string(value.unwrap().1.annotation_default(), "y"));
 
-    Annotation { type_index, element_value_pairs }
+    let size = parse("Size");
+    let components = size.attributes.iter().find_map(|attribute| match attribute {
+      Attribute::Record { components } => Some(components),
+      _ => None
+    });
+    assert!(components.unwrap()[0].annotation("LPositive;").is_some());
   }
 }
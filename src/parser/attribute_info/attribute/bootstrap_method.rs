@@ -1,17 +1,58 @@
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::ConstantPool,
+  cp_info_resolved::{MethodHandle, ResolvedCpInfo},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
-pub struct BootstrapMethod {
+/// A `BootstrapMethods` entry as stored in the class file.
+/// Constant pool resolution needs these before the pool itself can be resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawBootstrapMethod {
   pub bootstrap_method_ref: u16,
-  pub bootstrap_methods: Vec<u16>
+  pub bootstrap_arguments: Vec<u16>
+}
+
+impl RawBootstrapMethod {
+  pub fn read(sr: &mut StreamReader) -> Result<Self, ClassFormatError> {
+    let bootstrap_method_ref = sr.get_u16()?;
+    let num_bootstrap_arguments = sr.get_u16()?;
+    let bootstrap_arguments: Vec<u16> =
+      (0..num_bootstrap_arguments).map(|_| sr.get_u16()).collect::<Result<_, _>>()?;
+
+    Ok(RawBootstrapMethod { bootstrap_method_ref, bootstrap_arguments })
+  }
+
+  pub fn write(&self, sw: &mut StreamWriter) -> Result<(), ClassFormatError> {
+    sw.put_u16(self.bootstrap_method_ref);
+    sw.put_count(self.bootstrap_arguments.len())?;
+    for argument in &self.bootstrap_arguments {
+      sw.put_u16(*argument);
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootstrapMethod {
+  pub bootstrap_method: MethodHandle,
+  pub bootstrap_arguments: Vec<ResolvedCpInfo>
 }
 
 impl BootstrapMethod {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let bootstrap_method_ref = sr.get_u16();
-    let num_bootstrap_arguments = sr.get_u16();
-    let bootstrap_methods: Vec<u16> = (0..num_bootstrap_arguments).map(|_| sr.get_u16()).collect();
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let RawBootstrapMethod { bootstrap_method_ref, bootstrap_arguments } =
+      RawBootstrapMethod::read(sr)?;
+    let bootstrap_method = constant_pool.get_method_handle(bootstrap_method_ref)?.clone();
+    let bootstrap_arguments: Vec<ResolvedCpInfo> = bootstrap_arguments
+      .into_iter()
+      .map(|index| constant_pool.get(index).cloned())
+      .collect::<Result<_, _>>()?;
 
-    BootstrapMethod { bootstrap_method_ref, bootstrap_methods }
+    Ok(BootstrapMethod { bootstrap_method, bootstrap_arguments })
   }
 }
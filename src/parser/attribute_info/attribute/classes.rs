@@ -1,20 +1,55 @@
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Classes {
-  pub inner_class_info_index: u16,
-  pub outer_class_info_index: u16,
-  pub inner_name_index: u16,
+  pub inner_class: Class,
+  /// `None` unless the inner class is a member of another class.
+  pub outer_class: Option<Class>,
+  /// `None` for an anonymous class.
+  pub inner_name: Option<String>,
   pub inner_class_access_flags: u16
 }
 
 impl Classes {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    Classes {
-      inner_class_info_index: sr.get_u16(),
-      outer_class_info_index: sr.get_u16(),
-      inner_name_index: sr.get_u16(),
-      inner_class_access_flags: sr.get_u16()
-    }
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Ok(Classes {
+      inner_class: constant_pool.get_class(sr.get_u16()?)?.clone(),
+      outer_class: match sr.get_u16()? {
+        0 => None,
+        index => Some(constant_pool.get_class(index)?.clone())
+      },
+      inner_name: match sr.get_u16()? {
+        0 => None,
+        index => Some(constant_pool.get_utf8(index)?)
+      },
+      inner_class_access_flags: sr.get_u16()?
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.class(&self.inner_class)?);
+    sw.put_u16(match &self.outer_class {
+      Some(outer_class) => constant_pool.class(outer_class)?,
+      None => 0
+    });
+    sw.put_u16(match &self.inner_name {
+      Some(inner_name) => constant_pool.utf8(inner_name)?,
+      None => 0
+    });
+    sw.put_u16(self.inner_class_access_flags);
+    Ok(())
   }
 }
@@ -0,0 +1,88 @@
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::{JavaString, ResolvedCpInfo},
+  descriptor::FieldType,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+/// The initial value of a static field, from its `ConstantValue` attribute.
+#[derive(Debug, Clone)]
+pub enum Constant {
+  /// Also used for `boolean`, `byte`, `char` and `short` fields.
+  Int(i32),
+  Long(i64),
+  Float(f32),
+  Double(f64),
+  String(JavaString)
+}
+
+impl Constant {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let constantvalue_index = sr.get_u16()?;
+    Ok(match constant_pool.get(constantvalue_index)? {
+      ResolvedCpInfo::Integer(value) => Constant::Int(*value),
+      ResolvedCpInfo::Long(value) => Constant::Long(*value),
+      ResolvedCpInfo::Float(value) => Constant::Float(*value),
+      ResolvedCpInfo::Double(value) => Constant::Double(*value),
+      ResolvedCpInfo::String(value) => Constant::String(value.clone()),
+      other => {
+        return Err(ClassFormatError::WrongConstantKind {
+          index: constantvalue_index,
+          expected: "Integer, Long, Float, Double or String",
+          found: other.kind()
+        })
+      }
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    let constant = match self {
+      Constant::Int(value) => ResolvedCpInfo::Integer(*value),
+      Constant::Long(value) => ResolvedCpInfo::Long(*value),
+      Constant::Float(value) => ResolvedCpInfo::Float(*value),
+      Constant::Double(value) => ResolvedCpInfo::Double(*value),
+      Constant::String(value) => ResolvedCpInfo::String(value.clone())
+    };
+    sw.put_u16(constant_pool.constant(&constant)?);
+    Ok(())
+  }
+
+  /// Whether a field with this descriptor may be initialised to this constant (JVMS §4.7.2).
+  pub fn fits(&self, descriptor: &FieldType) -> bool {
+    match self {
+      Constant::Int(_) => matches!(
+        descriptor,
+        FieldType::Int | FieldType::Short | FieldType::Char | FieldType::Byte | FieldType::Boolean
+      ),
+      Constant::Long(_) => *descriptor == FieldType::Long,
+      Constant::Float(_) => *descriptor == FieldType::Float,
+      Constant::Double(_) => *descriptor == FieldType::Double,
+      Constant::String(_) => {
+        matches!(descriptor, FieldType::Object(name) if name == "java/lang/String")
+      }
+    }
+  }
+}
+
+/// Floats and doubles compare by their bits, so a NaN constant equals itself.
+impl PartialEq for Constant {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Constant::Int(a), Constant::Int(b)) => a == b,
+      (Constant::Long(a), Constant::Long(b)) => a == b,
+      (Constant::Float(a), Constant::Float(b)) => a.to_bits() == b.to_bits(),
+      (Constant::Double(a), Constant::Double(b)) => a.to_bits() == b.to_bits(),
+      (Constant::String(a), Constant::String(b)) => a == b,
+      _ => false
+    }
+  }
+}
@@ -1,41 +1,193 @@
 use super::annotation::Annotation;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::{JavaString, ResolvedCpInfo},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
-pub enum ElementValues {
-  ConstValueIndex(u16),
-  EnumConstValue { type_name_index: u16, const_name_index: u16 },
-  ClassInfoIndex(u16),
-  AnnotationValue(Annotation),
-  ArrayValue { num_values: u16, values: Vec<ElementValue> }
-}
+/// How deeply arrays and annotations may nest inside an element value, so that a hostile class file
+/// can't exhaust the stack.
+pub const MAX_NESTING: usize = 255;
 
 #[derive(Debug, Clone)]
-pub struct ElementValue {
-  pub tag: u8,
-  pub value: ElementValues
+pub enum ElementValue {
+  Byte(i8),
+  /// A UTF-16 code unit, like a Java `char`.
+  Char(u16),
+  Double(f64),
+  Float(f32),
+  Int(i32),
+  Long(i64),
+  Short(i16),
+  Boolean(bool),
+  String(JavaString),
+  Enum {
+    type_name: String,
+    const_name: String
+  },
+  /// A return descriptor, such as `Ljava/lang/Object;`, `[I` or `V`.
+  Class(String),
+  Annotation(Annotation),
+  Array(Vec<ElementValue>)
 }
 
 impl ElementValue {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let tag = sr.get_u8();
-    let value = match tag as char {
-      'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 's' => {
-        ElementValues::ConstValueIndex(sr.get_u16())
-      }
-      'e' => ElementValues::EnumConstValue {
-        type_name_index: sr.get_u16(),
-        const_name_index: sr.get_u16()
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Self::read_nested(sr, constant_pool, 0)
+  }
+
+  /// Reads a value `depth` arrays or annotations deep.
+  pub(super) fn read_nested(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool,
+    depth: usize
+  ) -> Result<Self, ClassFormatError> {
+    if depth > MAX_NESTING {
+      return Err(ClassFormatError::NestingTooDeep { offset: sr.offset() });
+    }
+    let tag = sr.get_u8()?;
+    Ok(match tag {
+      b'B' => ElementValue::Byte(*constant_pool.get_integer(sr.get_u16()?)? as i8),
+      b'C' => ElementValue::Char(*constant_pool.get_integer(sr.get_u16()?)? as u16),
+      b'D' => ElementValue::Double(*constant_pool.get_double(sr.get_u16()?)?),
+      b'F' => ElementValue::Float(*constant_pool.get_float(sr.get_u16()?)?),
+      b'I' => ElementValue::Int(*constant_pool.get_integer(sr.get_u16()?)?),
+      b'J' => ElementValue::Long(*constant_pool.get_long(sr.get_u16()?)?),
+      b'S' => ElementValue::Short(*constant_pool.get_integer(sr.get_u16()?)? as i16),
+      b'Z' => ElementValue::Boolean(*constant_pool.get_integer(sr.get_u16()?)? != 0),
+      b's' => ElementValue::String(constant_pool.get_java_string(sr.get_u16()?)?.clone()),
+      b'e' => ElementValue::Enum {
+        type_name: constant_pool.get_utf8(sr.get_u16()?)?,
+        const_name: constant_pool.get_utf8(sr.get_u16()?)?
       },
-      'c' => ElementValues::ClassInfoIndex(sr.get_u16()),
-      '@' => ElementValues::AnnotationValue(Annotation::read(sr)),
-      '[' => {
-        let num_values = sr.get_u16();
-        let values: Vec<ElementValue> = (0..num_values).map(|_| ElementValue::read(sr)).collect();
-        ElementValues::ArrayValue { num_values, values }
+      b'c' => ElementValue::Class(constant_pool.get_utf8(sr.get_u16()?)?),
+      b'@' => ElementValue::Annotation(Annotation::read_nested(sr, constant_pool, depth + 1)?),
+      b'[' => {
+        let num_values = sr.get_u16()?;
+        let values: Vec<ElementValue> = (0..num_values)
+          .map(|_| ElementValue::read_nested(sr, constant_pool, depth + 1))
+          .collect::<Result<_, _>>()?;
+        ElementValue::Array(values)
       }
-      _ => panic!()
+      _ => return Err(ClassFormatError::BadElementValueTag { tag })
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u8(self.tag());
+    let mut constant = |constant: ResolvedCpInfo| -> Result<(), ClassFormatError> {
+      sw.put_u16(constant_pool.constant(&constant)?);
+      Ok(())
     };
-    ElementValue { tag, value }
+    match self {
+      ElementValue::Byte(value) => constant(ResolvedCpInfo::Integer(*value as i32)),
+      ElementValue::Char(value) => constant(ResolvedCpInfo::Integer(*value as i32)),
+      ElementValue::Double(value) => constant(ResolvedCpInfo::Double(*value)),
+      ElementValue::Float(value) => constant(ResolvedCpInfo::Float(*value)),
+      ElementValue::Int(value) => constant(ResolvedCpInfo::Integer(*value)),
+      ElementValue::Long(value) => constant(ResolvedCpInfo::Long(*value)),
+      ElementValue::Short(value) => constant(ResolvedCpInfo::Integer(*value as i32)),
+      ElementValue::Boolean(value) => constant(ResolvedCpInfo::Integer(*value as i32)),
+      ElementValue::String(value) => {
+        sw.put_u16(constant_pool.java_string(value)?);
+        Ok(())
+      }
+      ElementValue::Class(value) => {
+        sw.put_u16(constant_pool.utf8(value)?);
+        Ok(())
+      }
+      ElementValue::Enum { type_name, const_name } => {
+        sw.put_u16(constant_pool.utf8(type_name)?);
+        sw.put_u16(constant_pool.utf8(const_name)?);
+        Ok(())
+      }
+      ElementValue::Annotation(annotation) => annotation.write(constant_pool, sw),
+      ElementValue::Array(values) => {
+        sw.put_count(values.len())?;
+        values.iter().try_for_each(|value| value.write(constant_pool, sw))
+      }
+    }
+  }
+
+  /// The tag that precedes this value in the class file.
+  pub fn tag(&self) -> u8 {
+    match self {
+      ElementValue::Byte(_) => b'B',
+      ElementValue::Char(_) => b'C',
+      ElementValue::Double(_) => b'D',
+      ElementValue::Float(_) => b'F',
+      ElementValue::Int(_) => b'I',
+      ElementValue::Long(_) => b'J',
+      ElementValue::Short(_) => b'S',
+      ElementValue::Boolean(_) => b'Z',
+      ElementValue::String(_) => b's',
+      ElementValue::Enum { .. } => b'e',
+      ElementValue::Class(_) => b'c',
+      ElementValue::Annotation(_) => b'@',
+      ElementValue::Array(_) => b'['
+    }
+  }
+}
+
+/// Floats and doubles compare by their bits, so a NaN value equals itself.
+impl PartialEq for ElementValue {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (ElementValue::Byte(a), ElementValue::Byte(b)) => a == b,
+      (ElementValue::Char(a), ElementValue::Char(b)) => a == b,
+      (ElementValue::Double(a), ElementValue::Double(b)) => a.to_bits() == b.to_bits(),
+      (ElementValue::Float(a), ElementValue::Float(b)) => a.to_bits() == b.to_bits(),
+      (ElementValue::Int(a), ElementValue::Int(b)) => a == b,
+      (ElementValue::Long(a), ElementValue::Long(b)) => a == b,
+      (ElementValue::Short(a), ElementValue::Short(b)) => a == b,
+      (ElementValue::Boolean(a), ElementValue::Boolean(b)) => a == b,
+      (ElementValue::String(a), ElementValue::String(b)) => a == b,
+      (
+        ElementValue::Enum { type_name, const_name },
+        ElementValue::Enum { type_name: other_type_name, const_name: other_const_name }
+      ) => type_name == other_type_name && const_name == other_const_name,
+      (ElementValue::Class(a), ElementValue::Class(b)) => a == b,
+      (ElementValue::Annotation(a), ElementValue::Annotation(b)) => a == b,
+      (ElementValue::Array(a), ElementValue::Array(b)) => a == b,
+      _ => false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::classfile::ClassFile;
+
+  #[test]
+  fn deeply_nested_arrays_are_rejected() {
+    let constant_pool = ConstantPool::read(&mut StreamReader::from(&[0, 1])).unwrap();
+    let constant_pool = constant_pool.resolve(&[]).unwrap();
+    // 20,000 arrays each holding the next, which would overflow the stack if read recursively
+    let bytes = [b'[', 0, 1].repeat(20_000);
+    let error = ElementValue::read(&mut StreamReader::from(&bytes), &constant_pool).unwrap_err();
+    assert_eq!(error, ClassFormatError::NestingTooDeep { offset: 3 * (MAX_NESTING + 1) });
+  }
+
+  #[test]
+  fn strings_keep_unpaired_surrogates() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/java_test/Annotated.class");
+    let class = ClassFile::parse(&std::fs::read(path).unwrap()).unwrap().0;
+    let unpaired = &class.methods.iter().find(|(name, _)| name == "unpaired:()V").unwrap().1;
+    let value = unpaired.annotation("LText;").unwrap().get("value");
+    let expected = JavaString { chars: vec![0x78, 0xd800, 0x79] };
+    assert!(matches!(value, Some(ElementValue::String(value)) if *value == expected));
+    // Written back in the same six bytes, not as U+FFFD
+    let bytes = class.write().unwrap();
+    assert!(bytes.windows(5).any(|window| window == [0x78, 0xed, 0xa0, 0x80, 0x79]));
   }
 }
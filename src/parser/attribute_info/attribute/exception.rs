@@ -1,20 +1,68 @@
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  attribute_info::code::offset_map::OffsetMap,
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Exception {
+  /// Instruction index, once the table has been translated.
   pub start_pc: u16,
+  /// Exclusive instruction index, once the table has been translated.
   pub end_pc: u16,
+  /// Instruction index, once the table has been translated.
   pub handler_pc: u16,
-  pub catch_type: u16
+  /// `None` for a handler that catches everything, as `finally` blocks compile to.
+  pub catch_type: Option<Class>
 }
 
 impl Exception {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    return Self {
-      start_pc: sr.get_u16(),
-      end_pc: sr.get_u16(),
-      handler_pc: sr.get_u16(),
-      catch_type: sr.get_u16()
-    };
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Ok(Self {
+      start_pc: sr.get_u16()?,
+      end_pc: sr.get_u16()?,
+      handler_pc: sr.get_u16()?,
+      catch_type: match sr.get_u16()? {
+        0 => None,
+        index => Some(constant_pool.get_class(index)?.clone())
+      }
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(self.start_pc);
+    sw.put_u16(self.end_pc);
+    sw.put_u16(self.handler_pc);
+    sw.put_u16(match &self.catch_type {
+      Some(catch_type) => constant_pool.class(catch_type)?,
+      None => 0
+    });
+    Ok(())
+  }
+
+  /// Replaces the byte offsets with instruction indices.
+  pub fn translate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    self.start_pc = offsets.index(self.start_pc)?;
+    self.end_pc = offsets.end_index(self.end_pc as usize)?;
+    self.handler_pc = offsets.index(self.handler_pc)?;
+    Ok(())
+  }
+
+  /// Replaces the instruction indices with byte offsets, undoing `translate`.
+  pub fn untranslate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    self.start_pc = offsets.offset(self.start_pc)?;
+    self.end_pc = offsets.end_offset(self.end_pc as usize)?;
+    self.handler_pc = offsets.offset(self.handler_pc)?;
+    Ok(())
   }
 }
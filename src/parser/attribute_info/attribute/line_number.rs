@@ -1,13 +1,34 @@
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  attribute_info::code::offset_map::OffsetMap,
+  class_format_error::ClassFormatError,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LineNumber {
+  /// Instruction index, once the enclosing `Code` has been read.
   pub start_pc: u16,
   pub line_number: u16
 }
 
 impl LineNumber {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    LineNumber { start_pc: sr.get_u16(), line_number: sr.get_u16() }
+  pub fn read(sr: &mut StreamReader) -> Result<Self, ClassFormatError> {
+    Ok(LineNumber { start_pc: sr.get_u16()?, line_number: sr.get_u16()? })
+  }
+
+  pub fn write(&self, sw: &mut StreamWriter) {
+    sw.put_u16(self.start_pc);
+    sw.put_u16(self.line_number);
+  }
+
+  pub fn translate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    self.start_pc = offsets.index(self.start_pc)?;
+    Ok(())
+  }
+
+  pub fn untranslate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    self.start_pc = offsets.offset(self.start_pc)?;
+    Ok(())
   }
 }
@@ -1,22 +1,64 @@
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  attribute_info::code::offset_map::OffsetMap,
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LocalVariable {
+  /// Instruction index, once the enclosing `Code` has been read.
   pub start_pc: u16,
+  /// The number of instructions in scope, once the enclosing `Code` has been read.
   pub length: u16,
-  pub name_index: u16,
-  pub descriptor_index: u16,
+  pub name: String,
+  pub descriptor: String,
   pub index: u16
 }
 
 impl LocalVariable {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    LocalVariable {
-      start_pc: sr.get_u16(),
-      length: sr.get_u16(),
-      name_index: sr.get_u16(),
-      descriptor_index: sr.get_u16(),
-      index: sr.get_u16()
-    }
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Ok(LocalVariable {
+      start_pc: sr.get_u16()?,
+      length: sr.get_u16()?,
+      name: constant_pool.get_utf8(sr.get_u16()?)?,
+      descriptor: constant_pool.get_utf8(sr.get_u16()?)?,
+      index: sr.get_u16()?
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(self.start_pc);
+    sw.put_u16(self.length);
+    sw.put_u16(constant_pool.utf8(&self.name)?);
+    sw.put_u16(constant_pool.utf8(&self.descriptor)?);
+    sw.put_u16(self.index);
+    Ok(())
+  }
+
+  /// The variable is in scope up to, but not including, `start_pc + length`.
+  pub fn translate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    let start_pc = offsets.index(self.start_pc)?;
+    let end_pc = offsets.end_index(self.start_pc as usize + self.length as usize)?;
+    self.start_pc = start_pc;
+    self.length = end_pc - start_pc;
+    Ok(())
+  }
+
+  /// Replaces the instruction indices with byte offsets, undoing `translate`.
+  pub fn untranslate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    let start_pc = offsets.offset(self.start_pc)?;
+    let end_pc = offsets.end_offset(self.start_pc as usize + self.length as usize)?;
+    self.start_pc = start_pc;
+    self.length = end_pc - start_pc;
+    Ok(())
   }
 }
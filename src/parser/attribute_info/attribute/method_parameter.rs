@@ -0,0 +1,58 @@
+use bitmask::bitmask;
+
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+bitmask! {
+  #[derive(Debug)]
+  pub mask MethodParameterFlags: u16 where
+  #[allow(clippy::enum_variant_names)]
+  flags MethodParameterFlag {
+    AccFinal = 0x0010,
+    AccSynthetic = 0x1000,
+    AccMandated = 0x8000
+  }
+}
+
+impl MethodParameterFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { MethodParameterFlags { mask } }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodParameter {
+  /// `None` for a formal parameter with no name.
+  pub name: Option<String>,
+  pub access_flags: MethodParameterFlags
+}
+
+impl MethodParameter {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let name = match sr.get_u16()? {
+      0 => None,
+      name_index => Some(constant_pool.get_utf8(name_index)?)
+    };
+    let access_flags = MethodParameterFlags::from_mask(sr.get_u16()?);
+
+    Ok(MethodParameter { name, access_flags })
+  }
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(match &self.name {
+      Some(name) => constant_pool.utf8(name)?,
+      None => 0
+    });
+    sw.put_u16(*self.access_flags);
+    Ok(())
+  }
+}
@@ -0,0 +1,266 @@
+use bitmask::bitmask;
+
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+bitmask! {
+  #[derive(Debug)]
+  pub mask ModuleFlags: u16 where
+  #[allow(clippy::enum_variant_names)]
+  flags ModuleFlag {
+    AccOpen = 0x0020,
+    AccSynthetic = 0x1000,
+    AccMandated = 0x8000
+  }
+}
+
+bitmask! {
+  #[derive(Debug)]
+  pub mask RequiresFlags: u16 where
+  #[allow(clippy::enum_variant_names)]
+  flags RequiresFlag {
+    AccTransitive = 0x0020,
+    AccStaticPhase = 0x0040,
+    AccSynthetic = 0x1000,
+    AccMandated = 0x8000
+  }
+}
+
+bitmask! {
+  #[derive(Debug)]
+  pub mask ExportsFlags: u16 where
+  #[allow(clippy::enum_variant_names)]
+  flags ExportsFlag {
+    AccSynthetic = 0x1000,
+    AccMandated = 0x8000
+  }
+}
+
+bitmask! {
+  #[derive(Debug)]
+  pub mask OpensFlags: u16 where
+  #[allow(clippy::enum_variant_names)]
+  flags OpensFlag {
+    AccSynthetic = 0x1000,
+    AccMandated = 0x8000
+  }
+}
+
+impl ModuleFlags {
+  pub fn read(sr: &mut StreamReader) -> Result<Self, ClassFormatError> {
+    Ok(ModuleFlags { mask: sr.get_u16()? })
+  }
+
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { ModuleFlags { mask } }
+}
+
+impl RequiresFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { RequiresFlags { mask } }
+}
+
+impl ExportsFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { ExportsFlags { mask } }
+}
+
+impl OpensFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { OpensFlags { mask } }
+}
+
+/// An optional constant pool index, where 0 means absent.
+pub fn optional_utf8(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Option<String>, ClassFormatError> {
+  match sr.get_u16()? {
+    0 => Ok(None),
+    index => Ok(Some(constant_pool.get_utf8(index)?))
+  }
+}
+
+/// Writes 0 for `None`, like `optional_utf8` reads it.
+pub fn write_optional_utf8(
+  value: Option<&str>,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(match value {
+    Some(value) => constant_pool.utf8(value)?,
+    None => 0
+  });
+  Ok(())
+}
+
+fn module_names(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Vec<String>, ClassFormatError> {
+  let count = sr.get_u16()?;
+  (0..count).map(|_| Ok(constant_pool.get_module(sr.get_u16()?)?.name.clone())).collect()
+}
+
+fn write_module_names(
+  names: &[String],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(names.len())?;
+  for name in names {
+    sw.put_u16(constant_pool.module(name)?);
+  }
+  Ok(())
+}
+
+pub fn write_classes(
+  classes: &[Class],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(classes.len())?;
+  for class in classes {
+    sw.put_u16(constant_pool.class(class)?);
+  }
+  Ok(())
+}
+
+pub fn read_class(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Class, ClassFormatError> {
+  Ok(constant_pool.get_class(sr.get_u16()?)?.clone())
+}
+
+pub fn read_package(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<String, ClassFormatError> {
+  Ok(constant_pool.get_package(sr.get_u16()?)?.name.clone())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requires {
+  pub requires: String,
+  pub requires_flags: RequiresFlags,
+  pub requires_version: Option<String>
+}
+
+impl Requires {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Ok(Requires {
+      requires: constant_pool.get_module(sr.get_u16()?)?.name.clone(),
+      requires_flags: RequiresFlags { mask: sr.get_u16()? },
+      requires_version: optional_utf8(sr, constant_pool)?
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.module(&self.requires)?);
+    sw.put_u16(*self.requires_flags);
+    write_optional_utf8(self.requires_version.as_deref(), constant_pool, sw)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exports {
+  pub exports: String,
+  pub exports_flags: ExportsFlags,
+  /// The modules the package is exported to, or every module if empty.
+  pub exports_to: Vec<String>
+}
+
+impl Exports {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Ok(Exports {
+      exports: read_package(sr, constant_pool)?,
+      exports_flags: ExportsFlags { mask: sr.get_u16()? },
+      exports_to: module_names(sr, constant_pool)?
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.package(&self.exports)?);
+    sw.put_u16(*self.exports_flags);
+    write_module_names(&self.exports_to, constant_pool, sw)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opens {
+  pub opens: String,
+  pub opens_flags: OpensFlags,
+  /// The modules the package is opened to, or every module if empty.
+  pub opens_to: Vec<String>
+}
+
+impl Opens {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    Ok(Opens {
+      opens: read_package(sr, constant_pool)?,
+      opens_flags: OpensFlags { mask: sr.get_u16()? },
+      opens_to: module_names(sr, constant_pool)?
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.package(&self.opens)?);
+    sw.put_u16(*self.opens_flags);
+    write_module_names(&self.opens_to, constant_pool, sw)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provides {
+  pub provides: Class,
+  pub provides_with: Vec<Class>
+}
+
+impl Provides {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let provides = read_class(sr, constant_pool)?;
+    let provides_with_count = sr.get_u16()?;
+    let provides_with: Vec<Class> =
+      (0..provides_with_count).map(|_| read_class(sr, constant_pool)).collect::<Result<_, _>>()?;
+
+    Ok(Provides { provides, provides_with })
+  }
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.class(&self.provides)?);
+    write_classes(&self.provides_with, constant_pool, sw)
+  }
+}
@@ -1,16 +1,33 @@
 use super::annotation::Annotation;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParameterAnnotation {
-  pub num_annotations: u16,
   pub annotations: Vec<Annotation>
 }
 
 impl ParameterAnnotation {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let num_annotations = sr.get_u16();
-    let annotations: Vec<Annotation> = (0..num_annotations).map(|_| Annotation::read(sr)).collect();
-    ParameterAnnotation { num_annotations, annotations }
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let num_annotations = sr.get_u16()?;
+    let annotations: Vec<Annotation> = (0..num_annotations)
+      .map(|_| Annotation::read(sr, constant_pool))
+      .collect::<Result<_, _>>()?;
+    Ok(ParameterAnnotation { annotations })
+  }
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_count(self.annotations.len())?;
+    self.annotations.iter().try_for_each(|annotation| annotation.write(constant_pool, sw))
   }
 }
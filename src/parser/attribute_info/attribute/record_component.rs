@@ -0,0 +1,58 @@
+use crate::parser::{
+  attribute_info::{
+    attribute::annotation::{self, Annotation},
+    attribute_registry::AttributeRegistry,
+    signature::find_signature,
+    Attribute
+  },
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  signature::FieldSignature,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordComponent {
+  pub name: String,
+  pub descriptor: String,
+  pub attributes: Vec<Attribute>
+}
+
+impl RecordComponent {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool,
+    registry: &AttributeRegistry
+  ) -> Result<Self, ClassFormatError> {
+    let name = constant_pool.get_utf8(sr.get_u16()?)?;
+    let descriptor = constant_pool.get_utf8(sr.get_u16()?)?;
+    let attributes_count = sr.get_u16()?;
+    let attributes: Vec<Attribute> = (0..attributes_count)
+      .map(|_| Attribute::read(sr, constant_pool, registry))
+      .collect::<Result<_, _>>()?;
+
+    Ok(RecordComponent { name, descriptor, attributes })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u16(constant_pool.utf8(&self.name)?);
+    sw.put_u16(constant_pool.utf8(&self.descriptor)?);
+    sw.put_count(self.attributes.len())?;
+    self.attributes.iter().try_for_each(|attribute| attribute.write(constant_pool, sw))
+  }
+
+  /// The generic component signature, if it has one.
+  pub fn signature(&self) -> Result<Option<FieldSignature>, ClassFormatError> {
+    find_signature(&self.attributes).map(FieldSignature::parse).transpose()
+  }
+
+  /// The annotation of the given type, such as `Lorg/junit/Test;`, visible or not.
+  pub fn annotation(&self, type_name: &str) -> Option<&Annotation> {
+    annotation::annotations(&self.attributes).find(|annotation| annotation.type_name == type_name)
+  }
+}
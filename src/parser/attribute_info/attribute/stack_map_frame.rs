@@ -1,9 +1,16 @@
 use self::verification_type_info::VerificationTypeInfo;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  attribute_info::code::offset_map::OffsetMap,
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-mod verification_type_info;
+pub mod frame;
+pub mod verification_type_info;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum StackMapFrame {
   SameFrame {
     frame_type: u8
@@ -41,35 +48,41 @@ pub enum StackMapFrame {
 }
 
 impl StackMapFrame {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let frame_type = sr.get_u8();
-    match frame_type {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let frame_type = sr.get_u8()?;
+    Ok(match frame_type {
       (0..=63) => StackMapFrame::SameFrame { frame_type },
       (64..=127) => StackMapFrame::SameLocals1StackItemFrame {
         frame_type,
-        stack: [VerificationTypeInfo::read(sr)]
+        stack: [VerificationTypeInfo::read(sr, constant_pool)?]
       },
       247 => StackMapFrame::SameLocals1StackItemFrameExtended {
         frame_type,
-        offset_delta: sr.get_u16(),
-        stack: [VerificationTypeInfo::read(sr)]
+        offset_delta: sr.get_u16()?,
+        stack: [VerificationTypeInfo::read(sr, constant_pool)?]
       },
-      (248..=250) => StackMapFrame::ChopFrame { frame_type, offset_delta: sr.get_u16() },
-      251 => StackMapFrame::SameFrameExtended { frame_type, offset_delta: sr.get_u16() },
+      (248..=250) => StackMapFrame::ChopFrame { frame_type, offset_delta: sr.get_u16()? },
+      251 => StackMapFrame::SameFrameExtended { frame_type, offset_delta: sr.get_u16()? },
       (252..=254) => {
-        let offset_delta = sr.get_u16();
-        let locals: Vec<VerificationTypeInfo> =
-          (0..frame_type - 251).map(|_| VerificationTypeInfo::read(sr)).collect();
+        let offset_delta = sr.get_u16()?;
+        let locals: Vec<VerificationTypeInfo> = (0..frame_type - 251)
+          .map(|_| VerificationTypeInfo::read(sr, constant_pool))
+          .collect::<Result<_, _>>()?;
         StackMapFrame::AppendFrame { frame_type, offset_delta, locals }
       }
       255 => {
-        let offset_delta = sr.get_u16();
-        let number_of_locals = sr.get_u16();
-        let locals: Vec<VerificationTypeInfo> =
-          (0..number_of_locals).map(|_| VerificationTypeInfo::read(sr)).collect();
-        let number_of_stack_items = sr.get_u16();
-        let stack: Vec<VerificationTypeInfo> =
-          (0..number_of_stack_items).map(|_| VerificationTypeInfo::read(sr)).collect();
+        let offset_delta = sr.get_u16()?;
+        let number_of_locals = sr.get_u16()?;
+        let locals: Vec<VerificationTypeInfo> = (0..number_of_locals)
+          .map(|_| VerificationTypeInfo::read(sr, constant_pool))
+          .collect::<Result<_, _>>()?;
+        let number_of_stack_items = sr.get_u16()?;
+        let stack: Vec<VerificationTypeInfo> = (0..number_of_stack_items)
+          .map(|_| VerificationTypeInfo::read(sr, constant_pool))
+          .collect::<Result<_, _>>()?;
         StackMapFrame::FullFrame {
           frame_type,
           offset_delta,
@@ -79,7 +92,138 @@ impl StackMapFrame {
           stack
         }
       }
-      _ => panic!("This tag is reserved for future use")
+      _ => return Err(ClassFormatError::BadStackMapFrameType { frame_type })
+    })
+  }
+
+  /// Writes the frame with the frame type its contents call for. Only `ChopFrame` relies on
+  /// `frame_type`, for the number of locals it chops.
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    match self {
+      StackMapFrame::SameFrame { frame_type } => sw.put_u8(*frame_type),
+      StackMapFrame::SameLocals1StackItemFrame { frame_type, stack } => {
+        sw.put_u8(*frame_type);
+        stack[0].write(constant_pool, sw)?;
+      }
+      StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, stack, .. } => {
+        sw.put_u8(247);
+        sw.put_u16(*offset_delta);
+        stack[0].write(constant_pool, sw)?;
+      }
+      StackMapFrame::ChopFrame { frame_type, offset_delta } => {
+        sw.put_u8(*frame_type);
+        sw.put_u16(*offset_delta);
+      }
+      StackMapFrame::SameFrameExtended { offset_delta, .. } => {
+        sw.put_u8(251);
+        sw.put_u16(*offset_delta);
+      }
+      StackMapFrame::AppendFrame { offset_delta, locals, .. } => {
+        sw.put_u8(251 + locals.len() as u8);
+        sw.put_u16(*offset_delta);
+        locals.iter().try_for_each(|local| local.write(constant_pool, sw))?;
+      }
+      StackMapFrame::FullFrame { offset_delta, locals, stack, .. } => {
+        sw.put_u8(255);
+        sw.put_u16(*offset_delta);
+        sw.put_count(locals.len())?;
+        locals.iter().try_for_each(|local| local.write(constant_pool, sw))?;
+        sw.put_count(stack.len())?;
+        stack.iter().try_for_each(|item| item.write(constant_pool, sw))?;
+      }
+    }
+    Ok(())
+  }
+
+  /// The distance from the previous frame, which the compact frame types keep in `frame_type`.
+  pub fn offset_delta(&self) -> u16 {
+    match self {
+      StackMapFrame::SameFrame { frame_type } => *frame_type as u16,
+      StackMapFrame::SameLocals1StackItemFrame { frame_type, .. } => *frame_type as u16 - 64,
+      StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. }
+      | StackMapFrame::ChopFrame { offset_delta, .. }
+      | StackMapFrame::SameFrameExtended { offset_delta, .. }
+      | StackMapFrame::AppendFrame { offset_delta, .. }
+      | StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta
+    }
+  }
+
+  /// Replaces the offset delta with `index_delta`, the distance in instructions, and the offsets
+  /// of `new` instructions with their indices.
+  /// An instruction takes at least a byte, so `index_delta` always fits the frame's encoding.
+  pub fn translate(
+    &mut self,
+    index_delta: u16,
+    offsets: &OffsetMap
+  ) -> Result<(), ClassFormatError> {
+    match self {
+      StackMapFrame::SameFrame { frame_type } => *frame_type = index_delta as u8,
+      StackMapFrame::SameLocals1StackItemFrame { frame_type, stack } => {
+        *frame_type = 64 + index_delta as u8;
+        stack[0].translate(offsets)?;
+      }
+      StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, stack, .. } => {
+        *offset_delta = index_delta;
+        stack[0].translate(offsets)?;
+      }
+      StackMapFrame::ChopFrame { offset_delta, .. }
+      | StackMapFrame::SameFrameExtended { offset_delta, .. } => *offset_delta = index_delta,
+      StackMapFrame::AppendFrame { offset_delta, locals, .. } => {
+        *offset_delta = index_delta;
+        locals.iter_mut().try_for_each(|local| local.translate(offsets))?;
+      }
+      StackMapFrame::FullFrame { offset_delta, locals, stack, .. } => {
+        *offset_delta = index_delta;
+        locals.iter_mut().chain(stack.iter_mut()).try_for_each(|item| item.translate(offsets))?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Replaces the index delta with `offset_delta`, the distance in bytes, and the indices of `new`
+  /// instructions with their offsets, undoing `translate`.
+  /// A compact frame whose `offset_delta` no longer fits its frame type becomes the extended form.
+  pub fn untranslate(
+    &mut self,
+    offset_delta: u16,
+    offsets: &OffsetMap
+  ) -> Result<(), ClassFormatError> {
+    match self {
+      StackMapFrame::SameFrame { .. } if offset_delta > 63 => {
+        *self = StackMapFrame::SameFrameExtended { frame_type: 251, offset_delta }
+      }
+      StackMapFrame::SameFrame { frame_type } => *frame_type = offset_delta as u8,
+      StackMapFrame::SameLocals1StackItemFrame { stack, .. } if offset_delta > 63 => {
+        stack[0].untranslate(offsets)?;
+        *self = StackMapFrame::SameLocals1StackItemFrameExtended {
+          frame_type: 247,
+          offset_delta,
+          stack: stack.clone()
+        };
+      }
+      StackMapFrame::SameLocals1StackItemFrame { frame_type, stack } => {
+        *frame_type = 64 + offset_delta as u8;
+        stack[0].untranslate(offsets)?;
+      }
+      StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta: delta, stack, .. } => {
+        *delta = offset_delta;
+        stack[0].untranslate(offsets)?;
+      }
+      StackMapFrame::ChopFrame { offset_delta: delta, .. }
+      | StackMapFrame::SameFrameExtended { offset_delta: delta, .. } => *delta = offset_delta,
+      StackMapFrame::AppendFrame { offset_delta: delta, locals, .. } => {
+        *delta = offset_delta;
+        locals.iter_mut().try_for_each(|local| local.untranslate(offsets))?;
+      }
+      StackMapFrame::FullFrame { offset_delta: delta, locals, stack, .. } => {
+        *delta = offset_delta;
+        locals.iter_mut().chain(stack.iter_mut()).try_for_each(|item| item.untranslate(offsets))?;
+      }
     }
+    Ok(())
   }
 }
@@ -0,0 +1,189 @@
+use super::{verification_type_info::VerificationTypeInfo, StackMapFrame};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  cp_info_resolved::Class,
+  descriptor::MethodDescriptor
+};
+
+/// The types of the local variables and operand stack at an instruction, one per slot.
+/// A long or double is followed by a `TopVariable` standing in for its second slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+  pub locals: Vec<VerificationTypeInfo>,
+  pub stack: Vec<VerificationTypeInfo>
+}
+
+impl Frame {
+  /// Lays out values listed the way StackMapTable entries list them, one item per value,
+  /// padding the locals with `TopVariable` up to `max_locals`.
+  pub fn new(
+    locals: &[VerificationTypeInfo],
+    stack: &[VerificationTypeInfo],
+    max_locals: u16
+  ) -> Self {
+    let mut locals = slots(locals);
+    if locals.len() < max_locals as usize {
+      locals.resize(max_locals as usize, VerificationTypeInfo::TopVariable);
+    }
+    Frame { locals, stack: slots(stack) }
+  }
+}
+
+fn slots(values: &[VerificationTypeInfo]) -> Vec<VerificationTypeInfo> {
+  values
+    .iter()
+    .flat_map(|value| match value.slots() {
+      2 => vec![value.clone(), VerificationTypeInfo::TopVariable],
+      _ => vec![value.clone()]
+    })
+    .collect()
+}
+
+/// The local variables on entry to a method, one item per value (JVMS §4.10.1.6).
+/// `this` starts out uninitialized in every constructor except `java/lang/Object`'s.
+pub fn initial_locals(
+  class: &Class,
+  name: &str,
+  is_static: bool,
+  descriptor: &MethodDescriptor
+) -> Vec<VerificationTypeInfo> {
+  let this = match (is_static, name) {
+    (true, _) => None,
+    (false, "<init>") if class.name != "java/lang/Object" => {
+      Some(VerificationTypeInfo::UninitializedThisVariable)
+    }
+    (false, _) => Some(VerificationTypeInfo::ObjectVariable { class: class.clone() })
+  };
+  this.into_iter().chain(descriptor.params.iter().map(VerificationTypeInfo::from)).collect()
+}
+
+/// Replays the StackMapTable from the locals on entry, giving each entry's full frame along with
+/// the instruction index it applies to.
+pub fn expand(
+  initial_locals: Vec<VerificationTypeInfo>,
+  entries: &[StackMapFrame],
+  max_locals: u16
+) -> Result<Vec<(usize, Frame)>, ClassFormatError> {
+  let mut locals = initial_locals;
+  let mut previous: Option<usize> = None;
+  entries
+    .iter()
+    .map(|entry| {
+      let index = match previous {
+        Some(previous) => previous + entry.offset_delta() as usize + 1,
+        None => entry.offset_delta() as usize
+      };
+      previous = Some(index);
+      let stack = match entry {
+        StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => Vec::new(),
+        StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+        | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => stack.to_vec(),
+        StackMapFrame::ChopFrame { frame_type, .. } => {
+          let chopped = 251 - *frame_type as usize;
+          if chopped > locals.len() {
+            return Err(ClassFormatError::BadChopFrame { index });
+          }
+          locals.truncate(locals.len() - chopped);
+          Vec::new()
+        }
+        StackMapFrame::AppendFrame { locals: appended, .. } => {
+          locals.extend(appended.iter().cloned());
+          Vec::new()
+        }
+        StackMapFrame::FullFrame { locals: full, stack, .. } => {
+          locals = full.clone();
+          stack.clone()
+        }
+      };
+      Ok((index, Frame::new(&locals, &stack, max_locals)))
+    })
+    .collect()
+}
+
+/// Encodes full frames as StackMapTable entries, the inverse of `expand`, each in the most compact
+/// form that describes it.
+/// `frames` must be in instruction order, and `initial_locals` listed as `initial_locals` lists
+/// them.
+pub fn compress(
+  initial_locals: &[VerificationTypeInfo],
+  frames: &[(usize, Frame)]
+) -> Vec<StackMapFrame> {
+  let mut locals = initial_locals.to_vec();
+  let mut previous: Option<usize> = None;
+  frames
+    .iter()
+    .map(|(index, frame)| {
+      let offset_delta = match previous {
+        Some(previous) => (index - previous - 1) as u16,
+        None => *index as u16
+      };
+      previous = Some(*index);
+      let mut current = values(&frame.locals);
+      // The locals after the last one in use are padding, which `Frame::new` adds back
+      while current.last() == Some(&VerificationTypeInfo::TopVariable) {
+        current.pop();
+      }
+      let stack = values(&frame.stack);
+      let compact = offset_delta <= 63;
+      let entry = match stack.len() {
+        0 if current == locals && compact => {
+          StackMapFrame::SameFrame { frame_type: offset_delta as u8 }
+        }
+        0 if current == locals => {
+          StackMapFrame::SameFrameExtended { frame_type: 251, offset_delta }
+        }
+        1 if current == locals && compact => StackMapFrame::SameLocals1StackItemFrame {
+          frame_type: 64 + offset_delta as u8,
+          stack: [stack[0].clone()]
+        },
+        1 if current == locals => StackMapFrame::SameLocals1StackItemFrameExtended {
+          frame_type: 247,
+          offset_delta,
+          stack: [stack[0].clone()]
+        },
+        0 if current.len() < locals.len()
+          && locals.len() - current.len() <= 3
+          && locals.starts_with(&current) =>
+        {
+          StackMapFrame::ChopFrame {
+            frame_type: (251 - (locals.len() - current.len())) as u8,
+            offset_delta
+          }
+        }
+        0 if current.len() > locals.len()
+          && current.len() - locals.len() <= 3
+          && current.starts_with(&locals) =>
+        {
+          StackMapFrame::AppendFrame {
+            frame_type: (251 + current.len() - locals.len()) as u8,
+            offset_delta,
+            locals: current[locals.len()..].to_vec()
+          }
+        }
+        _ => StackMapFrame::FullFrame {
+          frame_type: 255,
+          offset_delta,
+          number_of_locals: current.len() as u16,
+          locals: current.clone(),
+          number_of_stack_items: stack.len() as u16,
+          stack
+        }
+      };
+      locals = current;
+      entry
+    })
+    .collect()
+}
+
+/// Lists slots the way StackMapTable entries do, one item per value, the inverse of `slots`.
+fn values(slots: &[VerificationTypeInfo]) -> Vec<VerificationTypeInfo> {
+  let mut values = Vec::new();
+  let mut slots = slots.iter();
+  while let Some(slot) = slots.next() {
+    if slot.slots() == 2 {
+      slots.next();
+    }
+    values.push(slot.clone());
+  }
+  values
+}
@@ -1,6 +1,14 @@
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  attribute_info::code::offset_map::OffsetMap,
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  descriptor::FieldType,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VerificationTypeInfo {
   TopVariable,
   IntegerVariable,
@@ -9,14 +17,24 @@ pub enum VerificationTypeInfo {
   DoubleVariable,
   NullVariable,
   UninitializedThisVariable,
-  ObjectVariable { cpool_index: u16 },
-  UninitializedVariable { offset: u16 }
+  /// Array classes are named by their descriptor, such as `[I`.
+  ObjectVariable {
+    class: Class
+  },
+  /// `offset` locates the `new` instruction that created the object, as an instruction index once
+  /// the enclosing `Code` has been read.
+  UninitializedVariable {
+    offset: u16
+  }
 }
 
 impl VerificationTypeInfo {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let tag = sr.get_u8();
-    match tag {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let tag = sr.get_u8()?;
+    Ok(match tag {
       0 => VerificationTypeInfo::TopVariable,
       1 => VerificationTypeInfo::IntegerVariable,
       2 => VerificationTypeInfo::FloatVariable,
@@ -24,9 +42,75 @@ impl VerificationTypeInfo {
       4 => VerificationTypeInfo::LongVariable,
       5 => VerificationTypeInfo::NullVariable,
       6 => VerificationTypeInfo::UninitializedThisVariable,
-      7 => VerificationTypeInfo::ObjectVariable { cpool_index: sr.get_u16() },
-      8 => VerificationTypeInfo::UninitializedVariable { offset: sr.get_u16() },
-      _ => panic!("Invalid VarificationTypeInfo")
+      7 => VerificationTypeInfo::ObjectVariable {
+        class: constant_pool.get_class(sr.get_u16()?)?.clone()
+      },
+      8 => VerificationTypeInfo::UninitializedVariable { offset: sr.get_u16()? },
+      _ => return Err(ClassFormatError::BadVerificationType { tag })
+    })
+  }
+
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    match self {
+      VerificationTypeInfo::TopVariable => sw.put_u8(0),
+      VerificationTypeInfo::IntegerVariable => sw.put_u8(1),
+      VerificationTypeInfo::FloatVariable => sw.put_u8(2),
+      VerificationTypeInfo::DoubleVariable => sw.put_u8(3),
+      VerificationTypeInfo::LongVariable => sw.put_u8(4),
+      VerificationTypeInfo::NullVariable => sw.put_u8(5),
+      VerificationTypeInfo::UninitializedThisVariable => sw.put_u8(6),
+      VerificationTypeInfo::ObjectVariable { class } => {
+        sw.put_u8(7);
+        sw.put_u16(constant_pool.class(class)?);
+      }
+      VerificationTypeInfo::UninitializedVariable { offset } => {
+        sw.put_u8(8);
+        sw.put_u16(*offset);
+      }
+    }
+    Ok(())
+  }
+
+  /// The type a value of `field_type` has on the operand stack or in a local variable.
+  pub fn from(field_type: &FieldType) -> Self {
+    match field_type {
+      FieldType::Byte
+      | FieldType::Char
+      | FieldType::Int
+      | FieldType::Short
+      | FieldType::Boolean => VerificationTypeInfo::IntegerVariable,
+      FieldType::Float => VerificationTypeInfo::FloatVariable,
+      FieldType::Long => VerificationTypeInfo::LongVariable,
+      FieldType::Double => VerificationTypeInfo::DoubleVariable,
+      FieldType::Object(name) => {
+        VerificationTypeInfo::ObjectVariable { class: Class { name: name.clone() } }
+      }
+      array => VerificationTypeInfo::ObjectVariable { class: Class { name: array.to_string() } }
+    }
+  }
+
+  /// Longs and doubles take two slots, the second of which is `TopVariable`.
+  pub fn slots(&self) -> usize {
+    match self {
+      VerificationTypeInfo::LongVariable | VerificationTypeInfo::DoubleVariable => 2,
+      _ => 1
+    }
+  }
+
+  pub fn translate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    if let VerificationTypeInfo::UninitializedVariable { offset } = self {
+      *offset = offsets.index(*offset)?;
+    }
+    Ok(())
+  }
+  pub fn untranslate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    if let VerificationTypeInfo::UninitializedVariable { offset } = self {
+      *offset = offsets.offset(*offset)?;
     }
+    Ok(())
   }
 }
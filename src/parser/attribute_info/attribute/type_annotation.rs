@@ -0,0 +1,216 @@
+use super::annotation::Annotation;
+use crate::parser::{
+  attribute_info::code::offset_map::OffsetMap,
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+/// Which type in a declaration or expression is annotated, keyed by `target_type` (JVMS §4.7.20.1).
+/// The offsets of targets inside code are instruction indices once the enclosing `Code` has been
+/// read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetInfo {
+  TypeParameter { type_parameter_index: u8 },
+  Supertype { supertype_index: u16 },
+  TypeParameterBound { type_parameter_index: u8, bound_index: u8 },
+  Empty,
+  FormalParameter { formal_parameter_index: u8 },
+  Throws { throws_type_index: u16 },
+  Localvar { table: Vec<LocalvarTarget> },
+  Catch { exception_table_index: u16 },
+  Offset { offset: u16 },
+  TypeArgument { offset: u16, type_argument_index: u8 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalvarTarget {
+  /// Instruction index, once the enclosing `Code` has been read.
+  pub start_pc: u16,
+  /// The number of instructions in scope, once the enclosing `Code` has been read.
+  pub length: u16,
+  pub index: u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypePathKind {
+  Array,
+  Nested,
+  WildcardBound,
+  TypeArgument
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypePathEntry {
+  pub type_path_kind: TypePathKind,
+  pub type_argument_index: u8
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAnnotation {
+  pub target_type: u8,
+  pub target_info: TargetInfo,
+  pub type_path: Vec<TypePathEntry>,
+  pub annotation: Annotation
+}
+
+impl TargetInfo {
+  pub fn read(sr: &mut StreamReader, target_type: u8) -> Result<Self, ClassFormatError> {
+    Ok(match target_type {
+      0x00 | 0x01 => TargetInfo::TypeParameter { type_parameter_index: sr.get_u8()? },
+      0x10 => TargetInfo::Supertype { supertype_index: sr.get_u16()? },
+      0x11 | 0x12 => TargetInfo::TypeParameterBound {
+        type_parameter_index: sr.get_u8()?,
+        bound_index: sr.get_u8()?
+      },
+      0x13..=0x15 => TargetInfo::Empty,
+      0x16 => TargetInfo::FormalParameter { formal_parameter_index: sr.get_u8()? },
+      0x17 => TargetInfo::Throws { throws_type_index: sr.get_u16()? },
+      0x40 | 0x41 => {
+        let table_length = sr.get_u16()?;
+        let table: Vec<LocalvarTarget> = (0..table_length)
+          .map(|_| {
+            Ok(LocalvarTarget {
+              start_pc: sr.get_u16()?,
+              length: sr.get_u16()?,
+              index: sr.get_u16()?
+            })
+          })
+          .collect::<Result<_, ClassFormatError>>()?;
+        TargetInfo::Localvar { table }
+      }
+      0x42 => TargetInfo::Catch { exception_table_index: sr.get_u16()? },
+      0x43..=0x46 => TargetInfo::Offset { offset: sr.get_u16()? },
+      0x47..=0x4b => {
+        TargetInfo::TypeArgument { offset: sr.get_u16()?, type_argument_index: sr.get_u8()? }
+      }
+      _ => return Err(ClassFormatError::BadTargetType { target_type })
+    })
+  }
+
+  pub fn write(&self, sw: &mut StreamWriter) -> Result<(), ClassFormatError> {
+    match self {
+      TargetInfo::TypeParameter { type_parameter_index } => sw.put_u8(*type_parameter_index),
+      TargetInfo::Supertype { supertype_index } => sw.put_u16(*supertype_index),
+      TargetInfo::TypeParameterBound { type_parameter_index, bound_index } => {
+        sw.put_u8(*type_parameter_index);
+        sw.put_u8(*bound_index);
+      }
+      TargetInfo::Empty => {}
+      TargetInfo::FormalParameter { formal_parameter_index } => sw.put_u8(*formal_parameter_index),
+      TargetInfo::Throws { throws_type_index } => sw.put_u16(*throws_type_index),
+      TargetInfo::Localvar { table } => {
+        sw.put_count(table.len())?;
+        for LocalvarTarget { start_pc, length, index } in table {
+          sw.put_u16(*start_pc);
+          sw.put_u16(*length);
+          sw.put_u16(*index);
+        }
+      }
+      TargetInfo::Catch { exception_table_index } => sw.put_u16(*exception_table_index),
+      TargetInfo::Offset { offset } => sw.put_u16(*offset),
+      TargetInfo::TypeArgument { offset, type_argument_index } => {
+        sw.put_u16(*offset);
+        sw.put_u8(*type_argument_index);
+      }
+    }
+    Ok(())
+  }
+
+  /// Replaces the byte offsets of the targets inside code with instruction indices.
+  /// Targets outside code hold no offsets and are left alone.
+  pub fn translate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    match self {
+      TargetInfo::Localvar { table } => {
+        table.iter_mut().try_for_each(|variable| variable.translate(offsets))
+      }
+      TargetInfo::Offset { offset } | TargetInfo::TypeArgument { offset, .. } => {
+        *offset = offsets.index(*offset)?;
+        Ok(())
+      }
+      _ => Ok(())
+    }
+  }
+
+  /// Replaces the instruction indices with byte offsets, undoing `translate`.
+  pub fn untranslate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    match self {
+      TargetInfo::Localvar { table } => {
+        table.iter_mut().try_for_each(|variable| variable.untranslate(offsets))
+      }
+      TargetInfo::Offset { offset } | TargetInfo::TypeArgument { offset, .. } => {
+        *offset = offsets.offset(*offset)?;
+        Ok(())
+      }
+      _ => Ok(())
+    }
+  }
+}
+
+impl LocalvarTarget {
+  /// The variable is in scope up to, but not including, `start_pc + length`.
+  pub fn translate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    let start_pc = offsets.index(self.start_pc)?;
+    let end_pc = offsets.end_index(self.start_pc as usize + self.length as usize)?;
+    self.start_pc = start_pc;
+    self.length = end_pc - start_pc;
+    Ok(())
+  }
+
+  /// Replaces the instruction indices with byte offsets, undoing `translate`.
+  pub fn untranslate(&mut self, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+    let start_pc = offsets.offset(self.start_pc)?;
+    let end_pc = offsets.end_offset(self.start_pc as usize + self.length as usize)?;
+    self.start_pc = start_pc;
+    self.length = end_pc - start_pc;
+    Ok(())
+  }
+}
+
+impl TypePathEntry {
+  pub fn read(sr: &mut StreamReader) -> Result<Self, ClassFormatError> {
+    let type_path_kind = match sr.get_u8()? {
+      0 => TypePathKind::Array,
+      1 => TypePathKind::Nested,
+      2 => TypePathKind::WildcardBound,
+      3 => TypePathKind::TypeArgument,
+      kind => return Err(ClassFormatError::BadTypePathKind { kind })
+    };
+    Ok(TypePathEntry { type_path_kind, type_argument_index: sr.get_u8()? })
+  }
+
+  pub fn write(&self, sw: &mut StreamWriter) {
+    sw.put_u8(self.type_path_kind as u8);
+    sw.put_u8(self.type_argument_index);
+  }
+}
+
+impl TypeAnnotation {
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool
+  ) -> Result<Self, ClassFormatError> {
+    let target_type = sr.get_u8()?;
+    let target_info = TargetInfo::read(sr, target_type)?;
+    let path_length = sr.get_u8()?;
+    let type_path: Vec<TypePathEntry> =
+      (0..path_length).map(|_| TypePathEntry::read(sr)).collect::<Result<_, _>>()?;
+    let annotation = Annotation::read(sr, constant_pool)?;
+
+    Ok(TypeAnnotation { target_type, target_info, type_path, annotation })
+  }
+  pub fn write(
+    &self,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    sw.put_u8(self.target_type);
+    self.target_info.write(sw)?;
+    sw.put_short_count(self.type_path.len())?;
+    for entry in &self.type_path {
+      entry.write(sw);
+    }
+    self.annotation.write(constant_pool, sw)
+  }
+}
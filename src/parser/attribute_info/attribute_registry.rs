@@ -0,0 +1,184 @@
+use std::{any::Any, collections::HashMap, fmt, rc::Rc};
+
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder}
+};
+
+/// The decoded form of an attribute the parser itself doesn't understand.
+/// Anything `Debug` and `PartialEq` qualifies, and `Attribute::Custom` hands it back through
+/// `downcast_ref`.
+pub trait CustomAttribute: fmt::Debug {
+  fn as_any(&self) -> &dyn Any;
+
+  /// Whether `other` is a value of the same type that is equal to this one.
+  fn eq_custom(&self, other: &dyn CustomAttribute) -> bool;
+}
+
+impl<T: fmt::Debug + PartialEq + Any> CustomAttribute for T {
+  fn as_any(&self) -> &dyn Any { self }
+
+  fn eq_custom(&self, other: &dyn CustomAttribute) -> bool {
+    other.as_any().downcast_ref::<T>() == Some(self)
+  }
+}
+
+impl PartialEq for dyn CustomAttribute {
+  fn eq(&self, other: &Self) -> bool { self.eq_custom(other) }
+}
+
+/// Decodes the `info` bytes of a custom attribute, which exclude its name index and length.
+pub type AttributeDecoder =
+  fn(&[u8], &ConstantPool) -> Result<Rc<dyn CustomAttribute>, ClassFormatError>;
+
+/// Encodes the value of a custom attribute back into its `info` bytes, adding any constants it
+/// refers to. Encoders take no part in comparing attributes, which compare by value.
+#[derive(Clone, Copy)]
+pub struct AttributeEncoder(
+  pub fn(&dyn CustomAttribute, &mut ConstantPoolBuilder) -> Result<Vec<u8>, ClassFormatError>
+);
+
+impl fmt::Debug for AttributeEncoder {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str("AttributeEncoder") }
+}
+
+impl PartialEq for AttributeEncoder {
+  fn eq(&self, _: &Self) -> bool { true }
+}
+
+/// Decoders for attributes outside the JVM specification, keyed by attribute name, each with the
+/// encoder that writes it back if there is one.
+/// Attributes with no decoder registered are kept as `Attribute::Unknown`.
+#[derive(Debug, Clone, Default)]
+pub struct AttributeRegistry {
+  decoders: HashMap<String, (AttributeDecoder, Option<AttributeEncoder>)>
+}
+
+impl AttributeRegistry {
+  pub fn new() -> Self { Self::default() }
+
+  /// Registers a decoder, replacing any previously registered under the same name.
+  /// Decoders are never consulted for the attributes defined by the JVM specification.
+  /// Attributes it decodes can't be written, as with `register_with_encoder`.
+  pub fn register(&mut self, name: impl Into<String>, decoder: AttributeDecoder) -> &mut Self {
+    self.decoders.insert(name.into(), (decoder, None));
+    self
+  }
+
+  /// Registers a decoder along with the encoder `ClassFile::write` uses for the attributes it
+  /// decodes.
+  pub fn register_with_encoder(
+    &mut self,
+    name: impl Into<String>,
+    decoder: AttributeDecoder,
+    encoder: AttributeEncoder
+  ) -> &mut Self {
+    self.decoders.insert(name.into(), (decoder, Some(encoder)));
+    self
+  }
+
+  pub fn get(&self, name: &str) -> Option<(AttributeDecoder, Option<AttributeEncoder>)> {
+    self.decoders.get(name).copied()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::{
+    attribute_info::Attribute,
+    classfile::ClassFile,
+    stream_reader::StreamReader,
+    stream_writer::StreamWriter
+  };
+
+  #[derive(Debug, PartialEq)]
+  struct Answer(u32);
+
+  fn answer(info: &[u8], _: &ConstantPool) -> Result<Rc<dyn CustomAttribute>, ClassFormatError> {
+    Ok(Rc::new(Answer(StreamReader::from(info).get_u32()?)))
+  }
+
+  fn encode_answer(
+    value: &dyn CustomAttribute,
+    _: &mut ConstantPoolBuilder
+  ) -> Result<Vec<u8>, ClassFormatError> {
+    let Answer(answer) = value.as_any().downcast_ref::<Answer>().unwrap();
+    Ok(answer.to_be_bytes().to_vec())
+  }
+
+  /// A class `C` with nothing in it but an `Answer` attribute holding 42.
+  fn class_with_answer() -> Vec<u8> {
+    let mut sw = StreamWriter::new();
+    sw.put_u32(0xcafebabe);
+    sw.put_u16(0);
+    sw.put_u16(52);
+    sw.put_u16(6);
+    for (index, name) in [(1, "C"), (3, "java/lang/Object")] {
+      sw.put_u8(1);
+      sw.put_u16(name.len() as u16);
+      sw.put_bytes(name.as_bytes());
+      sw.put_u8(7);
+      sw.put_u16(index);
+    }
+    sw.put_u8(1);
+    sw.put_u16(6);
+    sw.put_bytes(b"Answer");
+    // public super, this class, super class, then no interfaces, fields or methods
+    for value in [0x21, 2, 4, 0, 0, 0] {
+      sw.put_u16(value);
+    }
+    sw.put_u16(1);
+    sw.put_u16(5);
+    sw.put_u32(4);
+    sw.put_u32(42);
+    sw.stream
+  }
+
+  #[test]
+  fn registered_decoder_reads_attribute() {
+    let bytes = class_with_answer();
+    let mut registry = AttributeRegistry::new();
+    registry.register("Answer", answer);
+    let (class, _) = ClassFile::parse_with(&bytes, &registry).unwrap();
+    assert_eq!(class.attributes[0].downcast_ref::<Answer>(), Some(&Answer(42)));
+    assert_eq!(class.attributes[0].downcast_ref::<u32>(), None);
+    assert_eq!(class, ClassFile::parse_with(&bytes, &registry).unwrap().0);
+
+    let (class, _) = ClassFile::parse(&bytes).unwrap();
+    assert!(matches!(&class.attributes[0], Attribute::Unknown { name, .. } if name == "Answer"));
+  }
+
+  #[test]
+  fn undecoded_attributes_are_not_written() {
+    let bytes = class_with_answer();
+    let mut registry = AttributeRegistry::new();
+    registry.register("Answer", answer);
+    for parsed in [ClassFile::parse_with(&bytes, &registry), ClassFile::parse(&bytes)] {
+      assert_eq!(
+        parsed.unwrap().0.write(),
+        Err(ClassFormatError::UnwritableAttribute { name: "Answer".to_string() })
+      );
+    }
+  }
+
+  #[test]
+  fn registered_encoder_writes_attribute() {
+    let bytes = class_with_answer();
+    let mut registry = AttributeRegistry::new();
+    registry.register_with_encoder("Answer", answer, AttributeEncoder(encode_answer));
+    let (class, _) = ClassFile::parse_with(&bytes, &registry).unwrap();
+    let written = class.write().unwrap();
+    let (reparsed, _) = ClassFile::parse_with(&written, &registry).unwrap();
+    assert_eq!(reparsed.attributes[0].downcast_ref::<Answer>(), Some(&Answer(42)));
+    assert_eq!(reparsed, class);
+  }
+
+  #[test]
+  fn values_compare_by_type_and_contents() {
+    let answer: Rc<dyn CustomAttribute> = Rc::new(Answer(42));
+    assert!(answer == Rc::new(Answer(42)) as Rc<dyn CustomAttribute>);
+    assert!(answer != Rc::new(Answer(7)) as Rc<dyn CustomAttribute>);
+    assert!(answer != Rc::new(42u32) as Rc<dyn CustomAttribute>);
+  }
+}
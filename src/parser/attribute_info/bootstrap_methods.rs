@@ -1,10 +1,39 @@
-use super::{attribute::bootstrap_method::BootstrapMethod, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use super::{
+  attribute::bootstrap_method::{BootstrapMethod, RawBootstrapMethod},
+  Attribute
+};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  let num_bootstrap_methods = sr.get_u16();
-  let bootstrap_methods: Vec<BootstrapMethod> =
-    (0..num_bootstrap_methods).map(|_| BootstrapMethod::read(sr)).collect();
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let num_bootstrap_methods = sr.get_u16()?;
+  let bootstrap_methods: Vec<BootstrapMethod> = (0..num_bootstrap_methods)
+    .map(|_| BootstrapMethod::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
 
-  Attribute::BootstrapMethods { bootstrap_methods }
+  Ok(Attribute::BootstrapMethods { bootstrap_methods })
+}
+
+pub fn read_raw(sr: &mut StreamReader) -> Result<Vec<RawBootstrapMethod>, ClassFormatError> {
+  let num_bootstrap_methods = sr.get_u16()?;
+  (0..num_bootstrap_methods).map(|_| RawBootstrapMethod::read(sr)).collect()
+}
+
+/// Writes the bootstrap methods the constant pool has gathered, rather than the attribute's own
+/// list. Dynamic constants refer to them by index, so only the pool knows the final table, which
+/// keeps each declared method at its index and adds any the code needs besides.
+pub fn write(
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  let bootstrap_methods = constant_pool.bootstrap_methods()?;
+  sw.put_count(bootstrap_methods.len())?;
+  bootstrap_methods.iter().try_for_each(|bootstrap_method| bootstrap_method.write(sw))
 }
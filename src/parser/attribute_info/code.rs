@@ -2,28 +2,126 @@ use std::collections::BTreeMap;
 
 use self::{
   clean_jumps::clean,
-  code_generator::{generate_instructions, Instructions}
+  code_generator::{generate_instructions, Instructions},
+  code_writer::{layout, write_instructions},
+  offset_map::OffsetMap
+};
+use super::{
+  attribute::exception::Exception,
+  attribute_registry::AttributeRegistry,
+  stack_map_table,
+  Attribute
+};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
 };
-use super::{attribute::exception::Exception, Attribute};
-use crate::parser::{cp_info_resolved::ResolvedCpInfo, stream_reader::StreamReader};
 
 pub mod clean_jumps;
 pub mod code_generator;
+pub mod code_writer;
+pub mod offset_map;
 
-pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> Attribute {
-  let max_stack = sr.get_u16();
-  let max_locals = sr.get_u16();
-  let code_length = sr.get_u32();
-  let raw_code = sr.take_n(code_length as usize);
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool,
+  registry: &AttributeRegistry
+) -> Result<Attribute, ClassFormatError> {
+  let max_stack = sr.get_u16()?;
+  let max_locals = sr.get_u16()?;
+  let code_length = sr.get_u32()?;
+  let mut code_reader = sr.sub_reader(code_length as usize)?;
   let tuple_code: BTreeMap<usize, (usize, Instructions)> =
-    generate_instructions(&mut StreamReader::from(raw_code), constant_pool).into_iter().collect();
-  let code = clean(tuple_code);
-  let exception_table_length = sr.get_u16();
-  let exception_table: Vec<Exception> =
-    (0..exception_table_length).map(|_| Exception::read(sr)).collect();
-  let attributes_count = sr.get_u16();
-  let attributes: Vec<Attribute> =
-    (0..attributes_count).map(|_| Attribute::read(sr, constant_pool)).collect();
+    generate_instructions(&mut code_reader, constant_pool)?.into_iter().collect();
+  let offsets = OffsetMap::new(tuple_code.keys().copied().collect(), code_length as usize);
+  let code = clean(tuple_code)?;
+  let exception_table_length = sr.get_u16()?;
+  let mut exception_table: Vec<Exception> = (0..exception_table_length)
+    .map(|_| Exception::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
+  exception_table.iter_mut().try_for_each(|exception| exception.translate(&offsets))?;
+  let attributes_count = sr.get_u16()?;
+  let mut attributes: Vec<Attribute> = (0..attributes_count)
+    .map(|_| Attribute::read(sr, constant_pool, registry))
+    .collect::<Result<_, _>>()?;
+  attributes.iter_mut().try_for_each(|attribute| translate(attribute, &offsets))?;
+
+  Ok(Attribute::Code { max_stack, max_locals, code, exception_table, attributes, offsets })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn write(
+  max_stack: u16,
+  max_locals: u16,
+  code: &[Instructions],
+  exception_table: &[Exception],
+  attributes: &[Attribute],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  let offsets = layout(code)?;
+  let mut code_writer = StreamWriter::new();
+  write_instructions(code, &offsets, constant_pool, &mut code_writer)?;
+  sw.put_u16(max_stack);
+  sw.put_u16(max_locals);
+  sw.put_u32(code_writer.len() as u32);
+  sw.put_bytes(&code_writer.stream);
+  sw.put_count(exception_table.len())?;
+  for exception in exception_table {
+    let mut exception = exception.clone();
+    exception.untranslate(&offsets)?;
+    exception.write(constant_pool, sw)?;
+  }
+  sw.put_count(attributes.len())?;
+  for attribute in attributes {
+    let mut attribute = attribute.clone();
+    untranslate(&mut attribute, &offsets)?;
+    attribute.write(constant_pool, sw)?;
+  }
+  Ok(())
+}
+
+/// Rewrites the byte offsets in the attributes nested in `Code` as instruction indices.
+fn translate(attribute: &mut Attribute, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+  match attribute {
+    Attribute::StackMapTable { entries } => stack_map_table::translate(entries, offsets),
+    Attribute::LineNumberTable { line_number_table } => {
+      line_number_table.iter_mut().try_for_each(|line_number| line_number.translate(offsets))
+    }
+    Attribute::LocalVariableTable { local_variable_table } => {
+      local_variable_table.iter_mut().try_for_each(|variable| variable.translate(offsets))
+    }
+    Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+      local_variable_type_table.iter_mut().try_for_each(|variable| variable.translate(offsets))
+    }
+    Attribute::RuntimeVisibleTypeAnnotations { annotations }
+    | Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+      annotations.iter_mut().try_for_each(|annotation| annotation.target_info.translate(offsets))
+    }
+    _ => Ok(())
+  }
+}
 
-  Attribute::Code { max_stack, max_locals, code, exception_table, attributes }
+/// Rewrites the instruction indices in the attributes nested in `Code` as byte offsets, the inverse
+/// of `translate`.
+pub fn untranslate(attribute: &mut Attribute, offsets: &OffsetMap) -> Result<(), ClassFormatError> {
+  match attribute {
+    Attribute::StackMapTable { entries } => stack_map_table::untranslate(entries, offsets),
+    Attribute::LineNumberTable { line_number_table } => {
+      line_number_table.iter_mut().try_for_each(|line_number| line_number.untranslate(offsets))
+    }
+    Attribute::LocalVariableTable { local_variable_table } => {
+      local_variable_table.iter_mut().try_for_each(|variable| variable.untranslate(offsets))
+    }
+    Attribute::LocalVariableTypeTable { local_variable_type_table } => {
+      local_variable_type_table.iter_mut().try_for_each(|variable| variable.untranslate(offsets))
+    }
+    Attribute::RuntimeVisibleTypeAnnotations { annotations }
+    | Attribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+      annotations.iter_mut().try_for_each(|annotation| annotation.target_info.untranslate(offsets))
+    }
+    _ => Ok(())
+  }
 }
@@ -1,37 +1,67 @@
 use std::collections::BTreeMap;
 
 use super::code_generator::Instructions;
+use crate::parser::class_format_error::ClassFormatError;
 
-pub fn clean(map: BTreeMap<usize, (usize, Instructions)>) -> Vec<Instructions> {
-  for (bytepos, (instpos, instruction)) in map.clone().iter_mut() {
-    match instruction {
-      Instructions::ifeq { offset }
-      | Instructions::ifne { offset }
-      | Instructions::iflt { offset }
-      | Instructions::ifge { offset }
-      | Instructions::ifgt { offset }
-      | Instructions::ifle { offset }
-      | Instructions::if_icmpeq { offset }
-      | Instructions::if_icmpne { offset }
-      | Instructions::if_icmplt { offset }
-      | Instructions::if_icmpge { offset }
-      | Instructions::if_icmpgt { offset }
-      | Instructions::if_icmple { offset }
-      | Instructions::if_acmpeq { offset }
-      | Instructions::if_acmpne { offset }
-      | Instructions::goto { offset }
-      | Instructions::jsr { offset }
-      | Instructions::ifnull { offset }
-      | Instructions::ifnonnull { offset } => {
-        let (instpos2, _) = map.get(&((*bytepos as isize + *offset as isize) as usize)).unwrap();
-        *offset = (*instpos2 as isize - *instpos as isize) as i16;
+/// Rewrites every branch and switch offset from bytes into a number of instructions.
+pub fn clean(
+  map: BTreeMap<usize, (usize, Instructions)>
+) -> Result<Vec<Instructions>, ClassFormatError> {
+  map
+    .iter()
+    .map(|(bytepos, (instpos, instruction))| {
+      let jump = |offset: isize| -> Result<isize, ClassFormatError> {
+        let (instpos2, _) = target(&map, *bytepos, offset)?;
+        Ok(*instpos2 as isize - *instpos as isize)
+      };
+      let mut instruction = instruction.clone();
+      match &mut instruction {
+        Instructions::ifeq { offset }
+        | Instructions::ifne { offset }
+        | Instructions::iflt { offset }
+        | Instructions::ifge { offset }
+        | Instructions::ifgt { offset }
+        | Instructions::ifle { offset }
+        | Instructions::if_icmpeq { offset }
+        | Instructions::if_icmpne { offset }
+        | Instructions::if_icmplt { offset }
+        | Instructions::if_icmpge { offset }
+        | Instructions::if_icmpgt { offset }
+        | Instructions::if_icmple { offset }
+        | Instructions::if_acmpeq { offset }
+        | Instructions::if_acmpne { offset }
+        | Instructions::goto { offset }
+        | Instructions::jsr { offset }
+        | Instructions::ifnull { offset }
+        | Instructions::ifnonnull { offset } => *offset = jump(*offset as isize)? as i16,
+        Instructions::goto_w { offset } | Instructions::jsr_w { offset } => {
+          *offset = jump(*offset as isize)? as i32
+        }
+        Instructions::tableswitch { default, offsets, .. } => {
+          *default = jump(*default as isize)? as i32;
+          for offset in offsets {
+            *offset = jump(*offset as isize)? as i32;
+          }
+        }
+        Instructions::lookupswith { default, pairs, .. } => {
+          *default = jump(*default as isize)? as i32;
+          for (_, offset) in pairs {
+            *offset = jump(*offset as isize)? as i32;
+          }
+        }
+        _ => {}
       }
-      Instructions::goto_w { offset } | Instructions::jsr_w { offset } => {
-        let (instpos2, _) = map.get(&((*bytepos as isize + *offset as isize) as usize)).unwrap();
-        *offset = (*instpos2 as isize - *instpos as isize) as i32;
-      }
-      _ => {}
-    }
-  }
-  map.into_values().map(|x| x.1).collect()
+      Ok(instruction)
+    })
+    .collect()
+}
+
+fn target(
+  map: &BTreeMap<usize, (usize, Instructions)>,
+  bytepos: usize,
+  offset: isize
+) -> Result<&(usize, Instructions), ClassFormatError> {
+  let target = bytepos as isize + offset;
+  let instruction = usize::try_from(target).ok().and_then(|target| map.get(&target));
+  instruction.ok_or(ClassFormatError::BadBranchTarget { offset: bytepos, target })
 }
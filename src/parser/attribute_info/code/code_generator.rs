@@ -1,16 +1,11 @@
 use crate::parser::{
-  cp_info_resolved::{
-    Class,
-    Fieldref,
-    InterfaceMethodref,
-    InvokeDynamic,
-    Methodref,
-    ResolvedCpInfo
-  },
+  class_format_error::ClassFormatError,
+  constant_pool::ConstantPool,
+  cp_info_resolved::{Class, Fieldref, InterfaceMethodref, InvokeDynamic, Methodref, ResolvedCpInfo},
   stream_reader::StreamReader
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum Instructions {
   nop,
@@ -21,9 +16,9 @@ pub enum Instructions {
   dconst { value: f64 },
   bipush { value: i8 },
   sipush { value: i16 },
-  ldc { index: u8 },
-  ldc_w { index: u16 },
-  ldc2_w { index: u16 },
+  ldc { constant: ResolvedCpInfo },
+  ldc_w { constant: ResolvedCpInfo },
+  ldc2_w { constant: ResolvedCpInfo },
   iload { index: u8 },
   lload { index: u8 },
   fload { index: u8 },
@@ -152,16 +147,16 @@ pub enum Instructions {
   invokedynamic { invokedynamic: InvokeDynamic },
   new { class: Class },
   newarray { atype: u8 },
-  anewarray { index: u16 },
+  anewarray { class: Class },
   arraylength,
   athrow,
-  checkcast { index: u16 },
-  instanceof { index: u16 },
+  checkcast { class: Class },
+  instanceof { class: Class },
   monitorenter,
   monitorexit,
   wide1 { opcode: u8, index_extension: u16 },
   wide2 { opcode: u8, index_extension: u16, constbytes: i16 },
-  multianewarray { index: u16, dimensions: u8 },
+  multianewarray { class: Class, dimensions: u8 },
   ifnull { offset: i16 },
   ifnonnull { offset: i16 },
   goto_w { offset: i32 },
@@ -170,31 +165,32 @@ pub enum Instructions {
 
 pub fn generate_instructions(
   sr: &mut StreamReader,
-  constant_pool: &Vec<ResolvedCpInfo>
-) -> Vec<(usize, (usize, Instructions))> {
+  constant_pool: &ConstantPool
+) -> Result<Vec<(usize, (usize, Instructions))>, ClassFormatError> {
   let mut instructions: Vec<(usize, (usize, Instructions))> = Vec::new();
   for i in 0.. {
     if sr.done() {
-      return instructions;
+      return Ok(instructions);
     }
-    let inst = sr.get_u8();
-    instructions.push((sr.ptr, (i, match inst {
+    let inst_offset = sr.ptr;
+    let inst = sr.get_u8()?;
+    instructions.push((inst_offset, (i, match inst {
       0 => Instructions::nop,
       1 => Instructions::aconst_null,
       (2..=8) => Instructions::iconst { value: inst as i32 - 3 },
       (9..=10) => Instructions::lconst { value: inst as i64 - 9 },
       (11..=13) => Instructions::fconst { value: inst as f32 - 11.0 },
       (14..=15) => Instructions::dconst { value: inst as f64 - 14.0 },
-      16 => Instructions::bipush { value: sr.get_i8() },
-      17 => Instructions::sipush { value: sr.get_i16() },
-      18 => Instructions::ldc { index: sr.get_u8() },
-      19 => Instructions::ldc_w { index: sr.get_u16() },
-      20 => Instructions::ldc2_w { index: sr.get_u16() },
-      21 => Instructions::iload { index: sr.get_u8() },
-      22 => Instructions::lload { index: sr.get_u8() },
-      23 => Instructions::fload { index: sr.get_u8() },
-      24 => Instructions::dload { index: sr.get_u8() },
-      25 => Instructions::aload { index: sr.get_u8() },
+      16 => Instructions::bipush { value: sr.get_i8()? },
+      17 => Instructions::sipush { value: sr.get_i16()? },
+      18 => Instructions::ldc { constant: constant_pool.get(sr.get_u8()? as u16)?.clone() },
+      19 => Instructions::ldc_w { constant: constant_pool.get(sr.get_u16()?)?.clone() },
+      20 => Instructions::ldc2_w { constant: constant_pool.get(sr.get_u16()?)?.clone() },
+      21 => Instructions::iload { index: sr.get_u8()? },
+      22 => Instructions::lload { index: sr.get_u8()? },
+      23 => Instructions::fload { index: sr.get_u8()? },
+      24 => Instructions::dload { index: sr.get_u8()? },
+      25 => Instructions::aload { index: sr.get_u8()? },
       (26..=29) => Instructions::iload { index: inst - 26 },
       (30..=33) => Instructions::lload { index: inst - 30 },
       (34..=37) => Instructions::fload { index: inst - 34 },
@@ -208,11 +204,11 @@ pub fn generate_instructions(
       51 => Instructions::baload,
       52 => Instructions::caload,
       53 => Instructions::saload,
-      54 => Instructions::istore { index: sr.get_u8() },
-      55 => Instructions::lstore { index: sr.get_u8() },
-      56 => Instructions::fstore { index: sr.get_u8() },
-      57 => Instructions::dstore { index: sr.get_u8() },
-      58 => Instructions::astore { index: sr.get_u8() },
+      54 => Instructions::istore { index: sr.get_u8()? },
+      55 => Instructions::lstore { index: sr.get_u8()? },
+      56 => Instructions::fstore { index: sr.get_u8()? },
+      57 => Instructions::dstore { index: sr.get_u8()? },
+      58 => Instructions::astore { index: sr.get_u8()? },
       (59..=62) => Instructions::istore { index: inst - 59 },
       (63..=66) => Instructions::lstore { index: inst - 63 },
       (67..=70) => Instructions::fstore { index: inst - 67 },
@@ -271,7 +267,7 @@ pub fn generate_instructions(
       129 => Instructions::lor,
       130 => Instructions::ixor,
       131 => Instructions::lxor,
-      132 => Instructions::iinc { index: sr.get_u8(), r#const: sr.get_i8() },
+      132 => Instructions::iinc { index: sr.get_u8()?, r#const: sr.get_i8()? },
       133 => Instructions::i2l,
       134 => Instructions::i2f,
       135 => Instructions::i2d,
@@ -292,34 +288,39 @@ pub fn generate_instructions(
       150 => Instructions::fcmpg,
       151 => Instructions::dcmpl,
       152 => Instructions::dcmpg,
-      153 => Instructions::ifeq { offset: sr.get_i16() },
-      154 => Instructions::ifne { offset: sr.get_i16() },
-      155 => Instructions::iflt { offset: sr.get_i16() },
-      156 => Instructions::ifge { offset: sr.get_i16() },
-      157 => Instructions::ifgt { offset: sr.get_i16() },
-      158 => Instructions::ifle { offset: sr.get_i16() },
-      159 => Instructions::if_icmpeq { offset: sr.get_i16() },
-      160 => Instructions::if_icmpne { offset: sr.get_i16() },
-      161 => Instructions::if_icmplt { offset: sr.get_i16() },
-      162 => Instructions::if_icmpge { offset: sr.get_i16() },
-      163 => Instructions::if_icmpgt { offset: sr.get_i16() },
-      164 => Instructions::if_icmple { offset: sr.get_i16() },
-      165 => Instructions::if_acmpeq { offset: sr.get_i16() },
-      166 => Instructions::if_acmpne { offset: sr.get_i16() },
-      167 => Instructions::goto { offset: sr.get_i16() },
-      168 => Instructions::jsr { offset: sr.get_i16() },
-      169 => Instructions::ret { index: sr.get_u8() },
+      153 => Instructions::ifeq { offset: sr.get_i16()? },
+      154 => Instructions::ifne { offset: sr.get_i16()? },
+      155 => Instructions::iflt { offset: sr.get_i16()? },
+      156 => Instructions::ifge { offset: sr.get_i16()? },
+      157 => Instructions::ifgt { offset: sr.get_i16()? },
+      158 => Instructions::ifle { offset: sr.get_i16()? },
+      159 => Instructions::if_icmpeq { offset: sr.get_i16()? },
+      160 => Instructions::if_icmpne { offset: sr.get_i16()? },
+      161 => Instructions::if_icmplt { offset: sr.get_i16()? },
+      162 => Instructions::if_icmpge { offset: sr.get_i16()? },
+      163 => Instructions::if_icmpgt { offset: sr.get_i16()? },
+      164 => Instructions::if_icmple { offset: sr.get_i16()? },
+      165 => Instructions::if_acmpeq { offset: sr.get_i16()? },
+      166 => Instructions::if_acmpne { offset: sr.get_i16()? },
+      167 => Instructions::goto { offset: sr.get_i16()? },
+      168 => Instructions::jsr { offset: sr.get_i16()? },
+      169 => Instructions::ret { index: sr.get_u8()? },
       170 => {
-        let default = sr.get_i32();
-        let low = sr.get_i32();
-        let high = sr.get_i32();
-        let offsets: Vec<i32> = (0..high - low + 1).map(|_| sr.get_i32()).collect();
+        sr.take_n(switch_padding(sr.ptr))?;
+        let default = sr.get_i32()?;
+        let low = sr.get_i32()?;
+        let high = sr.get_i32()?;
+        let offsets: Vec<i32> =
+          (0..high as i64 - low as i64 + 1).map(|_| sr.get_i32()).collect::<Result<_, _>>()?;
         Instructions::tableswitch { default, low, high, offsets }
       }
       171 => {
-        let default = sr.get_i32();
-        let npairs = sr.get_i32();
-        let pairs: Vec<(i32, i32)> = (0..npairs).map(|_| (sr.get_i32(), sr.get_i32())).collect();
+        sr.take_n(switch_padding(sr.ptr))?;
+        let default = sr.get_i32()?;
+        let npairs = sr.get_i32()?;
+        let pairs: Vec<(i32, i32)> = (0..npairs)
+          .map(|_| Ok((sr.get_i32()?, sr.get_i32()?)))
+          .collect::<Result<_, ClassFormatError>>()?;
         Instructions::lookupswith { default, npairs, pairs }
       }
       172 => Instructions::ireturn,
@@ -329,87 +330,98 @@ pub fn generate_instructions(
       176 => Instructions::areturn,
       177 => Instructions::r#return,
       178 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Fieldref(fieldref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let fieldref = constant_pool.get_fieldref(index)?;
         Instructions::getstatic { fieldref: fieldref.clone() }
       }
       179 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Fieldref(fieldref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let fieldref = constant_pool.get_fieldref(index)?;
         Instructions::putstatic { fieldref: fieldref.clone() }
       }
       180 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Fieldref(fieldref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let fieldref = constant_pool.get_fieldref(index)?;
         Instructions::getfield { fieldref: fieldref.clone() }
       }
       181 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Fieldref(fieldref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let fieldref = constant_pool.get_fieldref(index)?;
         Instructions::putfield { fieldref: fieldref.clone() }
       }
       182 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Methodref(methodref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let methodref = constant_pool.get_methodref(index)?;
         Instructions::invokevirtual { methodref: methodref.clone() }
       }
       183 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Methodref(methodref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let methodref = constant_pool.get_methodref(index)?;
         Instructions::invokespecial { methodref: methodref.clone() }
       }
       184 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Methodref(methodref) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let methodref = constant_pool.get_methodref(index)?;
         Instructions::invokestatic { methodref: methodref.clone() }
       }
       185 => {
-        let index = sr.get_u16();
-        let count = sr.get_u8();
-        assert_eq!(sr.get_u8(), 0, "Final byte of invokestatic was non-zero");
+        let index = sr.get_u16()?;
+        let count = sr.get_u8()?;
+        if sr.get_u8()? != 0 {
+          return Err(ClassFormatError::NonZeroPadding { offset: inst_offset });
+        }
 
-        let ResolvedCpInfo::InterfaceMethodref(interfacemethodref) = &constant_pool[index as usize - 1] else {panic!()};
-        Instructions::invokeinterface { interfacemethodref: interfacemethodref.clone(), count}
+        let interfacemethodref = constant_pool.get_interface_methodref(index)?;
+        Instructions::invokeinterface { interfacemethodref: interfacemethodref.clone(), count }
       }
       186 => {
-        let index = sr.get_u16();
-        assert_eq!(sr.get_u16(), 0, "Final bytes of invokedynamic was non-zero");
+        let index = sr.get_u16()?;
+        if sr.get_u16()? != 0 {
+          return Err(ClassFormatError::NonZeroPadding { offset: inst_offset });
+        }
 
-        let ResolvedCpInfo::InvokeDynamic(invokedynamic) = &constant_pool[index as usize - 1] else {panic!()};
+        let invokedynamic = constant_pool.get_invoke_dynamic(index)?;
         Instructions::invokedynamic { invokedynamic: invokedynamic.clone() }
       }
       187 => {
-        let index = sr.get_u16();
-        let ResolvedCpInfo::Class(class) = &constant_pool[index as usize - 1] else {panic!()};
+        let index = sr.get_u16()?;
+        let class = constant_pool.get_class(index)?;
         Instructions::new { class: class.clone() }
       }
-      188 => Instructions::newarray { atype: sr.get_u8() },
-      189 => Instructions::anewarray { index: sr.get_u16() },
+      188 => Instructions::newarray { atype: sr.get_u8()? },
+      189 => Instructions::anewarray { class: constant_pool.get_class(sr.get_u16()?)?.clone() },
       190 => Instructions::arraylength,
       191 => Instructions::athrow,
-      192 => Instructions::checkcast { index: sr.get_u16() },
-      193 => Instructions::instanceof { index: sr.get_u16() },
+      192 => Instructions::checkcast { class: constant_pool.get_class(sr.get_u16()?)?.clone() },
+      193 => Instructions::instanceof { class: constant_pool.get_class(sr.get_u16()?)?.clone() },
       194 => Instructions::monitorenter,
       195 => Instructions::monitorexit,
       196 => {
-        let opcode = sr.get_u8();
+        let opcode = sr.get_u8()?;
         match opcode {
           (21..=25) | (54..=58) | 169 => {
-            Instructions::wide1 { opcode, index_extension: sr.get_u16() }
+            Instructions::wide1 { opcode, index_extension: sr.get_u16()? }
           }
           132 => {
-            Instructions::wide2 { opcode, index_extension: sr.get_u16(), constbytes: sr.get_i16() }
+            let index_extension = sr.get_u16()?;
+            Instructions::wide2 { opcode, index_extension, constbytes: sr.get_i16()? }
           }
-          _ => panic!("Attempted to perform 'wide' on invalid opcode")
+          _ => return Err(ClassFormatError::BadWideOpcode { offset: inst_offset, opcode })
         }
       }
-      197 => Instructions::multianewarray { index: sr.get_u16(), dimensions: sr.get_u8() },
-      198 => Instructions::ifnull { offset: sr.get_i16() },
-      199 => Instructions::ifnonnull { offset: sr.get_i16() },
-      200 => Instructions::goto_w { offset: sr.get_i32() },
-      201 => Instructions::jsr_w { offset: sr.get_i32() },
-      (202..) => panic!("Instruction not yet implemented")
+      197 => {
+        let class = constant_pool.get_class(sr.get_u16()?)?.clone();
+        Instructions::multianewarray { class, dimensions: sr.get_u8()? }
+      }
+      198 => Instructions::ifnull { offset: sr.get_i16()? },
+      199 => Instructions::ifnonnull { offset: sr.get_i16()? },
+      200 => Instructions::goto_w { offset: sr.get_i32()? },
+      201 => Instructions::jsr_w { offset: sr.get_i32()? },
+      (202..) => return Err(ClassFormatError::BadOpcode { offset: inst_offset, opcode: inst })
     })));
   }
-  instructions //technically, this should never be reached. It's just here to hint the return type to the compiler
+  Ok(instructions) //technically, this should never be reached. It's just here to hint the return type to the compiler
 }
+
+/// The switch operands are aligned to a multiple of 4 bytes from the start of the code.
+pub fn switch_padding(ptr: usize) -> usize { (4 - ptr % 4) % 4 }
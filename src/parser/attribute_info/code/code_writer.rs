@@ -0,0 +1,470 @@
+use super::{
+  code_generator::{switch_padding, Instructions},
+  offset_map::OffsetMap
+};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::ConstantPoolBuilder,
+  stream_writer::StreamWriter
+};
+
+/// Works out where each instruction will start once encoded, with switches padded to a multiple of
+/// 4 bytes from the start of the code.
+/// Loads and stores take their shortest form, as javac writes them, so `iload { index: 0 }`
+/// becomes `iload_0`.
+pub fn layout(code: &[Instructions]) -> Result<OffsetMap, ClassFormatError> {
+  let mut offsets = Vec::with_capacity(code.len());
+  let mut offset = 0;
+  for instruction in code {
+    offsets.push(offset);
+    offset += size(instruction, offset);
+  }
+  if offset > u16::MAX as usize {
+    return Err(ClassFormatError::CodeTooLong { length: offset });
+  }
+  Ok(OffsetMap::new(offsets, offset))
+}
+
+fn size(instruction: &Instructions, offset: usize) -> usize {
+  match instruction {
+    Instructions::iload { index }
+    | Instructions::lload { index }
+    | Instructions::fload { index }
+    | Instructions::dload { index }
+    | Instructions::aload { index }
+    | Instructions::istore { index }
+    | Instructions::lstore { index }
+    | Instructions::fstore { index }
+    | Instructions::dstore { index }
+    | Instructions::astore { index } => match index {
+      0..=3 => 1,
+      _ => 2
+    },
+    Instructions::bipush { .. }
+    | Instructions::ldc { .. }
+    | Instructions::ret { .. }
+    | Instructions::newarray { .. } => 2,
+    Instructions::sipush { .. }
+    | Instructions::ldc_w { .. }
+    | Instructions::ldc2_w { .. }
+    | Instructions::iinc { .. }
+    | Instructions::ifeq { .. }
+    | Instructions::ifne { .. }
+    | Instructions::iflt { .. }
+    | Instructions::ifge { .. }
+    | Instructions::ifgt { .. }
+    | Instructions::ifle { .. }
+    | Instructions::if_icmpeq { .. }
+    | Instructions::if_icmpne { .. }
+    | Instructions::if_icmplt { .. }
+    | Instructions::if_icmpge { .. }
+    | Instructions::if_icmpgt { .. }
+    | Instructions::if_icmple { .. }
+    | Instructions::if_acmpeq { .. }
+    | Instructions::if_acmpne { .. }
+    | Instructions::goto { .. }
+    | Instructions::jsr { .. }
+    | Instructions::ifnull { .. }
+    | Instructions::ifnonnull { .. }
+    | Instructions::getstatic { .. }
+    | Instructions::putstatic { .. }
+    | Instructions::getfield { .. }
+    | Instructions::putfield { .. }
+    | Instructions::invokevirtual { .. }
+    | Instructions::invokespecial { .. }
+    | Instructions::invokestatic { .. }
+    | Instructions::new { .. }
+    | Instructions::anewarray { .. }
+    | Instructions::checkcast { .. }
+    | Instructions::instanceof { .. } => 3,
+    Instructions::multianewarray { .. } | Instructions::wide1 { .. } => 4,
+    Instructions::invokeinterface { .. }
+    | Instructions::invokedynamic { .. }
+    | Instructions::goto_w { .. }
+    | Instructions::jsr_w { .. } => 5,
+    Instructions::wide2 { .. } => 6,
+    Instructions::tableswitch { offsets, .. } => {
+      1 + switch_padding(offset + 1) + 12 + 4 * offsets.len()
+    }
+    Instructions::lookupswith { pairs, .. } => 1 + switch_padding(offset + 1) + 8 + 8 * pairs.len(),
+    _ => 1
+  }
+}
+
+/// Encodes `code` as laid out by `layout`, turning the relative instruction counts of branches and
+/// switches back into byte offsets.
+pub fn write_instructions(
+  code: &[Instructions],
+  offsets: &OffsetMap,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  for (index, instruction) in code.iter().enumerate() {
+    let unencodable = || ClassFormatError::UnencodableInstruction { index };
+    let branch = |jump: isize| -> Result<isize, ClassFormatError> {
+      let target = index as isize + jump;
+      let target_offset = usize::try_from(target)
+        .ok()
+        .filter(|target| *target < code.len())
+        .and_then(|target| offsets.offset_of(target))
+        .ok_or(ClassFormatError::BadCodeIndex { index: target })?;
+      Ok(target_offset as isize - offsets.offset_of(index).unwrap() as isize)
+    };
+    let short_branch = |jump: i16| -> Result<i16, ClassFormatError> {
+      let offset = branch(jump as isize)?;
+      i16::try_from(offset).map_err(|_| ClassFormatError::BranchOutOfRange { index, offset })
+    };
+    let long_branch =
+      |jump: i32| -> Result<i32, ClassFormatError> { Ok(branch(jump as isize)? as i32) };
+    // The one byte form where there is one, as `layout` expects
+    let local = |sw: &mut StreamWriter, opcode: u8, short_opcode: u8, index: u8| match index {
+      0..=3 => sw.put_u8(short_opcode + index),
+      _ => {
+        sw.put_u8(opcode);
+        sw.put_u8(index);
+      }
+    };
+
+    match instruction {
+      Instructions::nop => sw.put_u8(0),
+      Instructions::aconst_null => sw.put_u8(1),
+      Instructions::iaload => sw.put_u8(46),
+      Instructions::laload => sw.put_u8(47),
+      Instructions::faload => sw.put_u8(48),
+      Instructions::daload => sw.put_u8(49),
+      Instructions::aaload => sw.put_u8(50),
+      Instructions::baload => sw.put_u8(51),
+      Instructions::caload => sw.put_u8(52),
+      Instructions::saload => sw.put_u8(53),
+      Instructions::iastore => sw.put_u8(79),
+      Instructions::lastore => sw.put_u8(80),
+      Instructions::fastore => sw.put_u8(81),
+      Instructions::dastore => sw.put_u8(82),
+      Instructions::aastore => sw.put_u8(83),
+      Instructions::bastore => sw.put_u8(84),
+      Instructions::castore => sw.put_u8(85),
+      Instructions::sastore => sw.put_u8(86),
+      Instructions::pop => sw.put_u8(87),
+      Instructions::pop2 => sw.put_u8(88),
+      Instructions::dup => sw.put_u8(89),
+      Instructions::dup_x1 => sw.put_u8(90),
+      Instructions::dup_x2 => sw.put_u8(91),
+      Instructions::dup2 => sw.put_u8(92),
+      Instructions::dup2_x1 => sw.put_u8(93),
+      Instructions::dup2_x2 => sw.put_u8(94),
+      Instructions::swap => sw.put_u8(95),
+      Instructions::iadd => sw.put_u8(96),
+      Instructions::ladd => sw.put_u8(97),
+      Instructions::fadd => sw.put_u8(98),
+      Instructions::dadd => sw.put_u8(99),
+      Instructions::isub => sw.put_u8(100),
+      Instructions::lsub => sw.put_u8(101),
+      Instructions::fsub => sw.put_u8(102),
+      Instructions::dsub => sw.put_u8(103),
+      Instructions::imul => sw.put_u8(104),
+      Instructions::lmul => sw.put_u8(105),
+      Instructions::fmul => sw.put_u8(106),
+      Instructions::dmul => sw.put_u8(107),
+      Instructions::idiv => sw.put_u8(108),
+      Instructions::ldiv => sw.put_u8(109),
+      Instructions::fdiv => sw.put_u8(110),
+      Instructions::ddiv => sw.put_u8(111),
+      Instructions::irem => sw.put_u8(112),
+      Instructions::lrem => sw.put_u8(113),
+      Instructions::frem => sw.put_u8(114),
+      Instructions::drem => sw.put_u8(115),
+      Instructions::ineg => sw.put_u8(116),
+      Instructions::lneg => sw.put_u8(117),
+      Instructions::fneg => sw.put_u8(118),
+      Instructions::dneg => sw.put_u8(119),
+      Instructions::ishl => sw.put_u8(120),
+      Instructions::lshl => sw.put_u8(121),
+      Instructions::ishr => sw.put_u8(122),
+      Instructions::lshr => sw.put_u8(123),
+      Instructions::iushr => sw.put_u8(124),
+      Instructions::lushr => sw.put_u8(125),
+      Instructions::iand => sw.put_u8(126),
+      Instructions::land => sw.put_u8(127),
+      Instructions::ior => sw.put_u8(128),
+      Instructions::lor => sw.put_u8(129),
+      Instructions::ixor => sw.put_u8(130),
+      Instructions::lxor => sw.put_u8(131),
+      Instructions::i2l => sw.put_u8(133),
+      Instructions::i2f => sw.put_u8(134),
+      Instructions::i2d => sw.put_u8(135),
+      Instructions::l2i => sw.put_u8(136),
+      Instructions::l2f => sw.put_u8(137),
+      Instructions::l2d => sw.put_u8(138),
+      Instructions::f2i => sw.put_u8(139),
+      Instructions::f2l => sw.put_u8(140),
+      Instructions::f2d => sw.put_u8(141),
+      Instructions::d2i => sw.put_u8(142),
+      Instructions::d2l => sw.put_u8(143),
+      Instructions::d2f => sw.put_u8(144),
+      Instructions::i2b => sw.put_u8(145),
+      Instructions::i2c => sw.put_u8(146),
+      Instructions::i2s => sw.put_u8(147),
+      Instructions::lcmp => sw.put_u8(148),
+      Instructions::fcmpl => sw.put_u8(149),
+      Instructions::fcmpg => sw.put_u8(150),
+      Instructions::dcmpl => sw.put_u8(151),
+      Instructions::dcmpg => sw.put_u8(152),
+      Instructions::ireturn => sw.put_u8(172),
+      Instructions::lreturn => sw.put_u8(173),
+      Instructions::freturn => sw.put_u8(174),
+      Instructions::dreturn => sw.put_u8(175),
+      Instructions::areturn => sw.put_u8(176),
+      Instructions::r#return => sw.put_u8(177),
+      Instructions::arraylength => sw.put_u8(190),
+      Instructions::athrow => sw.put_u8(191),
+      Instructions::monitorenter => sw.put_u8(194),
+      Instructions::monitorexit => sw.put_u8(195),
+      Instructions::iconst { value } => match value {
+        -1..=5 => sw.put_u8((3 + value) as u8),
+        _ => return Err(unencodable())
+      },
+      Instructions::lconst { value } => match value {
+        0..=1 => sw.put_u8(9 + *value as u8),
+        _ => return Err(unencodable())
+      },
+      Instructions::fconst { value } => {
+        let constant =
+          [0.0f32, 1.0, 2.0].iter().position(|constant| constant.to_bits() == value.to_bits());
+        sw.put_u8(11 + constant.ok_or_else(unencodable)? as u8);
+      }
+      Instructions::dconst { value } => {
+        let constant =
+          [0.0f64, 1.0].iter().position(|constant| constant.to_bits() == value.to_bits());
+        sw.put_u8(14 + constant.ok_or_else(unencodable)? as u8);
+      }
+      Instructions::bipush { value } => {
+        sw.put_u8(16);
+        sw.put_i8(*value);
+      }
+      Instructions::sipush { value } => {
+        sw.put_u8(17);
+        sw.put_i16(*value);
+      }
+      Instructions::ldc { constant } => {
+        let constant = constant_pool.constant(constant)?;
+        sw.put_u8(18);
+        sw.put_u8(u8::try_from(constant).map_err(|_| unencodable())?);
+      }
+      Instructions::ldc_w { constant } => {
+        sw.put_u8(19);
+        sw.put_u16(constant_pool.constant(constant)?);
+      }
+      Instructions::ldc2_w { constant } => {
+        sw.put_u8(20);
+        sw.put_u16(constant_pool.constant(constant)?);
+      }
+      Instructions::iload { index } => local(sw, 21, 26, *index),
+      Instructions::lload { index } => local(sw, 22, 30, *index),
+      Instructions::fload { index } => local(sw, 23, 34, *index),
+      Instructions::dload { index } => local(sw, 24, 38, *index),
+      Instructions::aload { index } => local(sw, 25, 42, *index),
+      Instructions::istore { index } => local(sw, 54, 59, *index),
+      Instructions::lstore { index } => local(sw, 55, 63, *index),
+      Instructions::fstore { index } => local(sw, 56, 67, *index),
+      Instructions::dstore { index } => local(sw, 57, 71, *index),
+      Instructions::astore { index } => local(sw, 58, 75, *index),
+      Instructions::iinc { index, r#const } => {
+        sw.put_u8(132);
+        sw.put_u8(*index);
+        sw.put_i8(*r#const);
+      }
+      Instructions::ifeq { offset } => {
+        sw.put_u8(153);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ifne { offset } => {
+        sw.put_u8(154);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::iflt { offset } => {
+        sw.put_u8(155);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ifge { offset } => {
+        sw.put_u8(156);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ifgt { offset } => {
+        sw.put_u8(157);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ifle { offset } => {
+        sw.put_u8(158);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_icmpeq { offset } => {
+        sw.put_u8(159);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_icmpne { offset } => {
+        sw.put_u8(160);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_icmplt { offset } => {
+        sw.put_u8(161);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_icmpge { offset } => {
+        sw.put_u8(162);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_icmpgt { offset } => {
+        sw.put_u8(163);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_icmple { offset } => {
+        sw.put_u8(164);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_acmpeq { offset } => {
+        sw.put_u8(165);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::if_acmpne { offset } => {
+        sw.put_u8(166);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::goto { offset } => {
+        sw.put_u8(167);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::jsr { offset } => {
+        sw.put_u8(168);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ifnull { offset } => {
+        sw.put_u8(198);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ifnonnull { offset } => {
+        sw.put_u8(199);
+        sw.put_i16(short_branch(*offset)?);
+      }
+      Instructions::ret { index } => {
+        sw.put_u8(169);
+        sw.put_u8(*index);
+      }
+      Instructions::tableswitch { default, low, high, offsets: jumps } => {
+        if *high as i64 - *low as i64 + 1 != jumps.len() as i64 {
+          return Err(unencodable());
+        }
+        sw.put_u8(170);
+        sw.put_bytes(&[0; 3][..switch_padding(sw.len())]);
+        sw.put_i32(long_branch(*default)?);
+        sw.put_i32(*low);
+        sw.put_i32(*high);
+        for jump in jumps {
+          sw.put_i32(long_branch(*jump)?);
+        }
+      }
+      Instructions::lookupswith { default, npairs, pairs } => {
+        if *npairs as usize != pairs.len() {
+          return Err(unencodable());
+        }
+        sw.put_u8(171);
+        sw.put_bytes(&[0; 3][..switch_padding(sw.len())]);
+        sw.put_i32(long_branch(*default)?);
+        sw.put_i32(*npairs);
+        for (key, jump) in pairs {
+          sw.put_i32(*key);
+          sw.put_i32(long_branch(*jump)?);
+        }
+      }
+      Instructions::getstatic { fieldref } => {
+        sw.put_u8(178);
+        sw.put_u16(constant_pool.fieldref(fieldref)?);
+      }
+      Instructions::putstatic { fieldref } => {
+        sw.put_u8(179);
+        sw.put_u16(constant_pool.fieldref(fieldref)?);
+      }
+      Instructions::getfield { fieldref } => {
+        sw.put_u8(180);
+        sw.put_u16(constant_pool.fieldref(fieldref)?);
+      }
+      Instructions::putfield { fieldref } => {
+        sw.put_u8(181);
+        sw.put_u16(constant_pool.fieldref(fieldref)?);
+      }
+      Instructions::invokevirtual { methodref } => {
+        sw.put_u8(182);
+        sw.put_u16(constant_pool.methodref(methodref)?);
+      }
+      Instructions::invokespecial { methodref } => {
+        sw.put_u8(183);
+        sw.put_u16(constant_pool.methodref(methodref)?);
+      }
+      Instructions::invokestatic { methodref } => {
+        sw.put_u8(184);
+        sw.put_u16(constant_pool.methodref(methodref)?);
+      }
+      Instructions::invokeinterface { interfacemethodref, count } => {
+        sw.put_u8(185);
+        sw.put_u16(constant_pool.interface_methodref(interfacemethodref)?);
+        sw.put_u8(*count);
+        sw.put_u8(0);
+      }
+      Instructions::invokedynamic { invokedynamic } => {
+        sw.put_u8(186);
+        sw.put_u16(constant_pool.invoke_dynamic(invokedynamic)?);
+        sw.put_u16(0);
+      }
+      Instructions::new { class } => {
+        sw.put_u8(187);
+        sw.put_u16(constant_pool.class(class)?);
+      }
+      Instructions::newarray { atype } => {
+        sw.put_u8(188);
+        sw.put_u8(*atype);
+      }
+      Instructions::anewarray { class } => {
+        sw.put_u8(189);
+        sw.put_u16(constant_pool.class(class)?);
+      }
+      Instructions::checkcast { class } => {
+        sw.put_u8(192);
+        sw.put_u16(constant_pool.class(class)?);
+      }
+      Instructions::instanceof { class } => {
+        sw.put_u8(193);
+        sw.put_u16(constant_pool.class(class)?);
+      }
+      Instructions::wide1 { opcode, index_extension } => {
+        if !matches!(opcode, 21..=25 | 54..=58 | 169) {
+          return Err(unencodable());
+        }
+        sw.put_u8(196);
+        sw.put_u8(*opcode);
+        sw.put_u16(*index_extension);
+      }
+      Instructions::wide2 { opcode, index_extension, constbytes } => {
+        if *opcode != 132 {
+          return Err(unencodable());
+        }
+        sw.put_u8(196);
+        sw.put_u8(*opcode);
+        sw.put_u16(*index_extension);
+        sw.put_i16(*constbytes);
+      }
+      Instructions::multianewarray { class, dimensions } => {
+        sw.put_u8(197);
+        sw.put_u16(constant_pool.class(class)?);
+        sw.put_u8(*dimensions);
+      }
+      Instructions::goto_w { offset } => {
+        sw.put_u8(200);
+        sw.put_i32(long_branch(*offset)?);
+      }
+      Instructions::jsr_w { offset } => {
+        sw.put_u8(201);
+        sw.put_i32(long_branch(*offset)?);
+      }
+    }
+  }
+  Ok(())
+}
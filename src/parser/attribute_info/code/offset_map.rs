@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::parser::class_format_error::ClassFormatError;
+
+/// Links the byte offset each instruction starts at to its index in the decoded code.
+/// The end of the code counts as one past the last instruction, so exclusive ranges map too.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OffsetMap {
+  offsets: Vec<usize>,
+  indices: HashMap<usize, usize>,
+  code_length: usize
+}
+
+impl OffsetMap {
+  /// `offsets` holds the start of every instruction, in order.
+  pub fn new(offsets: Vec<usize>, code_length: usize) -> Self {
+    let indices = offsets.iter().enumerate().map(|(index, offset)| (*offset, index)).collect();
+    OffsetMap { offsets, indices, code_length }
+  }
+
+  /// The number of instructions.
+  pub fn len(&self) -> usize { self.offsets.len() }
+
+  pub fn is_empty(&self) -> bool { self.offsets.is_empty() }
+
+  /// The index of the instruction starting at `offset`, or `len()` for the end of the code.
+  pub fn index_of(&self, offset: usize) -> Option<usize> {
+    match offset == self.code_length {
+      true => Some(self.len()),
+      false => self.indices.get(&offset).copied()
+    }
+  }
+
+  /// The byte offset of the instruction at `index`, or the code's length for `len()`.
+  pub fn offset_of(&self, index: usize) -> Option<usize> {
+    match index == self.len() {
+      true => Some(self.code_length),
+      false => self.offsets.get(index).copied()
+    }
+  }
+
+  /// Translates the offset of an instruction into its index.
+  pub fn index(&self, offset: u16) -> Result<u16, ClassFormatError> {
+    match self.indices.get(&(offset as usize)) {
+      Some(index) => Ok(*index as u16),
+      None => Err(ClassFormatError::BadCodeOffset { offset: offset as usize })
+    }
+  }
+
+  /// Like `index`, but also accepts the end of the code.
+  pub fn end_index(&self, offset: usize) -> Result<u16, ClassFormatError> {
+    match self.index_of(offset) {
+      Some(index) => Ok(index as u16),
+      None => Err(ClassFormatError::BadCodeOffset { offset })
+    }
+  }
+
+  /// Translates the index of an instruction into its offset, the inverse of `index`.
+  pub fn offset(&self, index: u16) -> Result<u16, ClassFormatError> {
+    match self.offsets.get(index as usize) {
+      Some(offset) => Ok(*offset as u16),
+      None => Err(ClassFormatError::BadCodeIndex { index: index as isize })
+    }
+  }
+
+  /// Like `offset`, but also accepts the end of the code.
+  pub fn end_offset(&self, index: usize) -> Result<u16, ClassFormatError> {
+    match self.offset_of(index) {
+      Some(offset) => Ok(offset as u16),
+      None => Err(ClassFormatError::BadCodeIndex { index: index as isize })
+    }
+  }
+}
@@ -1,6 +1,22 @@
-use super::Attribute;
-use crate::parser::stream_reader::StreamReader;
+use super::{attribute::constant::Constant, Attribute};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  Attribute::ConstantValue { constantvalue_index: sr.get_u16() }
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  Ok(Attribute::ConstantValue { value: Constant::read(sr, constant_pool)? })
+}
+
+pub fn write(
+  value: &Constant,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  value.write(constant_pool, sw)
 }
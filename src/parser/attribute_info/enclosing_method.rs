@@ -1,6 +1,35 @@
 use super::Attribute;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::{Class, NameAndType},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  Attribute::EnclosingMethod { class_index: sr.get_u16(), method_index: sr.get_u16() }
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let class = constant_pool.get_class(sr.get_u16()?)?.clone();
+  let method = match sr.get_u16()? {
+    0 => None,
+    index => Some(constant_pool.get_name_and_type(index)?.clone())
+  };
+
+  Ok(Attribute::EnclosingMethod { class, method })
+}
+
+pub fn write(
+  class: &Class,
+  method: Option<&NameAndType>,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(constant_pool.class(class)?);
+  sw.put_u16(match method {
+    Some(method) => constant_pool.name_and_type(method)?,
+    None => 0
+  });
+  Ok(())
 }
@@ -1,9 +1,32 @@
 use super::Attribute;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  let number_of_exceptions = sr.get_u16();
-  let exception_index_table: Vec<u16> = (0..number_of_exceptions).map(|_| sr.get_u16()).collect();
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let number_of_exceptions = sr.get_u16()?;
+  let exceptions: Vec<Class> = (0..number_of_exceptions)
+    .map(|_| Ok(constant_pool.get_class(sr.get_u16()?)?.clone()))
+    .collect::<Result<_, _>>()?;
 
-  Attribute::Exceptions { exception_index_table }
+  Ok(Attribute::Exceptions { exceptions })
+}
+
+pub fn write(
+  exceptions: &[Class],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(exceptions.len())?;
+  for exception in exceptions {
+    sw.put_u16(constant_pool.class(exception)?);
+  }
+  Ok(())
 }
@@ -1,9 +1,27 @@
 use super::{attribute::classes::Classes, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  let number_of_classes = sr.get_u16();
-  let classes: Vec<Classes> = (0..number_of_classes).map(|_| Classes::read(sr)).collect();
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let number_of_classes = sr.get_u16()?;
+  let classes: Vec<Classes> =
+    (0..number_of_classes).map(|_| Classes::read(sr, constant_pool)).collect::<Result<_, _>>()?;
 
-  Attribute::InnerClasses { classes }
+  Ok(Attribute::InnerClasses { classes })
+}
+
+pub fn write(
+  classes: &[Classes],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(classes.len())?;
+  classes.iter().try_for_each(|class| class.write(constant_pool, sw))
 }
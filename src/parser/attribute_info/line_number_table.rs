@@ -1,10 +1,25 @@
 use super::{attribute::line_number::LineNumber, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  let line_number_table_length = sr.get_u16();
+pub fn read(sr: &mut StreamReader) -> Result<Attribute, ClassFormatError> {
+  let line_number_table_length = sr.get_u16()?;
   let line_number_table: Vec<LineNumber> =
-    (0..line_number_table_length).map(|_| LineNumber::read(sr)).collect();
+    (0..line_number_table_length).map(|_| LineNumber::read(sr)).collect::<Result<_, _>>()?;
 
-  Attribute::LineNumberTable { line_number_table }
+  Ok(Attribute::LineNumberTable { line_number_table })
+}
+
+pub fn write(
+  line_number_table: &[LineNumber],
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(line_number_table.len())?;
+  for line_number in line_number_table {
+    line_number.write(sw);
+  }
+  Ok(())
 }
@@ -1,10 +1,28 @@
 use super::{attribute::local_variable::LocalVariable, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  let local_variable_table_length = sr.get_u16();
-  let local_variable_table: Vec<LocalVariable> =
-    (0..local_variable_table_length).map(|_| LocalVariable::read(sr)).collect();
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let local_variable_table_length = sr.get_u16()?;
+  let local_variable_table: Vec<LocalVariable> = (0..local_variable_table_length)
+    .map(|_| LocalVariable::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
 
-  Attribute::LocalVariableTable { local_variable_table }
+  Ok(Attribute::LocalVariableTable { local_variable_table })
+}
+
+pub fn write(
+  local_variable_table: &[LocalVariable],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(local_variable_table.len())?;
+  local_variable_table.iter().try_for_each(|variable| variable.write(constant_pool, sw))
 }
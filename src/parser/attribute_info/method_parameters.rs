@@ -0,0 +1,28 @@
+use super::{attribute::method_parameter::MethodParameter, Attribute};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let parameters_count = sr.get_u8()?;
+  let parameters: Vec<MethodParameter> = (0..parameters_count)
+    .map(|_| MethodParameter::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
+
+  Ok(Attribute::MethodParameters { parameters })
+}
+
+pub fn write(
+  parameters: &[MethodParameter],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_short_count(parameters.len())?;
+  parameters.iter().try_for_each(|parameter| parameter.write(constant_pool, sw))
+}
@@ -0,0 +1,86 @@
+use super::{
+  attribute::module::{
+    optional_utf8,
+    read_class,
+    write_classes,
+    write_optional_utf8,
+    Exports,
+    ModuleFlags,
+    Opens,
+    Provides,
+    Requires
+  },
+  Attribute
+};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let module_name_index = sr.get_u16()?;
+  let module_name = constant_pool.get_module(module_name_index)?.name.clone();
+  let module_flags = ModuleFlags::read(sr)?;
+  let module_version = optional_utf8(sr, constant_pool)?;
+
+  let requires_count = sr.get_u16()?;
+  let requires: Vec<Requires> =
+    (0..requires_count).map(|_| Requires::read(sr, constant_pool)).collect::<Result<_, _>>()?;
+  let exports_count = sr.get_u16()?;
+  let exports: Vec<Exports> =
+    (0..exports_count).map(|_| Exports::read(sr, constant_pool)).collect::<Result<_, _>>()?;
+  let opens_count = sr.get_u16()?;
+  let opens: Vec<Opens> =
+    (0..opens_count).map(|_| Opens::read(sr, constant_pool)).collect::<Result<_, _>>()?;
+  let uses_count = sr.get_u16()?;
+  let uses: Vec<Class> =
+    (0..uses_count).map(|_| read_class(sr, constant_pool)).collect::<Result<_, _>>()?;
+  let provides_count = sr.get_u16()?;
+  let provides: Vec<Provides> =
+    (0..provides_count).map(|_| Provides::read(sr, constant_pool)).collect::<Result<_, _>>()?;
+
+  Ok(Attribute::Module {
+    module_name,
+    module_flags,
+    module_version,
+    requires,
+    exports,
+    opens,
+    uses,
+    provides
+  })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn write(
+  module_name: &str,
+  module_flags: ModuleFlags,
+  module_version: Option<&str>,
+  requires: &[Requires],
+  exports: &[Exports],
+  opens: &[Opens],
+  uses: &[Class],
+  provides: &[Provides],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(constant_pool.module(module_name)?);
+  sw.put_u16(*module_flags);
+  write_optional_utf8(module_version, constant_pool, sw)?;
+
+  sw.put_count(requires.len())?;
+  requires.iter().try_for_each(|requires| requires.write(constant_pool, sw))?;
+  sw.put_count(exports.len())?;
+  exports.iter().try_for_each(|exports| exports.write(constant_pool, sw))?;
+  sw.put_count(opens.len())?;
+  opens.iter().try_for_each(|opens| opens.write(constant_pool, sw))?;
+  write_classes(uses, constant_pool, sw)?;
+  sw.put_count(provides.len())?;
+  provides.iter().try_for_each(|provides| provides.write(constant_pool, sw))
+}
@@ -0,0 +1,26 @@
+use super::{attribute::module::read_class, Attribute};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let main_class = read_class(sr, constant_pool)?;
+
+  Ok(Attribute::ModuleMainClass { main_class })
+}
+
+pub fn write(
+  main_class: &Class,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(constant_pool.class(main_class)?);
+  Ok(())
+}
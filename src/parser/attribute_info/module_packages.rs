@@ -0,0 +1,30 @@
+use super::{attribute::module::read_package, Attribute};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let package_count = sr.get_u16()?;
+  let packages: Vec<String> =
+    (0..package_count).map(|_| read_package(sr, constant_pool)).collect::<Result<_, _>>()?;
+
+  Ok(Attribute::ModulePackages { packages })
+}
+
+pub fn write(
+  packages: &[String],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(packages.len())?;
+  for package in packages {
+    sw.put_u16(constant_pool.package(package)?);
+  }
+  Ok(())
+}
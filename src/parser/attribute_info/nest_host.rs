@@ -0,0 +1,27 @@
+use super::Attribute;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let host_class_index = sr.get_u16()?;
+  let host_class = constant_pool.get_class(host_class_index)?.clone();
+
+  Ok(Attribute::NestHost { host_class })
+}
+
+pub fn write(
+  host_class: &Class,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(constant_pool.class(host_class)?);
+  Ok(())
+}
@@ -0,0 +1,32 @@
+use super::Attribute;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::Class,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let number_of_classes = sr.get_u16()?;
+  let classes: Vec<Class> = (0..number_of_classes)
+    .map(|_| Ok(constant_pool.get_class(sr.get_u16()?)?.clone()))
+    .collect::<Result<_, _>>()?;
+
+  Ok(Attribute::NestMembers { classes })
+}
+
+pub fn write(
+  classes: &[Class],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(classes.len())?;
+  for class in classes {
+    sw.put_u16(constant_pool.class(class)?);
+  }
+  Ok(())
+}
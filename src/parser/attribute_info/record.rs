@@ -0,0 +1,33 @@
+use super::{
+  attribute::record_component::RecordComponent,
+  attribute_registry::AttributeRegistry,
+  Attribute
+};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool,
+  registry: &AttributeRegistry
+) -> Result<Attribute, ClassFormatError> {
+  let components_count = sr.get_u16()?;
+  let components: Vec<RecordComponent> = (0..components_count)
+    .map(|_| RecordComponent::read(sr, constant_pool, registry))
+    .collect::<Result<_, _>>()?;
+
+  Ok(Attribute::Record { components })
+}
+
+pub fn write(
+  components: &[RecordComponent],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(components.len())?;
+  components.iter().try_for_each(|component| component.write(constant_pool, sw))
+}
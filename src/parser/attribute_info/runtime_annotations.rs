@@ -1,13 +1,31 @@
 use super::{attribute::annotation::Annotation, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read<const VISIBLE: bool>(sr: &mut StreamReader) -> Attribute {
-  let num_annotations = sr.get_u16();
-  let annotations: Vec<Annotation> = (0..num_annotations).map(|_| Annotation::read(sr)).collect();
+pub fn read<const VISIBLE: bool>(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let num_annotations = sr.get_u16()?;
+  let annotations: Vec<Annotation> =
+    (0..num_annotations).map(|_| Annotation::read(sr, constant_pool)).collect::<Result<_, _>>()?;
 
   if VISIBLE {
-    return Attribute::RuntimeVisibleAnnotations { annotations };
+    Ok(Attribute::RuntimeVisibleAnnotations { annotations })
   } else {
-    return Attribute::RuntimeInvisibleAnnotations { annotations };
+    Ok(Attribute::RuntimeInvisibleAnnotations { annotations })
   }
 }
+
+pub fn write(
+  annotations: &[Annotation],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(annotations.len())?;
+  annotations.iter().try_for_each(|annotation| annotation.write(constant_pool, sw))
+}
@@ -1,14 +1,32 @@
 use super::{attribute::parameter_annotation::ParameterAnnotation, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read<const VISIBLE: bool>(sr: &mut StreamReader) -> Attribute {
-  let num_annotations = sr.get_u16();
-  let parameter_annotations: Vec<ParameterAnnotation> =
-    (0..num_annotations).map(|_| ParameterAnnotation::read(sr)).collect();
+pub fn read<const VISIBLE: bool>(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let num_annotations = sr.get_u8()?;
+  let parameter_annotations: Vec<ParameterAnnotation> = (0..num_annotations)
+    .map(|_| ParameterAnnotation::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
 
   if VISIBLE {
-    return Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations };
+    Ok(Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations })
   } else {
-    return Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations };
+    Ok(Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations })
   }
 }
+
+pub fn write(
+  parameter_annotations: &[ParameterAnnotation],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_short_count(parameter_annotations.len())?;
+  parameter_annotations.iter().try_for_each(|parameter| parameter.write(constant_pool, sw))
+}
@@ -0,0 +1,32 @@
+use super::{attribute::type_annotation::TypeAnnotation, Attribute};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+pub fn read<const VISIBLE: bool>(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let num_annotations = sr.get_u16()?;
+  let annotations: Vec<TypeAnnotation> = (0..num_annotations)
+    .map(|_| TypeAnnotation::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
+
+  if VISIBLE {
+    Ok(Attribute::RuntimeVisibleTypeAnnotations { annotations })
+  } else {
+    Ok(Attribute::RuntimeInvisibleTypeAnnotations { annotations })
+  }
+}
+
+pub fn write(
+  annotations: &[TypeAnnotation],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(annotations.len())?;
+  annotations.iter().try_for_each(|annotation| annotation.write(constant_pool, sw))
+}
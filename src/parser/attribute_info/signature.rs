@@ -1,9 +1,35 @@
 use super::Attribute;
-use crate::parser::{cp_info_resolved::ResolvedCpInfo, stream_reader::StreamReader};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> Attribute {
-  let signature_index = sr.get_u16();
-  let ResolvedCpInfo::Utf8 (signature) = &constant_pool[signature_index as usize - 1] else {panic!()};
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let signature_index = sr.get_u16()?;
+  let signature = constant_pool.get_utf8(signature_index)?;
 
-  Attribute::Signature { signature: signature.to_string() }
+  Ok(Attribute::Signature { signature: signature.to_string() })
+}
+
+pub fn write(
+  signature: &str,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(constant_pool.utf8(signature)?);
+  Ok(())
+}
+
+/// The raw signature among the attributes of a class, field, method or record component.
+/// Which grammar applies depends on which of those it belongs to.
+pub fn find_signature(attributes: &[Attribute]) -> Option<&str> {
+  attributes.iter().find_map(|attribute| match attribute {
+    Attribute::Signature { signature } => Some(signature.as_str()),
+    _ => None
+  })
 }
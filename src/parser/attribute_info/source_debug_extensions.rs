@@ -1,6 +1,14 @@
 use super::Attribute;
-use crate::parser::stream_reader::StreamReader;
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader, attribute_length: u32) -> Attribute {
-  Attribute::SourceDebugExtension { debug_extension: sr.take_n(attribute_length as usize) }
+pub fn read(sr: &mut StreamReader, attribute_length: u32) -> Result<Attribute, ClassFormatError> {
+  Ok(Attribute::SourceDebugExtension {
+    debug_extension: sr.take_n(attribute_length as usize)?.to_vec()
+  })
 }
+
+pub fn write(debug_extension: &[u8], sw: &mut StreamWriter) { sw.put_bytes(debug_extension) }
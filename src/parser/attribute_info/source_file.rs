@@ -1,9 +1,26 @@
 use super::Attribute;
-use crate::parser::{cp_info_resolved::ResolvedCpInfo, stream_reader::StreamReader};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> Attribute {
-  let sourcefile_index = sr.get_u16();
-  let ResolvedCpInfo::Utf8 (sourcefile) = &constant_pool[sourcefile_index as usize - 1] else {panic!()};
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let sourcefile_index = sr.get_u16()?;
+  let sourcefile = constant_pool.get_utf8(sourcefile_index)?;
 
-  Attribute::SourceFile { sourcefile: sourcefile.to_string() }
+  Ok(Attribute::SourceFile { sourcefile: sourcefile.to_string() })
+}
+
+pub fn write(
+  sourcefile: &str,
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_u16(constant_pool.utf8(sourcefile)?);
+  Ok(())
 }
@@ -1,10 +1,76 @@
-use super::{attribute::stack_map_frame::StackMapFrame, Attribute};
-use crate::parser::stream_reader::StreamReader;
+use super::{attribute::stack_map_frame::StackMapFrame, code::offset_map::OffsetMap, Attribute};
+use crate::parser::{
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
 
-pub fn read(sr: &mut StreamReader) -> Attribute {
-  let number_of_entries = sr.get_u16();
-  let entries: Vec<StackMapFrame> =
-    (0..number_of_entries).map(|_| StackMapFrame::read(sr)).collect();
+pub fn read(
+  sr: &mut StreamReader,
+  constant_pool: &ConstantPool
+) -> Result<Attribute, ClassFormatError> {
+  let number_of_entries = sr.get_u16()?;
+  let entries: Vec<StackMapFrame> = (0..number_of_entries)
+    .map(|_| StackMapFrame::read(sr, constant_pool))
+    .collect::<Result<_, _>>()?;
 
-  Attribute::StackMapTable { entries }
+  Ok(Attribute::StackMapTable { entries })
+}
+
+pub fn write(
+  entries: &[StackMapFrame],
+  constant_pool: &mut ConstantPoolBuilder,
+  sw: &mut StreamWriter
+) -> Result<(), ClassFormatError> {
+  sw.put_count(entries.len())?;
+  entries.iter().try_for_each(|entry| entry.write(constant_pool, sw))
+}
+
+/// Rewrites every frame's offset delta as a distance in instructions.
+/// Each frame after the first sits `offset_delta + 1` past the previous one, in bytes or indices.
+pub fn translate(
+  entries: &mut [StackMapFrame],
+  offsets: &OffsetMap
+) -> Result<(), ClassFormatError> {
+  let mut previous: Option<(usize, u16)> = None;
+  for entry in entries {
+    let offset = match previous {
+      Some((offset, _)) => offset + entry.offset_delta() as usize + 1,
+      None => entry.offset_delta() as usize
+    };
+    let index = offsets.index_of(offset).filter(|index| *index < offsets.len());
+    let index = index.ok_or(ClassFormatError::BadCodeOffset { offset })? as u16;
+    let index_delta = match previous {
+      Some((_, previous_index)) => index - previous_index - 1,
+      None => index
+    };
+    entry.translate(index_delta, offsets)?;
+    previous = Some((offset, index));
+  }
+  Ok(())
+}
+
+/// Rewrites every frame's index delta as a distance in bytes, undoing `translate`.
+pub fn untranslate(
+  entries: &mut [StackMapFrame],
+  offsets: &OffsetMap
+) -> Result<(), ClassFormatError> {
+  let mut previous: Option<(u16, u16)> = None;
+  for entry in entries {
+    let index = match previous {
+      Some((index, _)) => index as usize + entry.offset_delta() as usize + 1,
+      None => entry.offset_delta() as usize
+    };
+    let index =
+      u16::try_from(index).map_err(|_| ClassFormatError::BadCodeIndex { index: index as isize })?;
+    let offset = offsets.offset(index)?;
+    let offset_delta = match previous {
+      Some((_, previous_offset)) => offset - previous_offset - 1,
+      None => offset
+    };
+    entry.untranslate(offset_delta, offsets)?;
+    previous = Some((index, offset));
+  }
+  Ok(())
 }
@@ -0,0 +1,173 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassFormatError {
+  BadMagic { found: u32 },
+  Truncated { offset: usize },
+  TrailingBytes { offset: usize },
+  BadConstantTag { index: u16, tag: u8 },
+  BadConstantIndex { index: u16 },
+  WrongConstantKind { index: u16, expected: &'static str, found: &'static str },
+  BadReferenceKind { kind: u8 },
+  BadBootstrapMethodIndex { index: u16 },
+  CyclicBootstrapMethod { index: u16 },
+  BadUtf8 { index: u16 },
+  BadOpcode { offset: usize, opcode: u8 },
+  BadWideOpcode { offset: usize, opcode: u8 },
+  NonZeroPadding { offset: usize },
+  BadBranchTarget { offset: usize, target: isize },
+  BadCodeOffset { offset: usize },
+  BadStackMapFrameType { frame_type: u8 },
+  BadChopFrame { index: usize },
+  BadVerificationType { tag: u8 },
+  BadElementValueTag { tag: u8 },
+  NestingTooDeep { offset: usize },
+  BadTargetType { target_type: u8 },
+  BadConstantValue { field: String },
+  BadDescriptor { descriptor: String },
+  BadSignature { signature: String },
+  BadTypePathKind { kind: u8 },
+  BadAttributeLength { name: String, offset: usize },
+  BadModifiers { kind: &'static str, name: String, flags: u16 },
+  BadName { kind: &'static str, name: String },
+  DuplicateMember { kind: &'static str, name: String },
+  BadSuperclass { name: Option<String> },
+  MissingCode { method: String },
+  UnexpectedCode { method: String },
+  BadCodeIndex { index: isize },
+  BranchOutOfRange { index: usize, offset: isize },
+  UnencodableInstruction { index: usize },
+  CodeTooLong { length: usize },
+  TooManyConstants,
+  TableTooLong { length: usize },
+  UnwritableAttribute { name: String },
+  InexpressibleAttribute { name: String }
+}
+
+impl fmt::Display for ClassFormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ClassFormatError::BadMagic { found } => write!(f, "Incompatible magic value {:#010x}", found),
+      ClassFormatError::Truncated { offset } => {
+        write!(f, "Truncated class file: unexpected end of stream at byte {}", offset)
+      }
+      ClassFormatError::TrailingBytes { offset } => {
+        write!(f, "Extra bytes at the end of the class file, starting at byte {}", offset)
+      }
+      ClassFormatError::BadConstantTag { index, tag } => {
+        write!(f, "Unknown constant tag {} at constant pool index {}", tag, index)
+      }
+      ClassFormatError::BadConstantIndex { index } => {
+        write!(f, "Constant pool index {} is out of bounds", index)
+      }
+      ClassFormatError::WrongConstantKind { index, expected, found } => {
+        write!(f, "Constant pool index {} should be a {} but was a {}", index, expected, found)
+      }
+      ClassFormatError::BadReferenceKind { kind } => {
+        write!(f, "Bad method handle kind {}", kind)
+      }
+      ClassFormatError::BadBootstrapMethodIndex { index } => {
+        write!(f, "Bootstrap method index {} is out of bounds", index)
+      }
+      ClassFormatError::CyclicBootstrapMethod { index } => {
+        write!(f, "Bootstrap method {} has a cyclic dynamic constant argument", index)
+      }
+      ClassFormatError::BadUtf8 { index } => {
+        write!(f, "Illegal UTF8 string in constant pool at index {}", index)
+      }
+      ClassFormatError::BadOpcode { offset, opcode } => {
+        write!(f, "Unknown opcode {} at code offset {}", opcode, offset)
+      }
+      ClassFormatError::BadWideOpcode { offset, opcode } => {
+        write!(f, "Opcode {} cannot be widened (at code offset {})", opcode, offset)
+      }
+      ClassFormatError::NonZeroPadding { offset } => {
+        write!(f, "Reserved operand bytes were non-zero at code offset {}", offset)
+      }
+      ClassFormatError::BadBranchTarget { offset, target } => write!(
+        f,
+        "Branch at code offset {} targets offset {}, which is not the start of an instruction",
+        offset, target
+      ),
+      ClassFormatError::BadCodeOffset { offset } => {
+        write!(f, "Code offset {} does not mark the start of an instruction", offset)
+      }
+      ClassFormatError::BadStackMapFrameType { frame_type } => {
+        write!(f, "Reserved StackMapTable frame type {}", frame_type)
+      }
+      ClassFormatError::BadChopFrame { index } => {
+        write!(f, "StackMapTable frame at instruction {} chops more locals than there are", index)
+      }
+      ClassFormatError::BadVerificationType { tag } => {
+        write!(f, "Unknown verification type tag {}", tag)
+      }
+      ClassFormatError::BadElementValueTag { tag } => {
+        write!(f, "Unknown annotation element value tag {}", tag)
+      }
+      ClassFormatError::NestingTooDeep { offset } => {
+        write!(f, "Annotation element value nests too deeply at byte {}", offset)
+      }
+      ClassFormatError::BadConstantValue { field } => {
+        write!(f, "Inconsistent constant value type for field {}", field)
+      }
+      ClassFormatError::BadDescriptor { descriptor } => {
+        write!(f, "Invalid descriptor {}", descriptor)
+      }
+      ClassFormatError::BadSignature { signature } => {
+        write!(f, "Invalid generic signature {}", signature)
+      }
+      ClassFormatError::BadTargetType { target_type } => {
+        write!(f, "Unknown type annotation target type {:#04x}", target_type)
+      }
+      ClassFormatError::BadTypePathKind { kind } => {
+        write!(f, "Unknown type annotation path kind {}", kind)
+      }
+      ClassFormatError::BadAttributeLength { name, offset } => {
+        write!(f, "Attribute {} ends before its attribute_length at byte {}", name, offset)
+      }
+      ClassFormatError::BadModifiers { kind, name, flags } => {
+        write!(f, "Illegal {} modifiers {:#06x} on {}", kind, flags, name)
+      }
+      ClassFormatError::BadName { kind, name } => write!(f, "Illegal {} name \"{}\"", kind, name),
+      ClassFormatError::DuplicateMember { kind, name } => write!(f, "Duplicate {} {}", kind, name),
+      ClassFormatError::BadSuperclass { name: Some(name) } => {
+        write!(f, "Illegal superclass {}", name)
+      }
+      ClassFormatError::BadSuperclass { name: None } => {
+        write!(f, "Class other than java/lang/Object has no superclass")
+      }
+      ClassFormatError::MissingCode { method } => {
+        write!(f, "Method {} is neither abstract nor native but has no Code attribute", method)
+      }
+      ClassFormatError::UnexpectedCode { method } => {
+        write!(f, "Abstract or native method {} has a Code attribute", method)
+      }
+      ClassFormatError::BadCodeIndex { index } => {
+        write!(f, "Instruction index {} is out of bounds", index)
+      }
+      ClassFormatError::BranchOutOfRange { index, offset } => write!(
+        f,
+        "Branch at instruction {} jumps {} bytes, too far for its instruction to encode",
+        index, offset
+      ),
+      ClassFormatError::UnencodableInstruction { index } => {
+        write!(f, "Instruction {} has an operand its opcode can't encode", index)
+      }
+      ClassFormatError::CodeTooLong { length } => {
+        write!(f, "Code is {} bytes long, more than a method may have", length)
+      }
+      ClassFormatError::TooManyConstants => write!(f, "Too many constants for one constant pool"),
+      ClassFormatError::TableTooLong { length } => {
+        write!(f, "A table of {} entries is too long for the class file", length)
+      }
+      ClassFormatError::UnwritableAttribute { name } => {
+        write!(f, "Attribute {} was not decoded, so it can't be written", name)
+      }
+      ClassFormatError::InexpressibleAttribute { name } => {
+        write!(f, "Attribute {} has no assembly syntax", name)
+      }
+    }
+  }
+}
+
+impl Error for ClassFormatError {}
@@ -1,61 +1,82 @@
-use std::{
-  collections::{HashMap, HashSet},
-  fs
-};
+use std::collections::HashSet;
 
 use bitmask::bitmask;
 
 use super::{
-  attribute_info::Attribute,
+  attribute_info::{
+    attribute::{
+      annotation::{self, Annotation},
+      bootstrap_method::RawBootstrapMethod
+    },
+    attribute_registry::AttributeRegistry,
+    bootstrap_methods,
+    code::code_generator::Instructions,
+    signature::find_signature,
+    Attribute
+  },
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
   cp_info::CpInfo,
   cp_info_resolved::{Class, ResolvedCpInfo},
   field_info::FieldInfo,
   method_info::MethodInfo,
-  stream_reader::StreamReader
+  signature::ClassSignature,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
 };
 
 bitmask! {
   #[derive(Debug)]
   pub mask ClassAccessFlags: u16 where flags Flags {
     AccPublic = 0x0001,
-    AccPrivate = 0x0002,
-    AccProtected = 0x0004,
-    AccStatic = 0x0008,
     AccFinal = 0x0010,
-    AccVolatile = 0x0040,
-    AccTransient = 0x0080,
+    AccSuper = 0x0020,
+    AccInterface = 0x0200,
+    AccAbstract = 0x0400,
     AccSynthetic = 0x1000,
-    AccEnum = 0x4000
+    AccAnnotation = 0x2000,
+    AccEnum = 0x4000,
+    AccModule = 0x8000
   }
 }
 
-#[derive(Debug, Clone)]
+impl ClassAccessFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { ClassAccessFlags { mask } }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClassFile {
+  pub minor_version: u16,
+  pub major_version: u16,
   pub access_flags: ClassAccessFlags,
-  pub super_class: Class,
-  pub interfaces: Vec<u16>,
-  pub fields: HashMap<String, FieldInfo>,
-  pub methods: HashMap<String, MethodInfo>,
+  pub this_class: Class,
+  /// `None` only for `java/lang/Object` and `module-info`, which have no superclass.
+  pub super_class: Option<Class>,
+  pub interfaces: Vec<Class>,
+  /// Keyed by `name:descriptor`, in the order the class file declares them.
+  /// Duplicates are kept for `check_format` to report.
+  pub fields: Vec<(String, FieldInfo)>,
+  /// Keyed and ordered like `fields`.
+  pub methods: Vec<(String, MethodInfo)>,
   pub attributes: Vec<Attribute>
 }
 
 impl ClassFile {
-  pub fn read(path: String) -> (String, Self, HashSet<String>) {
-    let buf = fs::read(path.clone())
-      .or(fs::read(path.clone() + ".class"))
-      .expect(format!("Could not find a file at {0} or {0}.class", path).as_str());
-    let mut sr = &mut StreamReader::from(buf);
-    sr.stream =
-      sr.stream.strip_prefix(&[0xca, 0xfe, 0xba, 0xbe]).expect("File has invalid header").to_vec();
-    let _minor_version = sr.get_u16();
-    let _major_version = sr.get_u16();
-    let constant_pool_count = sr.get_u16();
-    let constant_pool: Vec<CpInfo> = (1..constant_pool_count).map(|_| CpInfo::read(sr)).collect();
-    let resolved_constant_pool: Vec<ResolvedCpInfo> =
-      constant_pool.iter().map(|val| ResolvedCpInfo::from(val, &constant_pool)).collect();
+  pub fn parse(bytes: &[u8]) -> Result<(Self, HashSet<String>), ClassFormatError> {
+    Self::parse_with(bytes, &AttributeRegistry::new())
+  }
+
+  /// Parses a class file, decoding any non-standard attributes with the registered decoders.
+  pub fn parse_with(
+    bytes: &[u8],
+    registry: &AttributeRegistry
+  ) -> Result<(Self, HashSet<String>), ClassFormatError> {
+    let sr = &mut StreamReader::from(bytes);
+    let (minor_version, major_version, _, resolved_constant_pool) = read_header(sr)?;
 
     let mut depends = HashSet::new();
-    for constant in resolved_constant_pool.iter() {
+    for (_, constant) in resolved_constant_pool.iter() {
       match constant {
         ResolvedCpInfo::Fieldref(a) => {
           depends.insert(a.class.name.clone());
@@ -70,34 +91,238 @@ impl ClassFile {
       }
     }
 
-    let access_flags_num = sr.get_u16();
+    let access_flags_num = sr.get_u16()?;
     let access_flags = ClassAccessFlags { mask: access_flags_num };
 
-    let this_class = sr.get_u16();
-    let super_class_index = sr.get_u16();
-    let ResolvedCpInfo::Class(super_class) = resolved_constant_pool[super_class_index as usize -1].clone() else {panic!()};
-    let interfaces_count = sr.get_u16();
-    let interfaces: Vec<u16> = (0..interfaces_count).map(|_| sr.get_u16()).collect();
-    let fields_count = sr.get_u16();
-    let fields: HashMap<String, FieldInfo> =
-      (0..fields_count).map(|_| FieldInfo::read(sr, &resolved_constant_pool)).collect();
-    let methods_count = sr.get_u16();
-    let methods: HashMap<String, MethodInfo> =
-      (0..methods_count).map(|_| MethodInfo::read(sr, &resolved_constant_pool)).collect();
-    let attributes_count = sr.get_u16();
-    let attributes: Vec<Attribute> =
-      (0..attributes_count).map(|_| Attribute::read(sr, &resolved_constant_pool)).collect();
+    let this_class = resolved_constant_pool.get_class(sr.get_u16()?)?.clone();
+    let super_class = match sr.get_u16()? {
+      0 => None,
+      super_class_index => Some(resolved_constant_pool.get_class(super_class_index)?.clone())
+    };
+    let interfaces_count = sr.get_u16()?;
+    let interfaces: Vec<Class> = (0..interfaces_count)
+      .map(|_| Ok(resolved_constant_pool.get_class(sr.get_u16()?)?.clone()))
+      .collect::<Result<_, _>>()?;
+    let fields_count = sr.get_u16()?;
+    let fields: Vec<(String, FieldInfo)> = (0..fields_count)
+      .map(|_| FieldInfo::read(sr, &resolved_constant_pool, registry))
+      .collect::<Result<_, _>>()?;
+    let methods_count = sr.get_u16()?;
+    let methods: Vec<(String, MethodInfo)> = (0..methods_count)
+      .map(|_| MethodInfo::read(sr, &resolved_constant_pool, registry))
+      .collect::<Result<_, _>>()?;
+    let attributes_count = sr.get_u16()?;
+    let attributes: Vec<Attribute> = (0..attributes_count)
+      .map(|_| Attribute::read(sr, &resolved_constant_pool, registry))
+      .collect::<Result<_, _>>()?;
     if !sr.done() {
-      panic!("Extra bytes were found at the end of the classfile")
+      return Err(ClassFormatError::TrailingBytes { offset: sr.offset() });
     }
 
-    let CpInfo::Class { name_index } = &constant_pool[this_class as usize - 1] else {panic!()};
-    let CpInfo::Utf8 { bytes: name } = &constant_pool[*name_index as usize - 1] else {panic!()};
-
-    (
-      name.to_string(),
-      Self { access_flags, super_class, interfaces, fields, methods, attributes },
+    Ok((
+      Self {
+        minor_version,
+        major_version,
+        access_flags,
+        this_class,
+        super_class,
+        interfaces,
+        fields,
+        methods,
+        attributes
+      },
       depends
-    )
+    ))
+  }
+
+  /// The constant pool of a class file, both as stored and resolved, for tools such as the
+  /// disassembler that show constant indices. `parse` keeps only the resolved constants.
+  pub fn constant_pool(
+    bytes: &[u8]
+  ) -> Result<(ConstantPool<CpInfo<'_>>, ConstantPool), ClassFormatError> {
+    let (_, _, constant_pool, resolved_constant_pool) =
+      read_header(&mut StreamReader::from(bytes))?;
+    Ok((constant_pool, resolved_constant_pool))
+  }
+
+  /// Encodes the class as a class file, with a constant pool rebuilt from what the class uses.
+  /// Parsing the result gives back an equal `ClassFile`. Fails on `Unknown` attributes, and on
+  /// `Custom` ones registered without an encoder, since only decoded attributes can be moved to the
+  /// new constant pool.
+  pub fn write(&self) -> Result<Vec<u8>, ClassFormatError> {
+    let declared = self.attributes.iter().find_map(|attribute| match attribute {
+      Attribute::BootstrapMethods { bootstrap_methods } => Some(bootstrap_methods.as_slice()),
+      _ => None
+    });
+    let mut constant_pool = ConstantPoolBuilder::new(declared.unwrap_or_default());
+    // `ldc` only has a byte for its index
+    let ldc_constants: Vec<&ResolvedCpInfo> = self
+      .methods
+      .iter()
+      .flat_map(|(_, method)| &method.attributes)
+      .filter_map(|attribute| match attribute {
+        Attribute::Code { code, .. } => Some(code),
+        _ => None
+      })
+      .flatten()
+      .filter_map(|instruction| match instruction {
+        Instructions::ldc { constant } => Some(constant),
+        _ => None
+      })
+      .collect();
+    constant_pool.reserve(&ldc_constants)?;
+
+    let body = &mut StreamWriter::new();
+    body.put_u16(*self.access_flags);
+    body.put_u16(constant_pool.class(&self.this_class)?);
+    match &self.super_class {
+      Some(super_class) => body.put_u16(constant_pool.class(super_class)?),
+      None => body.put_u16(0)
+    }
+    body.put_count(self.interfaces.len())?;
+    for interface in &self.interfaces {
+      body.put_u16(constant_pool.class(interface)?);
+    }
+    body.put_count(self.fields.len())?;
+    for (key, field) in &self.fields {
+      field.write(key, &mut constant_pool, body)?;
+    }
+    body.put_count(self.methods.len())?;
+    for (key, method) in &self.methods {
+      method.write(key, &mut constant_pool, body)?;
+    }
+
+    // BootstrapMethods goes last, once every constant has claimed its bootstrap method, but keeps
+    // its place in the attribute list
+    let mut attributes: Vec<Option<StreamWriter>> = Vec::new();
+    let mut bootstrap_methods = None;
+    for attribute in &self.attributes {
+      match attribute {
+        Attribute::BootstrapMethods { .. } => {
+          bootstrap_methods.get_or_insert(attributes.len());
+          attributes.push(None);
+        }
+        _ => {
+          let sw = &mut StreamWriter::new();
+          attribute.write(&mut constant_pool, sw)?;
+          attributes.push(Some(sw.clone()));
+        }
+      }
+    }
+    let bootstrap_methods = match bootstrap_methods {
+      Some(index) => Some(index),
+      None if !constant_pool.bootstrap_methods()?.is_empty() => {
+        attributes.push(None);
+        Some(attributes.len() - 1)
+      }
+      None => None
+    };
+    if let Some(index) = bootstrap_methods {
+      let sw = &mut StreamWriter::new();
+      Attribute::BootstrapMethods { bootstrap_methods: Vec::new() }
+        .write(&mut constant_pool, sw)?;
+      attributes[index] = Some(sw.clone());
+    }
+
+    let sw = &mut StreamWriter::new();
+    sw.put_u32(0xcafebabe);
+    sw.put_u16(self.minor_version);
+    sw.put_u16(self.major_version);
+    constant_pool.write(sw);
+    sw.put_bytes(&body.stream);
+    sw.put_count(attributes.len())?;
+    for attribute in attributes.iter().flatten() {
+      sw.put_bytes(&attribute.stream);
+    }
+    Ok(sw.stream.clone())
+  }
+
+  /// The generic class signature, if it has one.
+  /// Like the JVM, the parser only checks signatures when they are asked for.
+  pub fn signature(&self) -> Result<Option<ClassSignature>, ClassFormatError> {
+    find_signature(&self.attributes).map(ClassSignature::parse).transpose()
+  }
+
+  /// The annotation of the given type, such as `Lorg/junit/Test;`, visible or not.
+  pub fn annotation(&self, type_name: &str) -> Option<&Annotation> {
+    annotation::annotations(&self.attributes).find(|annotation| annotation.type_name == type_name)
+  }
+}
+
+/// Reads the magic number, the version and the constant pool, leaving `sr` at the access flags.
+fn read_header<'a>(
+  sr: &mut StreamReader<'a>
+) -> Result<(u16, u16, ConstantPool<CpInfo<'a>>, ConstantPool), ClassFormatError> {
+  let magic = sr.get_u32()?;
+  if magic != 0xcafebabe {
+    return Err(ClassFormatError::BadMagic { found: magic });
+  }
+  let minor_version = sr.get_u16()?;
+  let major_version = sr.get_u16()?;
+  let constant_pool: ConstantPool<CpInfo> = ConstantPool::read(sr)?;
+  let raw_bootstrap_methods = find_bootstrap_methods(sr.clone(), &constant_pool)?;
+  let resolved_constant_pool = constant_pool.resolve(&raw_bootstrap_methods)?;
+  Ok((minor_version, major_version, constant_pool, resolved_constant_pool))
+}
+
+/// Dynamic constants refer to the `BootstrapMethods` attribute, which comes after everything else
+/// in the class file, so skim ahead for it before the constant pool is resolved.
+fn find_bootstrap_methods(
+  mut sr: StreamReader,
+  constant_pool: &ConstantPool<CpInfo>
+) -> Result<Vec<RawBootstrapMethod>, ClassFormatError> {
+  let sr = &mut sr;
+  // access_flags, this_class and super_class
+  sr.take_n(6)?;
+  let interfaces_count = sr.get_u16()?;
+  sr.take_n(interfaces_count as usize * 2)?;
+  for _ in 0..2 {
+    // fields, then methods
+    let count = sr.get_u16()?;
+    for _ in 0..count {
+      // access_flags, name_index and descriptor_index
+      sr.take_n(6)?;
+      let attributes_count = sr.get_u16()?;
+      for _ in 0..attributes_count {
+        sr.take_n(2)?;
+        let attribute_length = sr.get_u32()?;
+        sr.take_n(attribute_length as usize)?;
+      }
+    }
+  }
+  let attributes_count = sr.get_u16()?;
+  for _ in 0..attributes_count {
+    let attribute_name_index = sr.get_u16()?;
+    let attribute_length = sr.get_u32()?;
+    let mut attribute_reader = sr.sub_reader(attribute_length as usize)?;
+    if constant_pool.get_utf8(attribute_name_index)? == "BootstrapMethods" {
+      return bootstrap_methods::read_raw(&mut attribute_reader);
+    }
+  }
+  Ok(Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Every class in `java_test`. `Constants` has both kinds of switch, long and double constants
+  /// and more than 255 constants, so the last strings need `ldc_w`.
+  #[test]
+  fn written_classes_parse_back_equal() {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/java_test");
+    let mut count = 0;
+    for entry in std::fs::read_dir(directory).unwrap() {
+      let path = entry.unwrap().path();
+      if path.extension().is_some_and(|extension| extension == "class") {
+        let (class, depends) = ClassFile::parse(&std::fs::read(&path).unwrap()).unwrap();
+        let written = class.write().unwrap();
+        let (reparsed, reparsed_depends) = ClassFile::parse(&written).unwrap();
+        assert_eq!(reparsed, class, "{}", path.display());
+        assert_eq!(reparsed_depends, depends, "{}", path.display());
+        count += 1;
+      }
+    }
+    assert_eq!(count, 17);
   }
 }
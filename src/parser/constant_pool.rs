@@ -0,0 +1,477 @@
+use std::{borrow::Cow, collections::HashMap, rc::Rc};
+
+use super::{
+  attribute_info::attribute::bootstrap_method::{BootstrapMethod, RawBootstrapMethod},
+  class_format_error::ClassFormatError,
+  cp_info::CpInfo,
+  cp_info_resolved::{
+    BootstrapMethods,
+    Class,
+    Dynamic,
+    Fieldref,
+    InterfaceMethodref,
+    InvokeDynamic,
+    JavaString,
+    MethodHandle,
+    MethodHandleRef,
+    MethodType,
+    Methodref,
+    Module,
+    NameAndType,
+    Package,
+    ResolvedCpInfo
+  },
+  modified_utf8,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
+};
+
+/// A class file's constant pool, indexed the way the JVM indexes it.
+/// Slot 0 is never valid, and each `Long` or `Double` entry makes the slot after it unusable,
+/// so both are stored as `None`.
+#[derive(Debug, Clone)]
+pub struct ConstantPool<T = ResolvedCpInfo> {
+  entries: Vec<Option<T>>
+}
+
+impl<T> ConstantPool<T> {
+  pub fn get(&self, index: u16) -> Result<&T, ClassFormatError> {
+    match self.entries.get(index as usize) {
+      Some(Some(entry)) => Ok(entry),
+      _ => Err(ClassFormatError::BadConstantIndex { index })
+    }
+  }
+
+  /// The `constant_pool_count` of the class file, one more than the highest valid index.
+  pub fn count(&self) -> u16 { self.entries.len() as u16 }
+
+  pub fn iter(&self) -> impl Iterator<Item = (u16, &T)> {
+    self
+      .entries
+      .iter()
+      .enumerate()
+      .filter_map(|(index, entry)| Some((index as u16, entry.as_ref()?)))
+  }
+}
+
+impl<'a> ConstantPool<CpInfo<'a>> {
+  pub fn read(sr: &mut StreamReader<'a>) -> Result<Self, ClassFormatError> {
+    let constant_pool_count = sr.get_u16()?;
+    let mut entries = Vec::with_capacity(constant_pool_count as usize);
+    entries.push(None);
+    while entries.len() < constant_pool_count as usize {
+      let entry = CpInfo::read(sr, entries.len() as u16)?;
+      let wide = matches!(entry, CpInfo::Long { .. } | CpInfo::Double { .. });
+      entries.push(Some(entry));
+      if wide {
+        entries.push(None);
+      }
+    }
+    if entries.len() > constant_pool_count as usize {
+      // The final entry was 8 bytes wide and claimed a slot past the end of the pool
+      return Err(ClassFormatError::BadConstantIndex { index: constant_pool_count });
+    }
+    Ok(Self { entries })
+  }
+
+  pub fn resolve(
+    &self,
+    bootstrap_methods: &[RawBootstrapMethod]
+  ) -> Result<ConstantPool, ClassFormatError> {
+    let bootstrap_methods = &mut BootstrapMethods::new(bootstrap_methods);
+    let entries = self
+      .entries
+      .iter()
+      .map(|entry| {
+        entry.as_ref().map(|entry| ResolvedCpInfo::from(entry, self, bootstrap_methods)).transpose()
+      })
+      .collect::<Result<_, _>>()?;
+    Ok(ConstantPool { entries })
+  }
+
+  pub fn get_utf8(&self, index: u16) -> Result<Cow<'a, str>, ClassFormatError> {
+    match self.get(index)? {
+      CpInfo::Utf8 { bytes } => Ok(bytes.to_str_lossy()),
+      other => Err(wrong_kind(index, "Utf8", other.kind()))
+    }
+  }
+
+  pub fn get_java_string(&self, index: u16) -> Result<JavaString, ClassFormatError> {
+    match self.get(index)? {
+      CpInfo::Utf8 { bytes } => Ok(JavaString { chars: bytes.to_utf16() }),
+      other => Err(wrong_kind(index, "Utf8", other.kind()))
+    }
+  }
+
+  pub fn get_class(&self, index: u16) -> Result<Class, ClassFormatError> {
+    match self.get(index)? {
+      CpInfo::Class { name_index } => Ok(Class { name: self.get_utf8(*name_index)?.to_string() }),
+      other => Err(wrong_kind(index, "Class", other.kind()))
+    }
+  }
+
+  pub fn get_name_and_type(&self, index: u16) -> Result<NameAndType, ClassFormatError> {
+    match self.get(index)? {
+      CpInfo::NameAndType { name_index, descriptor_index } => Ok(NameAndType {
+        name: self.get_utf8(*name_index)?.to_string(),
+        descriptor: self.get_utf8(*descriptor_index)?.to_string()
+      }),
+      other => Err(wrong_kind(index, "NameAndType", other.kind()))
+    }
+  }
+}
+
+// Each accessor checks the index and the kind of constant stored there
+macro_rules! resolved_accessors {
+  ($($accessor:ident => $variant:ident: $type:ty),*) => {
+    impl ConstantPool {
+      $(
+        pub fn $accessor(&self, index: u16) -> Result<&$type, ClassFormatError> {
+          match self.get(index)? {
+            ResolvedCpInfo::$variant(value) => Ok(value),
+            other => Err(wrong_kind(index, stringify!($variant), other.kind()))
+          }
+        }
+      )*
+    }
+  };
+}
+
+resolved_accessors! {
+  get_class => Class: Class,
+  get_fieldref => Fieldref: Fieldref,
+  get_methodref => Methodref: Methodref,
+  get_interface_methodref => InterfaceMethodref: InterfaceMethodref,
+  get_integer => Integer: i32,
+  get_float => Float: f32,
+  get_long => Long: i64,
+  get_double => Double: f64,
+  get_name_and_type => NameAndType: NameAndType,
+  get_method_handle => MethodHandle: MethodHandle,
+  get_invoke_dynamic => InvokeDynamic: InvokeDynamic,
+  get_module => Module: Module,
+  get_package => Package: Package,
+  get_java_string => Utf8: JavaString
+}
+
+impl ConstantPool {
+  /// A `CONSTANT_Utf8` as a Rust string, with unpaired surrogates replaced by U+FFFD. Names and
+  /// descriptors can't hold those, so only string values need `get_java_string`.
+  pub fn get_utf8(&self, index: u16) -> Result<String, ClassFormatError> {
+    Ok(self.get_java_string(index)?.to_string())
+  }
+}
+
+fn wrong_kind(index: u16, expected: &'static str, found: &'static str) -> ClassFormatError {
+  ClassFormatError::WrongConstantKind { index, expected, found }
+}
+
+/// Assembles the constant pool of a class file being written. Each constant gets an index the
+/// first time it is asked for, and the same index every time after.
+/// Bootstrap methods are gathered alongside, as `Dynamic` and `InvokeDynamic` entries refer to
+/// them by their index in the `BootstrapMethods` attribute.
+#[derive(Debug)]
+pub struct ConstantPoolBuilder {
+  /// Each entry's tag and contents, indexed like `ConstantPool`. Slot 0, the slot after a `Long`
+  /// or `Double` and a slot `reserve` has yet to fill are `None`.
+  entries: Vec<Option<Vec<u8>>>,
+  indices: HashMap<Vec<u8>, u16>,
+  /// The class's own bootstrap methods, which keep their indices.
+  declared: Vec<BootstrapMethod>,
+  bootstrap_methods: Vec<Option<RawBootstrapMethod>>,
+  /// The index given to each shared bootstrap method, so that one used by many constants is
+  /// only written out once. The `Rc` is held to keep its address from being reused.
+  shared: HashMap<*const BootstrapMethod, (Rc<BootstrapMethod>, u16)>
+}
+
+impl ConstantPoolBuilder {
+  pub fn new(declared: &[BootstrapMethod]) -> Self {
+    ConstantPoolBuilder {
+      entries: vec![None],
+      indices: HashMap::new(),
+      declared: declared.to_vec(),
+      bootstrap_methods: vec![None; declared.len()],
+      shared: HashMap::new()
+    }
+  }
+
+  /// Gives the lowest indices to `constants`, which `ldc` can only reach with a one byte index.
+  /// They must be single slot constants, as `ldc` can't load a `Long` or `Double`.
+  pub fn reserve(&mut self, constants: &[&ResolvedCpInfo]) -> Result<(), ClassFormatError> {
+    let mut distinct: Vec<&ResolvedCpInfo> = Vec::new();
+    for constant in constants {
+      if !distinct.contains(constant) {
+        distinct.push(constant);
+      }
+    }
+    let first = self.entries.len();
+    if first + distinct.len() > u16::MAX as usize {
+      return Err(ClassFormatError::TooManyConstants);
+    }
+    self.entries.resize(first + distinct.len(), None);
+    for (slot, constant) in (first..).zip(distinct) {
+      let entry = self.entry(constant)?;
+      // The constant may have been added already as part of an earlier one, but the reserved
+      // slot is the one to hand out
+      self.indices.insert(entry.clone(), slot as u16);
+      self.entries[slot] = Some(entry);
+    }
+    Ok(())
+  }
+
+  pub fn constant(&mut self, constant: &ResolvedCpInfo) -> Result<u16, ClassFormatError> {
+    let entry = self.entry(constant)?;
+    self.add(entry)
+  }
+
+  pub fn utf8(&mut self, value: &str) -> Result<u16, ClassFormatError> {
+    let entry = utf8_entry(&modified_utf8::encode_str(value))?;
+    self.add(entry)
+  }
+
+  /// Like `utf8`, for a string that may hold unpaired surrogates.
+  pub fn java_string(&mut self, value: &JavaString) -> Result<u16, ClassFormatError> {
+    let entry = utf8_entry(&modified_utf8::encode(&value.chars))?;
+    self.add(entry)
+  }
+
+  pub fn class(&mut self, class: &Class) -> Result<u16, ClassFormatError> {
+    let entry = self.class_entry(class)?;
+    self.add(entry)
+  }
+
+  pub fn name_and_type(&mut self, name_and_type: &NameAndType) -> Result<u16, ClassFormatError> {
+    let entry = self.name_and_type_entry(name_and_type)?;
+    self.add(entry)
+  }
+
+  pub fn fieldref(&mut self, fieldref: &Fieldref) -> Result<u16, ClassFormatError> {
+    let entry = self.member_entry(9, &fieldref.class, &fieldref.name_and_type)?;
+    self.add(entry)
+  }
+
+  pub fn methodref(&mut self, methodref: &Methodref) -> Result<u16, ClassFormatError> {
+    let entry = self.member_entry(10, &methodref.class, &methodref.name_and_type)?;
+    self.add(entry)
+  }
+
+  pub fn interface_methodref(
+    &mut self,
+    interface_methodref: &InterfaceMethodref
+  ) -> Result<u16, ClassFormatError> {
+    let entry =
+      self.member_entry(11, &interface_methodref.class, &interface_methodref.name_and_type)?;
+    self.add(entry)
+  }
+
+  pub fn invoke_dynamic(
+    &mut self,
+    invoke_dynamic: &InvokeDynamic
+  ) -> Result<u16, ClassFormatError> {
+    let entry = self.dynamic_entry(
+      18,
+      invoke_dynamic.bootstrap_method_attr_index,
+      &invoke_dynamic.bootstrap_method_attr,
+      &invoke_dynamic.name_and_type
+    )?;
+    self.add(entry)
+  }
+
+  pub fn module(&mut self, name: &str) -> Result<u16, ClassFormatError> {
+    let entry = tagged(19, &[self.utf8(name)?]);
+    self.add(entry)
+  }
+
+  pub fn package(&mut self, name: &str) -> Result<u16, ClassFormatError> {
+    let entry = tagged(20, &[self.utf8(name)?]);
+    self.add(entry)
+  }
+
+  /// The finished `BootstrapMethods` table: the class's own bootstrap methods at their original
+  /// indices, followed by any others the constants refer to.
+  pub fn bootstrap_methods(&mut self) -> Result<Vec<RawBootstrapMethod>, ClassFormatError> {
+    for index in 0..self.declared.len() {
+      if self.bootstrap_methods[index].is_none() {
+        let bootstrap_method = self.declared[index].clone();
+        self.bootstrap_method(index as u16, &bootstrap_method)?;
+      }
+    }
+    Ok(self.bootstrap_methods.iter().flatten().cloned().collect())
+  }
+
+  pub fn write(&self, sw: &mut StreamWriter) {
+    sw.put_u16(self.entries.len() as u16);
+    for entry in self.entries.iter().flatten() {
+      sw.put_bytes(entry);
+    }
+  }
+
+  fn add(&mut self, entry: Vec<u8>) -> Result<u16, ClassFormatError> {
+    if let Some(index) = self.indices.get(&entry) {
+      return Ok(*index);
+    }
+    let index = self.entries.len();
+    let wide = matches!(entry[0], 5 | 6);
+    if index + 1 + wide as usize > u16::MAX as usize {
+      return Err(ClassFormatError::TooManyConstants);
+    }
+    self.indices.insert(entry.clone(), index as u16);
+    self.entries.push(Some(entry));
+    if wide {
+      self.entries.push(None);
+    }
+    Ok(index as u16)
+  }
+
+  /// The entry for `constant`, adding the constants it refers to.
+  fn entry(&mut self, constant: &ResolvedCpInfo) -> Result<Vec<u8>, ClassFormatError> {
+    Ok(match constant {
+      ResolvedCpInfo::Class(class) => self.class_entry(class)?,
+      ResolvedCpInfo::Fieldref(Fieldref { class, name_and_type }) => {
+        self.member_entry(9, class, name_and_type)?
+      }
+      ResolvedCpInfo::Methodref(Methodref { class, name_and_type }) => {
+        self.member_entry(10, class, name_and_type)?
+      }
+      ResolvedCpInfo::InterfaceMethodref(InterfaceMethodref { class, name_and_type }) => {
+        self.member_entry(11, class, name_and_type)?
+      }
+      ResolvedCpInfo::String(JavaString { chars }) => {
+        let entry = utf8_entry(&modified_utf8::encode(chars))?;
+        tagged(8, &[self.add(entry)?])
+      }
+      ResolvedCpInfo::Integer(value) => [&[3], &value.to_be_bytes()[..]].concat(),
+      ResolvedCpInfo::Float(value) => [&[4], &value.to_be_bytes()[..]].concat(),
+      ResolvedCpInfo::Long(value) => [&[5], &value.to_be_bytes()[..]].concat(),
+      ResolvedCpInfo::Double(value) => [&[6], &value.to_be_bytes()[..]].concat(),
+      ResolvedCpInfo::NameAndType(name_and_type) => self.name_and_type_entry(name_and_type)?,
+      ResolvedCpInfo::MethodHandle(method_handle) => self.method_handle_entry(method_handle)?,
+      ResolvedCpInfo::MethodType(MethodType { descriptor }) => {
+        tagged(16, &[self.utf8(descriptor)?])
+      }
+      ResolvedCpInfo::Dynamic(Dynamic {
+        bootstrap_method_attr_index,
+        bootstrap_method_attr,
+        name_and_type
+      }) => self.dynamic_entry(
+        17,
+        *bootstrap_method_attr_index,
+        bootstrap_method_attr,
+        name_and_type
+      )?,
+      ResolvedCpInfo::InvokeDynamic(InvokeDynamic {
+        bootstrap_method_attr_index,
+        bootstrap_method_attr,
+        name_and_type
+      }) => self.dynamic_entry(
+        18,
+        *bootstrap_method_attr_index,
+        bootstrap_method_attr,
+        name_and_type
+      )?,
+      ResolvedCpInfo::Module(Module { name }) => tagged(19, &[self.utf8(name)?]),
+      ResolvedCpInfo::Package(Package { name }) => tagged(20, &[self.utf8(name)?]),
+      ResolvedCpInfo::Utf8(JavaString { chars }) => utf8_entry(&modified_utf8::encode(chars))?
+    })
+  }
+
+  fn class_entry(&mut self, class: &Class) -> Result<Vec<u8>, ClassFormatError> {
+    Ok(tagged(7, &[self.utf8(&class.name)?]))
+  }
+
+  fn name_and_type_entry(
+    &mut self,
+    name_and_type: &NameAndType
+  ) -> Result<Vec<u8>, ClassFormatError> {
+    Ok(tagged(12, &[self.utf8(&name_and_type.name)?, self.utf8(&name_and_type.descriptor)?]))
+  }
+
+  fn member_entry(
+    &mut self,
+    tag: u8,
+    class: &Class,
+    name_and_type: &NameAndType
+  ) -> Result<Vec<u8>, ClassFormatError> {
+    Ok(tagged(tag, &[self.class(class)?, self.name_and_type(name_and_type)?]))
+  }
+
+  fn method_handle_entry(
+    &mut self,
+    method_handle: &MethodHandle
+  ) -> Result<Vec<u8>, ClassFormatError> {
+    let reference_index = match &method_handle.reference {
+      MethodHandleRef::Field(fieldref) => self.fieldref(fieldref)?,
+      MethodHandleRef::Method(methodref) => self.methodref(methodref)?,
+      MethodHandleRef::InterfaceMethod(interface_methodref) => {
+        self.interface_methodref(interface_methodref)?
+      }
+    };
+    let mut entry = vec![15, method_handle.reference_kind as u8];
+    entry.extend(reference_index.to_be_bytes());
+    Ok(entry)
+  }
+
+  fn dynamic_entry(
+    &mut self,
+    tag: u8,
+    bootstrap_method_attr_index: u16,
+    bootstrap_method_attr: &Rc<BootstrapMethod>,
+    name_and_type: &NameAndType
+  ) -> Result<Vec<u8>, ClassFormatError> {
+    let bootstrap_method_attr_index = match self.shared.get(&Rc::as_ptr(bootstrap_method_attr)) {
+      Some((_, index)) => *index,
+      None => {
+        let index = self.bootstrap_method(bootstrap_method_attr_index, bootstrap_method_attr)?;
+        let shared = (bootstrap_method_attr.clone(), index);
+        self.shared.insert(Rc::as_ptr(bootstrap_method_attr), shared);
+        index
+      }
+    };
+    Ok(tagged(tag, &[bootstrap_method_attr_index, self.name_and_type(name_and_type)?]))
+  }
+
+  /// The index of `bootstrap_method` in the `BootstrapMethods` table. `hint` is where the class
+  /// had it, which is kept if the class's own table has it there.
+  fn bootstrap_method(
+    &mut self,
+    hint: u16,
+    bootstrap_method: &BootstrapMethod
+  ) -> Result<u16, ClassFormatError> {
+    let entry = self.method_handle_entry(&bootstrap_method.bootstrap_method)?;
+    let bootstrap_method_ref = self.add(entry)?;
+    let mut bootstrap_arguments = Vec::with_capacity(bootstrap_method.bootstrap_arguments.len());
+    for argument in &bootstrap_method.bootstrap_arguments {
+      bootstrap_arguments.push(self.constant(argument)?);
+    }
+    let raw = RawBootstrapMethod { bootstrap_method_ref, bootstrap_arguments };
+
+    let hint = hint as usize;
+    if self.declared.get(hint) == Some(bootstrap_method)
+      && self.bootstrap_methods[hint].as_ref().is_none_or(|declared| *declared == raw)
+    {
+      self.bootstrap_methods[hint] = Some(raw);
+      return Ok(hint as u16);
+    }
+    let index = match self.bootstrap_methods.iter().position(|known| known.as_ref() == Some(&raw)) {
+      Some(index) => index,
+      None => {
+        self.bootstrap_methods.push(Some(raw));
+        self.bootstrap_methods.len() - 1
+      }
+    };
+    u16::try_from(index).map_err(|_| ClassFormatError::TableTooLong { length: index + 1 })
+  }
+}
+
+/// An entry whose contents are constant pool indices.
+fn tagged(tag: u8, indices: &[u16]) -> Vec<u8> {
+  let mut entry = vec![tag];
+  entry.extend(indices.iter().flat_map(|index| index.to_be_bytes()));
+  entry
+}
+
+fn utf8_entry(bytes: &[u8]) -> Result<Vec<u8>, ClassFormatError> {
+  let length = u16::try_from(bytes.len())
+    .map_err(|_| ClassFormatError::TableTooLong { length: bytes.len() })?;
+  Ok([&[1], &length.to_be_bytes()[..], bytes].concat())
+}
@@ -1,7 +1,11 @@
-use super::stream_reader::StreamReader;
+use super::{
+  class_format_error::ClassFormatError,
+  modified_utf8::ModifiedUtf8,
+  stream_reader::StreamReader
+};
 
 #[derive(Debug)]
-pub enum CpInfo {
+pub enum CpInfo<'a> {
   Class { name_index: u16 },
   Fieldref { class_index: u16, name_and_type_index: u16 },
   Methodref { class_index: u16, name_and_type_index: u16 },
@@ -16,42 +20,73 @@ pub enum CpInfo {
   Long { value: i64 },
   Double { value: f64 },
   NameAndType { name_index: u16, descriptor_index: u16 },
-  Utf8 { bytes: String },
+  Utf8 { bytes: ModifiedUtf8<'a> },
   MethodHandle { reference_kind: u8, reference_index: u16 },
   MethodType { descriptor_index: u16 },
-  InvokeDynamic { bootstrap_method_attr_index: u16, reference_index: u16 }
+  Dynamic { bootstrap_method_attr_index: u16, name_and_type_index: u16 },
+  InvokeDynamic { bootstrap_method_attr_index: u16, reference_index: u16 },
+  Module { name_index: u16 },
+  Package { name_index: u16 }
 }
 
-impl CpInfo {
-  pub fn read(sr: &mut StreamReader) -> Self {
-    let tag = sr.get_u8();
-    match tag {
-      7 => CpInfo::Class { name_index: sr.get_u16() },
-      9 => CpInfo::Fieldref { class_index: sr.get_u16(), name_and_type_index: sr.get_u16() },
-      10 => CpInfo::Methodref { class_index: sr.get_u16(), name_and_type_index: sr.get_u16() },
-      11 => {
-        CpInfo::InterfaceMethodref { class_index: sr.get_u16(), name_and_type_index: sr.get_u16() }
-      }
-      8 => CpInfo::String { string_index: sr.get_u16() },
-      3 => CpInfo::Integer { value: sr.get_i32() },
-      4 => CpInfo::Float { value: sr.get_f32() },
-      5 => CpInfo::Long { value: sr.get_i64() },
-      6 => CpInfo::Double { value: sr.get_f64() },
-      12 => CpInfo::NameAndType { name_index: sr.get_u16(), descriptor_index: sr.get_u16() },
+impl<'a> CpInfo<'a> {
+  pub fn read(sr: &mut StreamReader<'a>, index: u16) -> Result<Self, ClassFormatError> {
+    let tag = sr.get_u8()?;
+    Ok(match tag {
+      7 => CpInfo::Class { name_index: sr.get_u16()? },
+      9 => CpInfo::Fieldref { class_index: sr.get_u16()?, name_and_type_index: sr.get_u16()? },
+      10 => CpInfo::Methodref { class_index: sr.get_u16()?, name_and_type_index: sr.get_u16()? },
+      11 => CpInfo::InterfaceMethodref {
+        class_index: sr.get_u16()?,
+        name_and_type_index: sr.get_u16()?
+      },
+      8 => CpInfo::String { string_index: sr.get_u16()? },
+      3 => CpInfo::Integer { value: sr.get_i32()? },
+      4 => CpInfo::Float { value: sr.get_f32()? },
+      5 => CpInfo::Long { value: sr.get_i64()? },
+      6 => CpInfo::Double { value: sr.get_f64()? },
+      12 => CpInfo::NameAndType { name_index: sr.get_u16()?, descriptor_index: sr.get_u16()? },
       1 => {
-        let length = sr.get_u16();
-        CpInfo::Utf8 { bytes: String::from_utf8(sr.take_n(length as usize)).unwrap() }
+        let length = sr.get_u16()?;
+        let bytes = ModifiedUtf8::new(sr.take_n(length as usize)?)
+          .ok_or(ClassFormatError::BadUtf8 { index })?;
+        CpInfo::Utf8 { bytes }
       }
-      15 => CpInfo::MethodHandle { reference_kind: sr.get_u8(), reference_index: sr.get_u16() },
-      16 => CpInfo::MethodType { descriptor_index: sr.get_u16() },
+      15 => CpInfo::MethodHandle { reference_kind: sr.get_u8()?, reference_index: sr.get_u16()? },
+      16 => CpInfo::MethodType { descriptor_index: sr.get_u16()? },
+      17 => CpInfo::Dynamic {
+        bootstrap_method_attr_index: sr.get_u16()?,
+        name_and_type_index: sr.get_u16()?
+      },
       18 => CpInfo::InvokeDynamic {
-        bootstrap_method_attr_index: sr.get_u16(),
-        reference_index: sr.get_u16()
+        bootstrap_method_attr_index: sr.get_u16()?,
+        reference_index: sr.get_u16()?
       },
-      other => {
-        eprintln!("ERROR, value was {}", other);
-        panic!()
-      }
+      19 => CpInfo::Module { name_index: sr.get_u16()? },
+      20 => CpInfo::Package { name_index: sr.get_u16()? },
+      tag => return Err(ClassFormatError::BadConstantTag { index, tag })
+    })
+  }
+
+  pub fn kind(&self) -> &'static str {
+    match self {
+      CpInfo::Class { .. } => "Class",
+      CpInfo::Fieldref { .. } => "Fieldref",
+      CpInfo::Methodref { .. } => "Methodref",
+      CpInfo::InterfaceMethodref { .. } => "InterfaceMethodref",
+      CpInfo::String { .. } => "String",
+      CpInfo::Integer { .. } => "Integer",
+      CpInfo::Float { .. } => "Float",
+      CpInfo::Long { .. } => "Long",
+      CpInfo::Double { .. } => "Double",
+      CpInfo::NameAndType { .. } => "NameAndType",
+      CpInfo::Utf8 { .. } => "Utf8",
+      CpInfo::MethodHandle { .. } => "MethodHandle",
+      CpInfo::MethodType { .. } => "MethodType",
+      CpInfo::Dynamic { .. } => "Dynamic",
+      CpInfo::InvokeDynamic { .. } => "InvokeDynamic",
+      CpInfo::Module { .. } => "Module",
+      CpInfo::Package { .. } => "Package"
     }
   }
 }
@@ -1,6 +1,13 @@
-use super::{attribute_info::attribute::bootstrap_method::BootstrapMethod, cp_info::CpInfo};
+use std::{fmt, rc::Rc};
 
-#[derive(Debug, Clone)]
+use super::{
+  attribute_info::attribute::bootstrap_method::{BootstrapMethod, RawBootstrapMethod},
+  class_format_error::ClassFormatError,
+  constant_pool::ConstantPool,
+  cp_info::CpInfo
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Class {
   pub name: String
 }
@@ -9,7 +16,7 @@ impl ToString for Class {
   fn to_string(&self) -> String { self.name.to_string() }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NameAndType {
   pub name: String,
   pub descriptor: String
@@ -19,16 +26,17 @@ impl ToString for NameAndType {
   fn to_string(&self) -> String { format!("{}:{}", self.name, self.descriptor) }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct JavaString {
-  pub string: String
+  // UTF-16 code units, as in a `java.lang.String`. These need not be valid UTF-16.
+  pub chars: Vec<u16>
 }
 
 impl ToString for JavaString {
-  fn to_string(&self) -> String { self.string.to_string() }
+  fn to_string(&self) -> String { String::from_utf16_lossy(&self.chars) }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Fieldref {
   pub class: Class,
   pub name_and_type: NameAndType
@@ -40,7 +48,7 @@ impl ToString for Fieldref {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Methodref {
   pub class: Class,
   pub name_and_type: NameAndType
@@ -52,28 +60,115 @@ impl ToString for Methodref {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InterfaceMethodref {
   pub class: Class,
   pub name_and_type: NameAndType
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+  GetField = 1,
+  GetStatic = 2,
+  PutField = 3,
+  PutStatic = 4,
+  InvokeVirtual = 5,
+  InvokeStatic = 6,
+  InvokeSpecial = 7,
+  NewInvokeSpecial = 8,
+  InvokeInterface = 9
+}
+
+impl ReferenceKind {
+  pub fn from(kind: u8) -> Option<Self> {
+    Some(match kind {
+      1 => ReferenceKind::GetField,
+      2 => ReferenceKind::GetStatic,
+      3 => ReferenceKind::PutField,
+      4 => ReferenceKind::PutStatic,
+      5 => ReferenceKind::InvokeVirtual,
+      6 => ReferenceKind::InvokeStatic,
+      7 => ReferenceKind::InvokeSpecial,
+      8 => ReferenceKind::NewInvokeSpecial,
+      9 => ReferenceKind::InvokeInterface,
+      _ => return None
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MethodHandleRef {
+  Field(Fieldref),
+  Method(Methodref),
+  InterfaceMethod(InterfaceMethodref)
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodHandle {
-  //TODO
+  pub reference_kind: ReferenceKind,
+  pub reference: MethodHandleRef
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodType {
   pub descriptor: String
 }
 
+/// The bootstrap method is shared with every other constant that uses the same one.
+#[derive(Debug, Clone)]
+pub struct Dynamic {
+  pub bootstrap_method_attr_index: u16,
+  pub bootstrap_method_attr: Rc<BootstrapMethod>,
+  pub name_and_type: NameAndType
+}
+
+impl PartialEq for Dynamic {
+  fn eq(&self, other: &Self) -> bool {
+    self.bootstrap_method_attr_index == other.bootstrap_method_attr_index
+      && self.name_and_type == other.name_and_type
+      && same_bootstrap_method(&self.bootstrap_method_attr, &other.bootstrap_method_attr)
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct InvokeDynamic {
-  pub bootstrap_method_attr: BootstrapMethod,
+  pub bootstrap_method_attr_index: u16,
+  pub bootstrap_method_attr: Rc<BootstrapMethod>,
   pub name_and_type: NameAndType
 }
 
+impl PartialEq for InvokeDynamic {
+  fn eq(&self, other: &Self) -> bool {
+    self.bootstrap_method_attr_index == other.bootstrap_method_attr_index
+      && self.name_and_type == other.name_and_type
+      && same_bootstrap_method(&self.bootstrap_method_attr, &other.bootstrap_method_attr)
+  }
+}
+
+// Comparing shared bootstrap methods by their contents would visit a nested argument once for
+// every path to it
+fn same_bootstrap_method(a: &Rc<BootstrapMethod>, b: &Rc<BootstrapMethod>) -> bool {
+  Rc::ptr_eq(a, b) || a == b
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+  pub name: String
+}
+
+impl fmt::Display for Module {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.name) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+  pub name: String
+}
+
+impl fmt::Display for Package {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.name) }
+}
+
 #[derive(Debug, Clone)]
 pub enum ResolvedCpInfo {
   Class(Class),
@@ -88,76 +183,226 @@ pub enum ResolvedCpInfo {
   NameAndType(NameAndType),
   MethodHandle(MethodHandle),
   MethodType(MethodType),
+  Dynamic(Dynamic),
   InvokeDynamic(InvokeDynamic),
-  Utf8(String)
+  Module(Module),
+  Package(Package),
+  /// Like `String`, kept as UTF-16 code units so that unpaired surrogates survive.
+  Utf8(JavaString)
 }
 
-impl ResolvedCpInfo {
-  pub fn from(cpinfo: &CpInfo, constant_pool: &Vec<CpInfo>) -> Self {
-    match cpinfo {
-      CpInfo::Class { name_index } => {
-        let CpInfo::Utf8 { bytes: name } = &constant_pool[*name_index as usize -1] else {panic!()};
-        ResolvedCpInfo::Class(Class { name: name.clone() })
-      }
-      CpInfo::Fieldref { class_index, name_and_type_index } => {
-        let CpInfo::Class { name_index } = constant_pool[*class_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: class_name } = &constant_pool[name_index as usize -1] else {panic!()};
+/// Floats and doubles compare by their bits, so a NaN constant equals itself.
+impl PartialEq for ResolvedCpInfo {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (ResolvedCpInfo::Class(a), ResolvedCpInfo::Class(b)) => a == b,
+      (ResolvedCpInfo::Fieldref(a), ResolvedCpInfo::Fieldref(b)) => a == b,
+      (ResolvedCpInfo::Methodref(a), ResolvedCpInfo::Methodref(b)) => a == b,
+      (ResolvedCpInfo::InterfaceMethodref(a), ResolvedCpInfo::InterfaceMethodref(b)) => a == b,
+      (ResolvedCpInfo::String(a), ResolvedCpInfo::String(b)) => a == b,
+      (ResolvedCpInfo::Integer(a), ResolvedCpInfo::Integer(b)) => a == b,
+      (ResolvedCpInfo::Float(a), ResolvedCpInfo::Float(b)) => a.to_bits() == b.to_bits(),
+      (ResolvedCpInfo::Long(a), ResolvedCpInfo::Long(b)) => a == b,
+      (ResolvedCpInfo::Double(a), ResolvedCpInfo::Double(b)) => a.to_bits() == b.to_bits(),
+      (ResolvedCpInfo::NameAndType(a), ResolvedCpInfo::NameAndType(b)) => a == b,
+      (ResolvedCpInfo::MethodHandle(a), ResolvedCpInfo::MethodHandle(b)) => a == b,
+      (ResolvedCpInfo::MethodType(a), ResolvedCpInfo::MethodType(b)) => a == b,
+      (ResolvedCpInfo::Dynamic(a), ResolvedCpInfo::Dynamic(b)) => a == b,
+      (ResolvedCpInfo::InvokeDynamic(a), ResolvedCpInfo::InvokeDynamic(b)) => a == b,
+      (ResolvedCpInfo::Module(a), ResolvedCpInfo::Module(b)) => a == b,
+      (ResolvedCpInfo::Package(a), ResolvedCpInfo::Package(b)) => a == b,
+      (ResolvedCpInfo::Utf8(a), ResolvedCpInfo::Utf8(b)) => a == b,
+      _ => false
+    }
+  }
+}
 
-        let CpInfo::NameAndType { name_index, descriptor_index } = constant_pool[*name_and_type_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: name } = &constant_pool[name_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: descriptor } = &constant_pool[descriptor_index as usize -1] else {panic!()};
+/// Resolves each `BootstrapMethods` entry the first time a constant asks for it, and hands the
+/// same one to every constant after. Dynamic constants may take other dynamic constants as
+/// bootstrap arguments, so resolving an entry for each use could take exponential time.
+pub struct BootstrapMethods<'r> {
+  raw: &'r [RawBootstrapMethod],
+  resolved: Vec<Resolution>
+}
 
-        ResolvedCpInfo::Fieldref(Fieldref {
-          class: Class { name: class_name.clone() },
-          name_and_type: NameAndType { name: name.clone(), descriptor: descriptor.clone() }
-        })
-      }
-      CpInfo::Methodref { class_index, name_and_type_index } => {
-        let CpInfo::Class { name_index } = constant_pool[*class_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: class_name } = &constant_pool[name_index as usize -1] else {panic!()};
+#[derive(Clone)]
+enum Resolution {
+  Unresolved,
+  /// Being resolved further up the stack, so asking for it again means it is its own argument.
+  Resolving,
+  Resolved(Rc<BootstrapMethod>)
+}
+
+impl<'r> BootstrapMethods<'r> {
+  pub fn new(raw: &'r [RawBootstrapMethod]) -> Self {
+    BootstrapMethods { raw, resolved: vec![Resolution::Unresolved; raw.len()] }
+  }
 
-        let CpInfo::NameAndType { name_index, descriptor_index } = constant_pool[*name_and_type_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: name } = &constant_pool[name_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: descriptor } = &constant_pool[descriptor_index as usize -1] else {panic!()};
+  fn get(
+    &mut self,
+    index: u16,
+    constant_pool: &ConstantPool<CpInfo>
+  ) -> Result<Rc<BootstrapMethod>, ClassFormatError> {
+    match self.resolved.get(index as usize) {
+      None => return Err(ClassFormatError::BadBootstrapMethodIndex { index }),
+      Some(Resolution::Resolving) => return Err(ClassFormatError::CyclicBootstrapMethod { index }),
+      Some(Resolution::Resolved(bootstrap_method)) => return Ok(bootstrap_method.clone()),
+      Some(Resolution::Unresolved) => {}
+    }
+    self.resolved[index as usize] = Resolution::Resolving;
+    let raw = self.raw;
+    let RawBootstrapMethod { bootstrap_method_ref, bootstrap_arguments } = &raw[index as usize];
+    let ResolvedCpInfo::MethodHandle(bootstrap_method) =
+      ResolvedCpInfo::from(constant_pool.get(*bootstrap_method_ref)?, constant_pool, self)?
+    else {
+      let found = constant_pool.get(*bootstrap_method_ref)?.kind();
+      return Err(ClassFormatError::WrongConstantKind {
+        index: *bootstrap_method_ref,
+        expected: "MethodHandle",
+        found
+      });
+    };
+    let bootstrap_arguments = bootstrap_arguments
+      .iter()
+      .map(|index| ResolvedCpInfo::from(constant_pool.get(*index)?, constant_pool, self))
+      .collect::<Result<_, _>>()?;
+    let bootstrap_method = Rc::new(BootstrapMethod { bootstrap_method, bootstrap_arguments });
+    self.resolved[index as usize] = Resolution::Resolved(bootstrap_method.clone());
+    Ok(bootstrap_method)
+  }
+}
 
+impl ResolvedCpInfo {
+  pub fn from(
+    cpinfo: &CpInfo,
+    constant_pool: &ConstantPool<CpInfo>,
+    bootstrap_methods: &mut BootstrapMethods
+  ) -> Result<Self, ClassFormatError> {
+    Ok(match cpinfo {
+      CpInfo::Class { name_index } => {
+        ResolvedCpInfo::Class(Class { name: constant_pool.get_utf8(*name_index)?.to_string() })
+      }
+      CpInfo::Fieldref { class_index, name_and_type_index } => ResolvedCpInfo::Fieldref(Fieldref {
+        class: constant_pool.get_class(*class_index)?,
+        name_and_type: constant_pool.get_name_and_type(*name_and_type_index)?
+      }),
+      CpInfo::Methodref { class_index, name_and_type_index } => {
         ResolvedCpInfo::Methodref(Methodref {
-          class: Class { name: class_name.clone() },
-          name_and_type: NameAndType { name: name.clone(), descriptor: descriptor.clone() }
+          class: constant_pool.get_class(*class_index)?,
+          name_and_type: constant_pool.get_name_and_type(*name_and_type_index)?
         })
       }
       CpInfo::InterfaceMethodref { class_index, name_and_type_index } => {
-        let CpInfo::Class { name_index } = constant_pool[*class_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: class_name } = &constant_pool[name_index as usize -1] else {panic!()};
-
-        let CpInfo::NameAndType { name_index, descriptor_index } = constant_pool[*name_and_type_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: name } = &constant_pool[name_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: descriptor } = &constant_pool[descriptor_index as usize -1] else {panic!()};
-
         ResolvedCpInfo::InterfaceMethodref(InterfaceMethodref {
-          class: Class { name: class_name.clone() },
-          name_and_type: NameAndType { name: name.clone(), descriptor: descriptor.clone() }
+          class: constant_pool.get_class(*class_index)?,
+          name_and_type: constant_pool.get_name_and_type(*name_and_type_index)?
         })
       }
       CpInfo::String { string_index } => {
-        let CpInfo::Utf8 { bytes: string } = &constant_pool[*string_index as usize -1] else {panic!()};
-        ResolvedCpInfo::String(JavaString { string: string.clone() })
+        ResolvedCpInfo::String(constant_pool.get_java_string(*string_index)?)
       }
       CpInfo::Integer { value } => ResolvedCpInfo::Integer(*value),
       CpInfo::Float { value } => ResolvedCpInfo::Float(*value),
       CpInfo::Long { value } => ResolvedCpInfo::Long(*value),
       CpInfo::Double { value } => ResolvedCpInfo::Double(*value),
       CpInfo::NameAndType { name_index, descriptor_index } => {
-        let CpInfo::Utf8 { bytes: name } = &constant_pool[*name_index as usize -1] else {panic!()};
-        let CpInfo::Utf8 { bytes: descriptor } = &constant_pool[*descriptor_index as usize -1] else {panic!()};
         ResolvedCpInfo::NameAndType(NameAndType {
-          name: name.clone(),
-          descriptor: descriptor.clone()
+          name: constant_pool.get_utf8(*name_index)?.to_string(),
+          descriptor: constant_pool.get_utf8(*descriptor_index)?.to_string()
         })
       }
-      CpInfo::Utf8 { bytes } => ResolvedCpInfo::Utf8(bytes.to_string()),
-      CpInfo::MethodHandle { reference_kind: _, reference_index: _ } => todo!(),
-      CpInfo::MethodType { descriptor_index: _ } => todo!(),
-      CpInfo::InvokeDynamic { bootstrap_method_attr_index: _, reference_index: _ } => todo!()
+      CpInfo::Utf8 { bytes } => ResolvedCpInfo::Utf8(JavaString { chars: bytes.to_utf16() }),
+      CpInfo::MethodHandle { reference_kind, reference_index } => {
+        let Some(kind) = ReferenceKind::from(*reference_kind) else {
+          return Err(ClassFormatError::BadReferenceKind { kind: *reference_kind });
+        };
+        let reference =
+          Self::from(constant_pool.get(*reference_index)?, constant_pool, bootstrap_methods)?;
+        let reference = match (kind, reference) {
+          (
+            ReferenceKind::GetField
+            | ReferenceKind::GetStatic
+            | ReferenceKind::PutField
+            | ReferenceKind::PutStatic,
+            ResolvedCpInfo::Fieldref(fieldref)
+          ) => MethodHandleRef::Field(fieldref),
+          (
+            ReferenceKind::InvokeVirtual
+            | ReferenceKind::InvokeStatic
+            | ReferenceKind::InvokeSpecial
+            | ReferenceKind::NewInvokeSpecial,
+            ResolvedCpInfo::Methodref(methodref)
+          ) => MethodHandleRef::Method(methodref),
+          (
+            ReferenceKind::InvokeStatic
+            | ReferenceKind::InvokeSpecial
+            | ReferenceKind::InvokeInterface,
+            ResolvedCpInfo::InterfaceMethodref(interfacemethodref)
+          ) => MethodHandleRef::InterfaceMethod(interfacemethodref),
+          (kind, other) => {
+            return Err(ClassFormatError::WrongConstantKind {
+              index: *reference_index,
+              expected: match kind {
+                ReferenceKind::GetField
+                | ReferenceKind::GetStatic
+                | ReferenceKind::PutField
+                | ReferenceKind::PutStatic => "Fieldref",
+                ReferenceKind::InvokeInterface => "InterfaceMethodref",
+                _ => "Methodref"
+              },
+              found: other.kind()
+            })
+          }
+        };
+        ResolvedCpInfo::MethodHandle(MethodHandle { reference_kind: kind, reference })
+      }
+      CpInfo::MethodType { descriptor_index } => ResolvedCpInfo::MethodType(MethodType {
+        descriptor: constant_pool.get_utf8(*descriptor_index)?.to_string()
+      }),
+      CpInfo::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+        ResolvedCpInfo::Dynamic(Dynamic {
+          bootstrap_method_attr_index: *bootstrap_method_attr_index,
+          bootstrap_method_attr: bootstrap_methods
+            .get(*bootstrap_method_attr_index, constant_pool)?,
+          name_and_type: constant_pool.get_name_and_type(*name_and_type_index)?
+        })
+      }
+      CpInfo::InvokeDynamic { bootstrap_method_attr_index, reference_index } => {
+        ResolvedCpInfo::InvokeDynamic(InvokeDynamic {
+          bootstrap_method_attr_index: *bootstrap_method_attr_index,
+          bootstrap_method_attr: bootstrap_methods
+            .get(*bootstrap_method_attr_index, constant_pool)?,
+          name_and_type: constant_pool.get_name_and_type(*reference_index)?
+        })
+      }
+      CpInfo::Module { name_index } => {
+        ResolvedCpInfo::Module(Module { name: constant_pool.get_utf8(*name_index)?.to_string() })
+      }
+      CpInfo::Package { name_index } => {
+        ResolvedCpInfo::Package(Package { name: constant_pool.get_utf8(*name_index)?.to_string() })
+      }
+    })
+  }
+
+  pub fn kind(&self) -> &'static str {
+    match self {
+      ResolvedCpInfo::Class(_) => "Class",
+      ResolvedCpInfo::Fieldref(_) => "Fieldref",
+      ResolvedCpInfo::Methodref(_) => "Methodref",
+      ResolvedCpInfo::InterfaceMethodref(_) => "InterfaceMethodref",
+      ResolvedCpInfo::String(_) => "String",
+      ResolvedCpInfo::Integer(_) => "Integer",
+      ResolvedCpInfo::Float(_) => "Float",
+      ResolvedCpInfo::Long(_) => "Long",
+      ResolvedCpInfo::Double(_) => "Double",
+      ResolvedCpInfo::NameAndType(_) => "NameAndType",
+      ResolvedCpInfo::MethodHandle(_) => "MethodHandle",
+      ResolvedCpInfo::MethodType(_) => "MethodType",
+      ResolvedCpInfo::Dynamic(_) => "Dynamic",
+      ResolvedCpInfo::InvokeDynamic(_) => "InvokeDynamic",
+      ResolvedCpInfo::Module(_) => "Module",
+      ResolvedCpInfo::Package(_) => "Package",
+      ResolvedCpInfo::Utf8(_) => "Utf8"
     }
   }
 }
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use super::class_format_error::ClassFormatError;
+
+/// The type of a field, parameter or local variable, parsed from a descriptor (JVMS §4.3.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+  Byte,
+  Char,
+  Double,
+  Float,
+  Int,
+  Long,
+  Short,
+  Boolean,
+  /// A class or interface, by binary name, such as `java/lang/String`.
+  Object(String),
+  /// `element` is never itself an array.
+  Array {
+    dimensions: u8,
+    element: Box<FieldType>
+  }
+}
+
+/// The parameter and return types of a method, parsed from a descriptor (JVMS §4.3.3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodDescriptor {
+  pub params: Vec<FieldType>,
+  /// `None` for a `void` method.
+  pub ret: Option<FieldType>
+}
+
+impl FieldType {
+  pub fn parse(descriptor: &str) -> Result<Self, ClassFormatError> {
+    let mut chars = descriptor;
+    match FieldType::take(&mut chars) {
+      Some(field_type) if chars.is_empty() => Ok(field_type),
+      _ => Err(bad_descriptor(descriptor))
+    }
+  }
+
+  /// Parses one field type from the front of `descriptor`, advancing past it.
+  fn take(descriptor: &mut &str) -> Option<Self> {
+    let dimensions = descriptor.bytes().take_while(|byte| *byte == b'[').count();
+    *descriptor = &descriptor[dimensions..];
+    let element = match descriptor.as_bytes().first()? {
+      b'B' => FieldType::Byte,
+      b'C' => FieldType::Char,
+      b'D' => FieldType::Double,
+      b'F' => FieldType::Float,
+      b'I' => FieldType::Int,
+      b'J' => FieldType::Long,
+      b'S' => FieldType::Short,
+      b'Z' => FieldType::Boolean,
+      b'L' => {
+        let (name, rest) = descriptor[1..].split_once(';')?;
+        if !is_binary_name(name) {
+          return None;
+        }
+        *descriptor = rest;
+        return FieldType::array(dimensions, FieldType::Object(name.to_string()));
+      }
+      _ => return None
+    };
+    *descriptor = &descriptor[1..];
+    FieldType::array(dimensions, element)
+  }
+
+  fn array(dimensions: usize, element: FieldType) -> Option<Self> {
+    match dimensions {
+      0 => Some(element),
+      // An array type may have at most 255 dimensions
+      1..=255 => {
+        Some(FieldType::Array { dimensions: dimensions as u8, element: Box::new(element) })
+      }
+      _ => None
+    }
+  }
+
+  /// The number of local variable or operand stack slots a value of this type takes.
+  pub fn slots(&self) -> usize {
+    match self {
+      FieldType::Long | FieldType::Double => 2,
+      _ => 1
+    }
+  }
+}
+
+impl fmt::Display for FieldType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FieldType::Byte => write!(f, "B"),
+      FieldType::Char => write!(f, "C"),
+      FieldType::Double => write!(f, "D"),
+      FieldType::Float => write!(f, "F"),
+      FieldType::Int => write!(f, "I"),
+      FieldType::Long => write!(f, "J"),
+      FieldType::Short => write!(f, "S"),
+      FieldType::Boolean => write!(f, "Z"),
+      FieldType::Object(name) => write!(f, "L{};", name),
+      FieldType::Array { dimensions, element } => {
+        write!(f, "{}{}", "[".repeat(*dimensions as usize), element)
+      }
+    }
+  }
+}
+
+impl MethodDescriptor {
+  pub fn parse(descriptor: &str) -> Result<Self, ClassFormatError> {
+    MethodDescriptor::take(descriptor).ok_or_else(|| bad_descriptor(descriptor))
+  }
+
+  fn take(descriptor: &str) -> Option<Self> {
+    let mut chars = descriptor.strip_prefix('(')?;
+    let mut params = Vec::new();
+    while !chars.starts_with(')') {
+      params.push(FieldType::take(&mut chars)?);
+    }
+    let ret = match &chars[1..] {
+      "V" => None,
+      ret => Some(FieldType::parse(ret).ok()?)
+    };
+    let descriptor = MethodDescriptor { params, ret };
+    // Parameters may take up at most 255 slots
+    (descriptor.param_slots() <= 255).then_some(descriptor)
+  }
+
+  /// The number of local variable slots the parameters take, not counting `this`.
+  pub fn param_slots(&self) -> usize { self.params.iter().map(FieldType::slots).sum() }
+}
+
+impl fmt::Display for MethodDescriptor {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "(")?;
+    for param in &self.params {
+      write!(f, "{}", param)?;
+    }
+    match &self.ret {
+      Some(ret) => write!(f, "){}", ret),
+      None => write!(f, ")V")
+    }
+  }
+}
+
+/// A class or interface name in internal form, made of `/` separated unqualified names (JVMS §4.2).
+fn is_binary_name(name: &str) -> bool {
+  name.split('/').all(|part| !part.is_empty() && !part.contains(['.', ';', '[']))
+}
+
+fn bad_descriptor(descriptor: &str) -> ClassFormatError {
+  ClassFormatError::BadDescriptor { descriptor: descriptor.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn object(name: &str) -> FieldType { FieldType::Object(name.to_string()) }
+
+  #[test]
+  fn parses_and_displays_field_types() {
+    for (descriptor, field_type) in [
+      ("I", FieldType::Int),
+      ("Z", FieldType::Boolean),
+      ("Ljava/lang/String;", object("java/lang/String")),
+      ("[J", FieldType::Array { dimensions: 1, element: Box::new(FieldType::Long) }),
+      ("[[LA$B;", FieldType::Array { dimensions: 2, element: Box::new(object("A$B")) })
+    ] {
+      assert_eq!(FieldType::parse(descriptor), Ok(field_type.clone()));
+      assert_eq!(field_type.to_string(), descriptor);
+    }
+  }
+
+  #[test]
+  fn parses_and_displays_method_descriptors() {
+    let descriptor = "(IJ[Ljava/lang/Object;D)Ljava/lang/String;";
+    let parsed = MethodDescriptor::parse(descriptor).unwrap();
+    assert_eq!(parsed.params.len(), 4);
+    assert_eq!(parsed.ret, Some(object("java/lang/String")));
+    assert_eq!(parsed.to_string(), descriptor);
+    assert_eq!(MethodDescriptor::parse("()V").unwrap(), MethodDescriptor {
+      params: vec![],
+      ret: None
+    });
+  }
+
+  #[test]
+  fn longs_and_doubles_take_two_slots() {
+    assert_eq!(MethodDescriptor::parse("(IJD[J[D)V").unwrap().param_slots(), 7);
+  }
+
+  #[test]
+  fn rejects_malformed_descriptors() {
+    for descriptor in ["", "L;", "Ljava/lang/String", "La//b;", "La.b;", "V", "II", "[", "Q"] {
+      assert!(FieldType::parse(descriptor).is_err(), "{}", descriptor);
+    }
+    for descriptor in ["(I", "I)V", "()", "(V)V", "()II", "(L;)V", "()[V"] {
+      assert!(MethodDescriptor::parse(descriptor).is_err(), "{}", descriptor);
+    }
+  }
+
+  #[test]
+  fn limits_dimensions_and_parameter_slots() {
+    let dimensions = |count: usize| format!("{}I", "[".repeat(count));
+    assert!(FieldType::parse(&dimensions(255)).is_ok());
+    assert!(FieldType::parse(&dimensions(256)).is_err());
+
+    let params =
+      |ints: usize, longs: usize| format!("({}{})V", "I".repeat(ints), "J".repeat(longs));
+    assert!(MethodDescriptor::parse(&params(255, 0)).is_ok());
+    assert!(MethodDescriptor::parse(&params(256, 0)).is_err());
+    assert!(MethodDescriptor::parse(&params(1, 127)).is_ok());
+    assert!(MethodDescriptor::parse(&params(2, 127)).is_err());
+  }
+}
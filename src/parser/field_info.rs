@@ -1,9 +1,23 @@
 use bitmask::bitmask;
 
 use super::{
-  attribute_info::Attribute,
-  cp_info_resolved::{NameAndType, ResolvedCpInfo},
-  stream_reader::StreamReader
+  attribute_info::{
+    attribute::{
+      annotation::{self, Annotation},
+      constant::Constant
+    },
+    attribute_registry::AttributeRegistry,
+    signature::find_signature,
+    Attribute
+  },
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::NameAndType,
+  descriptor::FieldType,
+  format_check::member_name,
+  signature::FieldSignature,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
 };
 
 bitmask! {
@@ -21,30 +35,83 @@ bitmask! {
   }
 }
 
-#[derive(Debug, Clone)]
+impl FieldAccessFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { FieldAccessFlags { mask } }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FieldInfo {
   pub access_flags: FieldAccessFlags,
+  pub descriptor: FieldType,
   pub attributes: Vec<Attribute>
 }
 
 impl FieldInfo {
-  pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> (String, Self) {
-    let access_flags_num = sr.get_u16();
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool,
+    registry: &AttributeRegistry
+  ) -> Result<(String, Self), ClassFormatError> {
+    let access_flags_num = sr.get_u16()?;
     let access_flags = FieldAccessFlags { mask: access_flags_num };
 
-    let name_index = sr.get_u16();
-    let ResolvedCpInfo::Utf8(name) = &constant_pool[name_index as usize -1] else {panic!()};
+    let name_index = sr.get_u16()?;
+    let name = constant_pool.get_utf8(name_index)?;
+
+    let descriptor_index = sr.get_u16()?;
+    let descriptor = constant_pool.get_utf8(descriptor_index)?;
 
-    let descriptor_index = sr.get_u16();
-    let ResolvedCpInfo::Utf8(descriptor) = &constant_pool[descriptor_index as usize -1] else {panic!()};
+    let attributes_count = sr.get_u16()?;
+    let attributes: Vec<Attribute> = (0..attributes_count)
+      .map(|_| Attribute::read(sr, constant_pool, registry))
+      .collect::<Result<_, _>>()?;
+    let field = Self { access_flags, descriptor: FieldType::parse(&descriptor)?, attributes };
+    if let Some(constant) = field.constant_value() {
+      if !constant.fits(&field.descriptor) {
+        return Err(ClassFormatError::BadConstantValue { field: name.to_string() });
+      }
+    }
 
-    let attributes_count = sr.get_u16();
-    let attributes: Vec<Attribute> =
-      (0..attributes_count).map(|_| Attribute::read(sr, &constant_pool)).collect();
+    Ok((NameAndType { name, descriptor }.to_string(), field))
+  }
+  /// Writes the field back out under the name in its `name:descriptor` key.
+  pub fn write(
+    &self,
+    key: &str,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    let descriptor = self.descriptor.to_string();
+    sw.put_u16(*self.access_flags);
+    sw.put_u16(constant_pool.utf8(member_name(key, &descriptor))?);
+    sw.put_u16(constant_pool.utf8(&descriptor)?);
+    sw.put_count(self.attributes.len())?;
+    for attribute in &self.attributes {
+      attribute.write(constant_pool, sw)?;
+    }
+    Ok(())
+  }
 
-    (NameAndType { name: name.to_string(), descriptor: descriptor.to_string() }.to_string(), Self {
-      access_flags,
-      attributes
+  /// The value a static field starts with, before the class's `<clinit>` runs.
+  /// Instance fields ignore any `ConstantValue` attribute, so this is always `None` for them.
+  pub fn constant_value(&self) -> Option<&Constant> {
+    if !self.access_flags.contains(Flags::AccStatic) {
+      return None;
+    }
+    self.attributes.iter().find_map(|attribute| match attribute {
+      Attribute::ConstantValue { value } => Some(value),
+      _ => None
     })
   }
+
+  /// The generic field signature, if it has one.
+  pub fn signature(&self) -> Result<Option<FieldSignature>, ClassFormatError> {
+    find_signature(&self.attributes).map(FieldSignature::parse).transpose()
+  }
+
+  /// The annotation of the given type, such as `Lorg/junit/Test;`, visible or not.
+  pub fn annotation(&self, type_name: &str) -> Option<&Annotation> {
+    annotation::annotations(&self.attributes).find(|annotation| annotation.type_name == type_name)
+  }
 }
@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+
+use super::{
+  attribute_info::Attribute,
+  class_format_error::ClassFormatError,
+  classfile::{self, ClassFile},
+  field_info::{self, FieldInfo},
+  method_info::{self, MethodInfo}
+};
+
+/// Checks what JVMS §4.8 asks of a class file beyond being parseable: legal combinations of
+/// access flags, well-formed names, no duplicate members, the rules for `<init>` and `<clinit>`,
+/// and a Code attribute on exactly the methods that need one.
+/// Gives every violation found, in class file order.
+pub fn check_format(class: &ClassFile) -> Result<(), Vec<ClassFormatError>> {
+  let mut errors = Vec::new();
+  check_class(class, &mut errors);
+
+  let is_interface = class.access_flags.contains(classfile::Flags::AccInterface);
+  let mut fields = HashSet::new();
+  for (key, field) in &class.fields {
+    if !fields.insert(key) {
+      errors.push(ClassFormatError::DuplicateMember { kind: "field", name: key.clone() });
+    }
+    check_field(key, field, is_interface, &mut errors);
+  }
+  let mut methods = HashSet::new();
+  for (key, method) in &class.methods {
+    if !methods.insert(key) {
+      errors.push(ClassFormatError::DuplicateMember { kind: "method", name: key.clone() });
+    }
+    check_method(key, method, is_interface, class.major_version, &mut errors);
+  }
+
+  match errors.is_empty() {
+    true => Ok(()),
+    false => Err(errors)
+  }
+}
+
+fn check_class(class: &ClassFile, errors: &mut Vec<ClassFormatError>) {
+  use classfile::Flags::*;
+
+  let flags = class.access_flags;
+  let name = &class.this_class.name;
+  if flags.contains(AccModule) && class.major_version >= 53 {
+    // A module-info class is nothing but its Module attribute
+    if flags.intersects(
+      AccPublic
+        | AccFinal
+        | AccSuper
+        | AccInterface
+        | AccAbstract
+        | AccSynthetic
+        | AccAnnotation
+        | AccEnum
+    ) {
+      errors.push(ClassFormatError::BadModifiers {
+        kind: "class",
+        name: name.clone(),
+        flags: *flags
+      });
+    }
+    return;
+  }
+
+  // Before Java 6, interfaces were abstract whether or not the flag said so
+  let is_interface = flags.contains(AccInterface);
+  let is_abstract = flags.contains(AccAbstract) || (is_interface && class.major_version < 50);
+  let since_java_5 = class.major_version >= 49;
+  if (is_abstract && flags.contains(AccFinal))
+    || (is_interface && !is_abstract)
+    || (is_interface && since_java_5 && flags.intersects(AccSuper | AccEnum))
+    || (!is_interface && since_java_5 && flags.contains(AccAnnotation))
+  {
+    errors.push(ClassFormatError::BadModifiers {
+      kind: "class",
+      name: name.clone(),
+      flags: *flags
+    });
+  }
+
+  if !is_class_name(name) {
+    errors.push(ClassFormatError::BadName { kind: "class", name: name.clone() });
+  }
+  match &class.super_class {
+    None if name != "java/lang/Object" => {
+      errors.push(ClassFormatError::BadSuperclass { name: None })
+    }
+    Some(super_class)
+      if !is_class_name(&super_class.name)
+        || (is_interface && super_class.name != "java/lang/Object") =>
+    {
+      errors.push(ClassFormatError::BadSuperclass { name: Some(super_class.name.clone()) })
+    }
+    _ => {}
+  }
+  let mut interfaces = HashSet::new();
+  for interface in &class.interfaces {
+    if !is_class_name(&interface.name) {
+      errors.push(ClassFormatError::BadName { kind: "class", name: interface.name.clone() });
+    }
+    if !interfaces.insert(&interface.name) {
+      errors.push(ClassFormatError::DuplicateMember {
+        kind: "interface",
+        name: interface.name.clone()
+      });
+    }
+  }
+}
+
+fn check_field(
+  key: &str,
+  field: &FieldInfo,
+  is_interface: bool,
+  errors: &mut Vec<ClassFormatError>
+) {
+  use field_info::Flags::*;
+
+  let flags = field.access_flags;
+  let name = member_name(key, &field.descriptor.to_string());
+  if !is_unqualified_name(name) {
+    errors.push(ClassFormatError::BadName { kind: "field", name: name.to_string() });
+  }
+  let legal = match is_interface {
+    true => {
+      flags.contains(AccPublic | AccStatic | AccFinal)
+        && !flags.intersects(AccPrivate | AccProtected | AccVolatile | AccTransient | AccEnum)
+    }
+    false => {
+      (*flags & *(AccPublic | AccPrivate | AccProtected)).count_ones() <= 1
+        && !flags.contains(AccFinal | AccVolatile)
+    }
+  };
+  if !legal {
+    errors.push(ClassFormatError::BadModifiers {
+      kind: "field",
+      name: key.to_string(),
+      flags: *flags
+    });
+  }
+}
+
+fn check_method(
+  key: &str,
+  method: &MethodInfo,
+  is_interface: bool,
+  major_version: u16,
+  errors: &mut Vec<ClassFormatError>
+) {
+  use method_info::Flags::*;
+
+  let descriptor = method.descriptor.to_string();
+  let name = member_name(key, &descriptor);
+  let mut flags = method.access_flags;
+  let bad_modifiers = || ClassFormatError::BadModifiers {
+    kind: "method",
+    name: key.to_string(),
+    flags: *method.access_flags
+  };
+  let bad_descriptor = || ClassFormatError::BadDescriptor { descriptor: key.to_string() };
+
+  match name {
+    "<clinit>" => {
+      if descriptor != "()V" {
+        errors.push(bad_descriptor());
+      }
+      // A class initializer's other flags are ignored, and before Java 7 so was ACC_STATIC
+      if major_version < 51 || flags.contains(AccStatic) {
+        flags = AccStatic.into();
+      } else {
+        errors.push(bad_modifiers());
+      }
+    }
+    "<init>" => {
+      if is_interface {
+        errors.push(ClassFormatError::BadName { kind: "method", name: name.to_string() });
+      }
+      if method.descriptor.ret.is_some() {
+        errors.push(bad_descriptor());
+      }
+      if flags
+        .intersects(AccStatic | AccFinal | AccSynchronised | AccBridge | AccNative | AccAbstract)
+      {
+        errors.push(bad_modifiers());
+      }
+    }
+    _ if !is_unqualified_name(name) || name.contains(['<', '>']) => {
+      errors.push(ClassFormatError::BadName { kind: "method", name: name.to_string() })
+    }
+    _ => {}
+  }
+  if name != "<clinit>" && !is_legal_method(flags, is_interface, major_version) {
+    errors.push(bad_modifiers());
+  }
+  // `this` takes a slot too
+  if method.descriptor.param_slots() + !flags.contains(AccStatic) as usize > 255 {
+    errors.push(bad_descriptor());
+  }
+
+  let has_code =
+    method.attributes.iter().any(|attribute| matches!(attribute, Attribute::Code { .. }));
+  match (flags.intersects(AccAbstract | AccNative), has_code) {
+    (false, false) => errors.push(ClassFormatError::MissingCode { method: key.to_string() }),
+    (true, true) => errors.push(ClassFormatError::UnexpectedCode { method: key.to_string() }),
+    _ => {}
+  }
+}
+
+/// Whether `flags` is a legal combination for a method other than `<clinit>` (JVMS §4.6).
+fn is_legal_method(
+  flags: method_info::MethodAccessFlags,
+  is_interface: bool,
+  major_version: u16
+) -> bool {
+  use method_info::Flags::*;
+
+  let access = (*flags & *(AccPublic | AccPrivate | AccProtected)).count_ones();
+  let access_legal = match (is_interface, major_version) {
+    // Before Java 8 interfaces only had abstract methods
+    (true, ..=51) => flags.contains(AccPublic | AccAbstract),
+    (true, _) => access == 1 && !flags.contains(AccProtected),
+    (false, _) => access <= 1
+  };
+  if is_interface && flags.intersects(AccProtected | AccFinal | AccSynchronised | AccNative) {
+    return false;
+  }
+  // Abstract methods, in classes and interfaces alike, can't have any flag needing a body. ACC_STRICT
+  // has been implied since Java 17, so it no longer clashes with ACC_ABSTRACT
+  let strict = (46..=60).contains(&major_version) && flags.contains(AccStrict);
+  let concrete = AccPrivate | AccStatic | AccFinal | AccSynchronised | AccNative;
+  access_legal && !(flags.contains(AccAbstract) && (strict || flags.intersects(concrete)))
+}
+
+/// The name in a `name:descriptor` key.
+pub fn member_name<'a>(key: &'a str, descriptor: &str) -> &'a str {
+  key.strip_suffix(descriptor).and_then(|key| key.strip_suffix(':')).unwrap_or(key)
+}
+
+/// Whether `name` is an unqualified name, as fields and methods have (JVMS §4.2.2).
+fn is_unqualified_name(name: &str) -> bool {
+  !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+/// Whether `name` is a class or interface name in internal form, with `/` between identifiers
+/// (JVMS §4.2.1).
+fn is_class_name(name: &str) -> bool { name.split('/').all(is_unqualified_name) }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::method_info::MethodAccessFlags;
+
+  #[test]
+  fn abstract_interface_methods_take_no_body_flags() {
+    // An annotation interface, whose methods are `public abstract`
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/java_test/Test.class");
+    let mut class = ClassFile::parse(&std::fs::read(path).unwrap()).unwrap().0;
+    assert_eq!(check_format(&class), Ok(()));
+    for flags in [0x0409, 0x0411, 0x0402] {
+      class.methods[0].1.access_flags = MethodAccessFlags::from_mask(flags);
+      let errors = check_format(&class).unwrap_err();
+      assert!(matches!(errors[0], ClassFormatError::BadModifiers { .. }), "{:#06x}", flags);
+    }
+  }
+}
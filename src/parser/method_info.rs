@@ -1,9 +1,27 @@
 use bitmask::bitmask;
 
 use super::{
-  attribute_info::Attribute,
-  cp_info_resolved::{NameAndType, ResolvedCpInfo},
-  stream_reader::StreamReader
+  attribute_info::{
+    attribute::{
+      annotation::{self, Annotation},
+      element_value::ElementValue,
+      stack_map_frame::{
+        frame::{self, Frame},
+        verification_type_info::VerificationTypeInfo
+      }
+    },
+    attribute_registry::AttributeRegistry,
+    signature::find_signature,
+    Attribute
+  },
+  class_format_error::ClassFormatError,
+  constant_pool::{ConstantPool, ConstantPoolBuilder},
+  cp_info_resolved::{Class, NameAndType},
+  descriptor::MethodDescriptor,
+  format_check::member_name,
+  signature::MethodSignature,
+  stream_reader::StreamReader,
+  stream_writer::StreamWriter
 };
 
 bitmask! {
@@ -24,30 +42,125 @@ bitmask! {
   }
 }
 
-#[derive(Debug, Clone)]
+impl MethodAccessFlags {
+  /// The flags with the bits `mask`, including any without a name.
+  pub fn from_mask(mask: u16) -> Self { MethodAccessFlags { mask } }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodInfo {
   pub access_flags: MethodAccessFlags,
+  pub descriptor: MethodDescriptor,
   pub attributes: Vec<Attribute>
 }
 
 impl MethodInfo {
-  pub fn read(sr: &mut StreamReader, constant_pool: &Vec<ResolvedCpInfo>) -> (String, Self) {
-    let access_flags_num = sr.get_u16();
+  pub fn read(
+    sr: &mut StreamReader,
+    constant_pool: &ConstantPool,
+    registry: &AttributeRegistry
+  ) -> Result<(String, Self), ClassFormatError> {
+    let access_flags_num = sr.get_u16()?;
     let access_flags = MethodAccessFlags { mask: access_flags_num };
 
-    let name_index = sr.get_u16();
-    let ResolvedCpInfo::Utf8(name) = &constant_pool[name_index as usize -1] else {panic!()};
+    let name_index = sr.get_u16()?;
+    let name = constant_pool.get_utf8(name_index)?;
+
+    let descriptor_index = sr.get_u16()?;
+    let descriptor = constant_pool.get_utf8(descriptor_index)?;
+
+    let attributes_count = sr.get_u16()?;
+    let attributes: Vec<Attribute> = (0..attributes_count)
+      .map(|_| Attribute::read(sr, constant_pool, registry))
+      .collect::<Result<_, _>>()?;
+
+    let method =
+      Self { access_flags, descriptor: MethodDescriptor::parse(&descriptor)?, attributes };
+    Ok((NameAndType { name, descriptor }.to_string(), method))
+  }
+  /// Writes the method back out under the name in its `name:descriptor` key.
+  pub fn write(
+    &self,
+    key: &str,
+    constant_pool: &mut ConstantPoolBuilder,
+    sw: &mut StreamWriter
+  ) -> Result<(), ClassFormatError> {
+    let descriptor = self.descriptor.to_string();
+    sw.put_u16(*self.access_flags);
+    sw.put_u16(constant_pool.utf8(member_name(key, &descriptor))?);
+    sw.put_u16(constant_pool.utf8(&descriptor)?);
+    sw.put_count(self.attributes.len())?;
+    for attribute in &self.attributes {
+      attribute.write(constant_pool, sw)?;
+    }
+    Ok(())
+  }
+
+  /// The generic method signature, if it has one.
+  pub fn signature(&self) -> Result<Option<MethodSignature>, ClassFormatError> {
+    find_signature(&self.attributes).map(MethodSignature::parse).transpose()
+  }
+
+  /// The annotation of the given type, such as `Lorg/junit/Test;`, visible or not.
+  pub fn annotation(&self, type_name: &str) -> Option<&Annotation> {
+    annotation::annotations(&self.attributes).find(|annotation| annotation.type_name == type_name)
+  }
+
+  /// The annotation of the given type on the parameter at `index`, visible or not.
+  /// Synthetic parameters, such as an inner class constructor's outer instance, may be omitted
+  /// from the count by the compiler.
+  pub fn parameter_annotation(&self, index: usize, type_name: &str) -> Option<&Annotation> {
+    self
+      .attributes
+      .iter()
+      .filter_map(|attribute| match attribute {
+        Attribute::RuntimeVisibleParameterAnnotations { parameter_annotations }
+        | Attribute::RuntimeInvisibleParameterAnnotations { parameter_annotations } => {
+          parameter_annotations.get(index)
+        }
+        _ => None
+      })
+      .flat_map(|parameter| parameter.annotations.iter())
+      .find(|annotation| annotation.type_name == type_name)
+  }
+
+  /// The default value of an annotation interface element.
+  pub fn annotation_default(&self) -> Option<&ElementValue> {
+    self.attributes.iter().find_map(|attribute| match attribute {
+      Attribute::AnnotationDefault { default_value } => Some(default_value),
+      _ => None
+    })
+  }
 
-    let descriptor_index = sr.get_u16();
-    let ResolvedCpInfo::Utf8(descriptor) = &constant_pool[descriptor_index as usize -1] else {panic!()};
+  /// The frame on entry, or `None` for a method without code.
+  /// `class` and `name` are the declaring class and the method's name, which decide the type of
+  /// `this`.
+  pub fn initial_frame(&self, class: &Class, name: &str) -> Option<Frame> {
+    let (max_locals, _) = self.code()?;
+    Some(Frame::new(&self.initial_locals(class, name), &[], max_locals))
+  }
 
-    let attributes_count = sr.get_u16();
-    let attributes: Vec<Attribute> =
-      (0..attributes_count).map(|_| Attribute::read(sr, &constant_pool)).collect();
+  /// The full frame of every StackMapTable entry, keyed by instruction index.
+  pub fn frames(&self, class: &Class, name: &str) -> Result<Vec<(usize, Frame)>, ClassFormatError> {
+    let Some((max_locals, attributes)) = self.code() else {
+      return Ok(Vec::new());
+    };
+    let entries = attributes.iter().find_map(|attribute| match attribute {
+      Attribute::StackMapTable { entries } => Some(entries.as_slice()),
+      _ => None
+    });
+    frame::expand(self.initial_locals(class, name), entries.unwrap_or_default(), max_locals)
+  }
+
+  fn initial_locals(&self, class: &Class, name: &str) -> Vec<VerificationTypeInfo> {
+    let is_static = self.access_flags.contains(Flags::AccStatic);
+    frame::initial_locals(class, name, is_static, &self.descriptor)
+  }
 
-    (NameAndType { name: name.to_string(), descriptor: descriptor.to_string() }.to_string(), Self {
-      access_flags,
-      attributes
+  fn code(&self) -> Option<(u16, &[Attribute])> {
+    self.attributes.iter().find_map(|attribute| match attribute {
+      Attribute::Code { max_locals, attributes, .. } => Some((*max_locals, attributes.as_slice())),
+      _ => None
     })
   }
 }
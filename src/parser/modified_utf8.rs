@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+
+/// A `CONSTANT_Utf8` payload, which uses the JVM's "modified UTF-8" (JVMS §4.4.7).
+/// NUL is written as the two bytes `C0 80`, and characters outside the BMP are written as a
+/// surrogate pair with each surrogate taking three bytes, so the natural decoded form is a
+/// sequence of UTF-16 code units, which (like a `java.lang.String`) may hold unpaired surrogates.
+#[derive(Debug, Clone, Copy)]
+pub struct ModifiedUtf8<'a> {
+  bytes: &'a [u8]
+}
+
+impl<'a> ModifiedUtf8<'a> {
+  pub fn new(bytes: &'a [u8]) -> Option<Self> {
+    units(bytes).all(|unit| unit.is_some()).then_some(Self { bytes })
+  }
+
+  pub fn to_utf16(self) -> Vec<u16> {
+    units(self.bytes).map(|unit| unit.unwrap_or(0xfffd)).collect()
+  }
+
+  /// Decodes into a Rust string, replacing unpaired surrogates with U+FFFD.
+  /// Plain ASCII, which covers nearly every name and descriptor, is borrowed without copying.
+  pub fn to_str_lossy(self) -> Cow<'a, str> {
+    match std::str::from_utf8(self.bytes) {
+      Ok(string) if self.bytes.is_ascii() => Cow::Borrowed(string),
+      _ => Cow::Owned(String::from_utf16_lossy(&self.to_utf16()))
+    }
+  }
+}
+
+/// Yields each UTF-16 code unit of a modified UTF-8 byte string, or `None` on a malformed byte.
+struct Units<'a> {
+  bytes: std::slice::Iter<'a, u8>
+}
+
+impl Iterator for Units<'_> {
+  type Item = Option<u16>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let first = *self.bytes.next()? as u16;
+    let mut continuation = || match self.bytes.next() {
+      Some(byte) if byte & 0xc0 == 0x80 => Some((byte & 0x3f) as u16),
+      _ => None
+    };
+    Some(match first {
+      0x01..=0x7f => Some(first),
+      0xc0..=0xdf => continuation()
+        .map(|second| (first & 0x1f) << 6 | second)
+        .filter(|unit| *unit == 0 || *unit >= 0x80),
+      0xe0..=0xef => continuation()
+        .and_then(|second| Some((first & 0x0f) << 12 | second << 6 | continuation()?))
+        .filter(|unit| *unit >= 0x800),
+      // NUL must use the two byte form, and there are no four byte forms. Overlong forms of
+      // anything else aren't allowed either
+      _ => None
+    })
+  }
+}
+
+fn units(bytes: &[u8]) -> Units<'_> { Units { bytes: bytes.iter() } }
+
+pub fn encode(units: &[u16]) -> Vec<u8> {
+  let mut bytes = Vec::with_capacity(units.len());
+  for &unit in units {
+    match unit {
+      0x01..=0x7f => bytes.push(unit as u8),
+      0x00 | 0x80..=0x7ff => bytes.extend([0xc0 | (unit >> 6) as u8, 0x80 | (unit & 0x3f) as u8]),
+      _ => bytes.extend([
+        0xe0 | (unit >> 12) as u8,
+        0x80 | (unit >> 6 & 0x3f) as u8,
+        0x80 | (unit & 0x3f) as u8
+      ])
+    }
+  }
+  bytes
+}
+
+pub fn encode_str(string: &str) -> Vec<u8> { encode(&string.encode_utf16().collect::<Vec<u16>>()) }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn decode(bytes: &[u8]) -> Option<Vec<u16>> {
+    ModifiedUtf8::new(bytes).map(|utf8| utf8.to_utf16())
+  }
+
+  #[test]
+  fn decodes_nul_as_two_bytes() {
+    assert_eq!(decode(&[b'a', 0xc0, 0x80, b'b']), Some(vec![0x61, 0, 0x62]));
+    assert_eq!(encode(&[0]), [0xc0, 0x80]);
+    // A raw zero byte is not modified UTF-8
+    assert_eq!(decode(&[0]), None);
+  }
+
+  #[test]
+  fn decodes_surrogate_pairs_as_six_bytes() {
+    // U+1F600 is the pair D83D DE00
+    let bytes = [0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80];
+    assert_eq!(decode(&bytes), Some(vec![0xd83d, 0xde00]));
+    assert_eq!(ModifiedUtf8::new(&bytes).unwrap().to_str_lossy(), "\u{1f600}");
+    assert_eq!(encode_str("\u{1f600}"), bytes);
+  }
+
+  #[test]
+  fn keeps_unpaired_surrogates() {
+    let bytes = [b'x', 0xed, 0xa0, 0x80, b'y'];
+    assert_eq!(decode(&bytes), Some(vec![0x78, 0xd800, 0x79]));
+    assert_eq!(ModifiedUtf8::new(&bytes).unwrap().to_str_lossy(), "x\u{fffd}y");
+    assert_eq!(encode(&[0x78, 0xd800, 0x79]), bytes);
+  }
+
+  #[test]
+  fn rejects_overlong_and_four_byte_forms() {
+    // `/` in two bytes and in three
+    assert_eq!(decode(&[0xc0, 0xaf]), None);
+    assert_eq!(decode(&[0xe0, 0x80, 0xaf]), None);
+    // U+07FF in three bytes
+    assert_eq!(decode(&[0xe0, 0x9f, 0xbf]), None);
+    // U+1F600 as standard UTF-8
+    assert_eq!(decode(&[0xf0, 0x9f, 0x98, 0x80]), None);
+    // Truncated and stray continuation bytes
+    assert_eq!(decode(&[0xe4, 0xb8]), None);
+    assert_eq!(decode(&[0x80]), None);
+  }
+
+  #[test]
+  fn encode_inverts_decoding() {
+    let units = [0x41, 0, 0x7f, 0x80, 0x7ff, 0x800, 0xd800, 0xdc00, 0xffff];
+    assert_eq!(decode(&encode(&units)), Some(units.to_vec()));
+    let ascii = b"java/lang/Object";
+    assert_eq!(encode(&decode(ascii).unwrap()), ascii);
+    assert!(matches!(ModifiedUtf8::new(ascii).unwrap().to_str_lossy(), Cow::Borrowed(_)));
+  }
+}
@@ -0,0 +1,504 @@
+use std::fmt;
+
+use super::class_format_error::ClassFormatError;
+
+/// A type from a `Signature` attribute (JVMS §4.7.9.1).
+/// Each part of a signature displays as Java-like syntax: `Display` qualifies class names, as in
+/// `java.util.Map<K, java.util.List<? extends V>>`, and `to_java(false)` leaves packages out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JavaTypeSignature {
+  Byte,
+  Char,
+  Double,
+  Float,
+  Int,
+  Long,
+  Short,
+  Boolean,
+  Reference(ReferenceTypeSignature)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceTypeSignature {
+  Class(ClassTypeSignature),
+  TypeVariable(String),
+  Array(Box<JavaTypeSignature>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassTypeSignature {
+  /// Such as `java/util/`, or empty for the unnamed package.
+  pub package: String,
+  /// The outermost class first, followed by each inner class suffix.
+  pub classes: Vec<SimpleClassTypeSignature>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleClassTypeSignature {
+  pub name: String,
+  pub type_arguments: Vec<TypeArgument>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeArgument {
+  /// `*`, printed as `?`
+  Any,
+  Exact(ReferenceTypeSignature),
+  Extends(ReferenceTypeSignature),
+  Super(ReferenceTypeSignature)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeParameter {
+  pub name: String,
+  pub class_bound: Option<ReferenceTypeSignature>,
+  pub interface_bounds: Vec<ReferenceTypeSignature>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassSignature {
+  pub type_parameters: Vec<TypeParameter>,
+  pub superclass: ClassTypeSignature,
+  pub superinterfaces: Vec<ClassTypeSignature>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodSignature {
+  pub type_parameters: Vec<TypeParameter>,
+  pub params: Vec<JavaTypeSignature>,
+  /// `None` for a `void` method.
+  pub ret: Option<JavaTypeSignature>,
+  /// Each is a class type or a type variable.
+  pub throws: Vec<ReferenceTypeSignature>
+}
+
+/// The signature of a field or record component.
+pub type FieldSignature = ReferenceTypeSignature;
+
+impl ClassSignature {
+  pub fn parse(signature: &str) -> Result<Self, ClassFormatError> {
+    parse(signature, |parser| {
+      let type_parameters = parser.type_parameters()?;
+      let superclass = parser.class_type()?;
+      let mut superinterfaces = Vec::new();
+      while !parser.done() {
+        superinterfaces.push(parser.class_type()?);
+      }
+      Some(ClassSignature { type_parameters, superclass, superinterfaces })
+    })
+  }
+
+  pub fn to_java(&self, qualified: bool) -> String {
+    let mut java = type_parameters_to_java(&self.type_parameters, qualified);
+    if !java.is_empty() {
+      java.push(' ');
+    }
+    java += &format!("extends {}", self.superclass.to_java(qualified));
+    if !self.superinterfaces.is_empty() {
+      let superinterfaces: Vec<String> = self
+        .superinterfaces
+        .iter()
+        .map(|superinterface| superinterface.to_java(qualified))
+        .collect();
+      java += &format!(" implements {}", superinterfaces.join(", "));
+    }
+    java
+  }
+}
+
+impl MethodSignature {
+  pub fn parse(signature: &str) -> Result<Self, ClassFormatError> {
+    parse(signature, |parser| {
+      let type_parameters = parser.type_parameters()?;
+      parser.expect(b'(')?;
+      let mut params = Vec::new();
+      while !parser.eat(b')') {
+        params.push(parser.java_type()?);
+      }
+      let ret = if parser.eat(b'V') { None } else { Some(parser.java_type()?) };
+      let mut throws = Vec::new();
+      while parser.eat(b'^') {
+        throws.push(match parser.peek()? {
+          b'T' => parser.type_variable()?,
+          _ => ReferenceTypeSignature::Class(parser.class_type()?)
+        });
+      }
+      Some(MethodSignature { type_parameters, params, ret, throws })
+    })
+  }
+
+  pub fn to_java(&self, qualified: bool) -> String {
+    let mut java = type_parameters_to_java(&self.type_parameters, qualified);
+    if !java.is_empty() {
+      java.push(' ');
+    }
+    let ret = self.ret.as_ref().map_or("void".to_string(), |ret| ret.to_java(qualified));
+    let params: Vec<String> = self.params.iter().map(|param| param.to_java(qualified)).collect();
+    java += &format!("{}({})", ret, params.join(", "));
+    if !self.throws.is_empty() {
+      let throws: Vec<String> =
+        self.throws.iter().map(|throws| throws.to_java(qualified)).collect();
+      java += &format!(" throws {}", throws.join(", "));
+    }
+    java
+  }
+}
+
+impl ReferenceTypeSignature {
+  /// Parses a field signature.
+  pub fn parse(signature: &str) -> Result<Self, ClassFormatError> {
+    parse(signature, |parser| parser.reference_type())
+  }
+
+  pub fn to_java(&self, qualified: bool) -> String {
+    match self {
+      ReferenceTypeSignature::Class(class) => class.to_java(qualified),
+      ReferenceTypeSignature::TypeVariable(name) => name.clone(),
+      ReferenceTypeSignature::Array(component) => format!("{}[]", component.to_java(qualified))
+    }
+  }
+}
+
+impl JavaTypeSignature {
+  pub fn to_java(&self, qualified: bool) -> String {
+    match self {
+      JavaTypeSignature::Byte => "byte".to_string(),
+      JavaTypeSignature::Char => "char".to_string(),
+      JavaTypeSignature::Double => "double".to_string(),
+      JavaTypeSignature::Float => "float".to_string(),
+      JavaTypeSignature::Int => "int".to_string(),
+      JavaTypeSignature::Long => "long".to_string(),
+      JavaTypeSignature::Short => "short".to_string(),
+      JavaTypeSignature::Boolean => "boolean".to_string(),
+      JavaTypeSignature::Reference(reference) => reference.to_java(qualified)
+    }
+  }
+}
+
+impl ClassTypeSignature {
+  pub fn to_java(&self, qualified: bool) -> String {
+    let classes: Vec<String> = self
+      .classes
+      .iter()
+      .map(|class| {
+        if class.type_arguments.is_empty() {
+          return class.name.clone();
+        }
+        let type_arguments: Vec<String> =
+          class.type_arguments.iter().map(|argument| argument.to_java(qualified)).collect();
+        format!("{}<{}>", class.name, type_arguments.join(", "))
+      })
+      .collect();
+    let package = if qualified { self.package.replace('/', ".") } else { String::new() };
+    package + &classes.join(".")
+  }
+}
+
+impl TypeArgument {
+  pub fn to_java(&self, qualified: bool) -> String {
+    match self {
+      TypeArgument::Any => "?".to_string(),
+      TypeArgument::Exact(reference) => reference.to_java(qualified),
+      TypeArgument::Extends(reference) => format!("? extends {}", reference.to_java(qualified)),
+      TypeArgument::Super(reference) => format!("? super {}", reference.to_java(qualified))
+    }
+  }
+}
+
+impl TypeParameter {
+  pub fn to_java(&self, qualified: bool) -> String {
+    // javac writes `T:Ljava/lang/Object;` for an unbounded `T`, which Java leaves implicit
+    let is_object = |bound: &&ReferenceTypeSignature| {
+      let ReferenceTypeSignature::Class(ClassTypeSignature { package, classes }) = bound else {
+        return false;
+      };
+      package == "java/lang/" && classes.len() == 1 && classes[0].name == "Object"
+    };
+    let bounds: Vec<String> = (self.class_bound.iter().filter(|bound| !is_object(bound)))
+      .chain(self.interface_bounds.iter())
+      .map(|bound| bound.to_java(qualified))
+      .collect();
+    match bounds.is_empty() {
+      true => self.name.clone(),
+      false => format!("{} extends {}", self.name, bounds.join(" & "))
+    }
+  }
+}
+
+/// Type parameters as Java writes them, such as `<K, V extends java.lang.Number>`, or nothing if
+/// there are none.
+pub fn type_parameters_to_java(type_parameters: &[TypeParameter], qualified: bool) -> String {
+  if type_parameters.is_empty() {
+    return String::new();
+  }
+  let type_parameters: Vec<String> =
+    type_parameters.iter().map(|parameter| parameter.to_java(qualified)).collect();
+  format!("<{}>", type_parameters.join(", "))
+}
+
+macro_rules! impl_display {
+  ($($type:ty),*) => {
+    $(
+      impl fmt::Display for $type {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.to_java(true)) }
+      }
+    )*
+  };
+}
+
+impl_display!(
+  ClassSignature,
+  MethodSignature,
+  JavaTypeSignature,
+  ReferenceTypeSignature,
+  ClassTypeSignature,
+  TypeArgument,
+  TypeParameter
+);
+
+fn parse<T>(
+  signature: &str,
+  rule: impl FnOnce(&mut Parser) -> Option<T>
+) -> Result<T, ClassFormatError> {
+  let mut parser = Parser { signature: signature.as_bytes(), ptr: 0, depth: 0 };
+  match rule(&mut parser) {
+    Some(parsed) if parser.done() => Ok(parsed),
+    _ => Err(ClassFormatError::BadSignature { signature: signature.to_string() })
+  }
+}
+
+/// How deeply type arguments may nest, so that a hostile signature can't exhaust the stack.
+const MAX_NESTING: usize = 255;
+
+/// A recursive descent parser, with one method per grammar rule.
+/// Each returns `None` when the signature doesn't match the rule.
+struct Parser<'a> {
+  signature: &'a [u8],
+  ptr: usize,
+  /// How many type argument lists the parser is inside.
+  depth: usize
+}
+
+impl Parser<'_> {
+  fn done(&self) -> bool { self.ptr >= self.signature.len() }
+
+  fn peek(&self) -> Option<u8> { self.signature.get(self.ptr).copied() }
+
+  fn eat(&mut self, byte: u8) -> bool {
+    let matched = self.peek() == Some(byte);
+    self.ptr += matched as usize;
+    matched
+  }
+
+  fn expect(&mut self, byte: u8) -> Option<()> { self.eat(byte).then_some(()) }
+
+  /// An unqualified name, which ends at any character with a meaning in signatures.
+  fn identifier(&mut self) -> Option<String> {
+    let start = self.ptr;
+    while !matches!(self.peek(), None | Some(b'.' | b';' | b'[' | b'/' | b'<' | b'>' | b':')) {
+      self.ptr += 1;
+    }
+    let identifier = std::str::from_utf8(&self.signature[start..self.ptr]).ok()?;
+    (!identifier.is_empty()).then(|| identifier.to_string())
+  }
+
+  fn java_type(&mut self) -> Option<JavaTypeSignature> {
+    let base = match self.peek()? {
+      b'B' => JavaTypeSignature::Byte,
+      b'C' => JavaTypeSignature::Char,
+      b'D' => JavaTypeSignature::Double,
+      b'F' => JavaTypeSignature::Float,
+      b'I' => JavaTypeSignature::Int,
+      b'J' => JavaTypeSignature::Long,
+      b'S' => JavaTypeSignature::Short,
+      b'Z' => JavaTypeSignature::Boolean,
+      _ => return Some(JavaTypeSignature::Reference(self.reference_type()?))
+    };
+    self.ptr += 1;
+    Some(base)
+  }
+
+  fn reference_type(&mut self) -> Option<ReferenceTypeSignature> {
+    match self.peek()? {
+      b'L' => Some(ReferenceTypeSignature::Class(self.class_type()?)),
+      b'T' => self.type_variable(),
+      b'[' => {
+        // Like a descriptor, an array type may have at most 255 dimensions
+        let dimensions =
+          self.signature[self.ptr..].iter().take_while(|byte| **byte == b'[').count();
+        if dimensions > 255 {
+          return None;
+        }
+        self.ptr += dimensions;
+        let mut array = ReferenceTypeSignature::Array(Box::new(self.java_type()?));
+        for _ in 1..dimensions {
+          array = ReferenceTypeSignature::Array(Box::new(JavaTypeSignature::Reference(array)));
+        }
+        Some(array)
+      }
+      _ => None
+    }
+  }
+
+  fn type_variable(&mut self) -> Option<ReferenceTypeSignature> {
+    self.expect(b'T')?;
+    let name = self.identifier()?;
+    self.expect(b';')?;
+    Some(ReferenceTypeSignature::TypeVariable(name))
+  }
+
+  fn class_type(&mut self) -> Option<ClassTypeSignature> {
+    self.expect(b'L')?;
+    let mut package = String::new();
+    let mut name = self.identifier()?;
+    while self.eat(b'/') {
+      package += &name;
+      package.push('/');
+      name = self.identifier()?;
+    }
+    let mut classes =
+      vec![SimpleClassTypeSignature { name, type_arguments: self.type_arguments()? }];
+    while self.eat(b'.') {
+      let name = self.identifier()?;
+      classes.push(SimpleClassTypeSignature { name, type_arguments: self.type_arguments()? });
+    }
+    self.expect(b';')?;
+    Some(ClassTypeSignature { package, classes })
+  }
+
+  fn type_arguments(&mut self) -> Option<Vec<TypeArgument>> {
+    let mut type_arguments = Vec::new();
+    if !self.eat(b'<') {
+      return Some(type_arguments);
+    }
+    if self.depth == MAX_NESTING {
+      return None;
+    }
+    self.depth += 1;
+    while !self.eat(b'>') {
+      type_arguments.push(match self.peek()? {
+        b'*' => {
+          self.ptr += 1;
+          TypeArgument::Any
+        }
+        b'+' => {
+          self.ptr += 1;
+          TypeArgument::Extends(self.reference_type()?)
+        }
+        b'-' => {
+          self.ptr += 1;
+          TypeArgument::Super(self.reference_type()?)
+        }
+        _ => TypeArgument::Exact(self.reference_type()?)
+      });
+    }
+    self.depth -= 1;
+    // At least one type argument is required between the brackets
+    (!type_arguments.is_empty()).then_some(type_arguments)
+  }
+
+  fn type_parameters(&mut self) -> Option<Vec<TypeParameter>> {
+    let mut type_parameters = Vec::new();
+    if !self.eat(b'<') {
+      return Some(type_parameters);
+    }
+    while !self.eat(b'>') {
+      let name = self.identifier()?;
+      self.expect(b':')?;
+      let class_bound = match self.peek()? {
+        b'L' | b'T' | b'[' => Some(self.reference_type()?),
+        _ => None
+      };
+      let mut interface_bounds = Vec::new();
+      while self.eat(b':') {
+        interface_bounds.push(self.reference_type()?);
+      }
+      type_parameters.push(TypeParameter { name, class_bound, interface_bounds });
+    }
+    (!type_parameters.is_empty()).then_some(type_parameters)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_class_signatures() {
+    let signature = "<K:Ljava/lang/Object;V::Ljava/lang/Comparable<-TV;>;>\
+      Ljava/util/AbstractMap<TK;TV;>;Ljava/util/Map<TK;TV;>;Ljava/io/Serializable;";
+    let parsed = ClassSignature::parse(signature).unwrap();
+    assert_eq!(parsed.type_parameters.len(), 2);
+    assert_eq!(parsed.type_parameters[1].class_bound, None);
+    assert_eq!(parsed.superinterfaces.len(), 2);
+    assert_eq!(
+      parsed.to_java(false),
+      "<K, V extends Comparable<? super V>> extends AbstractMap<K, V> implements Map<K, V>, \
+       Serializable"
+    );
+    assert_eq!(
+      parsed.to_string(),
+      "<K, V extends java.lang.Comparable<? super V>> extends java.util.AbstractMap<K, V> \
+       implements java.util.Map<K, V>, java.io.Serializable"
+    );
+  }
+
+  #[test]
+  fn parses_method_signatures() {
+    let signature =
+      "<T:Ljava/lang/Throwable;>(I[TT;Ljava/util/List<*>;)Ljava/util/Map<TK;Ljava/util/List<+TV;>;>;\
+       ^TT;^Ljava/io/IOException;";
+    let parsed = MethodSignature::parse(signature).unwrap();
+    assert_eq!(parsed.params.len(), 3);
+    assert_eq!(parsed.throws.len(), 2);
+    assert!(
+      matches!(parsed.throws[0], ReferenceTypeSignature::TypeVariable(ref name) if name == "T")
+    );
+    assert_eq!(
+      parsed.to_java(false),
+      "<T extends Throwable> Map<K, List<? extends V>>(int, T[], List<?>) throws T, IOException"
+    );
+    assert_eq!(MethodSignature::parse("()V").unwrap().to_java(true), "void()");
+  }
+
+  #[test]
+  fn parses_field_signatures() {
+    let parsed = ReferenceTypeSignature::parse("Ljava/util/Map<TK;Ljava/util/List<+TV;>;>;");
+    assert_eq!(parsed.unwrap().to_string(), "java.util.Map<K, java.util.List<? extends V>>");
+
+    // An inner class of a generic class, each with its own type arguments
+    let parsed = ReferenceTypeSignature::parse("La/Outer<Ljava/lang/String;>.Inner<[I>;").unwrap();
+    let ReferenceTypeSignature::Class(class) = &parsed else { panic!("{:?}", parsed) };
+    assert_eq!(class.package, "a/");
+    assert_eq!(class.classes.len(), 2);
+    assert_eq!(parsed.to_java(false), "Outer<String>.Inner<int[]>");
+    assert_eq!(parsed.to_java(true), "a.Outer<java.lang.String>.Inner<int[]>");
+
+    let parsed = ReferenceTypeSignature::parse("[[TT;").unwrap();
+    assert_eq!(parsed.to_string(), "T[][]");
+  }
+
+  #[test]
+  fn rejects_malformed_signatures() {
+    for signature in ["", "I", "Ljava/util/List<>;", "Ljava/util/List", "TT", "La//b;", "[V"] {
+      assert!(ReferenceTypeSignature::parse(signature).is_err(), "{}", signature);
+    }
+    for signature in ["(I", "()", "()VV", "<>()V", "<T>()V", "()V^I"] {
+      assert!(MethodSignature::parse(signature).is_err(), "{}", signature);
+    }
+    assert!(ClassSignature::parse("<T:>Ljava/lang/Object;").is_ok());
+    assert!(ClassSignature::parse("Ljava/lang/Object;I").is_err());
+  }
+
+  #[test]
+  fn limits_nesting() {
+    let array = |dimensions: usize| format!("{}I", "[".repeat(dimensions));
+    assert!(ReferenceTypeSignature::parse(&array(255)).is_ok());
+    assert!(ReferenceTypeSignature::parse(&array(256)).is_err());
+    assert!(ReferenceTypeSignature::parse(&array(60_000)).is_err());
+
+    let nested = |depth: usize| format!("{}I{}", "LL<[".repeat(depth), ">;".repeat(depth));
+    assert!(ReferenceTypeSignature::parse(&nested(255)).is_ok());
+    assert!(ReferenceTypeSignature::parse(&nested(100_000)).is_err());
+  }
+}
@@ -1,67 +1,72 @@
-pub struct StreamReader {
-  pub stream: Vec<u8>,
-  pub ptr: usize
+use super::class_format_error::ClassFormatError;
+
+/// A big-endian cursor over a borrowed byte slice.
+/// `start` is the position of `stream` within the whole class file, so that errors raised by
+/// readers over a sub-slice (such as a method's code array) still point at the right byte.
+#[derive(Clone)]
+pub struct StreamReader<'a> {
+  pub stream: &'a [u8],
+  pub ptr: usize,
+  start: usize
 }
 
-impl StreamReader {
-  pub fn from(stream: Vec<u8>) -> Self { return Self { stream, ptr: 0 } }
+impl<'a> StreamReader<'a> {
+  pub fn from(stream: &'a [u8]) -> Self { Self { stream, ptr: 0, start: 0 } }
+
+  pub fn done(&self) -> bool { self.ptr >= self.stream.len() }
+
+  /// The position of the cursor within the whole class file.
+  pub fn offset(&self) -> usize { self.start + self.ptr }
 
-  pub fn done(&self) -> bool { return self.ptr >= self.stream.len(); }
+  pub fn take_n(&mut self, n: usize) -> Result<&'a [u8], ClassFormatError> {
+    let end = self.ptr.checked_add(n).filter(|end| *end <= self.stream.len());
+    let Some(end) = end else {
+      return Err(ClassFormatError::Truncated { offset: self.start + self.stream.len() });
+    };
+    let ret = &self.stream[self.ptr..end];
+    self.ptr = end;
+    Ok(ret)
+  }
 
-  fn next(&mut self) -> u8 {
-    self.ptr += 1;
-    self.stream[self.ptr - 1]
+  /// Splits off the next `n` bytes into their own reader, advancing past them.
+  pub fn sub_reader(&mut self, n: usize) -> Result<StreamReader<'a>, ClassFormatError> {
+    let start = self.offset();
+    Ok(StreamReader { stream: self.take_n(n)?, ptr: 0, start })
   }
 
-  pub fn get_i8(&mut self) -> i8 { self.next() as i8 }
+  fn next_n<const N: usize>(&mut self) -> Result<[u8; N], ClassFormatError> {
+    Ok(self.take_n(N)?.try_into().unwrap())
+  }
 
-  pub fn get_u8(&mut self) -> u8 { self.next() }
+  pub fn get_i8(&mut self) -> Result<i8, ClassFormatError> { Ok(i8::from_be_bytes(self.next_n()?)) }
 
-  pub fn get_i16(&mut self) -> i16 { i16::from_be_bytes([self.next(), self.next()]) }
+  pub fn get_u8(&mut self) -> Result<u8, ClassFormatError> { Ok(u8::from_be_bytes(self.next_n()?)) }
 
-  pub fn get_u16(&mut self) -> u16 { u16::from_be_bytes([self.next(), self.next()]) }
+  pub fn get_i16(&mut self) -> Result<i16, ClassFormatError> {
+    Ok(i16::from_be_bytes(self.next_n()?))
+  }
 
-  pub fn get_i32(&mut self) -> i32 {
-    i32::from_be_bytes([self.next(), self.next(), self.next(), self.next()])
+  pub fn get_u16(&mut self) -> Result<u16, ClassFormatError> {
+    Ok(u16::from_be_bytes(self.next_n()?))
   }
 
-  pub fn get_u32(&mut self) -> u32 {
-    u32::from_be_bytes([self.next(), self.next(), self.next(), self.next()])
+  pub fn get_i32(&mut self) -> Result<i32, ClassFormatError> {
+    Ok(i32::from_be_bytes(self.next_n()?))
   }
 
-  pub fn get_i64(&mut self) -> i64 {
-    i64::from_be_bytes([
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next()
-    ])
+  pub fn get_u32(&mut self) -> Result<u32, ClassFormatError> {
+    Ok(u32::from_be_bytes(self.next_n()?))
   }
 
-  pub fn get_f32(&mut self) -> f32 {
-    f32::from_be_bytes([self.next(), self.next(), self.next(), self.next()])
+  pub fn get_i64(&mut self) -> Result<i64, ClassFormatError> {
+    Ok(i64::from_be_bytes(self.next_n()?))
   }
 
-  pub fn get_f64(&mut self) -> f64 {
-    f64::from_be_bytes([
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next(),
-      self.next()
-    ])
+  pub fn get_f32(&mut self) -> Result<f32, ClassFormatError> {
+    Ok(f32::from_be_bytes(self.next_n()?))
   }
 
-  pub fn take_n(&mut self, n: usize) -> Vec<u8> {
-    let ret = self.stream[self.ptr..n + self.ptr].to_vec();
-    self.ptr += n;
-    ret
+  pub fn get_f64(&mut self) -> Result<f64, ClassFormatError> {
+    Ok(f64::from_be_bytes(self.next_n()?))
   }
 }
@@ -0,0 +1,41 @@
+use super::class_format_error::ClassFormatError;
+
+/// A big-endian byte sink, the counterpart of `StreamReader`.
+#[derive(Debug, Clone, Default)]
+pub struct StreamWriter {
+  pub stream: Vec<u8>
+}
+
+impl StreamWriter {
+  pub fn new() -> Self { Self::default() }
+
+  pub fn len(&self) -> usize { self.stream.len() }
+
+  pub fn put_bytes(&mut self, bytes: &[u8]) { self.stream.extend_from_slice(bytes) }
+
+  pub fn put_i8(&mut self, value: i8) { self.put_bytes(&value.to_be_bytes()) }
+
+  pub fn put_u8(&mut self, value: u8) { self.put_bytes(&value.to_be_bytes()) }
+
+  pub fn put_i16(&mut self, value: i16) { self.put_bytes(&value.to_be_bytes()) }
+
+  pub fn put_u16(&mut self, value: u16) { self.put_bytes(&value.to_be_bytes()) }
+
+  pub fn put_i32(&mut self, value: i32) { self.put_bytes(&value.to_be_bytes()) }
+
+  pub fn put_u32(&mut self, value: u32) { self.put_bytes(&value.to_be_bytes()) }
+
+  /// The `u2` length that precedes most tables.
+  pub fn put_count(&mut self, length: usize) -> Result<(), ClassFormatError> {
+    let length = u16::try_from(length).map_err(|_| ClassFormatError::TableTooLong { length })?;
+    self.put_u16(length);
+    Ok(())
+  }
+
+  /// The `u1` length of the few tables that are capped at 255 entries.
+  pub fn put_short_count(&mut self, length: usize) -> Result<(), ClassFormatError> {
+    let length = u8::try_from(length).map_err(|_| ClassFormatError::TableTooLong { length })?;
+    self.put_u8(length);
+    Ok(())
+  }
+}